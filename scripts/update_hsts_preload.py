@@ -0,0 +1,96 @@
+#!/usr/bin/env python3
+# refreshes src/browser/hsts_preload.txt from chromium's preload list
+#
+#   scripts/update_hsts_preload.py                      re-check the hosts already in the file
+#   scripts/update_hsts_preload.py --policy public-suffix   also add every entry of a policy group
+#   scripts/update_hsts_preload.py --all                take the whole list
+#   scripts/update_hsts_preload.py --json path          use a local copy instead of downloading
+import argparse
+import json
+import sys
+import urllib.request
+from pathlib import Path
+
+SOURCE = (
+    "https://raw.githubusercontent.com/chromium/chromium/main/"
+    "net/http/transport_security_state_static.json"
+)
+PRELOAD = Path(__file__).resolve().parent.parent / "src" / "browser" / "hsts_preload.txt"
+
+
+def load_entries(path):
+    if path:
+        text = Path(path).read_text()
+    else:
+        with urllib.request.urlopen(SOURCE) as response:
+            text = response.read().decode()
+
+    # the file is json with // comment lines
+    text = "\n".join(line for line in text.splitlines() if not line.lstrip().startswith("//"))
+    entries = {}
+    for entry in json.loads(text)["entries"]:
+        # pin-only entries don't force https
+        if entry.get("mode") == "force-https":
+            entries[entry["name"].lower()] = entry
+    return entries
+
+
+def line_for(entry):
+    if entry.get("include_subdomains"):
+        return entry["name"].lower() + " include_subdomains"
+    return entry["name"].lower()
+
+
+def main():
+    parser = argparse.ArgumentParser(description="refresh the bundled hsts preload list")
+    parser.add_argument("--json", help="local transport_security_state_static.json")
+    parser.add_argument("--policy", action="append", default=[], help="add every entry of this policy")
+    parser.add_argument("--all", action="store_true", help="take the whole list")
+    args = parser.parse_args()
+
+    entries = load_entries(args.json)
+    lines = PRELOAD.read_text().splitlines()
+
+    # the comment block at the top stays as it is
+    header = []
+    while lines and lines[0].startswith("#"):
+        header.append(lines.pop(0))
+
+    out = header + [""]
+    seen = set()
+    if args.all:
+        for name in sorted(entries):
+            out.append(line_for(entries[name]))
+            seen.add(name)
+    else:
+        for line in lines:
+            fields = line.split()
+            if not fields or line.startswith("#"):
+                out.append(line)
+                continue
+            entry = entries.get(fields[0].lower())
+            if entry is None:
+                print("dropped " + fields[0] + ", it's no longer on the list", file=sys.stderr)
+                continue
+            out.append(line_for(entry))
+            seen.add(entry["name"].lower())
+
+    for policy in args.policy:
+        added = [name for name in sorted(entries) if entries[name].get("policy") == policy and name not in seen]
+        if not added:
+            continue
+        out += ["", "# policy " + policy]
+        for name in added:
+            out.append(line_for(entries[name]))
+            seen.add(name)
+
+    # collapse the blank lines dropped hosts can leave behind
+    text = "\n".join(out).strip() + "\n"
+    while "\n\n\n" in text:
+        text = text.replace("\n\n\n", "\n\n")
+    PRELOAD.write_text(text)
+    print("wrote {} hosts to {}".format(len(seen), PRELOAD), file=sys.stderr)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,756 @@
+// tls certificate checks
+// pages load over rustls with a verifier that remembers every host's chain
+// and what was wrong with it, so the lock icon can show it afterwards
+use crate::browser::HstsStore;
+use anyhow::Result;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use x509_parser::extensions::GeneralName;
+use x509_parser::objects::{oid2sn, oid_registry};
+use x509_parser::prelude::{FromDer, X509Certificate};
+use x509_parser::time::ASN1Time;
+
+/// Why a certificate wasn't accepted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum CertificateError {
+    #[error("the certificate has expired")]
+    Expired,
+    #[error("the certificate isn't valid yet")]
+    NotYetValid,
+    #[error("the certificate is for a different site")]
+    WrongHost,
+    #[error("the certificate is self-signed")]
+    SelfSigned,
+    #[error("the certificate was issued by an unknown authority")]
+    UnknownIssuer,
+    #[error("the certificate has been revoked")]
+    Revoked,
+    #[error("the certificate doesn't match the keys this site pinned")]
+    PinMismatch,
+    #[error("{0}")]
+    Other(String),
+}
+
+/// One certificate from a server's chain, the parts worth showing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub common_name: Option<String>,
+    /// DNS names and ip addresses from the subject alternative names.
+    pub sans: Vec<String>,
+    /// Hex, without separators.
+    pub serial: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// Colon separated uppercase hex, like browsers show it.
+    pub sha256_fingerprint: String,
+    pub sha1_fingerprint: String,
+    /// Base64 SHA-256 of the public key, what Public-Key-Pins pins.
+    pub spki_sha256: String,
+    pub signature_algorithm: String,
+    /// Issued by itself. Roots are, so this only means trouble for a leaf.
+    pub self_signed: bool,
+}
+
+impl CertificateInfo {
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let (_, cert) = X509Certificate::from_der(der)?;
+
+        let sans = match cert.subject_alternative_name()? {
+            Some(names) => names
+                .value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(dns) => Some(dns.to_string()),
+                    GeneralName::IPAddress(ip) => {
+                        let ip: IpAddr = match ip.len() {
+                            4 => <[u8; 4]>::try_from(*ip).ok()?.into(),
+                            16 => <[u8; 16]>::try_from(*ip).ok()?.into(),
+                            _ => return None,
+                        };
+                        Some(ip.to_string())
+                    }
+                    _ => None,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|entry| entry.as_str().ok())
+            .map(|name| name.to_string());
+        let algorithm = &cert.signature_algorithm.algorithm;
+        Ok(Self {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            common_name,
+            sans,
+            serial: serial_to_hex(cert.raw_serial()),
+            not_before: asn1_to_utc(cert.validity().not_before)?,
+            not_after: asn1_to_utc(cert.validity().not_after)?,
+            sha256_fingerprint: fingerprint(&Sha256::digest(der)),
+            sha1_fingerprint: fingerprint(&Sha1::digest(der)),
+            spki_sha256: base64::engine::general_purpose::STANDARD
+                .encode(Sha256::digest(cert.public_key().raw)),
+            signature_algorithm: oid2sn(algorithm, oid_registry())
+                .map(str::to_string)
+                .unwrap_or_else(|_| algorithm.to_id_string()),
+            self_signed: cert.subject().as_raw() == cert.issuer().as_raw(),
+        })
+    }
+
+    /// The name to show for the certificate, its common name if it has one.
+    pub fn display_name(&self) -> &str {
+        self.common_name.as_deref().unwrap_or(&self.subject)
+    }
+}
+
+/// What we found on the last tls handshake with a host.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateCheck {
+    pub host: String,
+    /// Leaf first, as the server sent it.
+    pub chain: Vec<CertificateInfo>,
+    pub error: Option<CertificateError>,
+    /// Only known for checks that did their own handshake.
+    pub protocol: Option<String>,
+    pub cipher_suite: Option<String>,
+    pub checked_at: DateTime<Utc>,
+    /// Connected anyway, the user made an exception for this certificate.
+    pub accepted: bool,
+}
+
+impl CertificateCheck {
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+    }
+
+    pub fn leaf(&self) -> Option<&CertificateInfo> {
+        self.chain.first()
+    }
+}
+
+/// A page load stopped by a certificate we don't trust. Load errors can be
+/// downcast to this to show the warning page instead of a plain error.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Couldn't verify the certificate for {}: {}", .check.host, .reason)]
+pub struct UntrustedCertificate {
+    pub check: CertificateCheck,
+    pub reason: CertificateError,
+}
+
+/// The latest check for each host, shared by everything that makes tls
+/// connections.
+#[derive(Debug, Clone, Default)]
+pub struct CertificateStore {
+    checks: Arc<RwLock<HashMap<String, CertificateCheck>>>,
+    /// Host and SHA-256 fingerprint pairs the user accepted despite errors.
+    exceptions: Arc<RwLock<HashSet<(String, String)>>>,
+    /// HSTS hosts get no exceptions, pinned hosts need a pinned key.
+    hsts: HstsStore,
+}
+
+impl CertificateStore {
+    pub fn with_hsts(mut self, hsts: HstsStore) -> Self {
+        self.hsts = hsts;
+        self
+    }
+
+    pub fn get(&self, host: &str) -> Option<CertificateCheck> {
+        self.checks
+            .read()
+            .unwrap()
+            .get(&host.to_ascii_lowercase())
+            .cloned()
+    }
+
+    pub fn set_exceptions(&self, exceptions: impl IntoIterator<Item = (String, String)>) {
+        *self.exceptions.write().unwrap() = exceptions.into_iter().collect();
+    }
+
+    pub fn allow(&self, host: &str, fingerprint: &str) {
+        self.exceptions
+            .write()
+            .unwrap()
+            .insert((host.to_ascii_lowercase(), fingerprint.to_string()));
+    }
+
+    pub fn revoke(&self, host: &str, fingerprint: &str) {
+        self.exceptions
+            .write()
+            .unwrap()
+            .remove(&(host.to_ascii_lowercase(), fingerprint.to_string()));
+    }
+
+    fn is_allowed(&self, host: &str, fingerprint: &str) -> bool {
+        !self.hsts.is_hsts_host(host)
+            && self
+                .exceptions
+                .read()
+                .unwrap()
+                .contains(&(host.to_string(), fingerprint.to_string()))
+    }
+
+    fn insert(&self, check: CertificateCheck) {
+        self.checks
+            .write()
+            .unwrap()
+            .insert(check.host.clone(), check);
+    }
+}
+
+/// Mozilla's roots, the ones we trust unless told otherwise.
+pub fn default_roots() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            anchor.subject,
+            anchor.spki,
+            anchor.name_constraints,
+        )
+    }));
+    roots
+}
+
+/// A rustls config that checks certificates against `roots` like normal and
+/// records each result in `store`.
+pub fn client_config(store: &CertificateStore, roots: RootCertStore) -> ClientConfig {
+    let verifier = RecordingVerifier {
+        inner: WebPkiVerifier::new(roots, None),
+        store: store.clone(),
+    };
+    ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth()
+}
+
+/// Does a handshake with `host` just to look at its certificate. Fails only
+/// when there was no certificate to look at, a bad certificate is reported in
+/// the check.
+pub async fn inspect_certificate(
+    host: &str,
+    port: u16,
+    store: &CertificateStore,
+    config: Arc<ClientConfig>,
+) -> Result<CertificateCheck> {
+    let server_name = ServerName::try_from(host)?;
+    let started = Utc::now();
+    let stream = tokio::time::timeout(Duration::from_secs(10), TcpStream::connect((host, port)))
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out connecting to {}", host))??;
+
+    let handshake = tokio::time::timeout(
+        Duration::from_secs(10),
+        TlsConnector::from(config).connect(server_name, stream),
+    )
+    .await
+    .map_err(|_| anyhow::anyhow!("Timed out during the tls handshake with {}", host))?;
+
+    let (protocol, cipher_suite) = match &handshake {
+        Ok(stream) => {
+            let (_, connection) = stream.get_ref();
+            (
+                connection
+                    .protocol_version()
+                    .and_then(|version| version.as_str())
+                    .map(|version| version.replace('_', ".").replace("TLSv", "TLS ")),
+                connection
+                    .negotiated_cipher_suite()
+                    .and_then(|suite| suite.suite().as_str())
+                    .map(str::to_string),
+            )
+        }
+        Err(_) => (None, None),
+    };
+
+    let check = store.get(host).filter(|check| check.checked_at >= started);
+    let mut check = match (check, handshake) {
+        (Some(check), _) => check,
+        // failed before the certificate was even looked at
+        (None, Err(e)) => return Err(e.into()),
+        (None, Ok(_)) => return Err(anyhow::anyhow!("{} sent no certificate", host)),
+    };
+    check.protocol = protocol;
+    check.cipher_suite = cipher_suite;
+    store.insert(check.clone());
+    Ok(check)
+}
+
+/// Checks like rustls normally would, but keeps the chain and the outcome.
+struct RecordingVerifier {
+    inner: WebPkiVerifier,
+    store: CertificateStore,
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let result = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        );
+
+        let chain: Vec<CertificateInfo> = std::iter::once(end_entity)
+            .chain(intermediates)
+            .filter_map(|cert| match CertificateInfo::from_der(&cert.0) {
+                Ok(info) => Some(info),
+                Err(e) => {
+                    log::warn!("Failed to parse a certificate: {}", e);
+                    None
+                }
+            })
+            .collect();
+
+        let host = match server_name {
+            ServerName::DnsName(name) => name.as_ref().to_ascii_lowercase(),
+            ServerName::IpAddress(ip) => ip.to_string(),
+            _ => return result,
+        };
+
+        // a trusted chain for a pinned host also has to carry a pinned key.
+        // only the certificates the server sent count, we don't see the root
+        let pin_mismatch = result.is_ok()
+            && !self
+                .store
+                .hsts
+                .pins_match(&host, chain.iter().map(|info| info.spki_sha256.as_str()));
+        let error = match &result {
+            Err(e) => {
+                // an unknown issuer that issued itself is the self-signed case
+                let self_signed =
+                    intermediates.is_empty() && chain.first().is_some_and(|leaf| leaf.self_signed);
+                Some(certificate_error(e, self_signed))
+            }
+            Ok(_) if pin_mismatch => Some(CertificateError::PinMismatch),
+            Ok(_) => None,
+        };
+        // only a bad certificate can be excepted, not a broken handshake or
+        // a pin mismatch
+        let accepted = matches!(result, Err(rustls::Error::InvalidCertificate(_)))
+            && chain
+                .first()
+                .is_some_and(|leaf| self.store.is_allowed(&host, &leaf.sha256_fingerprint));
+        if let Some(error) = &error {
+            if accepted {
+                log::info!("Certificate for {} accepted by exception: {}", host, error);
+            } else {
+                log::warn!("Certificate for {} rejected: {}", host, error);
+            }
+        }
+        self.store.insert(CertificateCheck {
+            host,
+            chain,
+            error,
+            protocol: None,
+            cipher_suite: None,
+            checked_at: Utc::now(),
+            accepted,
+        });
+
+        if accepted {
+            return Ok(ServerCertVerified::assertion());
+        }
+        if pin_mismatch {
+            return Err(rustls::Error::General(
+                CertificateError::PinMismatch.to_string(),
+            ));
+        }
+        result
+    }
+}
+
+fn certificate_error(error: &rustls::Error, self_signed: bool) -> CertificateError {
+    use rustls::CertificateError as Tls;
+
+    match error {
+        rustls::Error::InvalidCertificate(error) => match error {
+            Tls::Expired => CertificateError::Expired,
+            Tls::NotValidYet => CertificateError::NotYetValid,
+            Tls::NotValidForName => CertificateError::WrongHost,
+            Tls::Revoked => CertificateError::Revoked,
+            Tls::UnknownIssuer if self_signed => CertificateError::SelfSigned,
+            Tls::UnknownIssuer => CertificateError::UnknownIssuer,
+            other => CertificateError::Other(format!("{:?}", other)),
+        },
+        other => CertificateError::Other(other.to_string()),
+    }
+}
+
+/// Uppercase hex without leading zeros, like openssl prints serials.
+fn serial_to_hex(serial: &[u8]) -> String {
+    let hex: String = serial.iter().map(|byte| format!("{:02X}", byte)).collect();
+    match hex.trim_start_matches('0') {
+        "" => "0".to_string(),
+        hex => hex.to_string(),
+    }
+}
+
+fn asn1_to_utc(time: ASN1Time) -> Result<DateTime<Utc>> {
+    DateTime::from_timestamp(time.timestamp(), 0)
+        .ok_or_else(|| anyhow::anyhow!("Certificate date out of range"))
+}
+
+fn fingerprint(digest: &[u8]) -> String {
+    digest
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_server;
+    use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, SanType};
+    use rustls::{PrivateKey, ServerConfig};
+    use tokio_rustls::TlsAcceptor;
+
+    fn ca(name: &str) -> rcgen::Certificate {
+        let mut params = CertificateParams::new(Vec::new());
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name.push(DnType::CommonName, name);
+        rcgen::Certificate::from_params(params).unwrap()
+    }
+
+    fn leaf(names: &[&str], adjust: impl FnOnce(&mut CertificateParams)) -> rcgen::Certificate {
+        let mut params = CertificateParams::new(
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>(),
+        );
+        params.distinguished_name.push(DnType::CommonName, names[0]);
+        adjust(&mut params);
+        rcgen::Certificate::from_params(params).unwrap()
+    }
+
+    fn roots(ca_der: &[u8]) -> RootCertStore {
+        let mut roots = RootCertStore::empty();
+        roots.add(&Certificate(ca_der.to_vec())).unwrap();
+        roots
+    }
+
+    /// Serves `chain` on a local port with `key`, for as many handshakes as
+    /// the test makes.
+    async fn serve(chain: Vec<Vec<u8>>, key: Vec<u8>) -> u16 {
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                chain.into_iter().map(Certificate).collect(),
+                PrivateKey(key),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        test_server::accept(move |stream| {
+            let acceptor = acceptor.clone();
+            async move {
+                // the client hangs up on certificates it doesn't like
+                let _ = acceptor.accept(stream).await;
+            }
+        })
+        .await
+    }
+
+    /// Serves `leaf` signed by `ca`, or by itself without one.
+    async fn serve_leaf(leaf: &rcgen::Certificate, ca: Option<&rcgen::Certificate>) -> u16 {
+        let chain = match ca {
+            Some(ca) => vec![
+                leaf.serialize_der_with_signer(ca).unwrap(),
+                ca.serialize_der().unwrap(),
+            ],
+            None => vec![leaf.serialize_der().unwrap()],
+        };
+        serve(chain, leaf.serialize_private_key_der()).await
+    }
+
+    fn spki_sha256(cert: &rcgen::Certificate) -> String {
+        base64::engine::general_purpose::STANDARD
+            .encode(Sha256::digest(cert.get_key_pair().public_key_der()))
+    }
+
+    async fn inspect(
+        host: &str,
+        port: u16,
+        store: &CertificateStore,
+        roots: RootCertStore,
+    ) -> CertificateCheck {
+        let config = Arc::new(client_config(store, roots));
+        inspect_certificate(host, port, store, config)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_certificate_details() {
+        let ca = ca("Bagel Test CA");
+        let leaf = leaf(&["localhost"], |params| {
+            params
+                .subject_alt_names
+                .push(SanType::IpAddress("127.0.0.1".parse().unwrap()));
+            params.serial_number = Some(vec![0x00, 0x0a, 0xbc, 0xde].into());
+            params.not_before = rcgen::date_time_ymd(2024, 1, 2);
+            params.not_after = rcgen::date_time_ymd(2034, 1, 2);
+        });
+        let der = leaf.serialize_der_with_signer(&ca).unwrap();
+
+        let info = CertificateInfo::from_der(&der).unwrap();
+        assert_eq!(info.subject, "CN=localhost");
+        assert_eq!(info.issuer, "CN=Bagel Test CA");
+        assert_eq!(info.display_name(), "localhost");
+        assert_eq!(info.sans, vec!["localhost", "127.0.0.1"]);
+        assert_eq!(info.serial, "ABCDE");
+        assert_eq!(info.not_before.to_rfc3339(), "2024-01-02T00:00:00+00:00");
+        assert_eq!(info.not_after.to_rfc3339(), "2034-01-02T00:00:00+00:00");
+        assert_eq!(info.signature_algorithm, "ecdsa-with-SHA256");
+        assert!(!info.self_signed);
+
+        let sha256 = fingerprint(&Sha256::digest(&der));
+        assert_eq!(info.sha256_fingerprint, sha256);
+        assert_eq!(info.sha256_fingerprint.len(), 32 * 3 - 1);
+        assert_eq!(info.sha1_fingerprint.len(), 20 * 3 - 1);
+        assert!(info
+            .sha256_fingerprint
+            .chars()
+            .all(|c| c == ':' || c.is_ascii_digit() || c.is_ascii_uppercase()));
+        assert_eq!(info.spki_sha256, spki_sha256(&leaf));
+
+        let root = CertificateInfo::from_der(&ca.serialize_der().unwrap()).unwrap();
+        assert!(root.self_signed);
+        assert_eq!(root.common_name.as_deref(), Some("Bagel Test CA"));
+        assert!(root.sans.is_empty());
+
+        assert!(CertificateInfo::from_der(b"not a certificate").is_err());
+    }
+
+    #[test]
+    fn serials_print_like_openssl() {
+        assert_eq!(serial_to_hex(&[0x00, 0x01, 0xff]), "1FF");
+        assert_eq!(serial_to_hex(&[0x10]), "10");
+        assert_eq!(serial_to_hex(&[0x00]), "0");
+    }
+
+    #[tokio::test]
+    async fn trusted_chains_are_recorded() {
+        let ca = ca("Bagel Test CA");
+        let port = serve_leaf(&leaf(&["localhost"], |_| {}), Some(&ca)).await;
+        let store = CertificateStore::default();
+
+        let check = inspect(
+            "localhost",
+            port,
+            &store,
+            roots(&ca.serialize_der().unwrap()),
+        )
+        .await;
+        assert!(check.is_valid(), "{:?}", check.error);
+        assert!(!check.accepted);
+        assert_eq!(check.host, "localhost");
+        assert_eq!(check.chain.len(), 2);
+        assert_eq!(check.leaf().unwrap().display_name(), "localhost");
+        assert_eq!(check.chain[1].display_name(), "Bagel Test CA");
+        assert_eq!(check.protocol.as_deref(), Some("TLS 1.3"));
+        assert!(check.cipher_suite.is_some());
+
+        let stored = store.get("LOCALHOST").unwrap();
+        assert_eq!(stored.chain, check.chain);
+        assert_eq!(stored.protocol, check.protocol);
+    }
+
+    #[tokio::test]
+    async fn untrusted_certificates_are_reported() {
+        let ca = ca("Bagel Test CA");
+        let trusted = ca.serialize_der().unwrap();
+        let cases = [
+            (
+                serve_leaf(
+                    &leaf(&["localhost"], |_| {}),
+                    Some(&self::ca("Someone Else")),
+                )
+                .await,
+                CertificateError::UnknownIssuer,
+            ),
+            (
+                serve_leaf(&leaf(&["localhost"], |_| {}), None).await,
+                CertificateError::SelfSigned,
+            ),
+            (
+                serve_leaf(&leaf(&["other.example"], |_| {}), Some(&ca)).await,
+                CertificateError::WrongHost,
+            ),
+            (
+                serve_leaf(
+                    &leaf(&["localhost"], |params| {
+                        params.not_before = rcgen::date_time_ymd(2019, 1, 1);
+                        params.not_after = rcgen::date_time_ymd(2020, 1, 1);
+                    }),
+                    Some(&ca),
+                )
+                .await,
+                CertificateError::Expired,
+            ),
+            (
+                serve_leaf(
+                    &leaf(&["localhost"], |params| {
+                        params.not_before = rcgen::date_time_ymd(2090, 1, 1);
+                        params.not_after = rcgen::date_time_ymd(2091, 1, 1);
+                    }),
+                    Some(&ca),
+                )
+                .await,
+                CertificateError::NotYetValid,
+            ),
+        ];
+
+        for (port, expected) in cases {
+            let store = CertificateStore::default();
+            let check = inspect("localhost", port, &store, roots(&trusted)).await;
+            assert_eq!(check.error, Some(expected));
+            assert!(!check.accepted);
+            assert!(!check.chain.is_empty());
+            assert_eq!(check.protocol, None);
+            assert_eq!(store.get("localhost").unwrap().error, check.error);
+        }
+    }
+
+    #[tokio::test]
+    async fn nothing_listening_is_an_error() {
+        let port = test_server::closed_port();
+        let store = CertificateStore::default();
+        let config = Arc::new(client_config(&store, RootCertStore::empty()));
+        assert!(inspect_certificate("127.0.0.1", port, &store, config)
+            .await
+            .is_err());
+        assert!(store.get("127.0.0.1").is_none());
+    }
+
+    #[tokio::test]
+    async fn exceptions_accept_one_certificate_for_one_host() {
+        let port = serve_leaf(&leaf(&["localhost"], |_| {}), None).await;
+        let other_port = serve_leaf(&leaf(&["localhost"], |_| {}), None).await;
+        let store = CertificateStore::default();
+
+        let check = inspect("localhost", port, &store, RootCertStore::empty()).await;
+        assert_eq!(check.error, Some(CertificateError::SelfSigned));
+        assert!(!check.accepted);
+        let fingerprint = check.leaf().unwrap().sha256_fingerprint.clone();
+
+        store.allow("LocalHost", &fingerprint);
+        let check = inspect("localhost", port, &store, RootCertStore::empty()).await;
+        // still reported, but the handshake went through
+        assert_eq!(check.error, Some(CertificateError::SelfSigned));
+        assert!(check.accepted);
+        assert!(check.protocol.is_some());
+
+        // a different certificate on the same host warns again
+        let check = inspect("localhost", other_port, &store, RootCertStore::empty()).await;
+        assert!(!check.accepted);
+
+        // and the same certificate on another name isn't covered either
+        let check = inspect("127.0.0.1", port, &store, RootCertStore::empty()).await;
+        assert_eq!(check.host, "127.0.0.1");
+        assert!(!check.accepted);
+
+        store.revoke("localhost", &fingerprint);
+        let check = inspect("localhost", port, &store, RootCertStore::empty()).await;
+        assert!(!check.accepted);
+        assert_eq!(check.protocol, None);
+    }
+
+    #[tokio::test]
+    async fn hsts_hosts_get_no_exceptions() {
+        let port = serve_leaf(&leaf(&["localhost"], |_| {}), None).await;
+        let hsts = HstsStore::default();
+        let store = CertificateStore::default().with_hsts(hsts.clone());
+
+        let check = inspect("localhost", port, &store, RootCertStore::empty()).await;
+        store.allow("localhost", &check.leaf().unwrap().sha256_fingerprint);
+        assert!(
+            inspect("localhost", port, &store, RootCertStore::empty())
+                .await
+                .accepted
+        );
+
+        hsts.insert(crate::storage::HstsEntry {
+            host: "localhost".to_string(),
+            include_subdomains: false,
+            expires_at: Utc::now() + chrono::Duration::days(1),
+            updated_at: Utc::now(),
+        });
+        assert!(
+            !inspect("localhost", port, &store, RootCertStore::empty())
+                .await
+                .accepted
+        );
+    }
+
+    #[tokio::test]
+    async fn pinned_hosts_need_a_pinned_key() {
+        let ca = ca("Bagel Test CA");
+        let leaf = leaf(&["localhost"], |_| {});
+        let port = serve_leaf(&leaf, Some(&ca)).await;
+        let trusted = ca.serialize_der().unwrap();
+        let hsts = HstsStore::default();
+        let store = CertificateStore::default().with_hsts(hsts.clone());
+        let pin = |key: String| crate::storage::PinEntry {
+            host: "localhost".to_string(),
+            pins: vec![key],
+            include_subdomains: false,
+            expires_at: Utc::now() + chrono::Duration::days(1),
+            updated_at: Utc::now(),
+        };
+
+        // the ca's key is in the chain the server sends
+        hsts.insert_pins(pin(spki_sha256(&ca)));
+        let check = inspect("localhost", port, &store, roots(&trusted)).await;
+        assert!(check.is_valid(), "{:?}", check.error);
+        assert!(check.protocol.is_some());
+
+        let other = self::leaf(&["localhost"], |_| {});
+        hsts.insert_pins(pin(spki_sha256(&other)));
+        let check = inspect("localhost", port, &store, roots(&trusted)).await;
+        assert_eq!(check.error, Some(CertificateError::PinMismatch));
+        assert_eq!(check.protocol, None);
+
+        // and no exception gets around it
+        store.allow("localhost", &check.leaf().unwrap().sha256_fingerprint);
+        let check = inspect("localhost", port, &store, roots(&trusted)).await;
+        assert_eq!(check.error, Some(CertificateError::PinMismatch));
+        assert!(!check.accepted);
+        assert_eq!(check.protocol, None);
+
+        hsts.remove_pins("localhost");
+        assert!(inspect("localhost", port, &store, roots(&trusted))
+            .await
+            .is_valid());
+    }
+}
@@ -0,0 +1,686 @@
+// element hiding rules (`##` and `#@#`) from filter lists
+// rules are indexed by the id, class or tag their selector ends with,
+// so each element only gets tested against rules that could match it
+use crate::browser::{Document, Element};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AttributeOp {
+    Exists,
+    Equals(String),
+    Prefix(String),
+    Suffix(String),
+    Contains(String),
+    /// `~=`, one of the whitespace separated words.
+    Word(String),
+    /// `|=`, the value or the value followed by `-`.
+    DashPrefix(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Compound {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    attributes: Vec<(String, AttributeOp, bool)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+/// One selector from a comma separated list: compounds joined by combinators,
+/// stored right to left since that's how they're matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ComplexSelector {
+    subject: Compound,
+    ancestors: Vec<(Combinator, Compound)>,
+}
+
+/// The part of CSS selectors element hiding rules mostly use: tags, ids,
+/// classes, attribute selectors and the descendant and child combinators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    selectors: Vec<ComplexSelector>,
+}
+
+impl Selector {
+    /// `None` for anything outside the supported subset, like pseudo-classes.
+    pub fn parse(input: &str) -> Option<Self> {
+        let selectors = split_outside_brackets(input, ',')
+            .into_iter()
+            .map(|part| parse_complex(part.trim()))
+            .collect::<Option<Vec<_>>>()?;
+        (!selectors.is_empty()).then_some(Self { selectors })
+    }
+
+    /// Whether `element`, under `ancestors` (outermost first), matches.
+    pub fn matches(&self, element: &Element, ancestors: &[&Element]) -> bool {
+        self.selectors
+            .iter()
+            .any(|selector| selector.matches(element, ancestors))
+    }
+
+    /// Index keys for the rightmost compound of each selector in the list,
+    /// `None` when one of them has nothing to index on.
+    fn keys(&self) -> Option<Vec<String>> {
+        self.selectors
+            .iter()
+            .map(|selector| {
+                let subject = &selector.subject;
+                if let Some(id) = &subject.id {
+                    Some(format!("#{}", id))
+                } else if let Some(class) = subject.classes.first() {
+                    Some(format!(".{}", class))
+                } else {
+                    subject.tag.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+impl ComplexSelector {
+    fn matches(&self, element: &Element, ancestors: &[&Element]) -> bool {
+        self.subject.matches(element) && self.matches_ancestors(0, ancestors)
+    }
+
+    /// Matches `self.ancestors[index..]` against what's left of the chain.
+    fn matches_ancestors(&self, index: usize, ancestors: &[&Element]) -> bool {
+        let Some((combinator, compound)) = self.ancestors.get(index) else {
+            return true;
+        };
+        let Some((parent, rest)) = ancestors.split_last() else {
+            return false;
+        };
+
+        match combinator {
+            Combinator::Child => {
+                compound.matches(parent) && self.matches_ancestors(index + 1, rest)
+            }
+            Combinator::Descendant => (0..ancestors.len()).rev().any(|i| {
+                compound.matches(ancestors[i]) && self.matches_ancestors(index + 1, &ancestors[..i])
+            }),
+        }
+    }
+}
+
+impl Compound {
+    fn matches(&self, element: &Element) -> bool {
+        if self.tag.as_ref().is_some_and(|tag| *tag != element.tag) {
+            return false;
+        }
+        if self
+            .id
+            .as_ref()
+            .is_some_and(|id| element.id() != Some(id.as_str()))
+        {
+            return false;
+        }
+        if !self
+            .classes
+            .iter()
+            .all(|class| element.classes().any(|c| c == class))
+        {
+            return false;
+        }
+
+        self.attributes.iter().all(|(name, op, ignore_case)| {
+            let Some(value) = element.attr(name) else {
+                return false;
+            };
+            let fold = |s: &str| {
+                if *ignore_case {
+                    s.to_lowercase()
+                } else {
+                    s.to_string()
+                }
+            };
+            let value = fold(value);
+            match op {
+                AttributeOp::Exists => true,
+                AttributeOp::Equals(expected) => value == fold(expected),
+                AttributeOp::Prefix(prefix) => value.starts_with(&fold(prefix)),
+                AttributeOp::Suffix(suffix) => value.ends_with(&fold(suffix)),
+                AttributeOp::Contains(part) => value.contains(&fold(part)),
+                AttributeOp::Word(word) => value.split_whitespace().any(|w| w == fold(word)),
+                AttributeOp::DashPrefix(prefix) => {
+                    let prefix = fold(prefix);
+                    value == prefix || value.starts_with(&format!("{}-", prefix))
+                }
+            }
+        })
+    }
+}
+
+fn split_outside_brackets(input: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut quote, mut start) = (0, None, 0);
+    for (i, c) in input.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {}
+            (c, None) if c == separator && depth == 0 => {
+                parts.push(&input[start..i]);
+                start = i + c.len_utf8();
+            }
+            ('[' | '(', None) => depth += 1,
+            (']' | ')', None) => depth -= 1,
+            _ => {}
+        }
+    }
+    parts.push(&input[start..]);
+    parts
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Length of the identifier at the start of `input`, counting `\x` escapes.
+fn ident_len(input: &str) -> usize {
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            if chars.next().is_none() {
+                return i;
+            }
+        } else if !is_ident_char(c) {
+            return i;
+        }
+    }
+    input.len()
+}
+
+fn parse_complex(input: &str) -> Option<ComplexSelector> {
+    if input.is_empty() {
+        return None;
+    }
+
+    let mut compounds: Vec<Compound> = Vec::new();
+    let mut combinators: Vec<Combinator> = Vec::new();
+    let mut rest = input;
+
+    loop {
+        let (compound, after) = parse_compound(rest)?;
+        compounds.push(compound);
+
+        let trimmed = after.trim_start();
+        if trimmed.is_empty() {
+            break;
+        }
+        rest = if let Some(after) = trimmed.strip_prefix('>') {
+            combinators.push(Combinator::Child);
+            after.trim_start()
+        } else if trimmed.starts_with(['+', '~']) {
+            // sibling combinators need more of the tree than we keep around
+            return None;
+        } else if trimmed.len() < after.len() {
+            combinators.push(Combinator::Descendant);
+            trimmed
+        } else {
+            return None;
+        };
+    }
+
+    let subject = compounds.pop()?;
+    let ancestors = combinators
+        .into_iter()
+        .rev()
+        .zip(compounds.into_iter().rev())
+        .collect();
+    Some(ComplexSelector { subject, ancestors })
+}
+
+fn parse_compound(input: &str) -> Option<(Compound, &str)> {
+    let mut compound = Compound::default();
+    let mut rest = input;
+
+    let tag_len = ident_len(rest);
+    if tag_len > 0 {
+        compound.tag = Some(rest[..tag_len].to_ascii_lowercase());
+        rest = &rest[tag_len..];
+    } else if let Some(after) = rest.strip_prefix('*') {
+        rest = after;
+    }
+
+    loop {
+        if let Some(after) = rest.strip_prefix('#') {
+            let len = ident_len(after);
+            compound.id = Some(unescape(&after[..len])?);
+            rest = &after[len..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let len = ident_len(after);
+            compound.classes.push(unescape(&after[..len])?);
+            rest = &after[len..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = split_outside_brackets(after, ']').first()?.len();
+            if end == after.len() {
+                return None;
+            }
+            compound.attributes.push(parse_attribute(&after[..end])?);
+            rest = &after[end + 1..];
+        } else if rest.starts_with(':') {
+            // pseudo-classes, including the :has() and :-abp- ones, aren't supported
+            return None;
+        } else {
+            break;
+        }
+    }
+
+    if compound == Compound::default() && !input.starts_with('*') {
+        return None;
+    }
+    Some((compound, rest))
+}
+
+fn parse_attribute(input: &str) -> Option<(String, AttributeOp, bool)> {
+    let input = input.trim();
+    let Some(op_start) = input.find(['=', '^', '$', '*', '~', '|']) else {
+        return Some((input.to_ascii_lowercase(), AttributeOp::Exists, false));
+    };
+
+    let name = input[..op_start].trim().to_ascii_lowercase();
+    if name.is_empty() || !name.chars().all(is_ident_char) {
+        return None;
+    }
+    let after = &input[op_start..];
+    let (op, value) = match after.split_once('=') {
+        Some((op, value)) if op.len() <= 1 => (op, value.trim()),
+        _ => return None,
+    };
+
+    // a trailing ` i` asks for case-insensitive matching
+    let (value, ignore_case) = match value
+        .strip_suffix(" i")
+        .or_else(|| value.strip_suffix(" I"))
+    {
+        Some(value) => (value.trim_end(), true),
+        None => (value, false),
+    };
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value)
+        .to_string();
+
+    let op = match op {
+        "" => AttributeOp::Equals(value),
+        "^" => AttributeOp::Prefix(value),
+        "$" => AttributeOp::Suffix(value),
+        "*" => AttributeOp::Contains(value),
+        "~" => AttributeOp::Word(value),
+        "|" => AttributeOp::DashPrefix(value),
+        _ => return None,
+    };
+    Some((name, op, ignore_case))
+}
+
+/// Handles the simple `\:` style escapes, not hex escapes.
+fn unescape(ident: &str) -> Option<String> {
+    if ident.is_empty() {
+        return None;
+    }
+    let mut out = String::new();
+    let mut chars = ident.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            out.push(chars.next()?);
+        } else {
+            out.push(c);
+        }
+    }
+    Some(out)
+}
+
+#[derive(Debug, Clone)]
+struct CosmeticRule {
+    selector_text: String,
+    selector: Selector,
+    list: usize,
+    exclude_domains: Vec<String>,
+}
+
+/// Element hiding rules from every list, plus `#@#` exceptions.
+#[derive(Debug, Clone, Default)]
+pub struct CosmeticFilters {
+    rules: Vec<CosmeticRule>,
+    /// Rules for every site, by id, class or tag key.
+    generic: HashMap<String, Vec<usize>>,
+    /// Generic rules with no key, checked against every element.
+    generic_unkeyed: Vec<usize>,
+    /// Rules only for some domains, by domain.
+    specific: HashMap<String, Vec<usize>>,
+    /// `#@#` exceptions: selector text to the domains they apply on, empty for everywhere.
+    exceptions: HashMap<String, Vec<String>>,
+}
+
+impl CosmeticFilters {
+    /// Adds a `##` or `#@#` rule. Returns false for lines that aren't element
+    /// hiding rules we can apply.
+    pub fn add_rule(&mut self, line: &str, list: usize) -> bool {
+        if let Some((domains, selector)) = line.split_once("#@#") {
+            let domains = domains
+                .split(',')
+                .map(|domain| domain.trim().to_ascii_lowercase())
+                .filter(|domain| !domain.is_empty() && !domain.starts_with('~'));
+            self.exceptions
+                .entry(selector.trim().to_string())
+                .or_default()
+                .extend(domains);
+            return true;
+        }
+
+        let Some((domains, selector_text)) = line.split_once("##") else {
+            return false;
+        };
+        let selector_text = selector_text.trim();
+        let Some(selector) = Selector::parse(selector_text) else {
+            return false;
+        };
+
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+        for domain in domains.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match domain.strip_prefix('~') {
+                Some(domain) => exclude.push(domain.to_ascii_lowercase()),
+                None => include.push(domain.to_ascii_lowercase()),
+            }
+        }
+
+        let index = self.rules.len();
+        match (include.is_empty(), selector.keys()) {
+            (false, _) => {
+                for domain in include {
+                    self.specific.entry(domain).or_default().push(index);
+                }
+            }
+            (true, Some(keys)) => {
+                for key in keys.into_iter().collect::<HashSet<_>>() {
+                    self.generic.entry(key).or_default().push(index);
+                }
+            }
+            (true, None) => self.generic_unkeyed.push(index),
+        }
+
+        self.rules.push(CosmeticRule {
+            selector_text: selector_text.to_string(),
+            selector,
+            list,
+            exclude_domains: exclude,
+        });
+        true
+    }
+
+    pub fn rule_count(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Removes elements hidden by the rules for `host` from the document.
+    /// Generic rules are skipped when `generic` is false (`$generichide`), and
+    /// only rules from lists `enabled_list` accepts are used. Returns how many
+    /// elements were removed.
+    pub fn apply(
+        &self,
+        document: &mut Document,
+        host: &str,
+        generic: bool,
+        enabled_list: impl Fn(usize) -> bool,
+    ) -> usize {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let domains = parent_domains(&host);
+
+        let on_page = |domain: &String| domains.iter().any(|d| d == domain);
+        let usable = |index: &usize| {
+            let rule = &self.rules[*index];
+            enabled_list(rule.list)
+                && !rule.exclude_domains.iter().any(on_page)
+                && !self
+                    .exceptions
+                    .get(&rule.selector_text)
+                    .is_some_and(|domains| domains.is_empty() || domains.iter().any(on_page))
+        };
+
+        let specific: Vec<usize> = domains
+            .iter()
+            .filter_map(|domain| self.specific.get(domain))
+            .flatten()
+            .copied()
+            .filter(usable)
+            .collect();
+        let unkeyed: Vec<usize> = if generic {
+            self.generic_unkeyed
+                .iter()
+                .copied()
+                .filter(usable)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        if specific.is_empty() && unkeyed.is_empty() && (!generic || self.generic.is_empty()) {
+            return 0;
+        }
+
+        document.remove_elements(&|element: &Element, ancestors: &[&Element]| {
+            let matches = |index: &usize| self.rules[*index].selector.matches(element, ancestors);
+
+            if specific.iter().chain(unkeyed.iter()).any(matches) {
+                return true;
+            }
+            if !generic {
+                return false;
+            }
+
+            let mut keys: Vec<String> = element
+                .classes()
+                .map(|class| format!(".{}", class))
+                .collect();
+            if let Some(id) = element.id() {
+                keys.push(format!("#{}", id));
+            }
+            keys.push(element.tag.clone());
+
+            keys.iter()
+                .filter_map(|key| self.generic.get(key))
+                .flatten()
+                .any(|index| usable(index) && matches(index))
+        })
+    }
+}
+
+/// `host` and each domain above it, `a.b.com` gives `a.b.com`, `b.com` and `com`.
+fn parent_domains(host: &str) -> Vec<String> {
+    let mut domains = vec![host.to_string()];
+    let mut rest = host;
+    while let Some((_, parent)) = rest.split_once('.') {
+        domains.push(parent.to_string());
+        rest = parent;
+    }
+    domains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><body>
+        <div id="main"><p>Story</p><div class="ad banner">Buy</div></div>
+        <aside class="sidebar"><div class="ad">Sponsored</div></aside>
+        <a href="https://tracker.example/click" data-Track="1">Link</a>
+        <span lang="en-GB">Hello</span>
+    </body></html>"#;
+
+    fn filters(rules: &[&str]) -> CosmeticFilters {
+        let mut filters = CosmeticFilters::default();
+        for rule in rules {
+            assert!(filters.add_rule(rule, 0), "rule not added: {}", rule);
+        }
+        filters
+    }
+
+    /// Applies the rules on `host` and returns what's left of the page's text.
+    fn hide(filters: &CosmeticFilters, host: &str) -> String {
+        let mut document = Document::parse(PAGE);
+        filters.apply(&mut document, host, true, |_| true);
+        document.root.text_content()
+    }
+
+    fn matches(selector: &str, html: &str) -> bool {
+        let selector = Selector::parse(selector).unwrap();
+        let document = Document::parse(html);
+        let target = document
+            .elements()
+            .into_iter()
+            .find(|element| element.attr("data-target").is_some())
+            .unwrap();
+
+        let mut chain = Vec::new();
+        fn path<'a>(parent: &'a Element, target: &Element, chain: &mut Vec<&'a Element>) -> bool {
+            for child in parent.child_elements() {
+                if std::ptr::eq(child, target) {
+                    return true;
+                }
+                chain.push(child);
+                if path(child, target, chain) {
+                    return true;
+                }
+                chain.pop();
+            }
+            false
+        }
+        assert!(path(&document.root, target, &mut chain));
+        selector.matches(target, &chain)
+    }
+
+    #[test]
+    fn parses_the_supported_subset() {
+        for selector in [
+            "div",
+            "#ad",
+            ".ad.banner",
+            "*",
+            "div > .ad",
+            "aside .ad, #main .ad",
+            r#"a[href^="https://tracker"]"#,
+            "[data-track]",
+            r"#foo\:bar",
+        ] {
+            assert!(Selector::parse(selector).is_some(), "{}", selector);
+        }
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_match() {
+        for selector in [
+            "",
+            "div:has(> .ad)",
+            ".ad:-abp-contains(Sponsored)",
+            "h1 + .ad",
+            "h1 ~ .ad",
+            "div,",
+            "[href",
+            "a[href!=x]",
+        ] {
+            assert!(Selector::parse(selector).is_none(), "{}", selector);
+        }
+    }
+
+    #[test]
+    fn matches_attributes() {
+        let html = r#"<a data-target href="https://Ads.example/x?id=1" rel="nofollow sponsored" lang="en-GB">x</a>"#;
+        assert!(matches("a[href]", html));
+        assert!(matches(r#"[href^="https://Ads"]"#, html));
+        assert!(!matches(r#"[href^="https://ads"]"#, html));
+        assert!(matches(r#"[href^="https://ads" i]"#, html));
+        assert!(matches("[href$='id=1']", html));
+        assert!(matches("[href*=example]", html));
+        assert!(matches("[rel~=sponsored]", html));
+        assert!(!matches("[rel~=spon]", html));
+        assert!(matches("[lang|=en]", html));
+        assert!(!matches("[lang|=e]", html));
+        assert!(matches("[HREF]", html));
+    }
+
+    #[test]
+    fn matches_combinators() {
+        let html =
+            r#"<section class="outer"><div id="box"><p><b data-target>x</b></p></div></section>"#;
+        assert!(matches("#box b", html));
+        assert!(matches(".outer #box p > b", html));
+        assert!(matches("p > b", html));
+        assert!(!matches("div > b", html));
+        assert!(!matches("#box > p > p > b", html));
+        assert!(!matches("aside b", html));
+        assert!(matches("aside b, div b", html));
+    }
+
+    #[test]
+    fn generic_rules_hide_on_every_site() {
+        let filters = filters(&["##.ad", "##a[data-track]"]);
+        let text = hide(&filters, "news.example");
+        assert!(text.contains("Story") && text.contains("Hello"));
+        assert!(!text.contains("Buy") && !text.contains("Sponsored") && !text.contains("Link"));
+    }
+
+    #[test]
+    fn domain_rules_only_hide_on_their_sites() {
+        let filters = filters(&["news.example##aside", "other.example##span"]);
+        assert!(!hide(&filters, "www.news.example").contains("Sponsored"));
+        assert!(hide(&filters, "www.news.example").contains("Hello"));
+        assert!(hide(&filters, "news.example.org").contains("Sponsored"));
+    }
+
+    #[test]
+    fn excluded_domains_and_exceptions() {
+        let filters = filters(&["~news.example##.ad", "##span", "news.example#@#span"]);
+        let text = hide(&filters, "news.example");
+        assert!(text.contains("Sponsored") && text.contains("Hello"));
+
+        let text = hide(&filters, "blog.example");
+        assert!(!text.contains("Sponsored") && !text.contains("Hello"));
+
+        let everywhere = self::filters(&["##span", "#@#span"]);
+        assert!(hide(&everywhere, "blog.example").contains("Hello"));
+    }
+
+    #[test]
+    fn generichide_and_disabled_lists() {
+        let mut filters = filters(&["##.ad"]);
+        filters.add_rule("news.example##span", 1);
+
+        let mut document = Document::parse(PAGE);
+        assert_eq!(
+            filters.apply(&mut document, "news.example", false, |_| true),
+            1
+        );
+        assert!(document.root.text_content().contains("Sponsored"));
+
+        let mut document = Document::parse(PAGE);
+        assert_eq!(
+            filters.apply(&mut document, "news.example", true, |list| list == 1),
+            1
+        );
+        assert!(!document.root.text_content().contains("Hello"));
+        assert!(document.root.text_content().contains("Buy"));
+    }
+
+    #[test]
+    fn skips_lines_that_are_not_hiding_rules() {
+        let mut filters = CosmeticFilters::default();
+        assert!(!filters.add_rule("||ads.example^", 0));
+        assert!(!filters.add_rule("example.com##div:has(.ad)", 0));
+        assert!(filters.add_rule("example.com##.ad", 0));
+        assert_eq!(filters.rule_count(), 1);
+    }
+
+    #[test]
+    fn parent_domains_walk_up() {
+        assert_eq!(parent_domains("a.b.com"), vec!["a.b.com", "b.com", "com"]);
+        assert_eq!(parent_domains("localhost"), vec!["localhost"]);
+    }
+}
@@ -0,0 +1,786 @@
+// content security policy
+// pages send policies in Content-Security-Policy headers and <meta http-equiv>
+// tags, saying where scripts, styles, images and so on may come from. what
+// an enforced policy doesn't allow is kept off the page, report-only policies
+// only get their violations logged
+use crate::browser::RequestType;
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE as BASE64_URL};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::fmt;
+use url::Url;
+
+/// Directives whose value is a source list, the rest are parsed without one.
+const SOURCE_LIST_DIRECTIVES: &[&str] = &[
+    "default-src",
+    "script-src",
+    "script-src-elem",
+    "script-src-attr",
+    "style-src",
+    "style-src-elem",
+    "style-src-attr",
+    "img-src",
+    "media-src",
+    "font-src",
+    "frame-src",
+    "child-src",
+    "worker-src",
+    "object-src",
+    "connect-src",
+    "manifest-src",
+    "base-uri",
+    "form-action",
+    "frame-ancestors",
+];
+
+/// Directives a `<meta>` policy isn't allowed to set.
+const HEADER_ONLY_DIRECTIVES: &[&str] = &["frame-ancestors", "report-uri", "sandbox"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CspDisposition {
+    Enforce,
+    /// From Content-Security-Policy-Report-Only, nothing gets blocked.
+    Report,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn digest(self, content: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha256 => Sha256::digest(content).to_vec(),
+            HashAlgorithm::Sha384 => Sha384::digest(content).to_vec(),
+            HashAlgorithm::Sha512 => Sha512::digest(content).to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CspPort {
+    Any,
+    Number(u16),
+}
+
+/// One entry of a source list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CspSource {
+    /// `'none'`. Matches nothing, so a list of just this allows nothing.
+    None,
+    SelfOrigin,
+    UnsafeInline,
+    UnsafeEval,
+    StrictDynamic,
+    Nonce(String),
+    /// The decoded digest.
+    Hash(HashAlgorithm, Vec<u8>),
+    /// `*`
+    Wildcard,
+    /// `https:`, lowercased without the colon.
+    Scheme(String),
+    /// `https://*.example.com:8080/path/`. The host is lowercased and may
+    /// start with `*.`.
+    Host {
+        scheme: Option<String>,
+        host: String,
+        port: Option<CspPort>,
+        path: Option<String>,
+    },
+}
+
+impl CspSource {
+    /// Parses one source expression, `None` for ones we don't understand,
+    /// which browsers skip too.
+    pub fn parse(token: &str) -> Option<Self> {
+        let lower = token.to_ascii_lowercase();
+        match lower.as_str() {
+            "'none'" => return Some(CspSource::None),
+            "'self'" => return Some(CspSource::SelfOrigin),
+            "'unsafe-inline'" => return Some(CspSource::UnsafeInline),
+            "'unsafe-eval'" => return Some(CspSource::UnsafeEval),
+            "'strict-dynamic'" => return Some(CspSource::StrictDynamic),
+            "*" => return Some(CspSource::Wildcard),
+            _ => {}
+        }
+
+        if let Some(quoted) = token
+            .strip_prefix('\'')
+            .and_then(|token| token.strip_suffix('\''))
+        {
+            let (kind, value) = quoted.split_once('-')?;
+            if value.is_empty() {
+                return None;
+            }
+            let algorithm = match kind.to_ascii_lowercase().as_str() {
+                // nonces are compared as they are, case and all
+                "nonce" => return Some(CspSource::Nonce(value.to_string())),
+                "sha256" => HashAlgorithm::Sha256,
+                "sha384" => HashAlgorithm::Sha384,
+                "sha512" => HashAlgorithm::Sha512,
+                _ => return None,
+            };
+            let digest = BASE64
+                .decode(value)
+                .or_else(|_| BASE64_URL.decode(value))
+                .ok()?;
+            return Some(CspSource::Hash(algorithm, digest));
+        }
+
+        if let Some(scheme) = lower.strip_suffix(':') {
+            return is_scheme(scheme).then(|| CspSource::Scheme(scheme.to_string()));
+        }
+
+        // paths keep their case, the rest doesn't matter
+        let (scheme, rest) = match token.split_once("://") {
+            Some((scheme, rest)) if is_scheme(scheme) => (Some(scheme.to_ascii_lowercase()), rest),
+            Some(_) => return None,
+            None => (None, token),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (
+                rest[..slash].to_ascii_lowercase(),
+                Some(rest[slash..].to_string()),
+            ),
+            None => (rest.to_ascii_lowercase(), None),
+        };
+        let (host, port) = match authority.as_str().rsplit_once(':') {
+            Some((host, "*")) => (host, Some(CspPort::Any)),
+            Some((host, port)) => (host, Some(CspPort::Number(port.parse().ok()?))),
+            None => (authority.as_str(), None),
+        };
+        let labels = host.strip_prefix("*.").unwrap_or(host);
+        let valid = !labels.is_empty()
+            && labels.split('.').all(|label| {
+                !label.is_empty()
+                    && label
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+            });
+        valid.then(|| CspSource::Host {
+            scheme,
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    /// Whether a request to `url` from a page at `page_url` matches this
+    /// source. Only the url based sources ever do.
+    fn matches_url(&self, url: &Url, page_url: &Url) -> bool {
+        match self {
+            CspSource::Wildcard => {
+                matches!(url.scheme(), "http" | "https" | "ws" | "wss")
+                    || url.scheme() == page_url.scheme()
+            }
+            CspSource::Scheme(scheme) => scheme_matches(scheme, url.scheme()),
+            CspSource::SelfOrigin => {
+                let same_host = url.host_str().is_some() && url.host_str() == page_url.host_str();
+                let same_origin = url.scheme() == page_url.scheme()
+                    && url.port_or_known_default() == page_url.port_or_known_default();
+                // http pages may load their own host over https
+                let upgraded = url.scheme() != page_url.scheme()
+                    && scheme_matches(page_url.scheme(), url.scheme())
+                    && url.port().is_none();
+                same_host && (same_origin || upgraded)
+            }
+            CspSource::Host {
+                scheme,
+                host,
+                port,
+                path,
+            } => {
+                let scheme = scheme.as_deref().unwrap_or(page_url.scheme());
+                let Some(url_host) = url.host_str() else {
+                    return false;
+                };
+                let url_host = url_host.to_ascii_lowercase();
+                let host_matches = match host.strip_prefix("*.") {
+                    Some(parent) => url_host
+                        .strip_suffix(parent)
+                        .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+                    None => url_host == *host,
+                };
+                let url_port = url.port_or_known_default();
+                let port_matches = match port {
+                    Some(CspPort::Any) => true,
+                    // 80 covers 443 too, for sites moving to https
+                    Some(CspPort::Number(port)) => {
+                        url_port == Some(*port) || (*port == 80 && url_port == Some(443))
+                    }
+                    None => url.port().is_none(),
+                };
+                let path_matches = match path.as_deref() {
+                    None | Some("/") => true,
+                    Some(path) if path.ends_with('/') => url.path().starts_with(path),
+                    Some(path) => url.path() == path,
+                };
+                scheme_matches(scheme, url.scheme()) && host_matches && port_matches && path_matches
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A directive and, for the ones that take one, its source list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CspDirective {
+    /// Lowercased.
+    pub name: String,
+    pub sources: Vec<CspSource>,
+}
+
+impl CspDirective {
+    fn allows_url(&self, url: &Url, nonce: Option<&str>, page_url: &Url, script: bool) -> bool {
+        if self.allows_nonce(nonce) {
+            return true;
+        }
+        // with 'strict-dynamic' only nonces and hashes let scripts in
+        if script && self.sources.contains(&CspSource::StrictDynamic) {
+            return false;
+        }
+        self.sources
+            .iter()
+            .any(|source| source.matches_url(url, page_url))
+    }
+
+    fn allows_inline(&self, nonce: Option<&str>, content: &str, script: bool) -> bool {
+        if self.allows_nonce(nonce) {
+            return true;
+        }
+        let hashed = self.sources.iter().any(|source| match source {
+            CspSource::Hash(algorithm, digest) => algorithm.digest(content.as_bytes()) == *digest,
+            _ => false,
+        });
+        if hashed {
+            return true;
+        }
+
+        // 'unsafe-inline' is ignored once there's a nonce or hash to use instead
+        let has_nonce_or_hash = self
+            .sources
+            .iter()
+            .any(|source| matches!(source, CspSource::Nonce(_) | CspSource::Hash(..)));
+        let strict = script && self.sources.contains(&CspSource::StrictDynamic);
+        self.sources.contains(&CspSource::UnsafeInline) && !has_nonce_or_hash && !strict
+    }
+
+    fn allows_nonce(&self, nonce: Option<&str>) -> bool {
+        let Some(nonce) = nonce.filter(|nonce| !nonce.is_empty()) else {
+            return false;
+        };
+        self.sources
+            .iter()
+            .any(|source| matches!(source, CspSource::Nonce(value) if value == nonce))
+    }
+}
+
+/// One policy, from one header value or one meta tag.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentSecurityPolicy {
+    pub directives: Vec<CspDirective>,
+    pub disposition: CspDisposition,
+    /// The policy as the page wrote it, for violation logs.
+    pub text: String,
+}
+
+impl ContentSecurityPolicy {
+    /// Parses a header value. One header can hold several policies separated
+    /// by commas, and each has to be satisfied on its own.
+    pub fn parse_header(value: &str, disposition: CspDisposition) -> Vec<Self> {
+        value
+            .split(',')
+            .filter_map(|policy| Self::parse(policy, disposition, false))
+            .collect()
+    }
+
+    /// Parses the content of a `<meta http-equiv="Content-Security-Policy">`.
+    /// Meta policies are always enforced and can't set every directive.
+    pub fn parse_meta(content: &str) -> Option<Self> {
+        Self::parse(content, CspDisposition::Enforce, true)
+    }
+
+    /// `;` separated directives, each a name and whitespace separated
+    /// values. Only the first of a repeated directive counts.
+    fn parse(text: &str, disposition: CspDisposition, meta: bool) -> Option<Self> {
+        let mut directives: Vec<CspDirective> = Vec::new();
+        for directive in text.split(';') {
+            let mut tokens = directive.split_ascii_whitespace();
+            let Some(name) = tokens.next() else {
+                continue;
+            };
+            let name = name.to_ascii_lowercase();
+            if directives.iter().any(|directive| directive.name == name)
+                || (meta && HEADER_ONLY_DIRECTIVES.contains(&name.as_str()))
+            {
+                continue;
+            }
+
+            let sources = if SOURCE_LIST_DIRECTIVES.contains(&name.as_str()) {
+                tokens.filter_map(CspSource::parse).collect()
+            } else {
+                Vec::new()
+            };
+            directives.push(CspDirective { name, sources });
+        }
+
+        (!directives.is_empty()).then(|| Self {
+            directives,
+            disposition,
+            text: text.trim().to_string(),
+        })
+    }
+
+    pub fn directive(&self, name: &str) -> Option<&CspDirective> {
+        self.directives
+            .iter()
+            .find(|directive| directive.name == name)
+    }
+
+    /// Report-only policies can't upgrade anything.
+    pub fn upgrades_insecure_requests(&self) -> bool {
+        self.disposition == CspDisposition::Enforce
+            && self.directive("upgrade-insecure-requests").is_some()
+    }
+
+    /// The directive that decides on a request of this type, falling back to
+    /// the more general ones the way the spec lays out.
+    fn effective_directive(&self, request_type: RequestType) -> Option<&CspDirective> {
+        let names: &[&str] = match request_type {
+            RequestType::Script => &["script-src-elem", "script-src", "default-src"],
+            RequestType::Stylesheet => &["style-src-elem", "style-src", "default-src"],
+            RequestType::Image => &["img-src", "default-src"],
+            RequestType::Media => &["media-src", "default-src"],
+            RequestType::Font => &["font-src", "default-src"],
+            RequestType::Subdocument => &["frame-src", "child-src", "default-src"],
+            RequestType::Object => &["object-src", "default-src"],
+            RequestType::Xhr | RequestType::WebSocket | RequestType::Ping => {
+                &["connect-src", "default-src"]
+            }
+            RequestType::Document | RequestType::Other => &[],
+        };
+        names.iter().find_map(|name| self.directive(name))
+    }
+
+    /// Checks a subresource load, giving back what broke the policy if it
+    /// isn't allowed. `nonce` is the element's nonce attribute.
+    pub fn check_request(
+        &self,
+        url: &Url,
+        request_type: RequestType,
+        nonce: Option<&str>,
+        page_url: &Url,
+    ) -> Option<CspViolation> {
+        let directive = self.effective_directive(request_type)?;
+        let nonce =
+            nonce.filter(|_| matches!(request_type, RequestType::Script | RequestType::Stylesheet));
+        let script = request_type == RequestType::Script;
+        (!directive.allows_url(url, nonce, page_url, script))
+            .then(|| self.violation(directive, url.to_string(), request_type))
+    }
+
+    /// Checks an inline `<script>` or `<style>`.
+    pub fn check_inline(
+        &self,
+        request_type: RequestType,
+        nonce: Option<&str>,
+        content: &str,
+    ) -> Option<CspViolation> {
+        let directive = self.effective_directive(request_type)?;
+        let script = request_type == RequestType::Script;
+        (!directive.allows_inline(nonce, content, script))
+            .then(|| self.violation(directive, "inline".to_string(), request_type))
+    }
+
+    fn violation(
+        &self,
+        directive: &CspDirective,
+        blocked: String,
+        request_type: RequestType,
+    ) -> CspViolation {
+        CspViolation {
+            blocked,
+            request_type,
+            directive: directive.name.clone(),
+            disposition: self.disposition,
+            policy: self.text.clone(),
+        }
+    }
+}
+
+/// Something on a page its policy didn't allow.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CspViolation {
+    /// The url, or `inline` for inline scripts and styles.
+    pub blocked: String,
+    pub request_type: RequestType,
+    pub directive: String,
+    pub disposition: CspDisposition,
+    pub policy: String,
+}
+
+impl CspViolation {
+    pub fn is_blocked(&self) -> bool {
+        self.disposition == CspDisposition::Enforce
+    }
+}
+
+impl fmt::Display for CspViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} against {} in \"{}\"",
+            if self.is_blocked() {
+                "blocked"
+            } else {
+                "would block"
+            },
+            self.request_type.as_str(),
+            self.blocked,
+            self.directive,
+            self.policy
+        )
+    }
+}
+
+fn is_scheme(scheme: &str) -> bool {
+    let mut bytes = scheme.bytes();
+    bytes.next().is_some_and(|b| b.is_ascii_alphabetic())
+        && bytes.all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.'))
+}
+
+/// Whether a source with scheme `expected` covers a url with scheme `actual`.
+/// Secure versions of a scheme are always allowed in place of it.
+fn scheme_matches(expected: &str, actual: &str) -> bool {
+    expected == actual
+        || matches!(
+            (expected, actual),
+            ("http", "https") | ("ws", "wss" | "http" | "https") | ("wss", "https")
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALERT_SHA256: &str = "bhHHL3z2vDgxUt0W3dWQOrprscmda2Y5pLsLg4GF+pI=";
+
+    fn url(input: &str) -> Url {
+        Url::parse(input).unwrap()
+    }
+
+    fn policy(text: &str) -> ContentSecurityPolicy {
+        ContentSecurityPolicy::parse_header(text, CspDisposition::Enforce)
+            .pop()
+            .unwrap()
+    }
+
+    /// Whether `policy` lets a page at https://example.com load `request`.
+    fn allows(policy: &ContentSecurityPolicy, request: &str, request_type: RequestType) -> bool {
+        policy
+            .check_request(
+                &url(request),
+                request_type,
+                None,
+                &url("https://example.com/page"),
+            )
+            .is_none()
+    }
+
+    #[test]
+    fn parses_sources() {
+        assert_eq!(CspSource::parse("'NONE'"), Some(CspSource::None));
+        assert_eq!(CspSource::parse("'self'"), Some(CspSource::SelfOrigin));
+        assert_eq!(
+            CspSource::parse("'nonce-AbC'"),
+            Some(CspSource::Nonce("AbC".to_string()))
+        );
+        assert_eq!(
+            CspSource::parse(&format!("'sha256-{}'", ALERT_SHA256)),
+            Some(CspSource::Hash(
+                HashAlgorithm::Sha256,
+                BASE64.decode(ALERT_SHA256).unwrap()
+            ))
+        );
+        assert_eq!(
+            CspSource::parse("HTTPS:"),
+            Some(CspSource::Scheme("https".to_string()))
+        );
+        assert_eq!(
+            CspSource::parse("https://*.CDN.example:*/Lib/"),
+            Some(CspSource::Host {
+                scheme: Some("https".to_string()),
+                host: "*.cdn.example".to_string(),
+                port: Some(CspPort::Any),
+                path: Some("/Lib/".to_string()),
+            })
+        );
+        assert_eq!(
+            CspSource::parse("example.com:8080"),
+            Some(CspSource::Host {
+                scheme: None,
+                host: "example.com".to_string(),
+                port: Some(CspPort::Number(8080)),
+                path: None,
+            })
+        );
+
+        for token in [
+            "'nonce-'",
+            "'md5-abc'",
+            "'sha256-***'",
+            "'unknown'",
+            "1http:",
+            "ex ample.com",
+            "a..b",
+            "example.com:port",
+            "*.",
+        ] {
+            assert_eq!(CspSource::parse(token), None, "{}", token);
+        }
+    }
+
+    #[test]
+    fn parses_headers_and_meta_tags() {
+        let policies = ContentSecurityPolicy::parse_header(
+            "default-src 'self'; SCRIPT-SRC https: 'bogus' ; script-src *, img-src 'none'",
+            CspDisposition::Report,
+        );
+        assert_eq!(policies.len(), 2);
+        assert_eq!(policies[0].disposition, CspDisposition::Report);
+        // only the first script-src counts and unknown sources are skipped
+        assert_eq!(
+            policies[0].directive("script-src").unwrap().sources,
+            vec![CspSource::Scheme("https".to_string())]
+        );
+        assert_eq!(policies[0].directives.len(), 2);
+        assert_eq!(policies[1].text, "img-src 'none'");
+
+        assert!(ContentSecurityPolicy::parse_header(" ; ,", CspDisposition::Enforce).is_empty());
+
+        let meta = ContentSecurityPolicy::parse_meta(
+            "frame-ancestors 'none'; sandbox; upgrade-insecure-requests",
+        )
+        .unwrap();
+        assert_eq!(meta.disposition, CspDisposition::Enforce);
+        assert_eq!(meta.directives.len(), 1);
+        assert!(meta.upgrades_insecure_requests());
+        assert!(ContentSecurityPolicy::parse_meta("report-uri /csp").is_none());
+
+        let report = ContentSecurityPolicy::parse_header(
+            "upgrade-insecure-requests",
+            CspDisposition::Report,
+        );
+        assert!(!report[0].upgrades_insecure_requests());
+    }
+
+    #[test]
+    fn requests_fall_back_to_default_src() {
+        let policy = policy("default-src 'self'; img-src *; script-src-elem https://cdn.example");
+
+        assert!(allows(
+            &policy,
+            "https://example.com/style.css",
+            RequestType::Stylesheet
+        ));
+        assert!(!allows(
+            &policy,
+            "https://other.example/style.css",
+            RequestType::Stylesheet
+        ));
+        assert!(allows(
+            &policy,
+            "https://other.example/a.png",
+            RequestType::Image
+        ));
+        assert!(allows(
+            &policy,
+            "https://cdn.example/app.js",
+            RequestType::Script
+        ));
+        assert!(!allows(
+            &policy,
+            "https://example.com/app.js",
+            RequestType::Script
+        ));
+        // navigations aren't covered by any fetch directive
+        assert!(allows(
+            &policy,
+            "https://other.example/",
+            RequestType::Document
+        ));
+
+        let violation = policy
+            .check_request(
+                &url("https://other.example/font.woff"),
+                RequestType::Font,
+                None,
+                &url("https://example.com/"),
+            )
+            .unwrap();
+        assert_eq!(violation.directive, "default-src");
+        assert_eq!(violation.blocked, "https://other.example/font.woff");
+        assert!(violation.is_blocked());
+    }
+
+    #[test]
+    fn sources_match_urls() {
+        let image = |sources: &str, request: &str| {
+            allows(
+                &policy(&format!("img-src {}", sources)),
+                request,
+                RequestType::Image,
+            )
+        };
+
+        assert!(image("'self'", "https://example.com/a.png"));
+        assert!(!image("'self'", "http://example.com/a.png"));
+        assert!(!image("'self'", "https://example.com:8443/a.png"));
+        assert!(!image("'self'", "https://www.example.com/a.png"));
+        assert!(!image("'none'", "https://example.com/a.png"));
+
+        assert!(image("*", "http://anything.example/a.png"));
+        assert!(!image("*", "data:image/png;base64,AAAA"));
+        assert!(image("data:", "data:image/png;base64,AAAA"));
+        assert!(image("http:", "https://example.net/a.png"));
+
+        assert!(image("*.cdn.example", "https://img.cdn.example/a.png"));
+        assert!(!image("*.cdn.example", "https://cdn.example/a.png"));
+        assert!(!image("*.cdn.example", "https://evilcdn.example/a.png"));
+        // scheme-less sources take the page's scheme, or better
+        assert!(!image("cdn.example", "http://cdn.example/a.png"));
+        assert!(image("http://cdn.example", "https://cdn.example/a.png"));
+        assert!(!image("https://cdn.example", "http://cdn.example/a.png"));
+
+        assert!(image("cdn.example:8080", "https://cdn.example:8080/a.png"));
+        assert!(!image("cdn.example", "https://cdn.example:8080/a.png"));
+        assert!(image("cdn.example:*", "https://cdn.example:8080/a.png"));
+
+        assert!(image("cdn.example/img/", "https://cdn.example/img/a.png"));
+        assert!(!image("cdn.example/img/", "https://cdn.example/a.png"));
+        assert!(image(
+            "cdn.example/img/a.png",
+            "https://cdn.example/img/a.png"
+        ));
+        assert!(!image(
+            "cdn.example/img/a.png",
+            "https://cdn.example/img/b.png"
+        ));
+    }
+
+    #[test]
+    fn self_lets_http_pages_load_their_host_over_https() {
+        let policy = policy("img-src 'self'");
+        let check = |request: &str| {
+            policy
+                .check_request(
+                    &url(request),
+                    RequestType::Image,
+                    None,
+                    &url("http://example.com/"),
+                )
+                .is_none()
+        };
+        assert!(check("http://example.com/a.png"));
+        assert!(check("https://example.com/a.png"));
+        assert!(!check("https://example.com:8443/a.png"));
+    }
+
+    #[test]
+    fn nonces_and_hashes_let_inline_code_run() {
+        let inline = |text: &str, nonce: Option<&str>, content: &str| {
+            policy(text)
+                .check_inline(RequestType::Script, nonce, content)
+                .is_none()
+        };
+        let hash = format!("'sha256-{}'", ALERT_SHA256);
+
+        assert!(!inline("script-src 'self'", None, "alert(1)"));
+        assert!(inline("script-src 'unsafe-inline'", None, "alert(1)"));
+        assert!(!inline("default-src 'none'; style-src 'self'", None, "x"));
+        // nothing covers scripts here
+        assert!(inline("style-src 'none'", None, "alert(1)"));
+
+        assert!(inline("script-src 'nonce-r4nd'", Some("r4nd"), "anything"));
+        assert!(!inline("script-src 'nonce-r4nd'", Some("R4ND"), "anything"));
+        assert!(!inline("script-src 'nonce-r4nd'", Some(""), "anything"));
+        assert!(inline(&format!("script-src {}", hash), None, "alert(1)"));
+        assert!(!inline(&format!("script-src {}", hash), None, "alert(2)"));
+
+        // 'unsafe-inline' stops counting next to a nonce, a hash or
+        // 'strict-dynamic'
+        let with_nonce = "script-src 'unsafe-inline' 'nonce-r4nd'";
+        assert!(!inline(with_nonce, None, "alert(1)"));
+        assert!(!inline(
+            &format!("script-src 'unsafe-inline' {}", hash),
+            None,
+            "x"
+        ));
+        assert!(!inline(
+            "script-src 'unsafe-inline' 'strict-dynamic'",
+            None,
+            "x"
+        ));
+        assert!(policy("style-src 'unsafe-inline'")
+            .check_inline(RequestType::Stylesheet, None, "body{}")
+            .is_none());
+    }
+
+    #[test]
+    fn strict_dynamic_only_trusts_nonces_for_scripts() {
+        let policy =
+            policy("script-src 'strict-dynamic' 'nonce-r4nd' https:; style-src 'nonce-r4nd'");
+        let page = url("https://example.com/");
+        let script = url("https://cdn.example/app.js");
+
+        assert!(policy
+            .check_request(&script, RequestType::Script, None, &page)
+            .is_some());
+        assert!(policy
+            .check_request(&script, RequestType::Script, Some("r4nd"), &page)
+            .is_none());
+        assert!(policy
+            .check_request(
+                &url("https://cdn.example/a.css"),
+                RequestType::Stylesheet,
+                Some("r4nd"),
+                &page
+            )
+            .is_none());
+        // nonces don't count for anything but scripts and styles
+        let images =
+            ContentSecurityPolicy::parse_header("img-src 'nonce-r4nd'", CspDisposition::Enforce);
+        assert!(images[0]
+            .check_request(
+                &url("https://cdn.example/a.png"),
+                RequestType::Image,
+                Some("r4nd"),
+                &page
+            )
+            .is_some());
+    }
+
+    #[test]
+    fn report_only_violations_are_not_blocked() {
+        let policy = ContentSecurityPolicy::parse_header("img-src 'none'", CspDisposition::Report)
+            .pop()
+            .unwrap();
+        let violation = policy
+            .check_request(
+                &url("https://example.com/a.png"),
+                RequestType::Image,
+                None,
+                &url("https://example.com/"),
+            )
+            .unwrap();
+        assert!(!violation.is_blocked());
+        assert_eq!(
+            violation.to_string(),
+            "would block image https://example.com/a.png against img-src in \"img-src 'none'\""
+        );
+    }
+}
@@ -0,0 +1,710 @@
+// a small html parser, enough to build an element tree out of a page
+// it doesn't try to be the html5 algorithm, it just keeps going on bad markup
+// the way browsers do: stray end tags are dropped, open elements get closed
+use serde::{Deserialize, Serialize};
+
+/// Elements that never have children or an end tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements whose contents are text until the matching end tag.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea", "title", "xmp"];
+
+/// Elements a new `<p>` or block closes, like browsers do with `<p>one<p>two`.
+const CLOSED_BY_BLOCKS: &[&str] = &["p"];
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "div",
+    "dl",
+    "fieldset",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Elements with nothing to read in them.
+const HIDDEN_ELEMENTS: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "iframe", "object", "select",
+    "button", "input", "textarea",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockKind {
+    Heading(u8),
+    Paragraph,
+    ListItem,
+    Quote,
+    Preformatted,
+}
+
+/// A run of text the page renderer lays out as one block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextBlock {
+    pub kind: BlockKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Element {
+    /// Lowercased tag name.
+    pub tag: String,
+    /// Attribute names are lowercased, values have entities decoded.
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    pub fn new(tag: &str) -> Self {
+        Self {
+            tag: tag.to_ascii_lowercase(),
+            ..Default::default()
+        }
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets an attribute, replacing its value if it's already there.
+    pub fn set_attr(&mut self, name: &str, value: &str) {
+        let name = name.to_ascii_lowercase();
+        match self.attributes.iter_mut().find(|(key, _)| *key == name) {
+            Some((_, existing)) => *existing = value.to_string(),
+            None => self.attributes.push((name, value.to_string())),
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.attr("id").filter(|id| !id.is_empty())
+    }
+
+    pub fn classes(&self) -> impl Iterator<Item = &str> {
+        self.attr("class").unwrap_or_default().split_whitespace()
+    }
+
+    /// Every element below this one, depth first.
+    pub fn descendants(&self) -> Vec<&Element> {
+        let mut found = Vec::new();
+        let mut stack: Vec<&Element> = self.child_elements().rev().collect();
+        while let Some(element) = stack.pop() {
+            found.push(element);
+            stack.extend(element.child_elements().rev());
+        }
+        found
+    }
+
+    pub fn child_elements(&self) -> impl DoubleEndedIterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// The text inside this element with whitespace collapsed.
+    pub fn text_content(&self) -> String {
+        let mut text = String::new();
+        collect_text(self, &mut text);
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+fn collect_text(element: &Element, out: &mut String) {
+    for child in &element.children {
+        match child {
+            Node::Text(text) => out.push_str(text),
+            Node::Element(element) if !matches!(element.tag.as_str(), "script" | "style") => {
+                out.push(' ');
+                collect_text(element, out);
+                out.push(' ');
+            }
+            Node::Element(_) => {}
+        }
+    }
+}
+
+fn block_kind(tag: &str) -> Option<BlockKind> {
+    Some(match tag {
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => BlockKind::Heading(tag.as_bytes()[1] - b'0'),
+        "li" | "dt" | "dd" => BlockKind::ListItem,
+        "blockquote" => BlockKind::Quote,
+        "pre" => BlockKind::Preformatted,
+        "br" | "tr" | "td" | "th" | "figcaption" | "caption" | "summary" => BlockKind::Paragraph,
+        tag if BLOCK_ELEMENTS.contains(&tag) => BlockKind::Paragraph,
+        _ => return None,
+    })
+}
+
+fn collect_blocks(
+    element: &Element,
+    kind: BlockKind,
+    current: &mut String,
+    blocks: &mut Vec<TextBlock>,
+) {
+    for child in &element.children {
+        match child {
+            Node::Text(text) => current.push_str(text),
+            Node::Element(child) if HIDDEN_ELEMENTS.contains(&child.tag.as_str()) => {}
+            Node::Element(child) => match block_kind(&child.tag) {
+                Some(child_kind) => {
+                    // whatever came before belongs to the enclosing block
+                    flush_block(current, kind, blocks);
+                    let child_kind = match child_kind {
+                        BlockKind::Paragraph => kind,
+                        child_kind => child_kind,
+                    };
+                    collect_blocks(child, child_kind, current, blocks);
+                    flush_block(current, child_kind, blocks);
+                }
+                None => {
+                    if child.tag == "img" {
+                        if let Some(alt) = child.attr("alt").filter(|alt| !alt.trim().is_empty()) {
+                            current.push_str(&format!(" [{}] ", alt.trim()));
+                        }
+                    }
+                    collect_blocks(child, kind, current, blocks);
+                }
+            },
+        }
+    }
+}
+
+fn flush_block(current: &mut String, kind: BlockKind, blocks: &mut Vec<TextBlock>) {
+    let text = if kind == BlockKind::Preformatted {
+        current.trim_matches('\n').to_string()
+    } else {
+        current.split_whitespace().collect::<Vec<_>>().join(" ")
+    };
+    current.clear();
+
+    if !text.trim().is_empty() {
+        blocks.push(TextBlock { kind, text });
+    }
+}
+
+/// A parsed page. The root is a synthetic element holding the top-level nodes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Document {
+    pub root: Element,
+}
+
+impl Document {
+    pub fn parse(html: &str) -> Self {
+        Parser::new(html).parse()
+    }
+
+    /// The first element with this tag, in document order.
+    pub fn find(&self, tag: &str) -> Option<&Element> {
+        self.root
+            .descendants()
+            .into_iter()
+            .find(|element| element.tag == tag)
+    }
+
+    pub fn elements(&self) -> Vec<&Element> {
+        self.root.descendants()
+    }
+
+    /// The readable text of the page split into blocks, in document order.
+    pub fn text_blocks(&self) -> Vec<TextBlock> {
+        let mut blocks = Vec::new();
+        let mut current = String::new();
+        let body = self.find("body").unwrap_or(&self.root);
+        collect_blocks(body, BlockKind::Paragraph, &mut current, &mut blocks);
+        flush_block(&mut current, BlockKind::Paragraph, &mut blocks);
+        blocks
+    }
+
+    /// Calls `f` on every element, depth first.
+    pub fn for_each_element_mut(&mut self, f: &mut impl FnMut(&mut Element)) {
+        fn walk(parent: &mut Element, f: &mut impl FnMut(&mut Element)) {
+            for child in parent.children.iter_mut() {
+                if let Node::Element(element) = child {
+                    f(element);
+                    walk(element, f);
+                }
+            }
+        }
+        walk(&mut self.root, f)
+    }
+
+    /// Drops every element `should_remove` picks, along with its children.
+    /// `ancestors` is the chain of parents, outermost first. Returns how many
+    /// elements were removed.
+    pub fn remove_elements(
+        &mut self,
+        should_remove: &impl Fn(&Element, &[&Element]) -> bool,
+    ) -> usize {
+        fn walk(
+            parent: &mut Element,
+            ancestors: &mut Vec<Element>,
+            should_remove: &impl Fn(&Element, &[&Element]) -> bool,
+        ) -> usize {
+            let mut removed = 0;
+            let chain: Vec<&Element> = ancestors.iter().collect();
+            let before = parent.children.len();
+            parent.children.retain(|child| match child {
+                Node::Element(element) => !should_remove(element, &chain),
+                Node::Text(_) => true,
+            });
+            removed += before - parent.children.len();
+
+            for child in parent.children.iter_mut() {
+                if let Node::Element(element) = child {
+                    // ancestors only need tag and attributes for matching
+                    ancestors.push(Element {
+                        tag: element.tag.clone(),
+                        attributes: element.attributes.clone(),
+                        children: Vec::new(),
+                    });
+                    removed += walk(element, ancestors, should_remove);
+                    ancestors.pop();
+                }
+            }
+            removed
+        }
+
+        // the synthetic root isn't an ancestor a selector can see
+        walk(&mut self.root, &mut Vec::new(), should_remove)
+    }
+}
+
+struct Parser<'a> {
+    html: &'a str,
+    pos: usize,
+    /// Open elements, the root at the bottom.
+    stack: Vec<Element>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(html: &'a str) -> Self {
+        Self {
+            html,
+            pos: 0,
+            stack: vec![Element::new("#root")],
+        }
+    }
+
+    fn parse(mut self) -> Document {
+        while self.pos < self.html.len() {
+            let rest = &self.html[self.pos..];
+
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                self.pos += 4 + comment.find("-->").map_or(comment.len(), |end| end + 3);
+            } else if rest.starts_with("<!") || rest.starts_with("<?") {
+                // doctype and processing instructions
+                self.pos += rest.find('>').map_or(rest.len(), |end| end + 1);
+            } else if rest.starts_with("</") {
+                self.end_tag();
+            } else if rest.starts_with('<')
+                && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic())
+            {
+                self.start_tag();
+            } else {
+                // text up to the next thing that could be a tag
+                let end = rest[1..].find('<').map_or(rest.len(), |end| end + 1);
+                self.push_text(&rest[..end]);
+                self.pos += end;
+            }
+        }
+
+        while self.stack.len() > 1 {
+            self.close_top();
+        }
+        Document {
+            root: self.stack.pop().unwrap_or_default(),
+        }
+    }
+
+    fn push_text(&mut self, raw: &str) {
+        let text = decode_entities(raw);
+        let parent = self.stack.last_mut().expect("root is never popped");
+        match parent.children.last_mut() {
+            Some(Node::Text(existing)) => existing.push_str(&text),
+            _ => parent.children.push(Node::Text(text)),
+        }
+    }
+
+    fn close_top(&mut self) {
+        if let Some(element) = self.stack.pop() {
+            self.stack
+                .last_mut()
+                .expect("root is never popped")
+                .children
+                .push(Node::Element(element));
+        }
+    }
+
+    fn end_tag(&mut self) {
+        let rest = &self.html[self.pos + 2..];
+        let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+        let tag = rest[..end]
+            .trim_end_matches('>')
+            .trim()
+            .to_ascii_lowercase();
+        self.pos += 2 + end;
+
+        // close everything up to the matching element, ignore the tag if nothing matches
+        if let Some(index) = self.stack.iter().rposition(|element| element.tag == tag) {
+            if index > 0 {
+                while self.stack.len() > index {
+                    self.close_top();
+                }
+            }
+        }
+    }
+
+    fn start_tag(&mut self) {
+        let rest = &self.html[self.pos + 1..];
+        let name_len = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let mut element = Element::new(&rest[..name_len]);
+        let mut cursor = name_len;
+        let mut self_closing = false;
+
+        // attributes
+        loop {
+            let attrs = &rest[cursor..];
+            let trimmed = attrs.trim_start();
+            cursor += attrs.len() - trimmed.len();
+
+            if trimmed.is_empty() {
+                break;
+            }
+            if let Some(after) = trimmed.strip_prefix('>') {
+                cursor += trimmed.len() - after.len();
+                break;
+            }
+            if let Some(after) = trimmed.strip_prefix("/>") {
+                self_closing = true;
+                cursor += trimmed.len() - after.len();
+                break;
+            }
+            if trimmed.starts_with('/') {
+                cursor += 1;
+                continue;
+            }
+
+            let name_len = trimmed
+                .find(|c: char| c.is_ascii_whitespace() || matches!(c, '=' | '>' | '/'))
+                .unwrap_or(trimmed.len())
+                .max(1);
+            let name = trimmed[..name_len].to_ascii_lowercase();
+            cursor += name_len;
+
+            let after_name = &rest[cursor..];
+            let value_start = after_name.trim_start();
+            let value = if let Some(value) = value_start.strip_prefix('=') {
+                let value = value.trim_start();
+                cursor += after_name.len() - value.len();
+                match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = value[1..].find(quote).map_or(value.len(), |end| end + 1);
+                        cursor += (end + 1).min(value.len());
+                        &value[1..end]
+                    }
+                    _ => {
+                        let end = value
+                            .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                            .unwrap_or(value.len());
+                        cursor += end;
+                        &value[..end]
+                    }
+                }
+            } else {
+                ""
+            };
+
+            if element.attr(&name).is_none() {
+                element.attributes.push((name, decode_entities(value)));
+            }
+        }
+        self.pos += 1 + cursor;
+
+        if BLOCK_ELEMENTS.contains(&element.tag.as_str()) {
+            if let Some(open) = self.stack.last() {
+                if CLOSED_BY_BLOCKS.contains(&open.tag.as_str()) {
+                    self.close_top();
+                }
+            }
+        }
+        if element.tag == "li" && self.stack.last().is_some_and(|open| open.tag == "li") {
+            self.close_top();
+        }
+
+        if self_closing || VOID_ELEMENTS.contains(&element.tag.as_str()) {
+            self.stack
+                .last_mut()
+                .expect("root is never popped")
+                .children
+                .push(Node::Element(element));
+            return;
+        }
+
+        if RAW_TEXT_ELEMENTS.contains(&element.tag.as_str()) {
+            let rest = &self.html[self.pos..];
+            let close = format!("</{}", element.tag);
+            let end = rest
+                .match_indices("</")
+                .map(|(i, _)| i)
+                .find(|&i| {
+                    rest.as_bytes()[i..]
+                        .get(..close.len())
+                        .is_some_and(|tag| tag.eq_ignore_ascii_case(close.as_bytes()))
+                })
+                .unwrap_or(rest.len());
+            let text = &rest[..end];
+            if !text.is_empty() {
+                element.children.push(Node::Text(
+                    if element.tag == "script" || element.tag == "style" {
+                        text.to_string()
+                    } else {
+                        decode_entities(text)
+                    },
+                ));
+            }
+            self.pos += end;
+            self.pos += self.html[self.pos..]
+                .find('>')
+                .map_or(self.html.len() - self.pos, |end| end + 1);
+            self.stack
+                .last_mut()
+                .expect("root is never popped")
+                .children
+                .push(Node::Element(element));
+            return;
+        }
+
+        self.stack.push(element);
+    }
+}
+
+/// Decodes the character references pages actually use.
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| {
+                let name = &rest[1..1 + end];
+                let c = match name {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some('\u{a0}'),
+                    "copy" => Some('©'),
+                    "mdash" => Some('—'),
+                    "ndash" => Some('–'),
+                    "hellip" => Some('…'),
+                    _ => name
+                        .strip_prefix("#x")
+                        .or_else(|| name.strip_prefix("#X"))
+                        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                        .or_else(|| name.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                        .and_then(char::from_u32),
+                }?;
+                Some((c, end + 2))
+            });
+
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(document: &Document) -> Vec<&str> {
+        document
+            .elements()
+            .into_iter()
+            .map(|element| element.tag.as_str())
+            .collect()
+    }
+
+    fn block(kind: BlockKind, text: &str) -> TextBlock {
+        TextBlock {
+            kind,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_nested_elements_and_attributes() {
+        let document = Document::parse(
+            r#"<!DOCTYPE html><HTML><body><div ID="main" class='a b' data-x=1 hidden><p>Hi <b>there</b></p></div></body></html>"#,
+        );
+        assert_eq!(tags(&document), vec!["html", "body", "div", "p", "b"]);
+
+        let div = document.find("div").unwrap();
+        assert_eq!(div.id(), Some("main"));
+        assert_eq!(div.classes().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(div.attr("data-x"), Some("1"));
+        assert_eq!(div.attr("hidden"), Some(""));
+        assert_eq!(div.text_content(), "Hi there");
+    }
+
+    #[test]
+    fn keeps_going_on_bad_markup() {
+        // stray end tags are dropped and unclosed elements closed at the end
+        let document = Document::parse("<div></span><p>one<p>two<ul><li>a<li>b</ul><em>open");
+        assert_eq!(
+            tags(&document),
+            vec!["div", "p", "p", "ul", "li", "li", "em"]
+        );
+
+        // the <ul> closes the second <p>, so everything ends up in the div
+        let div = document.find("div").unwrap();
+        let children: Vec<&str> = div
+            .child_elements()
+            .map(|child| child.tag.as_str())
+            .collect();
+        assert_eq!(children, vec!["p", "p", "ul", "em"]);
+        assert_eq!(document.find("ul").unwrap().child_elements().count(), 2);
+    }
+
+    #[test]
+    fn void_and_self_closing_elements_have_no_children() {
+        let document = Document::parse(r#"<p>a<br>b<img src="x.png"/>c<input>d</p>"#);
+        let p = document.find("p").unwrap();
+        assert_eq!(p.child_elements().count(), 3);
+        assert!(p.child_elements().all(|child| child.children.is_empty()));
+        assert_eq!(p.text_content(), "a b c d");
+    }
+
+    #[test]
+    fn raw_text_elements_are_not_parsed() {
+        let document = Document::parse(
+            "<script>if (a < b) { x = '<div>'; }</script><title>A &amp; B</title><style>p{}</STYLE><p>after</p>",
+        );
+        assert_eq!(tags(&document), vec!["script", "title", "style", "p"]);
+
+        let script = document.find("script").unwrap();
+        assert_eq!(
+            script.children,
+            vec![Node::Text("if (a < b) { x = '<div>'; }".to_string())]
+        );
+        assert_eq!(document.find("title").unwrap().text_content(), "A & B");
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let document = Document::parse("<p>a<!-- <b>not here</b> -->b</p>");
+        assert_eq!(tags(&document), vec!["p"]);
+        assert_eq!(document.find("p").unwrap().text_content(), "ab");
+    }
+
+    #[test]
+    fn decodes_entities() {
+        assert_eq!(decode_entities("a &amp; b &lt;c&gt;"), "a & b <c>");
+        assert_eq!(decode_entities("&#65;&#x42;&#X43;"), "ABC");
+        assert_eq!(decode_entities("&copy;&nbsp;&hellip;"), "©\u{a0}…");
+        assert_eq!(decode_entities("AT&T &unknown; &"), "AT&T &unknown; &");
+        assert_eq!(decode_entities("&#xFFFFFFFF;"), "&#xFFFFFFFF;");
+
+        let document = Document::parse(r#"<a href="?a=1&amp;b=2">x</a>"#);
+        assert_eq!(document.find("a").unwrap().attr("href"), Some("?a=1&b=2"));
+    }
+
+    #[test]
+    fn text_blocks_follow_the_structure() {
+        let document = Document::parse(
+            "<html><head><title>T</title></head><body>\
+             <h2>Heading</h2>intro <i>text</i>\
+             <ul><li>one</li><li>two</li></ul>\
+             <blockquote>quoted</blockquote>\
+             <pre>\n  keep   spacing\n</pre>\
+             <script>hidden()</script><p>with <img alt=\"a photo\"> inside</p>\
+             </body></html>",
+        );
+        assert_eq!(
+            document.text_blocks(),
+            vec![
+                block(BlockKind::Heading(2), "Heading"),
+                block(BlockKind::Paragraph, "intro text"),
+                block(BlockKind::ListItem, "one"),
+                block(BlockKind::ListItem, "two"),
+                block(BlockKind::Quote, "quoted"),
+                block(BlockKind::Preformatted, "  keep   spacing"),
+                block(BlockKind::Paragraph, "with [a photo] inside"),
+            ]
+        );
+    }
+
+    #[test]
+    fn remove_elements_sees_ancestors() {
+        let mut document = Document::parse(
+            r#"<div class="ad"><span>x</span></div><section><div class="ad">y</div></section>"#,
+        );
+        let removed = document.remove_elements(&|element, ancestors| {
+            element.classes().any(|class| class == "ad")
+                && ancestors
+                    .last()
+                    .is_some_and(|parent| parent.tag == "section")
+        });
+        assert_eq!(removed, 1);
+        assert_eq!(tags(&document), vec!["div", "span", "section"]);
+    }
+
+    #[test]
+    fn elements_can_be_edited_in_place() {
+        let mut document = Document::parse(r#"<img src="http://a/x.png"><img>"#);
+        document.for_each_element_mut(&mut |element| element.set_attr("SRC", "https://a/x.png"));
+        assert!(document
+            .elements()
+            .iter()
+            .all(|element| element.attr("src") == Some("https://a/x.png")
+                && element.attributes.len() == 1));
+    }
+}
@@ -0,0 +1,1370 @@
+// adblock plus / easylist network filters
+// lists are parsed into filters and indexed by a token from each pattern,
+// so a request only gets checked against filters sharing a token with its url
+// hosts files skip all that and go in a plain hostname lookup
+use crate::browser::{CosmeticFilters, Document};
+use crate::utils::{domains_within_site, is_third_party};
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use url::{Position, Url};
+
+/// Used when there are no lists in the filters directory yet, roughly what we
+/// blocked before filter lists were supported.
+const DEFAULT_AD_FILTERS: &str = "\
+! Title: Bagel default ads
+||doubleclick.net^
+||googleadservices.com^
+||googlesyndication.com^
+||facebook.com^$third-party
+||connect.facebook.net^
+||outbrain.com^
+||taboola.com^
+/ads/*
+";
+
+const DEFAULT_TRACKING_FILTERS: &str = "\
+! Title: Bagel default tracking
+||google-analytics.com^
+||scorecardresearch.com^
+.analytics.
+.tracker.
+.telemetry.
+";
+
+/// Whether a list blocks ads or trackers, so each can follow its own setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterCategory {
+    Ads,
+    Tracking,
+    /// The user's own rules, on whenever any blocking is.
+    Custom,
+}
+
+impl FilterCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterCategory::Ads => "ads",
+            FilterCategory::Tracking => "tracking",
+            FilterCategory::Custom => "custom",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ads" => Some(FilterCategory::Ads),
+            "tracking" => Some(FilterCategory::Tracking),
+            "custom" => Some(FilterCategory::Custom),
+            _ => None,
+        }
+    }
+
+    /// Guesses from a list's name, EasyPrivacy and friends are about tracking.
+    pub fn from_list_name(name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        if name.contains("privacy") || name.contains("track") {
+            FilterCategory::Tracking
+        } else {
+            FilterCategory::Ads
+        }
+    }
+}
+
+/// What a list is written as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListKind {
+    /// Adblock Plus syntax, like EasyList.
+    Filters,
+    /// `/etc/hosts` lines or one domain per line.
+    Hosts,
+    /// The user's own rules, filters and hosts lines mixed.
+    Custom,
+}
+
+impl ListKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListKind::Filters => "filters",
+            ListKind::Hosts => "hosts",
+            ListKind::Custom => "custom",
+        }
+    }
+
+    /// Goes by the first line that isn't a comment. Hosts files start with
+    /// `0.0.0.0 some.host` or a bare hostname, anything else is a filter list.
+    pub fn detect(contents: &str) -> Self {
+        let first = contents
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('!') && !line.starts_with('#'));
+
+        match first {
+            Some(line) if !line.starts_with('[') && hosts_entries(line).is_some() => {
+                ListKind::Hosts
+            }
+            _ => ListKind::Filters,
+        }
+    }
+}
+
+/// What a request is for, matched against `$script`, `$image` and so on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestType {
+    Document,
+    Subdocument,
+    Script,
+    Stylesheet,
+    Image,
+    Font,
+    Media,
+    Xhr,
+    WebSocket,
+    Ping,
+    Object,
+    Other,
+}
+
+impl RequestType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestType::Document => "document",
+            RequestType::Subdocument => "subdocument",
+            RequestType::Script => "script",
+            RequestType::Stylesheet => "stylesheet",
+            RequestType::Image => "image",
+            RequestType::Font => "font",
+            RequestType::Media => "media",
+            RequestType::Xhr => "xhr",
+            RequestType::WebSocket => "websocket",
+            RequestType::Ping => "ping",
+            RequestType::Object => "object",
+            RequestType::Other => "other",
+        }
+    }
+
+    fn from_option(name: &str) -> Option<Self> {
+        Some(match name {
+            "document" | "doc" => RequestType::Document,
+            "subdocument" | "frame" => RequestType::Subdocument,
+            "script" => RequestType::Script,
+            "stylesheet" | "css" => RequestType::Stylesheet,
+            "image" => RequestType::Image,
+            "font" => RequestType::Font,
+            "media" => RequestType::Media,
+            "xmlhttprequest" | "xhr" => RequestType::Xhr,
+            "websocket" => RequestType::WebSocket,
+            "ping" | "beacon" => RequestType::Ping,
+            "object" | "object-subrequest" => RequestType::Object,
+            "other" => RequestType::Other,
+            _ => return None,
+        })
+    }
+
+    fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+const ALL_TYPES: u16 = (1 << (RequestType::Other as u16 + 1)) - 1;
+
+/// A request as the filters see it.
+#[derive(Debug, Clone)]
+pub struct FilterRequest<'a> {
+    pub url: &'a Url,
+    /// The top-level page that made the request, `None` for navigations.
+    pub source: Option<&'a Url>,
+    pub request_type: RequestType,
+}
+
+impl<'a> FilterRequest<'a> {
+    pub fn new(url: &'a Url, source: Option<&'a Url>, request_type: RequestType) -> Self {
+        Self {
+            url,
+            source,
+            request_type,
+        }
+    }
+}
+
+/// The filter that decided a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterMatch {
+    /// The rule as written in the list.
+    pub rule: String,
+    pub list: String,
+    pub kind: ListKind,
+    pub category: FilterCategory,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterResult {
+    NoMatch,
+    Blocked(FilterMatch),
+    /// A blocking filter matched but an `@@` exception let the request through.
+    Allowed(FilterMatch),
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    /// Adblock syntax with `*` and `^`, already lowercased unless `$match-case`.
+    Glob(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Anchor {
+    None,
+    /// `|` at the start, the pattern starts the url.
+    Start,
+    /// `||`, the pattern starts at the beginning of a host label.
+    Host,
+}
+
+/// What an `@@...$elemhide` or `$generichide` exception turns off on a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HideException {
+    All,
+    Generic,
+}
+
+#[derive(Debug, Clone)]
+struct NetworkFilter {
+    rule: String,
+    list: usize,
+    pattern: Pattern,
+    anchor: Anchor,
+    end_anchor: bool,
+    exception: bool,
+    important: bool,
+    hide_exception: Option<HideException>,
+    match_case: bool,
+    third_party: Option<bool>,
+    include_domains: Vec<String>,
+    exclude_domains: Vec<String>,
+    types: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct FilterList {
+    pub name: String,
+    pub kind: ListKind,
+    pub category: FilterCategory,
+    pub stats: ListStats,
+}
+
+/// Filters bucketed by one token each. Filters without a usable token are
+/// checked for every request.
+#[derive(Debug, Clone, Default)]
+struct TokenIndex {
+    buckets: HashMap<String, Vec<usize>>,
+    generic: Vec<usize>,
+}
+
+impl TokenIndex {
+    fn insert(&mut self, token: Option<String>, filter: usize) {
+        match token {
+            Some(token) => self.buckets.entry(token).or_default().push(filter),
+            None => self.generic.push(filter),
+        }
+    }
+
+    fn candidates<'a>(&'a self, tokens: &'a [&str]) -> impl Iterator<Item = usize> + 'a {
+        tokens
+            .iter()
+            .filter_map(|token| self.buckets.get(*token))
+            .flatten()
+            .chain(self.generic.iter())
+            .copied()
+    }
+}
+
+/// How many lines of a list turned into filters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListStats {
+    pub filters: usize,
+    /// Hostnames from hosts lines and bare domains.
+    pub hosts: usize,
+    /// Element hiding rules.
+    pub cosmetic: usize,
+    /// Rules we can't apply, like scriptlets and unknown options.
+    pub unsupported: usize,
+}
+
+#[derive(Clone, Default)]
+pub struct FilterEngine {
+    lists: Vec<FilterList>,
+    filters: Vec<NetworkFilter>,
+    blocking: TokenIndex,
+    exceptions: TokenIndex,
+    /// `$elemhide` and `$generichide` exceptions, checked against the page.
+    hide_exceptions: Vec<usize>,
+    cosmetic: CosmeticFilters,
+    /// Blocked hostnames from hosts lists, to the lists that have them.
+    hosts: HashMap<String, Vec<usize>>,
+}
+
+// the filter vec is huge with easylist loaded
+impl std::fmt::Debug for FilterEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FilterEngine")
+            .field("lists", &self.lists.len())
+            .field("filters", &self.filters.len())
+            .field("hosts", &self.hosts.len())
+            .field("cosmetic", &self.cosmetic.rule_count())
+            .finish()
+    }
+}
+
+impl FilterEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The built-in lists, for when no others are installed.
+    pub fn with_defaults() -> Self {
+        let mut engine = Self::new();
+        engine.add_list("default-ads", FilterCategory::Ads, DEFAULT_AD_FILTERS);
+        engine.add_list(
+            "default-tracking",
+            FilterCategory::Tracking,
+            DEFAULT_TRACKING_FILTERS,
+        );
+        engine
+    }
+
+    /// Adds every `.txt` list in `dir` that `enabled` says yes to, named after
+    /// the file. A missing directory just has no lists.
+    pub fn add_dir(&mut self, dir: &Path, enabled: impl Fn(&str) -> bool) -> Result<()> {
+        let mut paths: Vec<_> = match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        paths.sort();
+
+        for path in paths {
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if !enabled(name) {
+                continue;
+            }
+            let contents = match std::fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    log::warn!("Failed to read filter list {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            let stats = self.add_list(name, FilterCategory::from_list_name(name), &contents);
+            log::info!(
+                "Loaded filter list {}: {} filters, {} hosts, {} element hiding, {} unsupported",
+                name,
+                stats.filters,
+                stats.hosts,
+                stats.cosmetic,
+                stats.unsupported
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Parses a list, in Adblock Plus or hosts format going by
+    /// [`ListKind::detect`], and adds its filters.
+    pub fn add_list(&mut self, name: &str, category: FilterCategory, contents: &str) -> ListStats {
+        self.add_list_of_kind(name, ListKind::detect(contents), category, contents)
+    }
+
+    /// Adds the user's own rules. Filters, hosts lines and bare domains can
+    /// all go in there.
+    pub fn add_custom_list(&mut self, name: &str, contents: &str) -> ListStats {
+        self.add_list_of_kind(name, ListKind::Custom, FilterCategory::Custom, contents)
+    }
+
+    pub fn add_list_of_kind(
+        &mut self,
+        name: &str,
+        kind: ListKind,
+        category: FilterCategory,
+        contents: &str,
+    ) -> ListStats {
+        let list = self.lists.len();
+        let mut stats = ListStats::default();
+        for line in contents.lines() {
+            let line = line.trim();
+            // comments and the [Adblock Plus 2.0] header
+            if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+                continue;
+            }
+
+            if kind == ListKind::Hosts {
+                let line = line.split('#').next().unwrap_or_default().trim();
+                match hosts_entries(line) {
+                    Some(hosts) => stats.hosts += self.add_hosts(hosts, list),
+                    None if line.is_empty() => {}
+                    None => stats.unsupported += 1,
+                }
+                continue;
+            }
+
+            let cosmetic = line.contains("##") || line.contains("#@#");
+            if kind == ListKind::Custom && !cosmetic {
+                if let Some(hosts) = hosts_entries(line) {
+                    stats.hosts += self.add_hosts(hosts, list);
+                    continue;
+                }
+                // hosts style comments
+                if line.starts_with('#') {
+                    continue;
+                }
+            }
+
+            if cosmetic {
+                if self.cosmetic.add_rule(line, list) {
+                    stats.cosmetic += 1;
+                } else {
+                    stats.unsupported += 1;
+                }
+                continue;
+            }
+
+            match parse_network_filter(line, list) {
+                Some(filter) => {
+                    let index = self.filters.len();
+                    let token = filter_token(&filter);
+                    if filter.hide_exception.is_some() {
+                        self.hide_exceptions.push(index);
+                    } else if filter.exception {
+                        self.exceptions.insert(token, index);
+                    } else {
+                        self.blocking.insert(token, index);
+                    }
+                    self.filters.push(filter);
+                    stats.filters += 1;
+                }
+                None => stats.unsupported += 1,
+            }
+        }
+
+        self.lists.push(FilterList {
+            name: name.to_string(),
+            kind,
+            category,
+            stats,
+        });
+        stats
+    }
+
+    /// Returns how many hostnames were new to this list.
+    fn add_hosts(&mut self, hosts: Vec<String>, list: usize) -> usize {
+        let mut added = 0;
+        for host in hosts {
+            let lists = self.hosts.entry(host).or_default();
+            if !lists.contains(&list) {
+                lists.push(list);
+                added += 1;
+            }
+        }
+        added
+    }
+
+    pub fn lists(&self) -> &[FilterList] {
+        self.lists.as_slice()
+    }
+
+    pub fn list_count(&self) -> usize {
+        self.lists.len()
+    }
+
+    pub fn filter_count(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Checks a request against lists in the `enabled` categories.
+    pub fn check(&self, request: &FilterRequest, enabled: &[FilterCategory]) -> FilterResult {
+        let context = MatchContext::new(request);
+        let tokens = context.tokens();
+        let is_enabled =
+            |filter: &NetworkFilter| enabled.contains(&self.lists[filter.list].category);
+
+        let mut blocked: Option<&NetworkFilter> = None;
+        for index in self.blocking.candidates(&tokens) {
+            let filter = &self.filters[index];
+            if is_enabled(filter) && filter.matches(&context) {
+                if filter.important {
+                    return FilterResult::Blocked(self.to_match(filter));
+                }
+                blocked.get_or_insert(filter);
+            }
+        }
+        let blocked = match blocked {
+            Some(filter) => self.to_match(filter),
+            None => match self.find_host(request.url, enabled) {
+                Some(host) => host,
+                None => return FilterResult::NoMatch,
+            },
+        };
+
+        if let Some(exception) = self.find_exception(&context, &is_enabled) {
+            return FilterResult::Allowed(self.to_match(exception));
+        }
+
+        // `@@||site^$document` turns blocking off for everything on that page
+        if let Some(source) = request.source {
+            let page = FilterRequest::new(source, None, RequestType::Document);
+            if let Some(exception) = self.find_exception(&MatchContext::new(&page), &is_enabled) {
+                return FilterResult::Allowed(self.to_match(exception));
+            }
+        }
+
+        FilterResult::Blocked(blocked)
+    }
+
+    /// Drops the elements element hiding rules from the `enabled` categories
+    /// hide on the page at `page_url`. Returns how many were removed.
+    pub fn hide_elements(
+        &self,
+        document: &mut Document,
+        page_url: &Url,
+        enabled: &[FilterCategory],
+    ) -> usize {
+        let Some(host) = page_url.host_str() else {
+            return 0;
+        };
+
+        let page = FilterRequest::new(page_url, None, RequestType::Document);
+        let context = MatchContext::new(&page);
+        let mut generic = true;
+        for &index in &self.hide_exceptions {
+            let filter = &self.filters[index];
+            if enabled.contains(&self.lists[filter.list].category) && filter.matches(&context) {
+                match filter.hide_exception {
+                    Some(HideException::All) => return 0,
+                    Some(HideException::Generic) => generic = false,
+                    None => {}
+                }
+            }
+        }
+
+        self.cosmetic.apply(document, host, generic, |list| {
+            enabled.contains(&self.lists[list].category)
+        })
+    }
+
+    fn find_exception(
+        &self,
+        context: &MatchContext,
+        is_enabled: &impl Fn(&NetworkFilter) -> bool,
+    ) -> Option<&NetworkFilter> {
+        self.exceptions
+            .candidates(&context.tokens())
+            .map(|index| &self.filters[index])
+            .find(|filter| is_enabled(filter) && filter.matches(context))
+    }
+
+    /// A hosts entry for the request's host or one of its parent domains. Like
+    /// `||host^`, so blocking a domain blocks its subdomains too.
+    fn find_host(&self, url: &Url, enabled: &[FilterCategory]) -> Option<FilterMatch> {
+        if self.hosts.is_empty() {
+            return None;
+        }
+        let host = url.host_str()?;
+
+        domains_within_site(host).into_iter().find_map(|domain| {
+            let list = *self
+                .hosts
+                .get(&domain)?
+                .iter()
+                .find(|list| enabled.contains(&self.lists[**list].category))?;
+            Some(self.list_match(domain, list))
+        })
+    }
+
+    fn to_match(&self, filter: &NetworkFilter) -> FilterMatch {
+        self.list_match(filter.rule.clone(), filter.list)
+    }
+
+    fn list_match(&self, rule: String, list: usize) -> FilterMatch {
+        let list = &self.lists[list];
+        FilterMatch {
+            rule,
+            list: list.name.clone(),
+            kind: list.kind,
+            category: list.category,
+        }
+    }
+}
+
+/// Everything about a request the filters look at, worked out once.
+struct MatchContext<'a> {
+    url: &'a str,
+    url_lower: String,
+    /// Byte offsets where a host label starts, for `||` filters.
+    label_starts: Vec<usize>,
+    source_host: Option<&'a str>,
+    third_party: bool,
+    request_type: RequestType,
+}
+
+impl<'a> MatchContext<'a> {
+    fn new(request: &FilterRequest<'a>) -> Self {
+        let url = request.url.as_str();
+        let host_start = request.url[..Position::BeforeHost].len();
+        let host_end = request.url[..Position::AfterHost].len();
+
+        let mut label_starts = vec![host_start];
+        label_starts.extend(
+            url[host_start..host_end]
+                .match_indices('.')
+                .map(|(i, _)| host_start + i + 1),
+        );
+
+        Self {
+            url,
+            // parsed urls are ascii, so lowercasing keeps the offsets
+            url_lower: url.to_ascii_lowercase(),
+            label_starts,
+            source_host: request.source.and_then(Url::host_str),
+            third_party: request
+                .source
+                .is_some_and(|source| is_third_party(request.url, source)),
+            request_type: request.request_type,
+        }
+    }
+
+    /// The runs of letters, digits and `%` in the url, deduplicated.
+    fn tokens(&self) -> Vec<&str> {
+        let mut tokens: Vec<&str> = Vec::new();
+        for token in self
+            .url_lower
+            .split(|c: char| !is_token_char(c))
+            .filter(|token| token.len() >= 2)
+        {
+            if !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+        tokens
+    }
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || c == '%'
+}
+
+/// `^` in a pattern matches anything but letters, digits and `_-.%`, or the end of the url.
+fn is_separator(c: u8) -> bool {
+    !(c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'.' | b'%'))
+}
+
+impl NetworkFilter {
+    fn matches(&self, context: &MatchContext) -> bool {
+        if self.types & context.request_type.bit() == 0 {
+            return false;
+        }
+        if self
+            .third_party
+            .is_some_and(|third_party| third_party != context.third_party)
+        {
+            return false;
+        }
+        if !self.matches_domain(context.source_host) {
+            return false;
+        }
+
+        let url = if self.match_case {
+            context.url
+        } else {
+            context.url_lower.as_str()
+        };
+
+        match &self.pattern {
+            Pattern::Regex(regex) => regex.is_match(url),
+            Pattern::Glob(pattern) => match self.anchor {
+                // unanchored patterns are stored with a leading `*`
+                Anchor::Start | Anchor::None => {
+                    glob_match(pattern.as_bytes(), url.as_bytes(), self.end_anchor)
+                }
+                Anchor::Host => context.label_starts.iter().any(|&start| {
+                    glob_match(
+                        pattern.as_bytes(),
+                        &url.as_bytes()[start..],
+                        self.end_anchor,
+                    )
+                }),
+            },
+        }
+    }
+
+    fn matches_domain(&self, source_host: Option<&str>) -> bool {
+        if self.include_domains.is_empty() && self.exclude_domains.is_empty() {
+            return true;
+        }
+        let Some(host) = source_host else {
+            return self.include_domains.is_empty();
+        };
+
+        let on = |domain: &String| {
+            host == domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|rest| rest.ends_with('.'))
+        };
+        if self.exclude_domains.iter().any(on) {
+            return false;
+        }
+        self.include_domains.is_empty() || self.include_domains.iter().any(on)
+    }
+}
+
+/// Matches `pattern` at the start of `text`. Without `end_anchor` the pattern
+/// only has to match a prefix.
+fn glob_match(pattern: &[u8], text: &[u8], end_anchor: bool) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // where to resume after the last `*`
+    let mut star: Option<(usize, usize)> = None;
+
+    loop {
+        if p == pattern.len() && (!end_anchor || t == text.len()) {
+            return true;
+        }
+
+        if p < pattern.len() && t < text.len() {
+            let matched = match pattern[p] {
+                b'*' => {
+                    star = Some((p, t));
+                    p += 1;
+                    continue;
+                }
+                b'^' => is_separator(text[t]),
+                c => c == text[t],
+            };
+            if matched {
+                p += 1;
+                t += 1;
+                continue;
+            }
+        } else if p < pattern.len() && t == text.len() {
+            // only `*` and `^` can match the end of the url
+            if pattern[p..].iter().all(|&c| c == b'*' || c == b'^') {
+                return true;
+            }
+        }
+
+        match star {
+            Some((star_p, star_t)) if star_t < text.len() => {
+                star = Some((star_p, star_t + 1));
+                p = star_p + 1;
+                t = star_t + 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// Parses a network filter, `None` for anything we can't apply.
+fn parse_network_filter(line: &str, list: usize) -> Option<NetworkFilter> {
+    // procedural element hiding and snippets
+    if line.contains("#?#") || line.contains("#$#") || line.contains("#@?#") {
+        return None;
+    }
+
+    let (exception, rest) = match line.strip_prefix("@@") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    // options come after the last `$`, unless that `$` is inside a regex
+    let (pattern, options) = match rest.rfind('$') {
+        Some(i) if !(rest.starts_with('/') && rest[i..].contains('/')) => {
+            (&rest[..i], Some(&rest[i + 1..]))
+        }
+        _ => (rest, None),
+    };
+
+    let mut filter = NetworkFilter {
+        rule: line.to_string(),
+        list,
+        pattern: Pattern::Glob(String::new()),
+        anchor: Anchor::None,
+        end_anchor: false,
+        exception,
+        important: false,
+        hide_exception: None,
+        match_case: false,
+        third_party: None,
+        include_domains: Vec::new(),
+        exclude_domains: Vec::new(),
+        types: 0,
+    };
+
+    let mut excluded_types = 0;
+    let mut document = false;
+    for option in options.into_iter().flat_map(|options| options.split(',')) {
+        let option = option.trim();
+        let (negated, name) = match option.strip_prefix('~') {
+            Some(name) => (true, name),
+            None => (false, option),
+        };
+
+        match name {
+            "third-party" | "3p" => filter.third_party = Some(!negated),
+            "first-party" | "1p" => filter.third_party = Some(negated),
+            "match-case" => filter.match_case = true,
+            "important" => filter.important = true,
+            "elemhide" | "ehide" if exception => filter.hide_exception = Some(HideException::All),
+            "generichide" | "ghide" if exception => {
+                filter.hide_exception = Some(HideException::Generic)
+            }
+            "all" => filter.types = ALL_TYPES,
+            _ if name.starts_with("domain=") => {
+                for domain in name["domain=".len()..].split('|') {
+                    match domain.strip_prefix('~') {
+                        Some(domain) => filter.exclude_domains.push(domain.to_ascii_lowercase()),
+                        None => filter.include_domains.push(domain.to_ascii_lowercase()),
+                    }
+                }
+            }
+            _ => {
+                let request_type = RequestType::from_option(name)?;
+                if request_type == RequestType::Document {
+                    document = !negated;
+                }
+                if negated {
+                    excluded_types |= request_type.bit();
+                } else {
+                    filter.types |= request_type.bit();
+                }
+            }
+        }
+    }
+
+    if pattern.len() > 2 && pattern.starts_with('/') && pattern.ends_with('/') {
+        let source = &pattern[1..pattern.len() - 1];
+        let source = if filter.match_case {
+            source.to_string()
+        } else {
+            format!("(?i){}", source)
+        };
+        filter.pattern = Pattern::Regex(Regex::new(&source).ok()?);
+    } else {
+        let mut pattern = pattern;
+        if let Some(rest) = pattern.strip_prefix("||") {
+            filter.anchor = Anchor::Host;
+            pattern = rest;
+        } else if let Some(rest) = pattern.strip_prefix('|') {
+            filter.anchor = Anchor::Start;
+            pattern = rest;
+        }
+        if let Some(rest) = pattern.strip_suffix('|') {
+            filter.end_anchor = true;
+            pattern = rest;
+        }
+
+        let pattern = pattern.trim_start_matches('*');
+        if pattern.is_empty() && filter.include_domains.is_empty() && !exception {
+            // a bare `*` would block the web
+            return None;
+        }
+        let pattern = if filter.anchor == Anchor::None {
+            format!("*{}", pattern)
+        } else {
+            pattern.to_string()
+        };
+        filter.pattern = Pattern::Glob(if filter.match_case {
+            pattern
+        } else {
+            pattern.to_ascii_lowercase()
+        });
+    }
+
+    if filter.types == 0 {
+        // like uBlock, plain `||host^` filters block navigations to the host too,
+        // everything else leaves top-level documents alone unless it says $document
+        let blocks_host = matches!(&filter.pattern, Pattern::Glob(pattern)
+            if filter.anchor == Anchor::Host && !pattern.contains('/'));
+        // $elemhide and friends are matched against the page itself
+        filter.types = if document || blocks_host || filter.hide_exception.is_some() {
+            ALL_TYPES
+        } else {
+            ALL_TYPES & !RequestType::Document.bit()
+        };
+    }
+    filter.types &= !excluded_types;
+
+    Some(filter)
+}
+
+/// The longest token the url must contain for the filter to match. A token
+/// touching a `*`, or an unanchored end of the pattern, could be part of a
+/// longer token in the url, so it can't be used.
+fn filter_token(filter: &NetworkFilter) -> Option<String> {
+    let Pattern::Glob(pattern) = &filter.pattern else {
+        return None;
+    };
+    let pattern = pattern.to_ascii_lowercase();
+    let bytes = pattern.as_bytes();
+
+    let mut best: Option<&str> = None;
+    let mut start = 0;
+    while start < bytes.len() {
+        if !is_token_char(bytes[start] as char) {
+            start += 1;
+            continue;
+        }
+        let mut end = start;
+        while end < bytes.len() && is_token_char(bytes[end] as char) {
+            end += 1;
+        }
+
+        // unanchored patterns start with `*`, so this covers them too
+        let bounded_start = start == 0 || bytes[start - 1] != b'*';
+        let bounded_end = if end == bytes.len() {
+            filter.end_anchor
+        } else {
+            bytes[end] != b'*'
+        };
+        if bounded_start
+            && bounded_end
+            && end - start >= 2
+            && best.is_none_or(|best| end - start > best.len())
+        {
+            best = Some(&pattern[start..end]);
+        }
+        start = end;
+    }
+
+    best.map(str::to_string)
+}
+
+/// The hostnames on a hosts file line, already without its `#` comment:
+/// `0.0.0.0 ads.example.com` or just `ads.example.com`. `None` if it isn't
+/// one. Entries like `localhost` that hosts files carry for the machine
+/// itself are left out.
+fn hosts_entries(line: &str) -> Option<Vec<String>> {
+    let mut fields = line.split_whitespace();
+    let first = fields.next()?;
+
+    if first.parse::<IpAddr>().is_ok() {
+        return Some(
+            fields
+                .map(|host| host.trim_end_matches('.').to_ascii_lowercase())
+                .filter(|host| is_hostname(host) && host != "localhost.localdomain")
+                .collect(),
+        );
+    }
+
+    let host = first.trim_end_matches('.').to_ascii_lowercase();
+    (fields.next().is_none() && is_hostname(&host)).then(|| vec![host])
+}
+
+/// Needs a dot, so single labels like `localhost` and `broadcasthost` don't
+/// count, and ip addresses don't either.
+fn is_hostname(host: &str) -> bool {
+    host.contains('.')
+        && !host.starts_with(['.', '-'])
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        && host.parse::<IpAddr>().is_err()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(input: &str) -> Url {
+        Url::parse(input).unwrap()
+    }
+
+    fn engine(rules: &str) -> FilterEngine {
+        let mut engine = FilterEngine::new();
+        engine.add_list_of_kind("test", ListKind::Filters, FilterCategory::Ads, rules);
+        engine
+    }
+
+    /// The rule that blocked the request, `None` if it went through.
+    fn blocked_by(
+        engine: &FilterEngine,
+        request: &str,
+        page: Option<&str>,
+        request_type: RequestType,
+    ) -> Option<String> {
+        let request = url(request);
+        let page = page.map(url);
+        let request = FilterRequest::new(&request, page.as_ref(), request_type);
+        match engine.check(&request, &[FilterCategory::Ads]) {
+            FilterResult::Blocked(filter) => Some(filter.rule),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob_match(b"*ads", b"https://x.com/ads/1", false));
+        assert!(glob_match(b"ads.example.com^", b"ads.example.com/x", false));
+        assert!(glob_match(b"ads.example.com^", b"ads.example.com", false));
+        assert!(!glob_match(
+            b"ads.example.com^",
+            b"ads.example.com.evil/",
+            false
+        ));
+        assert!(glob_match(
+            b"*/banner/*.gif",
+            b"http://a.b/banner/big.gif",
+            true
+        ));
+        assert!(!glob_match(
+            b"*/banner/*.gif",
+            b"http://a.b/banner/big.gif?x",
+            true
+        ));
+        assert!(glob_match(b"*.gif", b"http://a.b/x.gif?x", false));
+        assert!(!glob_match(b"*.png", b"http://a.b/x.gif", false));
+    }
+
+    #[test]
+    fn parses_filter_options() {
+        let filter = parse_network_filter(
+            "@@||cdn.example.com^$script,~third-party,domain=a.com|~b.a.com",
+            0,
+        )
+        .unwrap();
+        assert!(filter.exception);
+        assert_eq!(filter.anchor, Anchor::Host);
+        assert_eq!(filter.third_party, Some(false));
+        assert_eq!(filter.types, RequestType::Script.bit());
+        assert_eq!(filter.include_domains, vec!["a.com"]);
+        assert_eq!(filter.exclude_domains, vec!["b.a.com"]);
+
+        let filter = parse_network_filter("|https://Ads.|$important", 0).unwrap();
+        assert_eq!(filter.anchor, Anchor::Start);
+        assert!(filter.end_anchor && filter.important);
+        assert!(matches!(&filter.pattern, Pattern::Glob(pattern) if pattern == "https://ads."));
+
+        let filter = parse_network_filter("/banner\\d+/$image", 0).unwrap();
+        assert!(matches!(filter.pattern, Pattern::Regex(_)));
+
+        assert!(parse_network_filter("*", 0).is_none());
+        assert!(parse_network_filter("ads$unknown-option", 0).is_none());
+        assert!(parse_network_filter("example.com#?#div:has(a)", 0).is_none());
+    }
+
+    #[test]
+    fn host_anchored_filters() {
+        let engine = engine("||ads.example.com^\n");
+        let page = Some("https://news.example/");
+        assert!(blocked_by(
+            &engine,
+            "https://ads.example.com/x.js",
+            page,
+            RequestType::Script
+        )
+        .is_some());
+        assert!(blocked_by(
+            &engine,
+            "https://cdn.ads.example.com/",
+            page,
+            RequestType::Image
+        )
+        .is_some());
+        assert!(blocked_by(
+            &engine,
+            "https://badads.example.com/",
+            page,
+            RequestType::Image
+        )
+        .is_none());
+        assert!(blocked_by(
+            &engine,
+            "https://ads.example.com.evil/",
+            page,
+            RequestType::Image
+        )
+        .is_none());
+        // a plain host filter blocks going to the site too
+        assert!(blocked_by(
+            &engine,
+            "https://ads.example.com/",
+            None,
+            RequestType::Document
+        )
+        .is_some());
+    }
+
+    #[test]
+    fn type_party_and_domain_options() {
+        let engine = engine(
+            "/tracker.js$script,third-party\n\
+             /banner/*$image,domain=news.example|~sports.news.example\n",
+        );
+        let page = Some("https://news.example/");
+
+        assert!(blocked_by(
+            &engine,
+            "https://cdn.net/tracker.js",
+            page,
+            RequestType::Script
+        )
+        .is_some());
+        assert!(blocked_by(
+            &engine,
+            "https://cdn.net/tracker.js",
+            page,
+            RequestType::Image
+        )
+        .is_none());
+        assert!(blocked_by(
+            &engine,
+            "https://news.example/tracker.js",
+            page,
+            RequestType::Script
+        )
+        .is_none());
+
+        assert!(blocked_by(
+            &engine,
+            "https://img.net/banner/1.png",
+            page,
+            RequestType::Image
+        )
+        .is_some());
+        assert!(blocked_by(
+            &engine,
+            "https://img.net/banner/1.png",
+            Some("https://sports.news.example/"),
+            RequestType::Image
+        )
+        .is_none());
+        assert!(blocked_by(
+            &engine,
+            "https://img.net/banner/1.png",
+            Some("https://other.example/"),
+            RequestType::Image
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn exceptions_and_important() {
+        let engine = engine(
+            "||ads.example.com^\n\
+             @@||ads.example.com/allowed/\n\
+             ||track.example.com^$important\n\
+             @@||track.example.com^\n\
+             @@||trusted.example^$document\n",
+        );
+        let page = Some("https://news.example/");
+
+        assert!(blocked_by(
+            &engine,
+            "https://ads.example.com/allowed/x.js",
+            page,
+            RequestType::Script
+        )
+        .is_none());
+        assert!(blocked_by(
+            &engine,
+            "https://ads.example.com/other.js",
+            page,
+            RequestType::Script
+        )
+        .is_some());
+        assert_eq!(
+            blocked_by(
+                &engine,
+                "https://track.example.com/p",
+                page,
+                RequestType::Image
+            )
+            .as_deref(),
+            Some("||track.example.com^$important")
+        );
+        assert!(blocked_by(
+            &engine,
+            "https://ads.example.com/x.js",
+            Some("https://trusted.example/"),
+            RequestType::Script
+        )
+        .is_none());
+
+        let request = url("https://ads.example.com/allowed/x.js");
+        let page = url("https://news.example/");
+        let result = engine.check(
+            &FilterRequest::new(&request, Some(&page), RequestType::Script),
+            &[FilterCategory::Ads],
+        );
+        assert!(
+            matches!(result, FilterResult::Allowed(filter) if filter.rule == "@@||ads.example.com/allowed/")
+        );
+    }
+
+    #[test]
+    fn disabled_categories_are_skipped() {
+        let engine = engine("||ads.example.com^\n");
+        let request = url("https://ads.example.com/");
+        let page = url("https://news.example/");
+        let request = FilterRequest::new(&request, Some(&page), RequestType::Image);
+        assert_eq!(
+            engine.check(&request, &[FilterCategory::Tracking]),
+            FilterResult::NoMatch
+        );
+    }
+
+    #[test]
+    fn filters_are_indexed_by_their_longest_safe_token() {
+        let token = |rule: &str| filter_token(&parse_network_filter(rule, 0).unwrap());
+        assert_eq!(token("||ads.example.com^").as_deref(), Some("example"));
+        assert_eq!(token("/banner/*").as_deref(), Some("banner"));
+        assert_eq!(token("ad*vert"), None);
+        assert_eq!(token("/ad.$image").as_deref(), Some("ad"));
+        assert_eq!(token("tracker"), None);
+        assert_eq!(token("/^ad/$image"), None);
+    }
+
+    #[test]
+    fn hosts_lines() {
+        let entries = |line: &str| hosts_entries(line);
+        assert_eq!(
+            entries("0.0.0.0 ads.example.com"),
+            Some(vec!["ads.example.com".to_string()])
+        );
+        assert_eq!(
+            entries("127.0.0.1\tAds.Example.com. track.example.net"),
+            Some(vec![
+                "ads.example.com".to_string(),
+                "track.example.net".to_string()
+            ])
+        );
+        assert_eq!(
+            entries(":: ads.example.com"),
+            Some(vec!["ads.example.com".to_string()])
+        );
+        assert_eq!(
+            entries("ads.example.com"),
+            Some(vec!["ads.example.com".to_string()])
+        );
+
+        // the machine's own entries are dropped but the line is still a hosts line
+        assert_eq!(entries("127.0.0.1 localhost"), Some(Vec::new()));
+        assert_eq!(entries("127.0.0.1 localhost.localdomain"), Some(Vec::new()));
+        assert_eq!(entries("255.255.255.255 broadcasthost"), Some(Vec::new()));
+        assert_eq!(entries("0.0.0.0 0.0.0.0"), Some(Vec::new()));
+
+        assert_eq!(entries(""), None);
+        assert_eq!(entries("localhost"), None);
+        assert_eq!(entries("ads.example.com extra"), None);
+        assert_eq!(entries("||ads.example.com^"), None);
+        assert_eq!(entries("/banner/*.gif"), None);
+        assert_eq!(entries("-ad-banner."), None);
+        assert_eq!(entries("example.com##.ad"), None);
+    }
+
+    #[test]
+    fn list_kind_is_detected() {
+        assert_eq!(
+            ListKind::detect("# hosts\n\n0.0.0.0 ads.example.com\n"),
+            ListKind::Hosts
+        );
+        assert_eq!(
+            ListKind::detect("! domains\nads.example.com\n"),
+            ListKind::Hosts
+        );
+        assert_eq!(
+            ListKind::detect("[Adblock Plus 2.0]\n||ads.example.com^\n"),
+            ListKind::Filters
+        );
+        assert_eq!(ListKind::detect("/banner/ad.\n"), ListKind::Filters);
+        assert_eq!(ListKind::detect(""), ListKind::Filters);
+    }
+
+    #[test]
+    fn hosts_lists_block_hosts_and_subdomains() {
+        let mut engine = FilterEngine::new();
+        let stats = engine.add_list(
+            "hosts",
+            FilterCategory::Tracking,
+            "# comment\n127.0.0.1 localhost\n0.0.0.0 ads.example.com # inline\n0.0.0.0 two.example one.example\nnot a hosts line\n",
+        );
+        assert_eq!(stats.hosts, 3);
+        assert_eq!(stats.unsupported, 1);
+
+        let check = |request: &str| {
+            engine.check(
+                &FilterRequest::new(&url(request), None, RequestType::Script),
+                &[FilterCategory::Tracking],
+            )
+        };
+        match check("https://cdn.ads.example.com/x.js") {
+            FilterResult::Blocked(found) => {
+                assert_eq!(found.rule, "ads.example.com");
+                assert_eq!(found.list, "hosts");
+                assert_eq!(found.kind, ListKind::Hosts);
+            }
+            other => panic!("not blocked: {:?}", other),
+        }
+        assert!(matches!(
+            check("https://one.example/"),
+            FilterResult::Blocked(_)
+        ));
+        assert_eq!(check("https://example.com/"), FilterResult::NoMatch);
+        assert_eq!(
+            check("https://notads.example.com.evil/"),
+            FilterResult::NoMatch
+        );
+        assert_eq!(
+            engine.check(
+                &FilterRequest::new(&url("https://ads.example.com/"), None, RequestType::Script),
+                &[FilterCategory::Ads],
+            ),
+            FilterResult::NoMatch
+        );
+    }
+
+    #[test]
+    fn custom_lists_mix_rules_and_domains() {
+        let mut engine = engine("@@||ads.example.com/ok/*\n");
+        let stats = engine.add_custom_list(
+            "mine",
+            "# my rules\nads.example.com\n0.0.0.0 track.example\n||cdn.example/ad/*\nnews.example##.promo\n",
+        );
+        assert_eq!(stats.hosts, 2);
+        assert_eq!(stats.filters, 1);
+        assert_eq!(stats.cosmetic, 1);
+        assert_eq!(stats.unsupported, 0);
+
+        let check = |request: &str| {
+            engine.check(
+                &FilterRequest::new(&url(request), None, RequestType::Image),
+                &[FilterCategory::Ads, FilterCategory::Custom],
+            )
+        };
+        match check("https://ads.example.com/banner.png") {
+            FilterResult::Blocked(found) => {
+                assert_eq!(found.kind, ListKind::Custom);
+                assert_eq!(found.category, FilterCategory::Custom);
+            }
+            other => panic!("not blocked: {:?}", other),
+        }
+        assert!(matches!(
+            check("https://cdn.example/ad/1.png"),
+            FilterResult::Blocked(_)
+        ));
+        // exceptions from other lists still apply to hosts entries
+        assert!(matches!(
+            check("https://ads.example.com/ok/1.png"),
+            FilterResult::Allowed(_)
+        ));
+    }
+}
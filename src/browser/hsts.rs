@@ -0,0 +1,559 @@
+// http strict transport security
+// hosts on the preload list or that sent a Strict-Transport-Security header
+// over a trusted connection only get https, and no certificate exceptions.
+// hosts that sent Public-Key-Pins also have to show one of their pinned keys
+use crate::storage::{HstsEntry, PinEntry};
+use base64::Engine;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Snapshot of part of Chromium's preload list, see the file for the format,
+/// where it comes from and how to refresh it.
+const HSTS_PRELOAD_LIST: &str = include_str!("hsts_preload.txt");
+
+static PRELOAD: OnceLock<HashMap<String, bool>> = OnceLock::new();
+
+/// Browsers cap max-age so a typo can't pin a host forever. Chromium uses a
+/// year, so do we.
+pub const HSTS_MAX_AGE_CAP: u64 = 365 * 24 * 60 * 60;
+
+/// A bad pin locks people out of a site until it expires, so pins get the
+/// 60 days RFC 7469 suggests at most.
+pub const PIN_MAX_AGE_CAP: u64 = 60 * 24 * 60 * 60;
+
+/// A parsed Strict-Transport-Security header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HstsHeader {
+    /// Seconds, already capped. Zero means forget the host.
+    pub max_age: u64,
+    pub include_subdomains: bool,
+}
+
+impl HstsHeader {
+    /// Parses a header value following RFC 6797: `;` separated directives,
+    /// case-insensitive names, optionally quoted values. Headers without a
+    /// max-age, with a bad one or with a directive given twice are ignored.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut max_age = None;
+        let mut include_subdomains = false;
+        let mut seen = Vec::new();
+
+        for directive in value.split(';') {
+            let directive = directive.trim();
+            if directive.is_empty() {
+                continue;
+            }
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (directive, None),
+            };
+            let name = name.to_ascii_lowercase();
+            if seen.contains(&name) {
+                return None;
+            }
+
+            match name.as_str() {
+                "max-age" => max_age = Some(parse_max_age(value?)?),
+                "includesubdomains" => {
+                    if value.is_some() {
+                        return None;
+                    }
+                    include_subdomains = true;
+                }
+                // unknown directives are allowed and ignored
+                _ => {}
+            }
+            seen.push(name);
+        }
+
+        Some(Self {
+            max_age: max_age?.min(HSTS_MAX_AGE_CAP),
+            include_subdomains,
+        })
+    }
+}
+
+/// A parsed Public-Key-Pins header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinHeader {
+    /// Base64 SHA-256 hashes of the pinned public keys.
+    pub pins: Vec<String>,
+    /// Seconds, already capped. Zero means forget the pins.
+    pub max_age: u64,
+    pub include_subdomains: bool,
+}
+
+impl PinHeader {
+    /// Parses a header value following RFC 7469. Same syntax as HSTS, but
+    /// `pin-sha256` may be given more than once. Pins with other hash
+    /// algorithms and report-uri are ignored, and headers without a max-age
+    /// or a pin-sha256 are too.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut pins = Vec::new();
+        let mut max_age = None;
+        let mut include_subdomains = false;
+        let mut seen = Vec::new();
+
+        for directive in value.split(';') {
+            let directive = directive.trim();
+            if directive.is_empty() {
+                continue;
+            }
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (directive, None),
+            };
+            let name = name.to_ascii_lowercase();
+
+            if name == "pin-sha256" {
+                let pin = unquote(value?);
+                let hash = base64::engine::general_purpose::STANDARD.decode(pin).ok()?;
+                if hash.len() != 32 {
+                    return None;
+                }
+                if !pins.iter().any(|known| known == pin) {
+                    pins.push(pin.to_string());
+                }
+                continue;
+            }
+            if seen.contains(&name) {
+                return None;
+            }
+
+            match name.as_str() {
+                "max-age" => max_age = Some(parse_max_age(value?)?),
+                "includesubdomains" => {
+                    if value.is_some() {
+                        return None;
+                    }
+                    include_subdomains = true;
+                }
+                _ => {}
+            }
+            seen.push(name);
+        }
+
+        if pins.is_empty() {
+            return None;
+        }
+        Some(Self {
+            pins,
+            max_age: max_age?.min(PIN_MAX_AGE_CAP),
+            include_subdomains,
+        })
+    }
+
+    /// Whether the pins can be kept for a connection whose chain has these
+    /// key hashes. RFC 7469 wants one pin for a key in the chain and a backup
+    /// pin for a key that isn't, so the site can switch keys without locking
+    /// everyone out.
+    pub fn fits_chain<'a>(&self, keys: impl IntoIterator<Item = &'a str> + Clone) -> bool {
+        let in_chain = |pin: &String| keys.clone().into_iter().any(|key| key == pin);
+        self.pins.iter().any(in_chain) && !self.pins.iter().all(in_chain)
+    }
+}
+
+/// A max-age value, optionally quoted, as plain digits.
+fn parse_max_age(value: &str) -> Option<u64> {
+    let value = unquote(value);
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    // anything too long for a u64 is well past the caps anyway
+    Some(value.parse::<u64>().unwrap_or(u64::MAX))
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// The HSTS hosts: the preload list plus what sites told us, and the pinned
+/// hosts. Shared by the security manager and the certificate verifier.
+#[derive(Debug, Clone, Default)]
+pub struct HstsStore {
+    entries: Arc<RwLock<HashMap<String, HstsEntry>>>,
+    pins: Arc<RwLock<HashMap<String, PinEntry>>>,
+}
+
+impl HstsStore {
+    pub fn set_entries(&self, entries: impl IntoIterator<Item = HstsEntry>) {
+        *self.entries.write().unwrap() = entries
+            .into_iter()
+            .map(|entry| (entry.host.clone(), entry))
+            .collect();
+    }
+
+    pub fn get(&self, host: &str) -> Option<HstsEntry> {
+        self.entries.read().unwrap().get(host).cloned()
+    }
+
+    pub fn insert(&self, entry: HstsEntry) {
+        self.entries
+            .write()
+            .unwrap()
+            .insert(entry.host.clone(), entry);
+    }
+
+    pub fn remove(&self, host: &str) {
+        self.entries.write().unwrap().remove(host);
+    }
+
+    /// Whether `host` may only be reached over https: it, or a parent domain
+    /// with include_subdomains, is preloaded or has an unexpired entry.
+    pub fn is_hsts_host(&self, host: &str) -> bool {
+        let Some(host) = hsts_host(host) else {
+            return false;
+        };
+        let preload = preload_list();
+        let entries = self.entries.read().unwrap();
+        let now = Utc::now();
+
+        let mut domain = host.as_str();
+        let mut exact = true;
+        loop {
+            if let Some(&include_subdomains) = preload.get(domain) {
+                if exact || include_subdomains {
+                    return true;
+                }
+            }
+            if let Some(entry) = entries.get(domain) {
+                if entry.expires_at > now && (exact || entry.include_subdomains) {
+                    return true;
+                }
+            }
+
+            match domain.split_once('.') {
+                Some((_, parent)) => domain = parent,
+                None => return false,
+            }
+            exact = false;
+        }
+    }
+}
+
+impl HstsStore {
+    pub fn set_pins(&self, entries: impl IntoIterator<Item = PinEntry>) {
+        *self.pins.write().unwrap() = entries
+            .into_iter()
+            .map(|entry| (entry.host.clone(), entry))
+            .collect();
+    }
+
+    pub fn get_pins(&self, host: &str) -> Option<PinEntry> {
+        self.pins.read().unwrap().get(host).cloned()
+    }
+
+    pub fn insert_pins(&self, entry: PinEntry) {
+        self.pins.write().unwrap().insert(entry.host.clone(), entry);
+    }
+
+    pub fn remove_pins(&self, host: &str) {
+        self.pins.write().unwrap().remove(host);
+    }
+
+    /// The keys `host` has to show: its own unexpired pins or else the
+    /// closest parent's with include_subdomains. `None` if it isn't pinned.
+    pub fn pins_for(&self, host: &str) -> Option<Vec<String>> {
+        let host = hsts_host(host)?;
+        let pins = self.pins.read().unwrap();
+        let now = Utc::now();
+
+        let mut domain = host.as_str();
+        let mut exact = true;
+        loop {
+            if let Some(entry) = pins.get(domain) {
+                if entry.expires_at > now && (exact || entry.include_subdomains) {
+                    return Some(entry.pins.clone());
+                }
+            }
+
+            match domain.split_once('.') {
+                Some((_, parent)) => domain = parent,
+                None => return None,
+            }
+            exact = false;
+        }
+    }
+
+    /// Whether a chain with these key hashes is fine for `host`: it isn't
+    /// pinned, or one of the keys is pinned.
+    pub fn pins_match<'a>(&self, host: &str, keys: impl IntoIterator<Item = &'a str>) -> bool {
+        match self.pins_for(host) {
+            Some(pins) => keys
+                .into_iter()
+                .any(|key| pins.iter().any(|pin| pin == key)),
+            None => true,
+        }
+    }
+}
+
+/// The host as HSTS stores it, lowercase without a trailing dot. Ip addresses
+/// can't be HSTS hosts.
+pub fn hsts_host(host: &str) -> Option<String> {
+    let host = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.');
+    if host.is_empty() || host.parse::<IpAddr>().is_ok() {
+        return None;
+    }
+    Some(host.to_ascii_lowercase())
+}
+
+fn preload_list() -> &'static HashMap<String, bool> {
+    PRELOAD.get_or_init(|| {
+        HSTS_PRELOAD_LIST
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let host = hsts_host(parts.next()?)?;
+                Some((host, parts.next() == Some("include_subdomains")))
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn header(max_age: u64, include_subdomains: bool) -> Option<HstsHeader> {
+        Some(HstsHeader {
+            max_age,
+            include_subdomains,
+        })
+    }
+
+    fn entry(host: &str, include_subdomains: bool, expires_in: Duration) -> HstsEntry {
+        HstsEntry {
+            host: host.to_string(),
+            include_subdomains,
+            expires_at: Utc::now() + expires_in,
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn parses_headers() {
+        assert_eq!(
+            HstsHeader::parse("max-age=31536000"),
+            header(31536000, false)
+        );
+        assert_eq!(
+            HstsHeader::parse("max-age=600; includeSubDomains"),
+            header(600, true)
+        );
+        assert_eq!(
+            HstsHeader::parse(" INCLUDESUBDOMAINS ;MAX-AGE = \"600\" ; preload;"),
+            header(600, true)
+        );
+        assert_eq!(HstsHeader::parse("max-age=0"), header(0, false));
+        // unknown directives, with or without values, are ignored
+        assert_eq!(
+            HstsHeader::parse("max-age=60; report-uri=\"https://x\"; preload"),
+            header(60, false)
+        );
+    }
+
+    #[test]
+    fn caps_max_age() {
+        assert_eq!(
+            HstsHeader::parse("max-age=63072000"),
+            header(HSTS_MAX_AGE_CAP, false)
+        );
+        assert_eq!(
+            HstsHeader::parse("max-age=999999999999999999999999"),
+            header(HSTS_MAX_AGE_CAP, false)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        for value in [
+            "",
+            "includeSubDomains",
+            "max-age",
+            "max-age=",
+            "max-age=-1",
+            "max-age=1.5",
+            "max-age=abc",
+            "max-age=60; max-age=60",
+            "max-age=60; includeSubDomains; includesubdomains",
+            "max-age=60; includeSubDomains=yes",
+        ] {
+            assert_eq!(HstsHeader::parse(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn hosts_are_normalized() {
+        assert_eq!(hsts_host("Example.COM.").as_deref(), Some("example.com"));
+        assert_eq!(hsts_host("127.0.0.1"), None);
+        assert_eq!(hsts_host("[::1]"), None);
+        assert_eq!(hsts_host(""), None);
+    }
+
+    #[test]
+    fn preload_list_is_loaded() {
+        let store = HstsStore::default();
+        // a whole top level domain and a single host with its subdomains
+        assert!(store.is_hsts_host("anything.dev"));
+        assert!(store.is_hsts_host("mail.google.com"));
+        assert!(store.is_hsts_host("inbox.mail.google.com"));
+        assert!(!store.is_hsts_host("example.com"));
+        assert!(!store.is_hsts_host("127.0.0.1"));
+        assert!(preload_list().len() > 10);
+    }
+
+    #[test]
+    fn entries_cover_subdomains_only_when_asked() {
+        let store = HstsStore::default();
+        store.insert(entry("example.com", false, Duration::days(1)));
+        store.insert(entry("example.net", true, Duration::days(1)));
+
+        assert!(store.is_hsts_host("example.com"));
+        assert!(store.is_hsts_host("EXAMPLE.com."));
+        assert!(!store.is_hsts_host("www.example.com"));
+        assert!(store.is_hsts_host("example.net"));
+        assert!(store.is_hsts_host("a.b.example.net"));
+        assert!(!store.is_hsts_host("notexample.net"));
+
+        store.remove("example.net");
+        assert!(!store.is_hsts_host("a.b.example.net"));
+    }
+
+    #[test]
+    fn expired_entries_do_not_count() {
+        let store = HstsStore::default();
+        store.set_entries([entry("old.example", true, Duration::seconds(-1))]);
+        assert!(!store.is_hsts_host("old.example"));
+        assert!(store.get("old.example").is_some());
+
+        store.set_entries([entry("new.example", false, Duration::hours(1))]);
+        assert!(store.get("old.example").is_none());
+        assert!(store.is_hsts_host("new.example"));
+    }
+
+    // base64 of 32 bytes of `byte`
+    fn pin(byte: u8) -> String {
+        base64::engine::general_purpose::STANDARD.encode([byte; 32])
+    }
+
+    fn pins(
+        host: &str,
+        pins: &[String],
+        include_subdomains: bool,
+        expires_in: Duration,
+    ) -> PinEntry {
+        PinEntry {
+            host: host.to_string(),
+            pins: pins.to_vec(),
+            include_subdomains,
+            expires_at: Utc::now() + expires_in,
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn parses_pin_headers() {
+        let value = format!(
+            "pin-sha256=\"{}\"; PIN-SHA256=\"{}\"; max-age=\"5000\"; includeSubDomains; \
+             report-uri=\"https://example.com/report\"; pin-sha512=\"whatever\"",
+            pin(1),
+            pin(2)
+        );
+        assert_eq!(
+            PinHeader::parse(&value),
+            Some(PinHeader {
+                pins: vec![pin(1), pin(2)],
+                max_age: 5000,
+                include_subdomains: true,
+            })
+        );
+        // the same pin twice counts once
+        let value = format!("max-age=0; pin-sha256=\"{0}\"; pin-sha256=\"{0}\"", pin(3));
+        assert_eq!(PinHeader::parse(&value).unwrap().pins, vec![pin(3)]);
+        // capped at 60 days
+        let value = format!("max-age=31536000; pin-sha256=\"{}\"", pin(1));
+        assert_eq!(PinHeader::parse(&value).unwrap().max_age, PIN_MAX_AGE_CAP);
+    }
+
+    #[test]
+    fn rejects_bad_pin_headers() {
+        let good = pin(1);
+        for value in [
+            "max-age=60".to_string(),
+            format!("pin-sha256=\"{}\"", good),
+            format!("max-age=60; pin-sha256=\"{}\"; max-age=70", good),
+            format!(
+                "max-age=60; pin-sha256=\"{}\"; includeSubDomains; includeSubDomains",
+                good
+            ),
+            "max-age=60; pin-sha256=\"not base64!\"".to_string(),
+            // a sha-1 sized hash
+            format!(
+                "max-age=60; pin-sha256=\"{}\"",
+                base64::engine::general_purpose::STANDARD.encode([1; 20])
+            ),
+            "max-age=60; pin-sha256".to_string(),
+        ] {
+            assert_eq!(PinHeader::parse(&value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn pins_need_a_key_from_the_chain_and_a_backup() {
+        let header = |pins: Vec<String>| PinHeader {
+            pins,
+            max_age: 60,
+            include_subdomains: false,
+        };
+        let chain = [pin(1), pin(2)];
+        let chain = chain.iter().map(String::as_str);
+
+        assert!(header(vec![pin(1), pin(9)]).fits_chain(chain.clone()));
+        assert!(header(vec![pin(9), pin(2)]).fits_chain(chain.clone()));
+        // no backup
+        assert!(!header(vec![pin(1)]).fits_chain(chain.clone()));
+        assert!(!header(vec![pin(1), pin(2)]).fits_chain(chain.clone()));
+        // nothing from the chain
+        assert!(!header(vec![pin(8), pin(9)]).fits_chain(chain));
+    }
+
+    #[test]
+    fn pins_apply_to_the_closest_pinned_host() {
+        let store = HstsStore::default();
+        store.set_pins([
+            pins("example.com", &[pin(1)], true, Duration::days(1)),
+            pins("www.example.com", &[pin(2)], false, Duration::days(1)),
+            pins("example.net", &[pin(3)], false, Duration::days(1)),
+            pins("old.example", &[pin(4)], true, Duration::seconds(-1)),
+        ]);
+
+        assert_eq!(store.pins_for("EXAMPLE.com."), Some(vec![pin(1)]));
+        assert_eq!(store.pins_for("a.b.example.com"), Some(vec![pin(1)]));
+        assert_eq!(store.pins_for("www.example.com"), Some(vec![pin(2)]));
+        assert_eq!(store.pins_for("www.example.net"), None);
+        assert_eq!(store.pins_for("old.example"), None);
+        assert_eq!(store.pins_for("127.0.0.1"), None);
+
+        assert!(store.pins_match("example.com", [pin(9).as_str(), pin(1).as_str()]));
+        assert!(!store.pins_match("example.com", [pin(2).as_str()]));
+        assert!(!store.pins_match("example.com", []));
+        assert!(store.pins_match("unpinned.example", [pin(9).as_str()]));
+
+        store.remove_pins("example.com");
+        assert!(store.get_pins("example.com").is_none());
+        assert!(store.pins_match("example.com", [pin(2).as_str()]));
+        // pins don't make a host an hsts host
+        assert!(!store.is_hsts_host("www.example.com"));
+    }
+}
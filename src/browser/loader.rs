@@ -0,0 +1,734 @@
+// fetches pages for the tabs
+// every request goes through our cookie jar so logins stick around
+use crate::browser::{
+    BlockedRequest, ContentSecurityPolicy, CspViolation, Document, Element, MixedContent,
+    MixedContentAction, Node, RequestType, SecurityManager, TextBlock, UntrustedCertificate,
+};
+use crate::storage::{CookieJar, CookieKeySource, CookieManager};
+use crate::utils::{BrowserConfig, CookieEncryption};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use reqwest::header::{COOKIE, LOCATION, PUBLIC_KEY_PINS, SET_COOKIE, STRICT_TRANSPORT_SECURITY};
+use reqwest::redirect::Policy;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+/// Same limit reqwest uses when it follows redirects itself.
+const MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageResponse {
+    pub url: String,
+    pub final_url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub title: Option<String>,
+    pub body: String,
+    /// The document's text with element hiding rules applied, worked out
+    /// once for the renderer.
+    pub blocks: Vec<TextBlock>,
+    pub hidden_elements: usize,
+    /// Scripts, images, frames and so on the filters kept the page from loading.
+    pub blocked_requests: Vec<BlockedRequest>,
+    /// Http subresources of an https page, blocked, upgraded or allowed.
+    pub mixed_content: Vec<MixedContent>,
+    /// What the page's content security policies didn't allow.
+    pub csp_violations: Vec<CspViolation>,
+}
+
+pub struct PageLoader {
+    client: Client,
+    cookie_jar: Arc<CookieJar>,
+    security: SecurityManager,
+}
+
+impl PageLoader {
+    pub fn new(config: &BrowserConfig, security: SecurityManager) -> Result<Self> {
+        let cookie_jar = Arc::new(CookieJar::new(
+            Self::open_cookie_manager(config)?,
+            config.privacy.cookie_policy,
+        )?);
+
+        // cookies are handled in `load` rather than by giving reqwest the jar
+        // as its cookie store, which can't tell which page a request was for
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .user_agent(concat!("Bagel/", env!("CARGO_PKG_VERSION")))
+            .use_preconfigured_tls(security.tls_config())
+            // followed in `load`, so hsts applies to every hop
+            .redirect(Policy::none())
+            .build()?;
+
+        Ok(Self {
+            client,
+            cookie_jar,
+            security,
+        })
+    }
+
+    /// Whether a load failed before any page came back, because the server
+    /// couldn't be reached or its certificate wasn't trusted. For an
+    /// upgraded http url, that's when plain http is worth offering instead.
+    pub fn is_connection_failure(error: &anyhow::Error) -> bool {
+        error.is::<UntrustedCertificate>()
+            || error
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(|e| e.is_connect() || e.is_timeout())
+    }
+
+    /// Opens cookies.db with the key the config asks for. A database still
+    /// encrypted the old way after the setting changed is opened with its old
+    /// key and re-encrypted, or decrypted when encryption was turned off.
+    fn open_cookie_manager(config: &BrowserConfig) -> Result<CookieManager> {
+        let mut manager =
+            CookieManager::new(config.data_dir(), config.privacy.auto_clear_cookies_days)?;
+        let wanted = Self::cookie_key_source(config.privacy.cookie_encryption)?;
+
+        let stored = manager.stored_kdf()?;
+        let previous = match stored.as_deref() {
+            Some(kdf) if Some(kdf) != wanted.as_ref().map(CookieKeySource::kdf) => {
+                let encryption = if kdf == "key_file" {
+                    CookieEncryption::KeyFile
+                } else {
+                    CookieEncryption::Passphrase
+                };
+                Self::cookie_key_source(encryption)?
+            }
+            _ => None,
+        };
+
+        match (previous, wanted) {
+            (Some(previous), Some(wanted)) => {
+                manager = manager.with_encryption(&previous)?;
+                manager.rotate_key(&wanted)?;
+            }
+            (Some(previous), None) => {
+                manager = manager.with_encryption(&previous)?;
+                manager.disable_encryption()?;
+                log::info!("Turned off cookie encryption");
+            }
+            (None, Some(wanted)) => {
+                manager = manager.with_encryption(&wanted)?;
+                // a new passphrase is given the same way as the current one
+                if let Ok(passphrase) = std::env::var("BAGEL_COOKIE_NEW_PASSPHRASE") {
+                    manager.rotate_key(&CookieKeySource::Passphrase(passphrase))?;
+                    log::warn!(
+                        "Changed the cookie passphrase, set BAGEL_COOKIE_PASSPHRASE to the new one"
+                    );
+                }
+            }
+            (None, None) => {}
+        }
+        Ok(manager)
+    }
+
+    fn cookie_key_source(encryption: CookieEncryption) -> Result<Option<CookieKeySource>> {
+        match encryption {
+            CookieEncryption::Off => Ok(None),
+            CookieEncryption::KeyFile => Ok(Some(CookieKeySource::KeyFile(
+                BrowserConfig::config_dir()?.join("cookies.key"),
+            ))),
+            CookieEncryption::Passphrase => {
+                // there's no prompt yet, so the passphrase comes from the environment
+                let passphrase = std::env::var("BAGEL_COOKIE_PASSPHRASE").map_err(|_| {
+                    anyhow::anyhow!("Cookie encryption needs BAGEL_COOKIE_PASSPHRASE to be set")
+                })?;
+                Ok(Some(CookieKeySource::Passphrase(passphrase)))
+            }
+        }
+    }
+
+    pub fn cookie_jar(&self) -> &Arc<CookieJar> {
+        &self.cookie_jar
+    }
+
+    pub async fn load(&self, url: &str) -> Result<PageResponse> {
+        let mut request_url = self.enforce_https(Url::parse(url)?)?;
+        // the page that redirected us, pages the user opens have none
+        let mut initiator: Option<Url> = None;
+        let mut redirects = 0;
+        let response = loop {
+            // each hop is a top-level navigation, so it's its own top-level page
+            let access = self
+                .cookie_jar
+                .access_for(&request_url, &request_url, initiator.as_ref());
+            let mut request = self.client.get(request_url.clone());
+            if let Some(cookies) = self.cookie_jar.cookie_header(&request_url, &access) {
+                request = request.header(COOKIE, cookies);
+            }
+
+            let started = Utc::now();
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(e) => return Err(self.certificate_failure(&e, started).unwrap_or(e.into())),
+            };
+
+            for header in response.headers().get_all(SET_COOKIE) {
+                if let Ok(header) = header.to_str() {
+                    self.cookie_jar
+                        .set_cookie_from_header(header, response.url(), &access);
+                }
+            }
+
+            // only the first header counts
+            if let Some(value) = response
+                .headers()
+                .get(STRICT_TRANSPORT_SECURITY)
+                .and_then(|value| value.to_str().ok())
+            {
+                self.security.record_hsts(response.url(), value);
+            }
+            if let Some(value) = response
+                .headers()
+                .get(PUBLIC_KEY_PINS)
+                .and_then(|value| value.to_str().ok())
+            {
+                self.security.record_pins(response.url(), value);
+            }
+
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .filter(|_| matches!(response.status().as_u16(), 301 | 302 | 303 | 307 | 308));
+            let Some(location) = location else {
+                break response;
+            };
+
+            redirects += 1;
+            if redirects > MAX_REDIRECTS {
+                return Err(anyhow::anyhow!("Too many redirects"));
+            }
+            let next_url = self.enforce_https(request_url.join(location)?)?;
+            initiator = Some(std::mem::replace(&mut request_url, next_url));
+        };
+
+        let final_url = response.url().clone();
+        let status = response.status().as_u16();
+        let headers: Vec<(String, String)> = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let body = response.text().await?;
+
+        let mut document = Document::parse(&body);
+
+        // the page's own policies go first. once upgrade-insecure-requests
+        // has run there's no http left for the mixed content check
+        let policies = self.security.content_security_policies(&headers, &document);
+        if policies
+            .iter()
+            .any(ContentSecurityPolicy::upgrades_insecure_requests)
+        {
+            upgrade_insecure_requests(&mut document, &final_url);
+        }
+        let csp_violations = enforce_content_security_policy(&mut document, &final_url, &policies);
+
+        // nothing past the page itself gets fetched yet, but these are the
+        // requests a real renderer would make, so they're what gets blocked
+        let mut blocked_requests = Vec::new();
+        let mut requests = Vec::new();
+        for (url, request_type) in subresources(&document, &final_url) {
+            match self
+                .security
+                .check_request(&url, Some(&final_url), request_type)
+            {
+                Some(filter) => blocked_requests.push(BlockedRequest::new(
+                    &url,
+                    Some(&final_url),
+                    request_type,
+                    filter,
+                )),
+                None => requests.push((url, request_type)),
+            }
+        }
+        self.security.record_blocked(&blocked_requests);
+
+        let mixed_content = self.security.check_mixed_content(&final_url, &requests);
+        apply_mixed_content(&mut document, &final_url, &mixed_content);
+
+        let hidden_elements = self
+            .security
+            .apply_cosmetic_filters(&mut document, &final_url);
+
+        Ok(PageResponse {
+            url: url.to_string(),
+            final_url: final_url.to_string(),
+            status,
+            headers,
+            title: document
+                .find("title")
+                .map(|title| title.text_content())
+                .filter(|title| !title.is_empty()),
+            body,
+            blocks: document.text_blocks(),
+            hidden_elements,
+            blocked_requests,
+            mixed_content,
+            csp_violations,
+        })
+    }
+
+    /// Upgrades http urls for HSTS hosts before anything is sent, and refuses
+    /// the rest in https-only mode.
+    fn enforce_https(&self, mut url: Url) -> Result<Url> {
+        if url.scheme() != "http" {
+            return Ok(url);
+        }
+        let host = url.host_str().unwrap_or_default();
+        if self.security.is_hsts_host(host) {
+            url.set_scheme("https")
+                .map_err(|_| anyhow::anyhow!("Couldn't upgrade {} to https", url))?;
+            return Ok(url);
+        }
+        if self.security.https_only() {
+            return Err(anyhow::anyhow!(
+                "Refused to load {} in HTTPS-only mode",
+                url
+            ));
+        }
+        Ok(url)
+    }
+
+    /// Turns a failed request into the certificate problem behind it, if
+    /// there was one. The verifier records every handshake, so a rejected
+    /// check from after the request started is the one that stopped it.
+    fn certificate_failure(
+        &self,
+        error: &reqwest::Error,
+        started: DateTime<Utc>,
+    ) -> Option<anyhow::Error> {
+        let host = error.url()?.host_str()?;
+        let check = self.security.certificate_for(host)?;
+        let reason = check
+            .error
+            .clone()
+            .filter(|_| check.checked_at >= started && !check.accepted)?;
+        Some(UntrustedCertificate { check, reason }.into())
+    }
+}
+
+/// The urls a page loads scripts, stylesheets, images, frames and media from,
+/// resolved against its `<base>` or its own url. Each url is listed once.
+fn subresources(document: &Document, page_url: &Url) -> Vec<(Url, RequestType)> {
+    let base = document_base(document, page_url);
+
+    let mut seen = HashSet::new();
+    document
+        .elements()
+        .into_iter()
+        .filter_map(|element| {
+            let (_, url, request_type) = subresource(element, &base)?;
+            seen.insert(url.to_string()).then_some((url, request_type))
+        })
+        .collect()
+}
+
+/// Takes blocked mixed content off the page and points upgraded mixed content
+/// at https.
+fn apply_mixed_content(document: &mut Document, page_url: &Url, mixed_content: &[MixedContent]) {
+    let with_action = |action: MixedContentAction| -> HashSet<&str> {
+        mixed_content
+            .iter()
+            .filter(|content| content.action == action)
+            .map(|content| content.url.as_str())
+            .collect()
+    };
+    let blocked = with_action(MixedContentAction::Blocked);
+    let upgraded = with_action(MixedContentAction::Upgraded);
+    if blocked.is_empty() && upgraded.is_empty() {
+        return;
+    }
+
+    let base = document_base(document, page_url);
+    document.remove_elements(&|element, _| {
+        subresource(element, &base).is_some_and(|(_, url, _)| blocked.contains(url.as_str()))
+    });
+    document.for_each_element_mut(&mut |element| {
+        let Some((attr, mut url, _)) = subresource(element, &base) else {
+            return;
+        };
+        if upgraded.contains(url.as_str()) && url.set_scheme("https").is_ok() {
+            element.set_attr(attr, url.as_str());
+        }
+    });
+}
+
+/// Points every http subresource at https, for pages whose policy has
+/// upgrade-insecure-requests.
+fn upgrade_insecure_requests(document: &mut Document, page_url: &Url) {
+    let base = document_base(document, page_url);
+    document.for_each_element_mut(&mut |element| {
+        let Some((attr, mut url, _)) = subresource(element, &base) else {
+            return;
+        };
+        if url.scheme() == "http" && url.set_scheme("https").is_ok() {
+            element.set_attr(attr, url.as_str());
+        }
+    });
+}
+
+/// Checks subresources and inline scripts and styles against the page's
+/// policies. What an enforced policy doesn't allow comes off the page.
+fn enforce_content_security_policy(
+    document: &mut Document,
+    page_url: &Url,
+    policies: &[ContentSecurityPolicy],
+) -> Vec<CspViolation> {
+    if policies.is_empty() {
+        return Vec::new();
+    }
+
+    let base = document_base(document, page_url);
+    let check = |element: &Element| -> Vec<CspViolation> {
+        let nonce = element.attr("nonce");
+        if let Some((_, url, request_type)) = subresource(element, &base) {
+            return policies
+                .iter()
+                .filter_map(|policy| policy.check_request(&url, request_type, nonce, page_url))
+                .collect();
+        }
+
+        let request_type = match element.tag.as_str() {
+            "script" if is_inline_script(element) => RequestType::Script,
+            "style" => RequestType::Stylesheet,
+            _ => return Vec::new(),
+        };
+        // hashes are of the source exactly as written
+        let content: String = element
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                Node::Text(text) => Some(text.as_str()),
+                Node::Element(_) => None,
+            })
+            .collect();
+        policies
+            .iter()
+            .filter_map(|policy| policy.check_inline(request_type, nonce, &content))
+            .collect()
+    };
+
+    let violations: Vec<CspViolation> = document.elements().into_iter().flat_map(check).collect();
+    if violations.iter().any(CspViolation::is_blocked) {
+        document.remove_elements(&|element, _| check(element).iter().any(CspViolation::is_blocked));
+    }
+    violations
+}
+
+/// Scripts without a src that would run, so not json or template blocks.
+fn is_inline_script(element: &Element) -> bool {
+    element.attr("src").is_none()
+        && element.attr("type").is_none_or(|kind| {
+            let kind = kind.trim().to_ascii_lowercase();
+            kind.is_empty()
+                || kind == "module"
+                || kind.contains("javascript")
+                || kind.contains("ecmascript")
+        })
+}
+
+fn document_base(document: &Document, page_url: &Url) -> Url {
+    document
+        .find("base")
+        .and_then(|base| base.attr("href"))
+        .and_then(|href| page_url.join(href.trim()).ok())
+        .unwrap_or_else(|| page_url.clone())
+}
+
+/// The attribute an element loads something from, the resolved url and what
+/// kind of request it is. Only http and https urls count.
+fn subresource(element: &Element, base: &Url) -> Option<(&'static str, Url, RequestType)> {
+    let (attr, request_type) = match element.tag.as_str() {
+        "script" => ("src", RequestType::Script),
+        "img" => ("src", RequestType::Image),
+        "iframe" | "frame" => ("src", RequestType::Subdocument),
+        "link"
+            if element.attr("rel").is_some_and(|rel| {
+                rel.split_ascii_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("stylesheet"))
+            }) =>
+        {
+            ("href", RequestType::Stylesheet)
+        }
+        "video" | "audio" | "source" | "track" => ("src", RequestType::Media),
+        "embed" => ("src", RequestType::Object),
+        "object" => ("data", RequestType::Object),
+        _ => return None,
+    };
+
+    let url = base.join(element.attr(attr)?.trim()).ok()?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return None;
+    }
+    Some((attr, url, request_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::{CertificateCheck, CertificateError, CspDisposition};
+    use crate::utils::test_server::{closed_port, serve_http, Reply};
+    use tempfile::TempDir;
+
+    /// Serves `page` at `/` and a redirect to it from `/old`, over plain http.
+    async fn serve(page: &'static str) -> String {
+        serve_http(move |_, path| match path {
+            "/" => Reply::new("200 OK")
+                .header("Content-Type", "text/html")
+                .body(page),
+            "/old" => Reply::new("302 Found").header("Location", "/"),
+            _ => Reply::new("404 Not Found"),
+        })
+        .await
+    }
+
+    fn loader(dir: &TempDir, https_only: bool) -> PageLoader {
+        let mut config = BrowserConfig::default();
+        config.storage.data_dir = dir.path().to_path_buf();
+        config.privacy.https_only = https_only;
+        config.privacy.cookie_encryption = CookieEncryption::Off;
+        let security = SecurityManager::new(config.clone());
+        PageLoader::new(&config, security).unwrap()
+    }
+
+    #[tokio::test]
+    async fn http_pages_load_unless_https_only() {
+        let base = serve("<title>Plain</title><p>Hello</p>").await;
+        let dir = TempDir::new().unwrap();
+
+        let page = loader(&dir, false)
+            .load(&format!("{}/old", base))
+            .await
+            .unwrap();
+        assert_eq!(page.status, 200);
+        assert_eq!(page.final_url, format!("{}/", base));
+        assert_eq!(page.title.as_deref(), Some("Plain"));
+
+        let error = loader(&dir, true)
+            .load(&format!("{}/", base))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("HTTPS-only"), "{}", error);
+        assert!(!PageLoader::is_connection_failure(&error));
+    }
+
+    #[test]
+    fn every_hop_is_checked_for_https() {
+        let dir = TempDir::new().unwrap();
+        let url = |input: &str| Url::parse(input).unwrap();
+
+        let loader = loader(&dir, false);
+        assert_eq!(
+            loader.enforce_https(url("http://example.com/a")).unwrap(),
+            url("http://example.com/a")
+        );
+        // preloaded hsts hosts are upgraded even with https-only off
+        assert_eq!(
+            loader
+                .enforce_https(url("http://mail.google.com/"))
+                .unwrap(),
+            url("https://mail.google.com/")
+        );
+
+        let strict = self::loader(&dir, true);
+        assert!(strict.enforce_https(url("http://example.com/")).is_err());
+        assert_eq!(
+            strict.enforce_https(url("https://example.com/")).unwrap(),
+            url("https://example.com/")
+        );
+        assert_eq!(
+            strict.enforce_https(url("bagel://privacy")).unwrap(),
+            url("bagel://privacy")
+        );
+    }
+
+    #[tokio::test]
+    async fn connection_failures_offer_http() {
+        let dir = TempDir::new().unwrap();
+        let error = loader(&dir, false)
+            .load(&format!("https://127.0.0.1:{}/", closed_port()))
+            .await
+            .unwrap_err();
+        assert!(PageLoader::is_connection_failure(&error), "{}", error);
+
+        let untrusted: anyhow::Error = UntrustedCertificate {
+            check: CertificateCheck {
+                host: "example.com".to_string(),
+                chain: Vec::new(),
+                error: Some(CertificateError::Expired),
+                protocol: None,
+                cipher_suite: None,
+                checked_at: Utc::now(),
+                accepted: false,
+            },
+            reason: CertificateError::Expired,
+        }
+        .into();
+        assert!(PageLoader::is_connection_failure(&untrusted));
+
+        assert!(!PageLoader::is_connection_failure(&anyhow::anyhow!(
+            "Too many redirects"
+        )));
+    }
+
+    const MIXED_PAGE: &str = r#"<base href="http://cdn.example/assets/">
+<script src="app.js"></script>
+<link rel="stylesheet" href="https://cdn.example/style.css">
+<img src="logo.png"><img src="/assets/logo.png">
+<video src="//media.example/clip.mp4"></video>
+<iframe src="http://frames.example/"></iframe>
+<a href="http://other.example/">link</a>"#;
+
+    fn srcs(document: &Document) -> Vec<String> {
+        document
+            .elements()
+            .into_iter()
+            .filter_map(|element| element.attr("src").or(element.attr("href")))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn subresources_are_resolved_against_the_base() {
+        let document = Document::parse(MIXED_PAGE);
+        let page_url = Url::parse("https://news.example/").unwrap();
+
+        let found: Vec<(String, RequestType)> = subresources(&document, &page_url)
+            .into_iter()
+            .map(|(url, request_type)| (url.to_string(), request_type))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "http://cdn.example/assets/app.js".to_string(),
+                    RequestType::Script
+                ),
+                (
+                    "https://cdn.example/style.css".to_string(),
+                    RequestType::Stylesheet
+                ),
+                (
+                    "http://cdn.example/assets/logo.png".to_string(),
+                    RequestType::Image
+                ),
+                (
+                    "http://media.example/clip.mp4".to_string(),
+                    RequestType::Media
+                ),
+                (
+                    "http://frames.example/".to_string(),
+                    RequestType::Subdocument
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn mixed_content_is_removed_or_upgraded_on_the_page() {
+        let dir = TempDir::new().unwrap();
+        let security = loader(&dir, false).security;
+        let page_url = Url::parse("https://news.example/").unwrap();
+        let mut document = Document::parse(MIXED_PAGE);
+
+        let mixed_content =
+            security.check_mixed_content(&page_url, &subresources(&document, &page_url));
+        apply_mixed_content(&mut document, &page_url, &mixed_content);
+        assert_eq!(
+            srcs(&document),
+            vec![
+                "http://cdn.example/assets/",
+                "https://cdn.example/style.css",
+                "https://cdn.example/assets/logo.png",
+                "https://cdn.example/assets/logo.png",
+                "https://media.example/clip.mp4",
+                "http://other.example/",
+            ]
+        );
+    }
+
+    #[test]
+    fn upgrade_insecure_requests_upgrades_everything() {
+        let page_url = Url::parse("https://news.example/").unwrap();
+        let mut document = Document::parse(MIXED_PAGE);
+
+        upgrade_insecure_requests(&mut document, &page_url);
+        assert_eq!(
+            srcs(&document),
+            vec![
+                "http://cdn.example/assets/",
+                "https://cdn.example/assets/app.js",
+                "https://cdn.example/style.css",
+                "https://cdn.example/assets/logo.png",
+                "https://cdn.example/assets/logo.png",
+                "https://media.example/clip.mp4",
+                "https://frames.example/",
+                "http://other.example/",
+            ]
+        );
+    }
+
+    #[test]
+    fn content_security_policies_take_elements_off_the_page() {
+        let page_url = Url::parse("https://news.example/").unwrap();
+        let mut document = Document::parse(
+            r#"<script src="/app.js"></script>
+<script src="https://cdn.example/lib.js"></script>
+<script nonce="r4nd">ok()</script>
+<script>evil()</script>
+<script type="application/json">{"data": 1}</script>
+<img src="https://img.example/a.png">
+<style>body { color: red }</style>"#,
+        );
+        let policies = [
+            ContentSecurityPolicy::parse_header(
+                "script-src 'self' 'nonce-r4nd'",
+                CspDisposition::Enforce,
+            ),
+            ContentSecurityPolicy::parse_header("img-src 'self'", CspDisposition::Report),
+        ]
+        .concat();
+
+        let violations = enforce_content_security_policy(&mut document, &page_url, &policies);
+        let blocked: Vec<(&str, bool)> = violations
+            .iter()
+            .map(|violation| (violation.blocked.as_str(), violation.is_blocked()))
+            .collect();
+        assert_eq!(
+            blocked,
+            vec![
+                ("https://cdn.example/lib.js", true),
+                ("inline", true),
+                ("https://img.example/a.png", false),
+            ]
+        );
+
+        let left: Vec<String> = document
+            .elements()
+            .into_iter()
+            .map(|element| {
+                element
+                    .attr("src")
+                    .map(str::to_string)
+                    .unwrap_or_else(|| element.tag.clone())
+            })
+            .collect();
+        assert_eq!(
+            left,
+            vec![
+                "/app.js",
+                "script",
+                "script",
+                "https://img.example/a.png",
+                "style"
+            ]
+        );
+        assert!(enforce_content_security_policy(&mut document, &page_url, &[]).is_empty());
+    }
+}
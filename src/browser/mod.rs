@@ -1,7 +1,23 @@
 pub mod webview;
+// the ui keeps its own tab list, nothing drives TabManager yet
+#[allow(dead_code)]
 pub mod tabs;
 pub mod security;
+pub mod loader;
+pub mod filters;
+pub mod cosmetic;
+pub mod dom;
+pub mod certificates;
+pub mod hsts;
+pub mod csp;
 
 pub use webview::*;
 pub use tabs::*;
-pub use security::*;
\ No newline at end of file
+pub use security::*;
+pub use loader::*;
+pub use filters::*;
+pub use cosmetic::*;
+pub use dom::*;
+pub use certificates::*;
+pub use hsts::*;
+pub use csp::*;
\ No newline at end of file
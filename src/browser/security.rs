@@ -1,136 +1,879 @@
-use crate::utils::{log_security_event, BrowserConfig};
+use crate::browser::{
+    client_config, default_roots, hsts_host, inspect_certificate, CertificateCheck,
+    CertificateStore, ContentSecurityPolicy, CspDisposition, Document, FilterCategory,
+    FilterEngine, FilterMatch, FilterRequest, FilterResult, HstsHeader, HstsStore, ListKind,
+    ListStats, PinHeader, RequestType,
+};
+use crate::storage::{
+    CertificateException, CertificateExceptionManager, FilterListUpdater, HstsEntry, HstsManager,
+    PinEntry, PrivacyReportManager, Shield, SiteShieldManager, SiteShields, SubscriptionManager,
+};
+use crate::utils::{log_security_event, site_for_host, BrowserConfig, PassiveMixedContent};
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 use url::Url;
 
+/// What the custom rules list is called in stats and block messages.
+pub const CUSTOM_LIST_NAME: &str = "Custom rules";
+
+const CUSTOM_FILTERS_TEMPLATE: &str = "\
+! Your own blocking rules, one per line. Adblock Plus filters work here,
+! and so do hosts file lines (0.0.0.0 ads.example.com) and plain domains.
+! Lines starting with ! or # are comments.
+";
+
+/// Where a filter list comes from, which decides how it's switched on and off.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterListSource {
+    Subscription(i64),
+    /// A `.txt` file in `filters/` in the data dir, by file name without `.txt`.
+    File(String),
+    Custom,
+}
+
+#[derive(Debug, Clone)]
+pub struct FilterListInfo {
+    pub name: String,
+    pub source: FilterListSource,
+    pub enabled: bool,
+    /// `None` until the list is loaded, so for lists that are off or haven't
+    /// been downloaded yet.
+    pub kind: Option<ListKind>,
+    pub stats: ListStats,
+}
+
+/// A request the filters stopped, kept per tab and for the privacy report.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockedRequest {
+    pub url: String,
+    /// The page that made the request, `None` when the page itself was blocked.
+    pub page_url: Option<String>,
+    pub request_type: RequestType,
+    pub rule: String,
+    pub list: String,
+    pub kind: ListKind,
+    pub category: FilterCategory,
+    pub blocked_at: DateTime<Utc>,
+}
+
+impl BlockedRequest {
+    pub fn new(
+        url: &Url,
+        page_url: Option<&Url>,
+        request_type: RequestType,
+        filter: FilterMatch,
+    ) -> Self {
+        Self {
+            url: url.to_string(),
+            page_url: page_url.map(Url::to_string),
+            request_type,
+            rule: filter.rule,
+            list: filter.list,
+            kind: filter.kind,
+            category: filter.category,
+            blocked_at: Utc::now(),
+        }
+    }
+
+    /// The site the request went to, what the report groups trackers by.
+    pub fn domain(&self) -> String {
+        Url::parse(&self.url)
+            .ok()
+            .and_then(|url| url.host_str().map(site_for_host))
+            .unwrap_or_else(|| self.url.clone())
+    }
+}
+
+/// How many requests one list has blocked since startup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListBlockStats {
+    pub list: String,
+    pub kind: ListKind,
+    pub category: FilterCategory,
+    pub blocked: u64,
+}
+
+/// Whether insecure content can change what a page does, or only what it shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MixedContentKind {
+    /// Scripts, frames, stylesheets and anything else that can run code or
+    /// restyle the page.
+    Active,
+    /// Images and media.
+    Passive,
+}
+
+impl MixedContentKind {
+    pub fn for_request(request_type: RequestType) -> Self {
+        match request_type {
+            RequestType::Image | RequestType::Media => MixedContentKind::Passive,
+            _ => MixedContentKind::Active,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MixedContentAction {
+    Blocked,
+    /// Rewritten to https.
+    Upgraded,
+    /// Left on http, the page isn't fully secure.
+    Allowed,
+}
+
+/// An http subresource on an https page and what was done about it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MixedContent {
+    /// The url as the page had it, before any upgrade.
+    pub url: String,
+    pub request_type: RequestType,
+    pub kind: MixedContentKind,
+    pub action: MixedContentAction,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityLevel {
     pub is_secure: bool,
     pub certificate_valid: bool,
+    /// Some of the page was loaded over http anyway.
     pub mixed_content: bool,
+    /// Insecure scripts, frames, images and so on kept off the page.
+    pub mixed_content_blocked: u32,
     pub tracking_blocked: bool,
     pub ads_blocked: u32,
 }
 
+/// The shields in effect on a site, its overrides over the global settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShieldSettings {
+    pub ad_blocking: bool,
+    pub tracking_protection: bool,
+    pub https_upgrade: bool,
+}
+
+impl ShieldSettings {
+    pub fn get(&self, shield: Shield) -> bool {
+        match shield {
+            Shield::AdBlocking => self.ad_blocking,
+            Shield::TrackingProtection => self.tracking_protection,
+            Shield::HttpsUpgrade => self.https_upgrade,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SecurityManager {
     config: BrowserConfig,
-    blocked_domains: Vec<String>,
-    tracking_patterns: Vec<String>,
+    /// Shared between clones and swapped whole when the lists change, so
+    /// requests in flight keep the engine they started with.
+    filters: Arc<RwLock<Arc<FilterEngine>>>,
+    /// Blocked request counts by list name.
+    block_stats: Arc<Mutex<HashMap<String, ListBlockStats>>>,
+    /// Per-site shield overrides by site, a copy of shields.db so every
+    /// request doesn't hit the disk.
+    site_shields: Arc<RwLock<HashMap<String, SiteShields>>>,
+    /// What the last handshake with each host turned up.
+    certificates: CertificateStore,
+    /// Preloaded and remembered HSTS hosts.
+    hsts: HstsStore,
+    tls_config: Arc<rustls::ClientConfig>,
 }
 
 impl SecurityManager {
     pub fn new(config: BrowserConfig) -> Self {
+        let filters = Arc::new(RwLock::new(Arc::new(Self::load_filters(&config))));
+        let site_shields = SiteShieldManager::new(config.data_dir())
+            .and_then(|manager| manager.get_all())
+            .map_err(|e| log::error!("Failed to load site shields: {}", e))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|shields| (shields.site.clone(), shields))
+            .collect();
+        let hsts = HstsStore::default();
+        match HstsManager::new(config.data_dir()).and_then(|manager| manager.get_all()) {
+            Ok(entries) => hsts.set_entries(entries),
+            Err(e) => log::error!("Failed to load HSTS entries: {}", e),
+        }
+        match HstsManager::new(config.data_dir()).and_then(|manager| manager.get_all_pins()) {
+            Ok(entries) => hsts.set_pins(entries),
+            Err(e) => log::error!("Failed to load pinned keys: {}", e),
+        }
+        let certificates = CertificateStore::default().with_hsts(hsts.clone());
+        match CertificateExceptionManager::new(config.data_dir())
+            .and_then(|manager| manager.get_all())
+        {
+            Ok(exceptions) => certificates.set_exceptions(
+                exceptions
+                    .into_iter()
+                    .map(|exception| (exception.host, exception.fingerprint)),
+            ),
+            Err(e) => log::error!("Failed to load certificate exceptions: {}", e),
+        }
+        let tls_config = Arc::new(client_config(&certificates, default_roots()));
+
         Self {
             config,
-            blocked_domains: Self::load_blocked_domains(),
-            tracking_patterns: Self::load_tracking_patterns(),
-        }
-    }
-
-    fn load_blocked_domains() -> Vec<String> {
-        vec![
-            "doubleclick.net".to_string(),
-            "googleadservices.com".to_string(),
-            "googlesyndication.com".to_string(),
-            "google-analytics.com".to_string(),
-            "facebook.com".to_string(),
-            "connect.facebook.net".to_string(),
-            "scorecardresearch.com".to_string(),
-            "outbrain.com".to_string(),
-            "taboola.com".to_string(),
-        ]
+            filters,
+            block_stats: Arc::new(Mutex::new(HashMap::new())),
+            site_shields: Arc::new(RwLock::new(site_shields)),
+            certificates,
+            hsts,
+            tls_config,
+        }
     }
 
-    fn load_tracking_patterns() -> Vec<String> {
-        vec![
-            r".*\.ads\..*".to_string(),
-            r".*\.analytics\..*".to_string(),
-            r".*\.tracker\..*".to_string(),
-            r".*\.telemetry\..*".to_string(),
-        ]
+    /// Lists dropped into `filters/` in the data dir, one `.txt` per list, the
+    /// downloaded copies of subscriptions and the custom rules, skipping the
+    /// ones switched off.
+    fn load_filters(config: &BrowserConfig) -> FilterEngine {
+        let manager = match SubscriptionManager::new(config.data_dir()) {
+            Ok(manager) => Some(manager),
+            Err(e) => {
+                log::error!("Failed to open filter subscriptions: {}", e);
+                None
+            }
+        };
+        let local_enabled = |name: &str| {
+            manager
+                .as_ref()
+                .is_none_or(|manager| manager.local_list_enabled(name).unwrap_or(true))
+        };
+
+        let dir = config.data_dir().join("filters");
+        let mut engine = FilterEngine::new();
+        if let Err(e) = engine.add_dir(&dir, local_enabled) {
+            log::error!("Failed to load filter lists from {}: {}", dir.display(), e);
+        }
+
+        if let Some(manager) = &manager {
+            for subscription in manager.get_subscriptions().unwrap_or_default() {
+                if !subscription.enabled {
+                    continue;
+                }
+                match manager.read_list(subscription.id) {
+                    Ok(Some(contents)) => {
+                        engine.add_list(&subscription.title, subscription.category, &contents);
+                    }
+                    Ok(None) => {}
+                    Err(e) => log::warn!("Failed to read {}: {}", subscription.title, e),
+                }
+            }
+        }
+
+        if engine.list_count() == 0 {
+            log::info!("No filter lists downloaded yet, using defaults");
+            engine = FilterEngine::with_defaults();
+        }
+
+        if local_enabled(CUSTOM_LIST_NAME) {
+            let path = Self::custom_filters_path_in(config);
+            match std::fs::read_to_string(&path) {
+                Ok(contents) => {
+                    engine.add_custom_list(CUSTOM_LIST_NAME, &contents);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    // leave something to edit, with a hint of what goes in it
+                    if let Err(e) = std::fs::write(&path, CUSTOM_FILTERS_TEMPLATE) {
+                        log::warn!("Failed to create {}: {}", path.display(), e);
+                    }
+                }
+                Err(e) => log::warn!("Failed to read {}: {}", path.display(), e),
+            }
+        }
+
+        log::info!("Loaded {} filters", engine.filter_count());
+        engine
+    }
+
+    fn custom_filters_path_in(config: &BrowserConfig) -> PathBuf {
+        config.data_dir().join("custom-filters.txt")
+    }
+
+    /// The user's own rules file. Edit it and call [`Self::reload_filters`].
+    pub fn custom_filters_path(&self) -> PathBuf {
+        Self::custom_filters_path_in(&self.config)
+    }
+
+    /// Every list we know of, on or off, with what it loaded.
+    pub fn filter_lists(&self) -> Result<Vec<FilterListInfo>> {
+        let manager = SubscriptionManager::new(self.config.data_dir())?;
+        let filters = Arc::clone(&self.filters.read().unwrap());
+        let info = |name: String, source: FilterListSource, enabled: bool| {
+            let loaded = filters.lists().iter().find(|list| list.name == name);
+            FilterListInfo {
+                kind: loaded.map(|list| list.kind),
+                stats: loaded.map(|list| list.stats).unwrap_or_default(),
+                name,
+                source,
+                enabled,
+            }
+        };
+
+        let mut lists = Vec::new();
+        let mut files: Vec<String> = match std::fs::read_dir(self.config.data_dir().join("filters"))
+        {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+                .filter_map(|path| path.file_stem()?.to_str().map(str::to_string))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        files.sort();
+        for name in files {
+            let enabled = manager.local_list_enabled(&name)?;
+            lists.push(info(name.clone(), FilterListSource::File(name), enabled));
+        }
+
+        for subscription in manager.get_subscriptions()? {
+            lists.push(info(
+                subscription.title,
+                FilterListSource::Subscription(subscription.id),
+                subscription.enabled,
+            ));
+        }
+
+        lists.push(info(
+            CUSTOM_LIST_NAME.to_string(),
+            FilterListSource::Custom,
+            manager.local_list_enabled(CUSTOM_LIST_NAME)?,
+        ));
+        Ok(lists)
+    }
+
+    /// Switches a list on or off and reloads the filters.
+    pub fn set_filter_list_enabled(&self, source: &FilterListSource, enabled: bool) -> Result<()> {
+        let manager = SubscriptionManager::new(self.config.data_dir())?;
+        match source {
+            FilterListSource::Subscription(id) => manager.set_enabled(*id, enabled)?,
+            FilterListSource::File(name) => manager.set_local_list_enabled(name, enabled)?,
+            FilterListSource::Custom => {
+                manager.set_local_list_enabled(CUSTOM_LIST_NAME, enabled)?
+            }
+        }
+        self.reload_filters();
+        Ok(())
+    }
+
+    /// Blocked request counts for each list that has blocked something, most
+    /// first.
+    pub fn block_statistics(&self) -> Vec<ListBlockStats> {
+        let mut stats: Vec<ListBlockStats> =
+            self.block_stats.lock().unwrap().values().cloned().collect();
+        stats.sort_by(|a, b| b.blocked.cmp(&a.blocked).then_with(|| a.list.cmp(&b.list)));
+        stats
+    }
+
+    fn record_block(&self, filter: &FilterMatch) {
+        let mut stats = self.block_stats.lock().unwrap();
+        stats
+            .entry(filter.list.clone())
+            .or_insert_with(|| ListBlockStats {
+                list: filter.list.clone(),
+                kind: filter.kind,
+                category: filter.category,
+                blocked: 0,
+            })
+            .blocked += 1;
+    }
+
+    /// Rebuilds the filters from disk and swaps them in.
+    pub fn reload_filters(&self) {
+        let engine = Arc::new(Self::load_filters(&self.config));
+        *self.filters.write().unwrap() = engine;
+    }
+
+    /// Downloads the subscriptions that have expired and swaps in the new
+    /// filters if any changed. Returns how many lists were updated.
+    pub async fn update_filter_lists(&self) -> Result<usize> {
+        let manager = SubscriptionManager::new(self.config.data_dir())?;
+        let updated = FilterListUpdater::new(Duration::from_secs(60))?
+            .update_due(&manager)
+            .await?;
+
+        if updated > 0 {
+            // parsing easylist takes a moment, keep it off the async workers
+            let security = self.clone();
+            tokio::task::spawn_blocking(move || security.reload_filters()).await?;
+        }
+        Ok(updated)
+    }
+
+    /// Adds blocked requests to the privacy report.
+    pub fn record_blocked(&self, blocked: &[BlockedRequest]) {
+        let result = PrivacyReportManager::new(self.config.data_dir())
+            .and_then(|report| report.record(blocked));
+        if let Err(e) = result {
+            log::warn!("Failed to record blocked requests: {}", e);
+        }
     }
 
-    pub fn check_url_security(&self, url: &str) -> Result<SecurityLevel> {
+    /// `blocked` is what the filters stopped on the page so far, it fills in
+    /// the ad and tracker counts, and `mixed_content` is the page's http
+    /// subresources.
+    pub fn check_url_security(
+        &self,
+        url: &str,
+        blocked: &[BlockedRequest],
+        mixed_content: &[MixedContent],
+    ) -> Result<SecurityLevel> {
         let parsed_url = Url::parse(url)?;
 
         let is_secure = parsed_url.scheme() == "https";
-        let mixed_content = false; // dummy mixed content detection; will do later
 
-        if !is_secure && self.config.privacy.https_upgrade {
+        let host = parsed_url.host_str().unwrap_or_default();
+        if !is_secure && self.shields_for(host).https_upgrade {
             log_security_event("HTTPS_UPGRADE_SUGGESTED", url);
         }
 
         let security_level = SecurityLevel {
             is_secure,
-            certificate_valid: true, // dummy certificate validation
-            mixed_content,
-            tracking_blocked: false,
-            ads_blocked: 0,
+            // nothing to check over plain http, and a host we haven't shaken
+            // hands with yet hasn't shown a bad certificate
+            certificate_valid: !is_secure
+                || self
+                    .certificate_for(host)
+                    .is_none_or(|check| check.is_valid()),
+            mixed_content: is_secure
+                && mixed_content
+                    .iter()
+                    .any(|content| content.action == MixedContentAction::Allowed),
+            mixed_content_blocked: mixed_content
+                .iter()
+                .filter(|content| content.action == MixedContentAction::Blocked)
+                .count() as u32,
+            tracking_blocked: blocked
+                .iter()
+                .any(|request| request.category == FilterCategory::Tracking),
+            ads_blocked: blocked
+                .iter()
+                .filter(|request| request.category == FilterCategory::Ads)
+                .count() as u32,
         };
 
         Ok(security_level)
     }
 
-    pub fn should_block_request(&self, url: &str) -> bool {
-        if !self.config.privacy.ad_blocking && !self.config.privacy.tracking_protection {
-            return false;
+    /// Sorts out the http subresources of an https page: active ones are
+    /// blocked, passive ones go by the config. HSTS hosts are upgraded
+    /// whatever they are, like they would be anywhere else.
+    pub fn check_mixed_content(
+        &self,
+        page_url: &Url,
+        requests: &[(Url, RequestType)],
+    ) -> Vec<MixedContent> {
+        if page_url.scheme() != "https" {
+            return Vec::new();
         }
 
-        if let Ok(parsed_url) = Url::parse(url) {
-            if let Some(domain) = parsed_url.domain() {
-                // Check against blocked domains
-                if self.config.privacy.ad_blocking && self.is_domain_blocked(domain) {
-                    log_security_event("AD_BLOCKED", url);
-                    return true;
+        requests
+            .iter()
+            .filter(|(url, _)| url.scheme() == "http")
+            .map(|(url, request_type)| {
+                let kind = MixedContentKind::for_request(*request_type);
+                let action = if self.is_hsts_host(url.host_str().unwrap_or_default()) {
+                    MixedContentAction::Upgraded
+                } else if kind == MixedContentKind::Active {
+                    MixedContentAction::Blocked
+                } else {
+                    match self.config.privacy.passive_mixed_content {
+                        PassiveMixedContent::Upgrade => MixedContentAction::Upgraded,
+                        PassiveMixedContent::Allow => MixedContentAction::Allowed,
+                        PassiveMixedContent::Block => MixedContentAction::Blocked,
+                    }
+                };
+                if action == MixedContentAction::Blocked {
+                    log_security_event(
+                        "MIXED_CONTENT_BLOCKED",
+                        &format!("{} on {}", url, page_url),
+                    );
                 }
 
-                // Check against tracking patterns
-                if self.config.privacy.tracking_protection && self.is_tracking_request(url) {
-                    log_security_event("TRACKER_BLOCKED", url);
-                    return true;
+                MixedContent {
+                    url: url.to_string(),
+                    request_type: *request_type,
+                    kind,
+                    action,
                 }
+            })
+            .collect()
+    }
+
+    /// Whether to block navigating to `url`.
+    pub fn should_block_request(&self, url: &str) -> bool {
+        match Url::parse(url) {
+            Ok(url) => self
+                .check_request(&url, None, RequestType::Document)
+                .is_some(),
+            Err(_) => false,
+        }
+    }
+
+    /// The filter blocking a request for `url` made by the page at `source`,
+    /// if ad blocking or tracking protection wants it blocked. The page's
+    /// shields decide, or the url's own for navigations.
+    pub fn check_request(
+        &self,
+        url: &Url,
+        source: Option<&Url>,
+        request_type: RequestType,
+    ) -> Option<FilterMatch> {
+        let page = source.unwrap_or(url);
+        let enabled = self.enabled_categories(page.host_str().unwrap_or_default());
+        if enabled.is_empty() {
+            return None;
+        }
+
+        let filters = Arc::clone(&self.filters.read().unwrap());
+        let request = FilterRequest::new(url, source, request_type);
+        match filters.check(&request, &enabled) {
+            FilterResult::Blocked(filter) => {
+                let event = match filter.category {
+                    FilterCategory::Ads => "AD_BLOCKED",
+                    FilterCategory::Tracking => "TRACKER_BLOCKED",
+                    FilterCategory::Custom => "CUSTOM_RULE_BLOCKED",
+                };
+                log_security_event(
+                    event,
+                    &format!("{} ({} in {})", url, filter.rule, filter.list),
+                );
+                self.record_block(&filter);
+                Some(filter)
             }
+            FilterResult::Allowed(_) | FilterResult::NoMatch => None,
+        }
+    }
+
+    /// Removes the elements element hiding rules hide on the page at `url`,
+    /// before the page is shown. Returns how many were removed.
+    pub fn apply_cosmetic_filters(&self, document: &mut Document, url: &Url) -> usize {
+        let enabled = self.enabled_categories(url.host_str().unwrap_or_default());
+        if enabled.is_empty() {
+            return 0;
         }
 
-        false
+        let filters = Arc::clone(&self.filters.read().unwrap());
+        filters.hide_elements(document, url, &enabled)
     }
 
-    fn is_domain_blocked(&self, domain: &str) -> bool {
-        self.blocked_domains
-            .iter()
-            .any(|blocked| domain == blocked || domain.ends_with(&format!(".{}", blocked)))
+    /// The shields in effect on the site `host` belongs to.
+    pub fn shields_for(&self, host: &str) -> ShieldSettings {
+        let privacy = &self.config.privacy;
+        let global = ShieldSettings {
+            ad_blocking: privacy.ad_blocking,
+            tracking_protection: privacy.tracking_protection,
+            https_upgrade: privacy.https_upgrade,
+        };
+        if host.is_empty() {
+            return global;
+        }
+
+        let site_shields = self.site_shields.read().unwrap();
+        let Some(site) = site_shields.get(&site_for_host(host)) else {
+            return global;
+        };
+        ShieldSettings {
+            ad_blocking: site.ad_blocking.unwrap_or(global.ad_blocking),
+            tracking_protection: site
+                .tracking_protection
+                .unwrap_or(global.tracking_protection),
+            https_upgrade: site.https_upgrade.unwrap_or(global.https_upgrade),
+        }
+    }
+
+    /// Turns a shield on or off for the site `host` belongs to. Setting it
+    /// back to the global setting drops the override.
+    pub fn set_site_shield(&self, host: &str, shield: Shield, enabled: bool) -> Result<()> {
+        let site = site_for_host(host);
+        let global = self.shields_for("").get(shield);
+        let value = (enabled != global).then_some(enabled);
+
+        let shields = SiteShieldManager::new(self.config.data_dir())?.set(&site, shield, value)?;
+        let mut site_shields = self.site_shields.write().unwrap();
+        match shields {
+            Some(shields) => site_shields.insert(site, shields),
+            None => site_shields.remove(&site),
+        };
+        Ok(())
+    }
+
+    /// Puts every shield for the site `host` belongs to back to the global settings.
+    pub fn reset_site_shields(&self, host: &str) -> Result<()> {
+        let site = site_for_host(host);
+        SiteShieldManager::new(self.config.data_dir())?.remove(&site)?;
+        self.site_shields.write().unwrap().remove(&site);
+        Ok(())
+    }
+
+    /// Whether the site `host` belongs to has any shield overridden.
+    pub fn has_site_shields(&self, host: &str) -> bool {
+        self.site_shields
+            .read()
+            .unwrap()
+            .contains_key(&site_for_host(host))
+    }
+
+    fn enabled_categories(&self, host: &str) -> Vec<FilterCategory> {
+        let shields = self.shields_for(host);
+        let mut enabled = Vec::new();
+        if shields.ad_blocking {
+            enabled.push(FilterCategory::Ads);
+        }
+        if shields.tracking_protection {
+            enabled.push(FilterCategory::Tracking);
+        }
+        if !enabled.is_empty() {
+            enabled.push(FilterCategory::Custom);
+        }
+        enabled
     }
 
-    fn is_tracking_request(&self, url: &str) -> bool {
-        self.tracking_patterns.iter().any(|_pattern| {
-            // literally need to do regex technically
-            // but this is good enough for now
-            url.contains("analytics")
-                || url.contains("tracker")
-                || url.contains("telemetry")
-                || url.contains("/ads/")
-        })
+    /// Whether plain http is refused everywhere.
+    pub fn https_only(&self) -> bool {
+        self.config.privacy.https_only
+    }
+
+    /// Whether `host` is preloaded or told us to only use https.
+    pub fn is_hsts_host(&self, host: &str) -> bool {
+        self.hsts.is_hsts_host(host)
+    }
+
+    /// Whether `host` pinned its keys, itself or through a parent domain.
+    pub fn is_pinned_host(&self, host: &str) -> bool {
+        self.hsts.pins_for(host).is_some()
+    }
+
+    /// Whether `host` may never be loaded over http, because of https-only
+    /// mode or HSTS.
+    pub fn https_required(&self, host: &str) -> bool {
+        self.https_only() || self.is_hsts_host(host)
+    }
+
+    /// Remembers or forgets `url`'s host as an HSTS host from a
+    /// Strict-Transport-Security header. Only counts over https with a
+    /// certificate we trust without an exception, like RFC 6797 says.
+    pub fn record_hsts(&self, url: &Url, value: &str) {
+        let Some(host) = url.host_str().and_then(hsts_host) else {
+            return;
+        };
+        if url.scheme() != "https" {
+            return;
+        }
+        let trusted = self
+            .certificate_for(&host)
+            .is_some_and(|check| check.is_valid());
+        if !trusted {
+            return;
+        }
+        let Some(header) = HstsHeader::parse(value) else {
+            log::warn!("Ignoring malformed Strict-Transport-Security from {}", host);
+            return;
+        };
+
+        let result = HstsManager::new(self.config.data_dir()).and_then(|manager| {
+            if header.max_age == 0 {
+                manager.remove(&host)?;
+                self.hsts.remove(&host);
+                return Ok(());
+            }
+
+            let now = Utc::now();
+            let expires_at = now + chrono::Duration::seconds(header.max_age as i64);
+            // sites send this on every response, only write when it changes
+            // more than a bit
+            let unchanged = self.hsts.get(&host).is_some_and(|entry| {
+                entry.include_subdomains == header.include_subdomains
+                    && (expires_at - entry.expires_at).num_hours().abs() < 24
+            });
+            if unchanged {
+                return Ok(());
+            }
+
+            let entry = HstsEntry {
+                host: host.clone(),
+                include_subdomains: header.include_subdomains,
+                expires_at,
+                updated_at: now,
+            };
+            manager.set(&entry)?;
+            self.hsts.insert(entry);
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::warn!("Failed to store HSTS entry for {}: {}", host, e);
+        }
+    }
+
+    /// Remembers or forgets the keys `url`'s host pinned with a
+    /// Public-Key-Pins header. Same rules as HSTS: https with a certificate we
+    /// trust without an exception. The pins also have to fit that
+    /// certificate's chain, with a backup, or they're ignored.
+    pub fn record_pins(&self, url: &Url, value: &str) {
+        let Some(host) = url.host_str().and_then(hsts_host) else {
+            return;
+        };
+        if url.scheme() != "https" {
+            return;
+        }
+        let Some(check) = self.certificate_for(&host).filter(|check| check.is_valid()) else {
+            return;
+        };
+        let Some(header) = PinHeader::parse(value) else {
+            log::warn!("Ignoring malformed Public-Key-Pins from {}", host);
+            return;
+        };
+        if header.max_age > 0
+            && !header.fits_chain(check.chain.iter().map(|info| info.spki_sha256.as_str()))
+        {
+            log::warn!(
+                "Ignoring Public-Key-Pins from {}, it needs a key from the certificate and a backup",
+                host
+            );
+            return;
+        }
+
+        let result = HstsManager::new(self.config.data_dir()).and_then(|manager| {
+            if header.max_age == 0 {
+                manager.remove_pins(&host)?;
+                self.hsts.remove_pins(&host);
+                return Ok(());
+            }
+
+            let now = Utc::now();
+            let expires_at = now + chrono::Duration::seconds(header.max_age as i64);
+            let unchanged = self.hsts.get_pins(&host).is_some_and(|entry| {
+                entry.pins == header.pins
+                    && entry.include_subdomains == header.include_subdomains
+                    && (expires_at - entry.expires_at).num_hours().abs() < 24
+            });
+            if unchanged {
+                return Ok(());
+            }
+
+            let entry = PinEntry {
+                host: host.clone(),
+                pins: header.pins,
+                include_subdomains: header.include_subdomains,
+                expires_at,
+                updated_at: now,
+            };
+            manager.set_pins(&entry)?;
+            self.hsts.insert_pins(entry);
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::warn!("Failed to store pinned keys for {}: {}", host, e);
+        }
     }
 
+    /// The https url to try first for an http one. Sites where the user chose
+    /// to continue over http have the https upgrade shield off, unless
+    /// https-only mode or HSTS says otherwise.
     pub fn suggest_https_upgrade(&self, url: &str) -> Option<String> {
-        if self.config.privacy.https_upgrade {
-            if let Ok(mut parsed_url) = Url::parse(url) {
-                if parsed_url.scheme() == "http" {
-                    parsed_url.set_scheme("https").ok()?;
-                    return Some(parsed_url.to_string());
-                }
+        if let Ok(mut parsed_url) = Url::parse(url) {
+            let host = parsed_url.host_str().unwrap_or_default();
+            if parsed_url.scheme() == "http"
+                && (self.https_required(host) || self.shields_for(host).https_upgrade)
+            {
+                parsed_url.set_scheme("https").ok()?;
+                return Some(parsed_url.to_string());
             }
         }
         None
     }
 
-    pub fn validate_certificate(&self, _url: &str) -> Result<bool> {
-        // Implement proper certificate validation later
-        // This would involve checking the certificate chain, validity dates, etc.
-        Ok(true)
+    /// The tls setup page loads go through, so their certificates end up in
+    /// `certificate_for`.
+    pub fn tls_config(&self) -> rustls::ClientConfig {
+        (*self.tls_config).clone()
+    }
+
+    /// The certificate `host` showed on its last handshake.
+    pub fn certificate_for(&self, host: &str) -> Option<CertificateCheck> {
+        self.certificates.get(host)
+    }
+
+    pub fn certificate_exceptions(&self) -> Result<Vec<CertificateException>> {
+        CertificateExceptionManager::new(self.config.data_dir())?.get_all()
     }
 
+    /// Trusts the certificate from `check` for its host from now on, errors
+    /// and all. Only that exact certificate, a new one warns again.
+    pub fn add_certificate_exception(&self, check: &CertificateCheck) -> Result<()> {
+        if self.is_hsts_host(&check.host) {
+            return Err(anyhow::anyhow!(
+                "{} uses HSTS, its certificate errors can't be bypassed",
+                check.host
+            ));
+        }
+        if self.is_pinned_host(&check.host) {
+            return Err(anyhow::anyhow!(
+                "{} pinned its keys, its certificate errors can't be bypassed",
+                check.host
+            ));
+        }
+        let leaf = check
+            .leaf()
+            .ok_or_else(|| anyhow::anyhow!("No certificate to trust for {}", check.host))?;
+        let reason = check
+            .error
+            .as_ref()
+            .map(|error| error.to_string())
+            .unwrap_or_default();
+
+        CertificateExceptionManager::new(self.config.data_dir())?.add(&CertificateException {
+            host: check.host.clone(),
+            fingerprint: leaf.sha256_fingerprint.clone(),
+            subject: leaf.subject.clone(),
+            reason: reason.clone(),
+            created_at: Utc::now(),
+        })?;
+        self.certificates
+            .allow(&check.host, &leaf.sha256_fingerprint);
+        log_security_event(
+            "CERTIFICATE_EXCEPTION_ADDED",
+            &format!("{} ({})", check.host, reason),
+        );
+        Ok(())
+    }
+
+    /// Connections already open keep going, new ones get the warning again.
+    pub fn remove_certificate_exception(&self, host: &str, fingerprint: &str) -> Result<()> {
+        CertificateExceptionManager::new(self.config.data_dir())?.remove(host, fingerprint)?;
+        self.certificates.revoke(host, fingerprint);
+        Ok(())
+    }
+
+    /// Connects to the url's host and checks its certificate chain, expiry,
+    /// name and trust. A bad certificate isn't an error, it's in the check.
+    pub async fn validate_certificate(&self, url: &str) -> Result<CertificateCheck> {
+        let parsed_url = Url::parse(url)?;
+        if parsed_url.scheme() != "https" {
+            return Err(anyhow::anyhow!("{} isn't loaded over https", url));
+        }
+        let host = parsed_url
+            .host_str()
+            .ok_or_else(|| anyhow::anyhow!("No host in {}", url))?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let port = parsed_url.port_or_known_default().unwrap_or(443);
+
+        let check =
+            inspect_certificate(host, port, &self.certificates, Arc::clone(&self.tls_config))
+                .await?;
+        if let Some(error) = &check.error {
+            log_security_event("CERTIFICATE_INVALID", &format!("{}: {}", host, error));
+        }
+        Ok(check)
+    }
+
+    #[allow(dead_code)]
     pub fn check_malicious_site(&self, _url: &str) -> Result<bool> {
         // Implement malicious site checking later
         // maybe theres an API for this?
@@ -138,8 +881,385 @@ impl SecurityManager {
         Ok(false)
     }
 
-    pub fn get_content_security_policy(&self) -> String {
-        "default-src 'self'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline';"
-            .to_string()
+    /// The policies a page sets in its headers and its `<meta http-equiv>`
+    /// tags. Every one of them applies.
+    pub fn content_security_policies(
+        &self,
+        headers: &[(String, String)],
+        document: &Document,
+    ) -> Vec<ContentSecurityPolicy> {
+        let mut policies = Vec::new();
+        for (name, value) in headers {
+            let disposition = if name.eq_ignore_ascii_case("content-security-policy") {
+                CspDisposition::Enforce
+            } else if name.eq_ignore_ascii_case("content-security-policy-report-only") {
+                CspDisposition::Report
+            } else {
+                continue;
+            };
+            policies.extend(ContentSecurityPolicy::parse_header(value, disposition));
+        }
+
+        // browsers only look for these in the head
+        if let Some(head) = document.find("head") {
+            policies.extend(
+                head.descendants()
+                    .into_iter()
+                    .filter(|element| {
+                        element.tag == "meta"
+                            && element.attr("http-equiv").is_some_and(|equiv| {
+                                equiv.trim().eq_ignore_ascii_case("content-security-policy")
+                            })
+                    })
+                    .filter_map(|meta| ContentSecurityPolicy::parse_meta(meta.attr("content")?)),
+            );
+        }
+        policies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A manager with one ad list and one tracker list in its data dir.
+    fn security(dir: &TempDir) -> SecurityManager {
+        let filters = dir.path().join("filters");
+        std::fs::create_dir_all(&filters).unwrap();
+        std::fs::write(filters.join("ads.txt"), "||ads.example^\n").unwrap();
+        std::fs::write(filters.join("trackers.txt"), "0.0.0.0 tracker.example\n").unwrap();
+
+        let mut config = BrowserConfig::default();
+        config.storage.data_dir = dir.path().to_path_buf();
+        SecurityManager::new(config)
+    }
+
+    fn blocked(security: &SecurityManager, request: &str, page: &str) -> bool {
+        let request = Url::parse(request).unwrap();
+        let page = Url::parse(page).unwrap();
+        security
+            .check_request(&request, Some(&page), RequestType::Script)
+            .is_some()
+    }
+
+    #[test]
+    fn site_shields_turn_blocking_off_for_one_site() {
+        let dir = TempDir::new().unwrap();
+        let security = security(&dir);
+        let ad = "https://ads.example/ad.js";
+        let tracker = "https://tracker.example/t.js";
+
+        assert!(blocked(&security, ad, "https://news.example/"));
+        assert!(blocked(&security, tracker, "https://news.example/"));
+
+        security
+            .set_site_shield("www.news.example", Shield::AdBlocking, false)
+            .unwrap();
+        assert!(security.has_site_shields("news.example"));
+        assert!(!blocked(&security, ad, "https://news.example/"));
+        assert!(!blocked(&security, ad, "https://m.news.example/"));
+        assert!(blocked(&security, tracker, "https://news.example/"));
+        assert!(blocked(&security, ad, "https://blog.example/"));
+
+        security
+            .set_site_shield("news.example", Shield::TrackingProtection, false)
+            .unwrap();
+        assert!(!blocked(&security, tracker, "https://news.example/"));
+
+        let stats = security.block_statistics();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].list, "ads");
+        assert_eq!(stats[0].blocked, 2);
+        assert_eq!(stats[1].kind, ListKind::Hosts);
+    }
+
+    #[test]
+    fn overrides_are_stored_and_reset() {
+        let dir = TempDir::new().unwrap();
+        let security = security(&dir);
+        security
+            .set_site_shield("news.example", Shield::HttpsUpgrade, false)
+            .unwrap();
+        assert_eq!(security.suggest_https_upgrade("http://news.example/"), None);
+        assert_eq!(
+            security
+                .suggest_https_upgrade("http://blog.example/")
+                .as_deref(),
+            Some("https://blog.example/")
+        );
+
+        // picked up again by the next manager
+        let security = self::security(&dir);
+        assert!(!security.shields_for("news.example").https_upgrade);
+        assert!(security.shields_for("news.example").ad_blocking);
+
+        // setting a shield back to the global setting drops the override
+        security
+            .set_site_shield("news.example", Shield::HttpsUpgrade, true)
+            .unwrap();
+        assert!(!security.has_site_shields("news.example"));
+
+        security
+            .set_site_shield("news.example", Shield::AdBlocking, false)
+            .unwrap();
+        security.reset_site_shields("news.example").unwrap();
+        assert!(!security.has_site_shields("news.example"));
+        assert!(self::security(&dir).shields_for("news.example").ad_blocking);
+    }
+
+    fn failed_check(host: &str) -> CertificateCheck {
+        let cert = rcgen::generate_simple_self_signed(vec![host.to_string()]).unwrap();
+        CertificateCheck {
+            host: host.to_string(),
+            chain: vec![
+                crate::browser::CertificateInfo::from_der(&cert.serialize_der().unwrap()).unwrap(),
+            ],
+            error: Some(crate::browser::CertificateError::SelfSigned),
+            protocol: None,
+            cipher_suite: None,
+            checked_at: Utc::now(),
+            accepted: false,
+        }
+    }
+
+    #[test]
+    fn certificate_exceptions_are_stored_and_removed() {
+        let dir = TempDir::new().unwrap();
+        let security = security(&dir);
+        let check = failed_check("dev.internal");
+        let fingerprint = check.leaf().unwrap().sha256_fingerprint.clone();
+
+        security.add_certificate_exception(&check).unwrap();
+        let exceptions = self::security(&dir).certificate_exceptions().unwrap();
+        assert_eq!(exceptions.len(), 1);
+        assert_eq!(exceptions[0].host, "dev.internal");
+        assert_eq!(exceptions[0].fingerprint, fingerprint);
+        assert_eq!(exceptions[0].subject, check.leaf().unwrap().subject);
+        assert_eq!(exceptions[0].reason, "the certificate is self-signed");
+
+        security
+            .remove_certificate_exception("dev.internal", &fingerprint)
+            .unwrap();
+        assert!(security.certificate_exceptions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn hsts_hosts_cannot_get_certificate_exceptions() {
+        let dir = TempDir::new().unwrap();
+        let security = security(&dir);
+        // preloaded, so always https with a good certificate
+        assert!(security
+            .add_certificate_exception(&failed_check("accounts.google.com"))
+            .is_err());
+
+        // pinned keys, set straight in the store since there's no handshake
+        let mut pinned = failed_check("bank.example");
+        security.hsts.insert_pins(PinEntry {
+            host: "bank.example".to_string(),
+            pins: vec![pinned.leaf().unwrap().spki_sha256.clone()],
+            include_subdomains: true,
+            expires_at: Utc::now() + chrono::Duration::days(1),
+            updated_at: Utc::now(),
+        });
+        assert!(security.is_pinned_host("www.bank.example"));
+        assert!(security.add_certificate_exception(&pinned).is_err());
+        pinned.host = "www.bank.example".to_string();
+        assert!(security.add_certificate_exception(&pinned).is_err());
+
+        // nothing to pin the exception to
+        let mut check = failed_check("nothing.example");
+        check.chain.clear();
+        assert!(security.add_certificate_exception(&check).is_err());
+        assert!(security.certificate_exceptions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn https_only_ignores_sites_left_on_http() {
+        let dir = TempDir::new().unwrap();
+        security(&dir)
+            .set_site_shield("news.example", Shield::HttpsUpgrade, false)
+            .unwrap();
+
+        let mut config = BrowserConfig::default();
+        config.storage.data_dir = dir.path().to_path_buf();
+        config.privacy.https_only = true;
+        let security = SecurityManager::new(config);
+        assert!(security.https_only());
+        assert!(security.https_required("news.example"));
+        assert_eq!(
+            security
+                .suggest_https_upgrade("http://news.example/a?b")
+                .as_deref(),
+            Some("https://news.example/a?b")
+        );
+        assert_eq!(
+            security.suggest_https_upgrade("https://news.example/"),
+            None
+        );
+    }
+
+    #[test]
+    fn hsts_headers_need_a_trusted_https_connection() {
+        let dir = TempDir::new().unwrap();
+        let security = security(&dir);
+        let header = "max-age=3600; includeSubDomains";
+
+        security.record_hsts(&Url::parse("http://shop.example/").unwrap(), header);
+        // no handshake with a good certificate on record
+        security.record_hsts(&Url::parse("https://shop.example/").unwrap(), header);
+
+        assert!(!security.is_hsts_host("shop.example"));
+        assert!(HstsManager::new(dir.path())
+            .unwrap()
+            .get_all()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn pins_need_a_trusted_https_connection() {
+        let dir = TempDir::new().unwrap();
+        let security = security(&dir);
+        let header = "max-age=3600; \
+            pin-sha256=\"AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=\"; \
+            pin-sha256=\"AgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgI=\"";
+
+        security.record_pins(&Url::parse("http://shop.example/").unwrap(), header);
+        security.record_pins(&Url::parse("https://shop.example/").unwrap(), header);
+
+        assert!(!security.is_pinned_host("shop.example"));
+        assert!(HstsManager::new(dir.path())
+            .unwrap()
+            .get_all_pins()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn mixed_content_is_blocked_upgraded_or_allowed() {
+        let dir = TempDir::new().unwrap();
+        let page = Url::parse("https://news.example/").unwrap();
+        let requests: Vec<(Url, RequestType)> = [
+            ("http://cdn.example/app.js", RequestType::Script),
+            ("http://cdn.example/logo.png", RequestType::Image),
+            ("http://mail.google.com/pic.png", RequestType::Image),
+            ("http://mail.google.com/widget.js", RequestType::Script),
+            ("https://cdn.example/style.css", RequestType::Stylesheet),
+        ]
+        .into_iter()
+        .map(|(url, request_type)| (Url::parse(url).unwrap(), request_type))
+        .collect();
+        let actions = |passive: PassiveMixedContent| -> Vec<MixedContentAction> {
+            let mut config = BrowserConfig::default();
+            config.storage.data_dir = dir.path().to_path_buf();
+            config.privacy.passive_mixed_content = passive;
+            SecurityManager::new(config)
+                .check_mixed_content(&page, &requests)
+                .into_iter()
+                .map(|content| content.action)
+                .collect()
+        };
+
+        use MixedContentAction::*;
+        // the https stylesheet isn't mixed, the preloaded hsts host is
+        // upgraded whatever it is
+        assert_eq!(
+            actions(PassiveMixedContent::Upgrade),
+            vec![Blocked, Upgraded, Upgraded, Upgraded]
+        );
+        assert_eq!(
+            actions(PassiveMixedContent::Allow),
+            vec![Blocked, Allowed, Upgraded, Upgraded]
+        );
+        assert_eq!(
+            actions(PassiveMixedContent::Block),
+            vec![Blocked, Blocked, Upgraded, Upgraded]
+        );
+
+        let security = security(&dir);
+        let content = security.check_mixed_content(&page, &requests);
+        assert_eq!(content[0].url, "http://cdn.example/app.js");
+        assert_eq!(content[0].kind, MixedContentKind::Active);
+        assert_eq!(content[1].kind, MixedContentKind::Passive);
+        assert!(security
+            .check_mixed_content(&Url::parse("http://news.example/").unwrap(), &requests)
+            .is_empty());
+    }
+
+    #[test]
+    fn allowed_mixed_content_makes_a_page_not_fully_secure() {
+        let dir = TempDir::new().unwrap();
+        let security = security(&dir);
+        let content = |action: MixedContentAction| MixedContent {
+            url: "http://cdn.example/logo.png".to_string(),
+            request_type: RequestType::Image,
+            kind: MixedContentKind::Passive,
+            action,
+        };
+
+        let level = security
+            .check_url_security("https://news.example/", &[], &[])
+            .unwrap();
+        assert!(level.is_secure && !level.mixed_content);
+
+        let level = security
+            .check_url_security(
+                "https://news.example/",
+                &[],
+                &[
+                    content(MixedContentAction::Blocked),
+                    content(MixedContentAction::Blocked),
+                    content(MixedContentAction::Upgraded),
+                ],
+            )
+            .unwrap();
+        assert!(!level.mixed_content);
+        assert_eq!(level.mixed_content_blocked, 2);
+
+        let level = security
+            .check_url_security(
+                "https://news.example/",
+                &[],
+                &[content(MixedContentAction::Allowed)],
+            )
+            .unwrap();
+        assert!(level.mixed_content);
+    }
+
+    #[test]
+    fn policies_come_from_headers_and_meta_tags_in_the_head() {
+        let dir = TempDir::new().unwrap();
+        let security = security(&dir);
+        let headers = [
+            ("Content-Type".to_string(), "text/html".to_string()),
+            (
+                "content-security-policy".to_string(),
+                "default-src 'self', img-src *".to_string(),
+            ),
+            (
+                "Content-Security-Policy-Report-Only".to_string(),
+                "script-src 'none'".to_string(),
+            ),
+        ];
+        let document = Document::parse(
+            r#"<html><head><meta http-equiv=" Content-Security-Policy " content="style-src 'self'"></head>
+<body><meta http-equiv="Content-Security-Policy" content="img-src 'none'"></body></html>"#,
+        );
+
+        let policies = security.content_security_policies(&headers, &document);
+        let found: Vec<(&str, CspDisposition)> = policies
+            .iter()
+            .map(|policy| (policy.text.as_str(), policy.disposition))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("default-src 'self'", CspDisposition::Enforce),
+                ("img-src *", CspDisposition::Enforce),
+                ("script-src 'none'", CspDisposition::Report),
+                ("style-src 'self'", CspDisposition::Enforce),
+            ]
+        );
     }
 }
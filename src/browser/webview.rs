@@ -1,4 +1,5 @@
-use crate::browser::{SecurityManager, TabManager};
+use crate::browser::{PageLoader, SecurityManager, TabManager};
+use crate::storage::BookmarkManager;
 use crate::utils::{log_navigation, log_security_event, BrowserConfig};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
@@ -12,6 +13,8 @@ pub struct NavigationRequest {
     pub is_new_tab: bool,
 }
 
+// the navigation and tab api below isn't driven by the ui yet
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NavigationEvent {
     pub tab_id: Uuid,
@@ -24,20 +27,32 @@ pub struct NavigationEvent {
 pub struct WebViewManager {
     tab_manager: Arc<Mutex<TabManager>>,
     security_manager: SecurityManager,
+    bookmark_manager: Option<BookmarkManager>,
+    page_loader: Option<Arc<PageLoader>>,
     config: BrowserConfig,
 }
 
 impl WebViewManager {
     pub fn new(config: BrowserConfig) -> Self {
         let security_manager = SecurityManager::new(config.clone());
+        let bookmark_manager = BookmarkManager::new(config.data_dir())
+            .map_err(|e| log::warn!("Bookmark keywords unavailable: {}", e))
+            .ok();
+        let page_loader = PageLoader::new(&config, security_manager.clone())
+            .map(Arc::new)
+            .map_err(|e| log::error!("Page loading unavailable: {}", e))
+            .ok();
 
         Self {
             tab_manager: Arc::new(Mutex::new(TabManager::new())),
             security_manager,
+            bookmark_manager,
+            page_loader,
             config,
         }
     }
 
+    #[allow(dead_code)]
     pub fn navigate(&self, request: NavigationRequest) -> Result<Uuid> {
         let url = self.process_url(&request.url)?;
 
@@ -57,21 +72,23 @@ impl WebViewManager {
 
         log_navigation(&final_url);
 
-        let tab_id = if request.is_new_tab || request.tab_id.is_none() {
-            let mut tab_manager = self.tab_manager.lock().unwrap();
-            tab_manager.create_tab(final_url.clone(), None)
-        } else {
-            let tab_id = request.tab_id.unwrap();
-            let mut tab_manager = self.tab_manager.lock().unwrap();
-            tab_manager.update_tab_url(tab_id, final_url.clone());
-            tab_manager.set_tab_loading(tab_id, true);
-            tab_id
+        let tab_id = match request.tab_id {
+            Some(tab_id) if !request.is_new_tab => {
+                let mut tab_manager = self.tab_manager.lock().unwrap();
+                tab_manager.update_tab_url(tab_id, final_url.clone());
+                tab_manager.set_tab_loading(tab_id, true);
+                tab_id
+            }
+            _ => {
+                let mut tab_manager = self.tab_manager.lock().unwrap();
+                tab_manager.create_tab(final_url.clone(), None)
+            }
         };
 
         Ok(tab_id)
     }
 
-    fn process_url(&self, input: &str) -> Result<String> {
+    pub fn process_url(&self, input: &str) -> Result<String> {
         let trimmed = input.trim();
 
         // Check if it's already a valid URL
@@ -92,6 +109,15 @@ impl WebViewManager {
             }
         }
 
+        // Check bookmark keywords like `gh rust-lang/rust`
+        if let Some(bookmark_manager) = &self.bookmark_manager {
+            match bookmark_manager.expand_keyword(trimmed) {
+                Ok(Some(url)) => return Ok(url),
+                Ok(None) => {}
+                Err(e) => log::warn!("Keyword lookup failed: {}", e),
+            }
+        }
+
         // Treat as search query
         self.create_search_url(trimmed)
     }
@@ -105,6 +131,7 @@ impl WebViewManager {
         Ok(search_url)
     }
 
+    #[allow(dead_code)]
     pub fn go_back(&self, tab_id: Uuid) -> Result<()> {
         let tab_manager = self.tab_manager.lock().unwrap();
         if let Some(tab) = tab_manager.get_tab(tab_id) {
@@ -117,6 +144,7 @@ impl WebViewManager {
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub fn go_forward(&self, tab_id: Uuid) -> Result<()> {
         let tab_manager = self.tab_manager.lock().unwrap();
         if let Some(tab) = tab_manager.get_tab(tab_id) {
@@ -128,32 +156,47 @@ impl WebViewManager {
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub fn reload(&self, tab_id: Uuid) -> Result<()> {
         // reloader i barely know her.
         log::info!("Reload tab: {}", tab_id);
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub fn create_new_tab(&self, url: Option<String>) -> Result<Uuid> {
         let mut tab_manager = self.tab_manager.lock().unwrap();
         let default_url = url.unwrap_or_else(|| "bagel://home".to_string());
         Ok(tab_manager.create_tab(default_url, None))
     }
 
+    #[allow(dead_code)]
     pub fn close_tab(&self, tab_id: Uuid) -> Result<bool> {
         let mut tab_manager = self.tab_manager.lock().unwrap();
         Ok(tab_manager.close_tab(tab_id))
     }
 
+    #[allow(dead_code)]
     pub fn set_active_tab(&self, tab_id: Uuid) -> Result<bool> {
         let mut tab_manager = self.tab_manager.lock().unwrap();
         Ok(tab_manager.set_active_tab(tab_id))
     }
 
+    /// Clones share the same filters, so updates reach every clone.
+    pub fn security_manager(&self) -> SecurityManager {
+        self.security_manager.clone()
+    }
+
+    pub fn page_loader(&self) -> Option<Arc<PageLoader>> {
+        self.page_loader.clone()
+    }
+
+    #[allow(dead_code)]
     pub fn get_tab_manager(&self) -> Arc<Mutex<TabManager>> {
         Arc::clone(&self.tab_manager)
     }
 
+    #[allow(dead_code)]
     pub fn update_tab_info(
         &self,
         tab_id: Uuid,
@@ -1,5 +1,7 @@
 pub mod userscripts;
 pub mod userstyles;
 
+#[allow(unused_imports)]
 pub use userscripts::*;
+#[allow(unused_imports)]
 pub use userstyles::*;
\ No newline at end of file
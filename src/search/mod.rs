@@ -1,3 +1,4 @@
 pub mod provider;
 
+#[allow(unused_imports)]
 pub use provider::*;
\ No newline at end of file
@@ -1,8 +1,10 @@
 use anyhow::Result;
-use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, Row};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{params, params_from_iter, Connection, Row};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::path::Path;
+use url::Url;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +18,7 @@ pub struct Bookmark {
     pub favicon: Option<String>,
     pub description: Option<String>,
     pub tags: Vec<String>,
+    pub keyword: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +30,183 @@ pub struct BookmarkFolder {
     pub position: i32,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: u32,
+}
+
+/// A combination of tags to filter bookmarks by. Empty lists don't constrain anything.
+/// Tags are compared ignoring ASCII case everywhere, the way sqlite's NOCASE does.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TagQuery {
+    /// Every one of these tags must be present.
+    pub all: Vec<String>,
+    /// At least one tag from each of these groups must be present.
+    pub any: Vec<Vec<String>>,
+    /// None of these tags may be present.
+    pub none: Vec<String>,
+}
+
+impl TagQuery {
+    /// Pulls `tag:` terms out of a search string and returns the query with what's left.
+    /// `tag:a` requires a tag, `tag:a|b` requires either and `-tag:a` excludes one.
+    pub fn parse(input: &str) -> (TagQuery, String) {
+        let mut query = TagQuery::default();
+        let mut rest = Vec::new();
+
+        for term in input.split_whitespace() {
+            if let Some(tag) = term.strip_prefix("-tag:") {
+                query.none.extend(split_tags(tag));
+            } else if let Some(tags) = term.strip_prefix("tag:") {
+                let tags = split_tags(tags);
+                if tags.len() > 1 {
+                    query.any.push(tags);
+                } else {
+                    query.all.extend(tags);
+                }
+            } else {
+                rest.push(term);
+            }
+        }
+
+        (query, rest.join(" "))
+    }
+
+    /// Builds the WHERE conditions for this query against the `bookmarks` table.
+    fn to_sql(&self, params: &mut Vec<String>) -> Vec<String> {
+        let mut conditions = Vec::new();
+
+        for tag in &self.all {
+            params.push(tag.clone());
+            conditions.push(format!(
+                "id IN (SELECT bookmark_id FROM bookmark_tags WHERE tag = ?{} COLLATE NOCASE)",
+                params.len()
+            ));
+        }
+
+        for group in &self.any {
+            conditions.push(format!(
+                "id IN (SELECT bookmark_id FROM bookmark_tags WHERE tag COLLATE NOCASE IN ({}))",
+                placeholders(group, params)
+            ));
+        }
+
+        if !self.none.is_empty() {
+            conditions.push(format!(
+                "id NOT IN (SELECT bookmark_id FROM bookmark_tags WHERE tag COLLATE NOCASE IN ({}))",
+                placeholders(&self.none, params)
+            ));
+        }
+
+        conditions
+    }
+}
+
+fn split_tags(input: &str) -> Vec<String> {
+    input
+        .split('|')
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+fn placeholders(values: &[String], params: &mut Vec<String>) -> String {
+    values
+        .iter()
+        .map(|value| {
+            params.push(value.clone());
+            format!("?{}", params.len())
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The outcome of the last dead-link check for a bookmark.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkCheck {
+    pub bookmark_id: String,
+    pub url: String,
+    pub status: Option<u16>,
+    pub final_url: Option<String>,
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl LinkCheck {
+    /// The request failed outright or the server answered with an error status.
+    pub fn is_broken(&self) -> bool {
+        self.error.is_some() || self.status.is_none_or(|status| status >= 400)
+    }
+
+    /// The bookmark still works but redirects somewhere else.
+    pub fn is_moved(&self) -> bool {
+        !self.is_broken()
+            && self.final_url.as_deref().is_some_and(|final_url| {
+                final_url.trim_end_matches('/') != self.url.trim_end_matches('/')
+            })
+    }
+}
+
+/// Returned by [`BookmarkManager::add_bookmark`]. `duplicates` lists bookmarks that
+/// already pointed at the same url before this one was added.
+#[derive(Debug, Clone)]
+pub struct AddedBookmark {
+    pub id: String,
+    pub duplicates: Vec<Bookmark>,
+}
+
+/// Bookmarks that point at the same page once their urls are normalized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub normalized_url: String,
+    pub bookmarks: Vec<Bookmark>,
+}
+
+/// Normalizes a url for duplicate detection. Scheme and `www.` differences,
+/// fragments, trailing slashes, `utm_*` tracking parameters and query order are
+/// all ignored. Anything that doesn't parse is compared as trimmed text.
+pub fn normalize_url(url: &str) -> String {
+    let Ok(parsed) = Url::parse(url.trim()) else {
+        return url.trim().to_string();
+    };
+
+    if !matches!(parsed.scheme(), "http" | "https") {
+        let mut parsed = parsed;
+        parsed.set_fragment(None);
+        return parsed.to_string();
+    }
+
+    let host = parsed.host_str().unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host);
+
+    let mut normalized = host.to_string();
+    if let Some(port) = parsed.port() {
+        normalized.push_str(&format!(":{}", port));
+    }
+    normalized.push_str(parsed.path().trim_end_matches('/'));
+
+    let mut query: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_"))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    query.sort();
+
+    if !query.is_empty() {
+        let query = query
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join("&");
+        normalized.push('?');
+        normalized.push_str(&query);
+    }
+
+    normalized
+}
+
+#[derive(Clone)]
 pub struct BookmarkManager {
     db_path: std::path::PathBuf,
 }
@@ -79,12 +259,58 @@ impl BookmarkManager {
             [],
         )?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS bookmark_link_checks (
+                bookmark_id TEXT PRIMARY KEY,
+                url TEXT NOT NULL,
+                status INTEGER,
+                final_url TEXT,
+                error TEXT,
+                checked_at DATETIME NOT NULL,
+                FOREIGN KEY(bookmark_id) REFERENCES bookmarks(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.migrate_keyword_column(&conn)?;
+
         // Create default folders
         self.create_default_folders()?;
 
         Ok(())
     }
 
+    /// Databases created before bookmark keywords existed don't have the column yet,
+    /// and ones from before keywords ignored case have a case-sensitive index.
+    fn migrate_keyword_column(&self, conn: &Connection) -> Result<()> {
+        let has_keyword: bool = conn
+            .prepare("SELECT 1 FROM pragma_table_info('bookmarks') WHERE name = 'keyword'")?
+            .exists([])?;
+
+        if !has_keyword {
+            conn.execute("ALTER TABLE bookmarks ADD COLUMN keyword TEXT", [])?;
+        }
+
+        // keywords used to be case-sensitive, so `GH` and `gh` could both be
+        // taken. The oldest one keeps its keyword.
+        conn.execute("DROP INDEX IF EXISTS idx_bookmarks_keyword", [])?;
+        conn.execute(
+            "UPDATE bookmarks SET keyword = NULL
+             WHERE keyword IS NOT NULL AND rowid NOT IN (
+                 SELECT MIN(rowid) FROM bookmarks WHERE keyword IS NOT NULL
+                 GROUP BY keyword COLLATE NOCASE
+             )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_bookmarks_keyword_nocase
+             ON bookmarks(keyword COLLATE NOCASE)",
+            [],
+        )?;
+
+        Ok(())
+    }
+
     fn create_default_folders(&self) -> Result<()> {
         let conn = Connection::open(&self.db_path)?;
         let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
@@ -119,7 +345,14 @@ impl BookmarkManager {
         Ok(())
     }
 
-    pub fn add_bookmark(&self, title: &str, url: &str, folder_id: Option<String>) -> Result<String> {
+    pub fn add_bookmark(
+        &self,
+        title: &str,
+        url: &str,
+        folder_id: Option<String>,
+    ) -> Result<AddedBookmark> {
+        let duplicates = self.find_bookmarks_by_url(url)?;
+
         let conn = Connection::open(&self.db_path)?;
         let id = Uuid::new_v4().to_string();
         let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
@@ -131,7 +364,93 @@ impl BookmarkManager {
             params![id, title, url, folder, now],
         )?;
 
-        Ok(id)
+        if !duplicates.is_empty() {
+            log::info!("{} is already bookmarked {} time(s)", url, duplicates.len());
+        }
+
+        Ok(AddedBookmark { id, duplicates })
+    }
+
+    /// Bookmarks whose url matches `url` after normalization, see [`normalize_url`].
+    pub fn find_bookmarks_by_url(&self, url: &str) -> Result<Vec<Bookmark>> {
+        let normalized = normalize_url(url);
+        Ok(self
+            .get_all_bookmarks()?
+            .into_iter()
+            .filter(|bookmark| normalize_url(&bookmark.url) == normalized)
+            .collect())
+    }
+
+    /// Groups bookmarks across all folders that point at the same normalized url.
+    /// Each group is ordered oldest first.
+    pub fn find_duplicates(&self) -> Result<Vec<DuplicateGroup>> {
+        let mut groups: BTreeMap<String, Vec<Bookmark>> = BTreeMap::new();
+        for bookmark in self.get_all_bookmarks()? {
+            groups
+                .entry(normalize_url(&bookmark.url))
+                .or_default()
+                .push(bookmark);
+        }
+
+        Ok(groups
+            .into_iter()
+            .filter(|(_, bookmarks)| bookmarks.len() > 1)
+            .map(|(normalized_url, mut bookmarks)| {
+                bookmarks.sort_by_key(|bookmark| bookmark.created_at);
+                DuplicateGroup {
+                    normalized_url,
+                    bookmarks,
+                }
+            })
+            .collect())
+    }
+
+    /// Merges `others` into `survivor_id`. The survivor gains their tags, their
+    /// descriptions are appended to its own, it takes over a keyword if it had
+    /// none, and the others are deleted.
+    pub fn merge_bookmarks(&self, survivor_id: &str, others: &[String]) -> Result<()> {
+        let survivor = self
+            .get_bookmark(survivor_id)?
+            .ok_or_else(|| anyhow::anyhow!("Bookmark {} not found", survivor_id))?;
+
+        let mut tags = survivor.tags.clone();
+        let mut descriptions: Vec<String> = survivor.description.clone().into_iter().collect();
+        let mut keyword = survivor.keyword.clone();
+
+        for id in others.iter().filter(|id| id.as_str() != survivor_id) {
+            let Some(other) = self.get_bookmark(id)? else {
+                continue;
+            };
+
+            for tag in other.tags {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+
+            if let Some(description) = other.description {
+                let description = description.trim().to_string();
+                if !description.is_empty() && !descriptions.contains(&description) {
+                    descriptions.push(description);
+                }
+            }
+
+            // the other bookmark is deleted before the keyword is set, so it's free to take
+            if keyword.is_none() && other.keyword.is_some() {
+                keyword = other.keyword;
+            }
+            self.delete_bookmark(&other.id)?;
+        }
+
+        self.set_bookmark_tags(survivor_id, &tags)?;
+        let description = descriptions.join("\n");
+        self.set_bookmark_description(
+            survivor_id,
+            (!description.is_empty()).then_some(description.as_str()),
+        )?;
+        self.set_bookmark_keyword(survivor_id, keyword.as_deref())?;
+
+        Ok(())
     }
 
     pub fn update_bookmark(&self, id: &str, title: Option<&str>, url: Option<&str>, folder_id: Option<&str>) -> Result<()> {
@@ -162,25 +481,140 @@ impl BookmarkManager {
         Ok(())
     }
 
+    pub fn set_bookmark_description(&self, id: &str, description: Option<&str>) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "UPDATE bookmarks SET description = ?1, updated_at = ?2 WHERE id = ?3",
+            params![description, now, id],
+        )?;
+        Ok(())
+    }
+
+    /// Sets or clears the address bar keyword for a bookmark.
+    /// Keywords are a single word and unique across all bookmarks, ignoring case.
+    pub fn set_bookmark_keyword(&self, id: &str, keyword: Option<&str>) -> Result<()> {
+        let keyword = keyword.map(str::trim).filter(|k| !k.is_empty());
+
+        if let Some(keyword) = keyword {
+            if keyword.contains(char::is_whitespace) {
+                return Err(anyhow::anyhow!(
+                    "Keyword '{}' cannot contain spaces",
+                    keyword
+                ));
+            }
+
+            if let Some(existing) = self.find_by_keyword(keyword)? {
+                if existing.id != id {
+                    return Err(anyhow::anyhow!(
+                        "Keyword '{}' is already used by '{}'",
+                        keyword,
+                        existing.title
+                    ));
+                }
+            }
+        }
+
+        let conn = Connection::open(&self.db_path)?;
+        let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "UPDATE bookmarks SET keyword = ?1, updated_at = ?2 WHERE id = ?3",
+            params![keyword, now, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn find_by_keyword(&self, keyword: &str) -> Result<Option<Bookmark>> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let id: Option<String> = conn
+            .prepare("SELECT id FROM bookmarks WHERE keyword = ?1 COLLATE NOCASE")?
+            .query_map(params![keyword], |row| row.get(0))?
+            .next()
+            .transpose()?;
+
+        match id {
+            Some(id) => self.get_bookmark(&id),
+            None => Ok(None),
+        }
+    }
+
+    /// Expands address bar input like `gh rust-lang/rust` using bookmark keywords.
+    /// `%s` in the bookmark url is replaced with the encoded arguments and `%S` with
+    /// the raw ones. Returns `None` when the first word isn't a keyword, or when
+    /// arguments were given to a bookmark that has nowhere to put them.
+    pub fn expand_keyword(&self, input: &str) -> Result<Option<String>> {
+        let input = input.trim();
+        let (keyword, args) = match input.split_once(char::is_whitespace) {
+            Some((keyword, args)) => (keyword, args.trim()),
+            None => (input, ""),
+        };
+
+        let Some(bookmark) = self.find_by_keyword(keyword)? else {
+            return Ok(None);
+        };
+
+        let has_placeholder = bookmark.url.contains("%s") || bookmark.url.contains("%S");
+        if !has_placeholder {
+            return Ok(args.is_empty().then_some(bookmark.url));
+        }
+
+        // slashes are left alone so `%s` works in paths as well as query strings
+        let encoded = args
+            .split('/')
+            .map(|segment| urlencoding::encode(segment).into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let url = bookmark.url.replace("%s", &encoded).replace("%S", args);
+        Ok(Some(url))
+    }
+
     pub fn delete_bookmark(&self, id: &str) -> Result<()> {
         let conn = Connection::open(&self.db_path)?;
+        // foreign keys aren't enabled on our connections so the cascade never fires
+        conn.execute(
+            "DELETE FROM bookmark_tags WHERE bookmark_id = ?1",
+            params![id],
+        )?;
+        conn.execute(
+            "DELETE FROM bookmark_link_checks WHERE bookmark_id = ?1",
+            params![id],
+        )?;
         conn.execute("DELETE FROM bookmarks WHERE id = ?1", params![id])?;
         Ok(())
     }
 
-    pub fn get_bookmarks_in_folder(&self, folder_id: &str) -> Result<Vec<Bookmark>> {
+    pub fn get_bookmark(&self, id: &str) -> Result<Option<Bookmark>> {
         let conn = Connection::open(&self.db_path)?;
-        
+
         let mut stmt = conn.prepare(
-            "SELECT id, title, url, folder_id, created_at, updated_at, favicon, description 
+            "SELECT id, title, url, folder_id, created_at, updated_at, favicon, description, keyword 
+             FROM bookmarks 
+             WHERE id = ?1"
+        )?;
+
+        let mut rows = stmt.query_map(params![id], |row| self.row_to_bookmark(row))?;
+        match rows.next() {
+            Some(row) => {
+                let mut bookmark = row?;
+                bookmark.tags = self.get_bookmark_tags(&bookmark.id)?;
+                Ok(Some(bookmark))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_all_bookmarks(&self) -> Result<Vec<Bookmark>> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, title, url, folder_id, created_at, updated_at, favicon, description, keyword 
              FROM bookmarks 
-             WHERE folder_id = ?1 
              ORDER BY title"
         )?;
 
-        let rows = stmt.query_map(params![folder_id], |row| {
-            Ok(self.row_to_bookmark(row)?)
-        })?;
+        let rows = stmt.query_map([], |row| self.row_to_bookmark(row))?;
 
         let mut bookmarks = Vec::new();
         for row in rows {
@@ -192,20 +626,65 @@ impl BookmarkManager {
         Ok(bookmarks)
     }
 
-    pub fn search_bookmarks(&self, query: &str) -> Result<Vec<Bookmark>> {
+    #[allow(dead_code)]
+    pub fn get_bookmarks_in_folder(&self, folder_id: &str) -> Result<Vec<Bookmark>> {
         let conn = Connection::open(&self.db_path)?;
         
         let mut stmt = conn.prepare(
-            "SELECT id, title, url, folder_id, created_at, updated_at, favicon, description 
+            "SELECT id, title, url, folder_id, created_at, updated_at, favicon, description, keyword 
              FROM bookmarks 
-             WHERE title LIKE ?1 OR url LIKE ?1 OR description LIKE ?1
+             WHERE folder_id = ?1 
              ORDER BY title"
         )?;
 
-        let search_pattern = format!("%{}%", query);
-        let rows = stmt.query_map(params![search_pattern], |row| {
-            Ok(self.row_to_bookmark(row)?)
-        })?;
+        let rows = stmt.query_map(params![folder_id], |row| self.row_to_bookmark(row))?;
+
+        let mut bookmarks = Vec::new();
+        for row in rows {
+            let mut bookmark = row?;
+            bookmark.tags = self.get_bookmark_tags(&bookmark.id)?;
+            bookmarks.push(bookmark);
+        }
+
+        Ok(bookmarks)
+    }
+
+    /// Searches titles, urls and descriptions. Supports `tag:` terms, see [`TagQuery::parse`].
+    pub fn search_bookmarks(&self, query: &str) -> Result<Vec<Bookmark>> {
+        let (tag_query, text) = TagQuery::parse(query);
+
+        let mut params = Vec::new();
+        let mut conditions = tag_query.to_sql(&mut params);
+
+        if !text.is_empty() {
+            params.push(format!("%{}%", text));
+            conditions.push(format!(
+                "(title LIKE ?{0} OR url LIKE ?{0} OR description LIKE ?{0})",
+                params.len()
+            ));
+        }
+
+        self.query_bookmarks(&conditions, &params)
+    }
+
+    fn query_bookmarks(&self, conditions: &[String], params: &[String]) -> Result<Vec<Bookmark>> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, title, url, folder_id, created_at, updated_at, favicon, description, keyword 
+             FROM bookmarks 
+             {} 
+             ORDER BY title",
+            where_clause
+        ))?;
+
+        let rows = stmt.query_map(params_from_iter(params), |row| self.row_to_bookmark(row))?;
 
         let mut bookmarks = Vec::new();
         for row in rows {
@@ -232,6 +711,43 @@ impl BookmarkManager {
         Ok(id)
     }
 
+    pub fn rename_folder(&self, id: &str, name: &str) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "UPDATE bookmark_folders SET name = ?1 WHERE id = ?2",
+            params![name, id],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes a folder, handing its bookmarks and subfolders to its parent.
+    /// The built-in folders can't be deleted.
+    pub fn delete_folder(&self, id: &str) -> Result<()> {
+        if matches!(id, "root" | "toolbar" | "other") {
+            return Err(anyhow::anyhow!("Cannot delete built-in folder '{}'", id));
+        }
+
+        let conn = Connection::open(&self.db_path)?;
+        let parent_id: Option<String> = conn.query_row(
+            "SELECT parent_id FROM bookmark_folders WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        let parent = parent_id.unwrap_or_else(|| "other".to_string());
+
+        conn.execute(
+            "UPDATE bookmarks SET folder_id = ?1 WHERE folder_id = ?2",
+            params![parent, id],
+        )?;
+        conn.execute(
+            "UPDATE bookmark_folders SET parent_id = ?1 WHERE parent_id = ?2",
+            params![parent, id],
+        )?;
+        conn.execute("DELETE FROM bookmark_folders WHERE id = ?1", params![id])?;
+
+        Ok(())
+    }
+
     pub fn get_folders(&self) -> Result<Vec<BookmarkFolder>> {
         let conn = Connection::open(&self.db_path)?;
         
@@ -241,9 +757,7 @@ impl BookmarkManager {
              ORDER BY position, name"
         )?;
 
-        let rows = stmt.query_map([], |row| {
-            Ok(self.row_to_folder(row)?)
-        })?;
+        let rows = stmt.query_map([], |row| self.row_to_folder(row))?;
 
         let mut folders = Vec::new();
         for row in rows {
@@ -253,31 +767,169 @@ impl BookmarkManager {
         Ok(folders)
     }
 
+    #[allow(dead_code)]
     pub fn add_bookmark_tag(&self, bookmark_id: &str, tag: &str) -> Result<()> {
         let conn = Connection::open(&self.db_path)?;
         conn.execute(
-            "INSERT OR IGNORE INTO bookmark_tags (bookmark_id, tag) VALUES (?1, ?2)",
+            "INSERT INTO bookmark_tags (bookmark_id, tag)
+             SELECT ?1, ?2 WHERE NOT EXISTS (
+                 SELECT 1 FROM bookmark_tags WHERE bookmark_id = ?1 AND tag = ?2 COLLATE NOCASE
+             )",
             params![bookmark_id, tag],
         )?;
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub fn remove_bookmark_tag(&self, bookmark_id: &str, tag: &str) -> Result<()> {
         let conn = Connection::open(&self.db_path)?;
         conn.execute(
-            "DELETE FROM bookmark_tags WHERE bookmark_id = ?1 AND tag = ?2",
+            "DELETE FROM bookmark_tags WHERE bookmark_id = ?1 AND tag = ?2 COLLATE NOCASE",
             params![bookmark_id, tag],
         )?;
         Ok(())
     }
 
+    /// Replaces all tags on a bookmark. Tags are trimmed and blanks are dropped,
+    /// and of tags differing only in case the first one is kept.
+    pub fn set_bookmark_tags(&self, bookmark_id: &str, tags: &[String]) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "DELETE FROM bookmark_tags WHERE bookmark_id = ?1",
+            params![bookmark_id],
+        )?;
+
+        let mut seen = HashSet::new();
+        for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            if !seen.insert(tag.to_ascii_lowercase()) {
+                continue;
+            }
+            conn.execute(
+                "INSERT OR IGNORE INTO bookmark_tags (bookmark_id, tag) VALUES (?1, ?2)",
+                params![bookmark_id, tag],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Every tag with how many bookmarks carry it. Spellings that differ only
+    /// in case count as one tag.
+    pub fn get_tags_with_counts(&self) -> Result<Vec<TagCount>> {
+        let conn = Connection::open(&self.db_path)?;
+        let mut stmt = conn.prepare(
+            "SELECT MIN(tag), COUNT(DISTINCT bookmark_id) FROM bookmark_tags
+             GROUP BY tag COLLATE NOCASE
+             ORDER BY COUNT(DISTINCT bookmark_id) DESC, MIN(tag) COLLATE NOCASE",
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(TagCount {
+                tag: row.get(0)?,
+                count: row.get(1)?,
+            })
+        })?;
+
+        let mut tags = Vec::new();
+        for row in rows {
+            tags.push(row?);
+        }
+
+        Ok(tags)
+    }
+
+    /// Renames a tag on every bookmark, ignoring case like everything else
+    /// about tags. Renaming onto a tag that already exists merges the two, and
+    /// renaming to a different case just respells it. Every spelling of both
+    /// ends up as `new_tag`. Returns how many bookmarks were touched.
+    pub fn rename_tag(&self, old_tag: &str, new_tag: &str) -> Result<u32> {
+        let new_tag = new_tag.trim();
+        if new_tag.is_empty() {
+            return Err(anyhow::anyhow!("Tag name cannot be empty"));
+        }
+        if new_tag == old_tag {
+            return Ok(0);
+        }
+
+        let mut conn = Connection::open(&self.db_path)?;
+        let tx = conn.transaction()?;
+
+        let touched: u32 = tx.query_row(
+            "SELECT COUNT(DISTINCT bookmark_id) FROM bookmark_tags
+             WHERE tag = ?1 COLLATE NOCASE AND tag != ?2",
+            params![old_tag, new_tag],
+            |row| row.get(0),
+        )?;
+
+        // respell what can be respelled, a bookmark with two spellings keeps
+        // one of them here and loses the other below
+        tx.execute(
+            "UPDATE OR IGNORE bookmark_tags SET tag = ?2
+             WHERE (tag = ?1 COLLATE NOCASE OR tag = ?2 COLLATE NOCASE) AND tag != ?2",
+            params![old_tag, new_tag],
+        )?;
+        tx.execute(
+            "DELETE FROM bookmark_tags
+             WHERE (tag = ?1 COLLATE NOCASE OR tag = ?2 COLLATE NOCASE) AND tag != ?2",
+            params![old_tag, new_tag],
+        )?;
+
+        tx.commit()?;
+        Ok(touched)
+    }
+
+    pub fn record_link_check(&self, check: &LinkCheck) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO bookmark_link_checks 
+             (bookmark_id, url, status, final_url, error, checked_at) 
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                check.bookmark_id,
+                check.url,
+                check.status,
+                check.final_url,
+                check.error,
+                check.checked_at.format("%Y-%m-%d %H:%M:%S").to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Link checks for bookmarks that still exist and haven't changed url since.
+    pub fn get_link_checks(&self) -> Result<Vec<LinkCheck>> {
+        let conn = Connection::open(&self.db_path)?;
+        let mut stmt = conn.prepare(
+            "SELECT c.bookmark_id, c.url, c.status, c.final_url, c.error, c.checked_at 
+             FROM bookmark_link_checks c 
+             JOIN bookmarks b ON b.id = c.bookmark_id AND b.url = c.url 
+             ORDER BY c.checked_at DESC",
+        )?;
+
+        let rows = stmt.query_map([], |row| self.row_to_link_check(row))?;
+
+        let mut checks = Vec::new();
+        for row in rows {
+            checks.push(row?);
+        }
+
+        Ok(checks)
+    }
+
+    pub fn clear_link_check(&self, bookmark_id: &str) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "DELETE FROM bookmark_link_checks WHERE bookmark_id = ?1",
+            params![bookmark_id],
+        )?;
+        Ok(())
+    }
+
     fn get_bookmark_tags(&self, bookmark_id: &str) -> Result<Vec<String>> {
         let conn = Connection::open(&self.db_path)?;
         let mut stmt = conn.prepare("SELECT tag FROM bookmark_tags WHERE bookmark_id = ?1")?;
         
-        let rows = stmt.query_map(params![bookmark_id], |row| {
-            Ok(row.get::<_, String>(0)?)
-        })?;
+        let rows = stmt.query_map(params![bookmark_id], |row| row.get::<_, String>(0))?;
 
         let mut tags = Vec::new();
         for row in rows {
@@ -291,13 +943,14 @@ impl BookmarkManager {
         let created_at_str: String = row.get(4)?;
         let updated_at_str: String = row.get(5)?;
         
-        let created_at = DateTime::parse_from_str(&created_at_str, "%Y-%m-%d %H:%M:%S")
+        // timestamps are stored without an offset, so parse them as naive UTC
+        let created_at = NaiveDateTime::parse_from_str(&created_at_str, "%Y-%m-%d %H:%M:%S")
             .map_err(|_| rusqlite::Error::InvalidColumnType(4, "created_at".to_string(), rusqlite::types::Type::Text))?
-            .with_timezone(&Utc);
+            .and_utc();
             
-        let updated_at = DateTime::parse_from_str(&updated_at_str, "%Y-%m-%d %H:%M:%S")
+        let updated_at = NaiveDateTime::parse_from_str(&updated_at_str, "%Y-%m-%d %H:%M:%S")
             .map_err(|_| rusqlite::Error::InvalidColumnType(5, "updated_at".to_string(), rusqlite::types::Type::Text))?
-            .with_timezone(&Utc);
+            .and_utc();
 
         Ok(Bookmark {
             id: row.get(0)?,
@@ -309,15 +962,39 @@ impl BookmarkManager {
             favicon: row.get(6)?,
             description: row.get(7)?,
             tags: Vec::new(), // Will be populated separately
+            keyword: row.get(8)?,
+        })
+    }
+
+    fn row_to_link_check(&self, row: &Row) -> Result<LinkCheck, rusqlite::Error> {
+        let checked_at_str: String = row.get(5)?;
+
+        let checked_at = NaiveDateTime::parse_from_str(&checked_at_str, "%Y-%m-%d %H:%M:%S")
+            .map_err(|_| {
+                rusqlite::Error::InvalidColumnType(
+                    5,
+                    "checked_at".to_string(),
+                    rusqlite::types::Type::Text,
+                )
+            })?
+            .and_utc();
+
+        Ok(LinkCheck {
+            bookmark_id: row.get(0)?,
+            url: row.get(1)?,
+            status: row.get(2)?,
+            final_url: row.get(3)?,
+            error: row.get(4)?,
+            checked_at,
         })
     }
 
     fn row_to_folder(&self, row: &Row) -> Result<BookmarkFolder, rusqlite::Error> {
         let created_at_str: String = row.get(3)?;
         
-        let created_at = DateTime::parse_from_str(&created_at_str, "%Y-%m-%d %H:%M:%S")
+        let created_at = NaiveDateTime::parse_from_str(&created_at_str, "%Y-%m-%d %H:%M:%S")
             .map_err(|_| rusqlite::Error::InvalidColumnType(3, "created_at".to_string(), rusqlite::types::Type::Text))?
-            .with_timezone(&Utc);
+            .and_utc();
 
         Ok(BookmarkFolder {
             id: row.get(0)?,
@@ -327,4 +1004,428 @@ impl BookmarkManager {
             position: row.get(4)?,
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn manager() -> (TempDir, BookmarkManager) {
+        let dir = TempDir::new().unwrap();
+        let manager = BookmarkManager::new(dir.path()).unwrap();
+        (dir, manager)
+    }
+
+    #[test]
+    fn new_bookmarks_go_on_the_toolbar() {
+        let (_dir, manager) = manager();
+        let added = manager
+            .add_bookmark("Rust", "https://www.rust-lang.org/", None)
+            .unwrap();
+
+        let bookmark = manager.get_bookmark(&added.id).unwrap().unwrap();
+        assert_eq!(bookmark.title, "Rust");
+        assert_eq!(bookmark.folder_id.as_deref(), Some("toolbar"));
+        assert!(manager.get_bookmark("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn tags_and_descriptions_can_be_replaced() {
+        let (_dir, manager) = manager();
+        let id = manager
+            .add_bookmark("Docs", "https://docs.rs/", None)
+            .unwrap()
+            .id;
+
+        manager
+            .set_bookmark_tags(
+                &id,
+                &["rust".to_string(), " ".to_string(), " docs ".to_string()],
+            )
+            .unwrap();
+        manager
+            .set_bookmark_description(&id, Some("crate docs"))
+            .unwrap();
+
+        let mut bookmark = manager.get_bookmark(&id).unwrap().unwrap();
+        bookmark.tags.sort();
+        assert_eq!(bookmark.tags, vec!["docs", "rust"]);
+        assert_eq!(bookmark.description.as_deref(), Some("crate docs"));
+
+        manager.set_bookmark_tags(&id, &[]).unwrap();
+        assert!(manager.get_bookmark(&id).unwrap().unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn deleting_a_bookmark_drops_its_tags() {
+        let (_dir, manager) = manager();
+        let id = manager
+            .add_bookmark("Docs", "https://docs.rs/", None)
+            .unwrap()
+            .id;
+        manager
+            .set_bookmark_tags(&id, &["rust".to_string()])
+            .unwrap();
+
+        manager.delete_bookmark(&id).unwrap();
+
+        assert!(manager.get_all_bookmarks().unwrap().is_empty());
+        assert!(manager.get_tags_with_counts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn deleting_a_folder_hands_its_contents_to_the_parent() {
+        let (_dir, manager) = manager();
+        let parent = manager.create_folder("Parent", None).unwrap();
+        let child = manager
+            .create_folder("Child", Some(parent.clone()))
+            .unwrap();
+        let inner = manager.create_folder("Inner", Some(child.clone())).unwrap();
+        let id = manager
+            .add_bookmark("Docs", "https://docs.rs/", Some(child.clone()))
+            .unwrap()
+            .id;
+
+        manager.delete_folder(&child).unwrap();
+
+        let bookmark = manager.get_bookmark(&id).unwrap().unwrap();
+        assert_eq!(bookmark.folder_id.as_deref(), Some(parent.as_str()));
+        let folders = manager.get_folders().unwrap();
+        assert!(folders.iter().all(|folder| folder.id != child));
+        let inner = folders.iter().find(|folder| folder.id == inner).unwrap();
+        assert_eq!(inner.parent_id.as_deref(), Some(parent.as_str()));
+    }
+
+    #[test]
+    fn built_in_folders_can_be_renamed_but_not_deleted() {
+        let (_dir, manager) = manager();
+        assert!(manager.delete_folder("toolbar").is_err());
+
+        let id = manager.create_folder("Reading", None).unwrap();
+        manager.rename_folder(&id, "Later").unwrap();
+
+        let folders = manager.get_folders().unwrap();
+        let folder = folders.iter().find(|folder| folder.id == id).unwrap();
+        assert_eq!(folder.name, "Later");
+    }
+
+    fn tagged(manager: &BookmarkManager, title: &str, tags: &[&str]) -> String {
+        let id = manager
+            .add_bookmark(title, &format!("https://{}.example/", title), None)
+            .unwrap()
+            .id;
+        let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+        manager.set_bookmark_tags(&id, &tags).unwrap();
+        id
+    }
+
+    fn titles(bookmarks: Vec<Bookmark>) -> Vec<String> {
+        bookmarks
+            .into_iter()
+            .map(|bookmark| bookmark.title)
+            .collect()
+    }
+
+    #[test]
+    fn tag_terms_are_pulled_out_of_searches() {
+        let (query, rest) = TagQuery::parse("tag:rust tag:web|cli -tag:old|draft async");
+        assert_eq!(query.all, vec!["rust"]);
+        assert_eq!(query.any, vec![vec!["web", "cli"]]);
+        assert_eq!(query.none, vec!["old", "draft"]);
+        assert_eq!(rest, "async");
+
+        let (query, rest) = TagQuery::parse("tag: plain");
+        assert!(query.all.is_empty() && query.any.is_empty() && query.none.is_empty());
+        assert_eq!(rest, "plain");
+    }
+
+    #[test]
+    fn searches_combine_tags_with_and_or_and_not() {
+        let (_dir, manager) = manager();
+        tagged(&manager, "a", &["rust", "web"]);
+        tagged(&manager, "b", &["rust", "cli"]);
+        tagged(&manager, "c", &["go", "cli"]);
+
+        assert_eq!(
+            titles(manager.search_bookmarks("tag:rust").unwrap()),
+            ["a", "b"]
+        );
+        assert_eq!(
+            titles(manager.search_bookmarks("tag:rust tag:cli").unwrap()),
+            ["b"]
+        );
+        assert_eq!(
+            titles(manager.search_bookmarks("tag:web|go").unwrap()),
+            ["a", "c"]
+        );
+        assert_eq!(
+            titles(manager.search_bookmarks("tag:cli -tag:rust").unwrap()),
+            ["c"]
+        );
+        assert_eq!(
+            titles(manager.search_bookmarks("tag:rust b.example").unwrap()),
+            ["b"]
+        );
+    }
+
+    #[test]
+    fn each_either_or_term_has_to_match_on_its_own() {
+        let (_dir, manager) = manager();
+        tagged(&manager, "a", &["rust", "web"]);
+        tagged(&manager, "b", &["rust", "cli"]);
+        tagged(&manager, "c", &["go", "db"]);
+
+        let (query, _) = TagQuery::parse("tag:rust|go tag:web|db");
+        assert_eq!(query.any, vec![vec!["rust", "go"], vec!["web", "db"]]);
+        assert_eq!(
+            titles(manager.search_bookmarks("tag:rust|go tag:web|db").unwrap()),
+            ["a", "c"]
+        );
+        assert!(manager
+            .search_bookmarks("tag:web|cli tag:go|db")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn tags_ignore_case_everywhere() {
+        let (_dir, manager) = manager();
+        let a = tagged(&manager, "a", &["Rust", "rust"]);
+        tagged(&manager, "b", &["rust"]);
+
+        assert_eq!(manager.get_bookmark(&a).unwrap().unwrap().tags, ["Rust"]);
+        assert_eq!(
+            titles(manager.search_bookmarks("tag:RUST").unwrap()),
+            ["a", "b"]
+        );
+
+        let counts = manager.get_tags_with_counts().unwrap();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[0].count, 2);
+
+        assert_eq!(manager.rename_tag("RUST", "Rust").unwrap(), 1);
+        let counts = manager.get_tags_with_counts().unwrap();
+        assert_eq!((counts[0].tag.as_str(), counts[0].count), ("Rust", 2));
+    }
+
+    #[test]
+    fn renaming_onto_an_existing_tag_merges_them() {
+        let (_dir, manager) = manager();
+        let a = tagged(&manager, "a", &["golang", "Go"]);
+        tagged(&manager, "b", &["go"]);
+        tagged(&manager, "c", &["Golang"]);
+
+        assert_eq!(manager.rename_tag("go", "golang").unwrap(), 2);
+
+        let counts = manager.get_tags_with_counts().unwrap();
+        assert_eq!(counts.len(), 1);
+        assert_eq!((counts[0].tag.as_str(), counts[0].count), ("golang", 3));
+        assert_eq!(manager.get_bookmark(&a).unwrap().unwrap().tags, ["golang"]);
+        assert!(manager.rename_tag("golang", "  ").is_err());
+    }
+
+    #[test]
+    fn keywords_expand_into_bookmark_urls() {
+        let (_dir, manager) = manager();
+        let gh = manager
+            .add_bookmark("GitHub", "https://github.com/%s", None)
+            .unwrap()
+            .id;
+        let wiki = manager
+            .add_bookmark(
+                "Wikipedia",
+                "https://en.wikipedia.org/w/index.php?search=%S",
+                None,
+            )
+            .unwrap()
+            .id;
+        let home = manager
+            .add_bookmark("Home", "https://example.com/", None)
+            .unwrap()
+            .id;
+        manager.set_bookmark_keyword(&gh, Some("gh")).unwrap();
+        manager.set_bookmark_keyword(&wiki, Some(" w ")).unwrap();
+        manager.set_bookmark_keyword(&home, Some("home")).unwrap();
+
+        assert_eq!(
+            manager
+                .expand_keyword("gh rust-lang/rust")
+                .unwrap()
+                .as_deref(),
+            Some("https://github.com/rust-lang/rust")
+        );
+        assert_eq!(
+            manager.expand_keyword("gh a b/c?").unwrap().as_deref(),
+            Some("https://github.com/a%20b/c%3F")
+        );
+        assert_eq!(
+            manager.expand_keyword("w rust lang").unwrap().as_deref(),
+            Some("https://en.wikipedia.org/w/index.php?search=rust lang")
+        );
+        assert_eq!(
+            manager.expand_keyword("home").unwrap().as_deref(),
+            Some("https://example.com/")
+        );
+        // nowhere to put the arguments, so it's a search instead
+        assert_eq!(manager.expand_keyword("home town").unwrap(), None);
+        assert_eq!(manager.expand_keyword("rust lang").unwrap(), None);
+    }
+
+    #[test]
+    fn keywords_are_single_unique_words() {
+        let (_dir, manager) = manager();
+        let a = manager
+            .add_bookmark("A", "https://a.example/", None)
+            .unwrap()
+            .id;
+        let b = manager
+            .add_bookmark("B", "https://b.example/", None)
+            .unwrap()
+            .id;
+
+        manager.set_bookmark_keyword(&a, Some("a")).unwrap();
+        assert!(manager.set_bookmark_keyword(&b, Some("a")).is_err());
+        assert!(manager.set_bookmark_keyword(&b, Some("two words")).is_err());
+        // setting its own keyword again is fine
+        manager.set_bookmark_keyword(&a, Some("a")).unwrap();
+
+        manager.set_bookmark_keyword(&a, Some("  ")).unwrap();
+        assert!(manager.find_by_keyword("a").unwrap().is_none());
+        manager.set_bookmark_keyword(&b, Some("a")).unwrap();
+        assert_eq!(manager.find_by_keyword("a").unwrap().unwrap().id, b);
+    }
+
+    #[test]
+    fn keywords_ignore_case() {
+        let (_dir, manager) = manager();
+        let a = manager
+            .add_bookmark("GitHub", "https://github.com/%s", None)
+            .unwrap()
+            .id;
+        let b = manager
+            .add_bookmark("GitLab", "https://gitlab.com/%s", None)
+            .unwrap()
+            .id;
+
+        manager.set_bookmark_keyword(&a, Some("GH")).unwrap();
+        assert!(manager.set_bookmark_keyword(&b, Some("gh")).is_err());
+        assert_eq!(manager.find_by_keyword("gh").unwrap().unwrap().id, a);
+        assert_eq!(
+            manager.expand_keyword("Gh rust-lang").unwrap().as_deref(),
+            Some("https://github.com/rust-lang")
+        );
+    }
+
+    #[test]
+    fn keywords_differing_only_in_case_are_cleaned_up_on_open() {
+        let (dir, manager) = manager();
+        let a = manager
+            .add_bookmark("A", "https://a.example/", None)
+            .unwrap()
+            .id;
+        let b = manager
+            .add_bookmark("B", "https://b.example/", None)
+            .unwrap()
+            .id;
+
+        // what an older, case-sensitive database could hold
+        let conn = Connection::open(&manager.db_path).unwrap();
+        conn.execute("DROP INDEX idx_bookmarks_keyword_nocase", [])
+            .unwrap();
+        conn.execute(
+            "UPDATE bookmarks SET keyword = 'gh' WHERE id = ?1",
+            params![a],
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE bookmarks SET keyword = 'GH' WHERE id = ?1",
+            params![b],
+        )
+        .unwrap();
+
+        let manager = BookmarkManager::new(dir.path()).unwrap();
+        assert_eq!(
+            manager
+                .get_bookmark(&a)
+                .unwrap()
+                .unwrap()
+                .keyword
+                .as_deref(),
+            Some("gh")
+        );
+        assert_eq!(manager.get_bookmark(&b).unwrap().unwrap().keyword, None);
+        assert!(manager.set_bookmark_keyword(&b, Some("Gh")).is_err());
+    }
+
+    #[test]
+    fn normalize_url_ignores_cosmetic_differences() {
+        assert_eq!(
+            normalize_url("https://www.example.com/a/?b=2&a=1&utm_source=x#top"),
+            normalize_url("http://example.com/a?a=1&b=2")
+        );
+        assert_eq!(
+            normalize_url("https://example.com:8080/"),
+            "example.com:8080"
+        );
+        assert_ne!(
+            normalize_url("https://example.com/a"),
+            normalize_url("https://example.com/b")
+        );
+        assert_eq!(normalize_url("  not a url "), "not a url");
+        assert_eq!(normalize_url("file:///tmp/x#frag"), "file:///tmp/x");
+    }
+
+    #[test]
+    fn adding_a_bookmark_reports_existing_copies() {
+        let (_dir, manager) = manager();
+        let first = manager
+            .add_bookmark("Example", "https://example.com/", None)
+            .unwrap();
+        assert!(first.duplicates.is_empty());
+
+        let second = manager
+            .add_bookmark("Example again", "http://www.example.com", None)
+            .unwrap();
+        assert_eq!(second.duplicates.len(), 1);
+        assert_eq!(second.duplicates[0].id, first.id);
+    }
+
+    #[test]
+    fn duplicates_are_grouped_and_merged() {
+        let (_dir, manager) = manager();
+        let a = manager
+            .add_bookmark("A", "https://example.com/page", None)
+            .unwrap()
+            .id;
+        let b = manager
+            .add_bookmark("B", "https://www.example.com/page/#x", None)
+            .unwrap()
+            .id;
+        manager
+            .add_bookmark("Other", "https://example.org/", None)
+            .unwrap();
+
+        manager.set_bookmark_tags(&a, &["one".to_string()]).unwrap();
+        manager.set_bookmark_tags(&b, &["two".to_string()]).unwrap();
+        manager
+            .set_bookmark_description(&b, Some("from b"))
+            .unwrap();
+        manager.set_bookmark_keyword(&b, Some("ex")).unwrap();
+
+        let groups = manager.find_duplicates().unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].normalized_url, "example.com/page");
+        assert_eq!(groups[0].bookmarks.len(), 2);
+
+        manager.merge_bookmarks(&a, std::slice::from_ref(&b)).unwrap();
+        assert!(manager.get_bookmark(&b).unwrap().is_none());
+
+        let mut survivor = manager.get_bookmark(&a).unwrap().unwrap();
+        survivor.tags.sort();
+        assert_eq!(survivor.tags, vec!["one", "two"]);
+        assert_eq!(survivor.description.as_deref(), Some("from b"));
+        assert_eq!(survivor.keyword.as_deref(), Some("ex"));
+        assert!(manager.find_duplicates().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+// certificates the user chose to trust anyway, kept in certificates.db
+// an exception is for one host and one certificate, so a server that
+// changes its certificate gets the warning again
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CertificateException {
+    pub host: String,
+    /// SHA-256 of the accepted certificate, as `CertificateInfo` shows it.
+    pub fingerprint: String,
+    /// Who the certificate was issued to, for the management page.
+    pub subject: String,
+    /// What was wrong with it when it was accepted.
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct CertificateExceptionManager {
+    db_path: PathBuf,
+}
+
+impl CertificateExceptionManager {
+    pub fn new(data_dir: &Path) -> Result<Self> {
+        let manager = Self {
+            db_path: data_dir.join("certificates.db"),
+        };
+        manager.init_database()?;
+        Ok(manager)
+    }
+
+    fn init_database(&self) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS certificate_exceptions (
+                host TEXT NOT NULL,
+                fingerprint TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                created_at DATETIME NOT NULL,
+                PRIMARY KEY (host, fingerprint)
+            )",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_all(&self) -> Result<Vec<CertificateException>> {
+        let conn = Connection::open(&self.db_path)?;
+        let mut stmt = conn.prepare(
+            "SELECT host, fingerprint, subject, reason, created_at
+             FROM certificate_exceptions ORDER BY host, created_at",
+        )?;
+
+        let exceptions = stmt
+            .query_map([], Self::row_to_exception)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(exceptions)
+    }
+
+    pub fn add(&self, exception: &CertificateException) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO certificate_exceptions
+             (host, fingerprint, subject, reason, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                exception.host,
+                exception.fingerprint,
+                exception.subject,
+                exception.reason,
+                exception.created_at.format("%Y-%m-%d %H:%M:%S").to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns whether there was an exception to remove.
+    pub fn remove(&self, host: &str, fingerprint: &str) -> Result<bool> {
+        let conn = Connection::open(&self.db_path)?;
+        let removed = conn.execute(
+            "DELETE FROM certificate_exceptions WHERE host = ?1 AND fingerprint = ?2",
+            params![host, fingerprint],
+        )?;
+        Ok(removed > 0)
+    }
+
+    fn row_to_exception(row: &Row) -> rusqlite::Result<CertificateException> {
+        let created_at: String = row.get(4)?;
+        Ok(CertificateException {
+            host: row.get(0)?,
+            fingerprint: row.get(1)?,
+            subject: row.get(2)?,
+            reason: row.get(3)?,
+            created_at: NaiveDateTime::parse_from_str(&created_at, "%Y-%m-%d %H:%M:%S")
+                .map(|date| date.and_utc())
+                .unwrap_or_else(|_| Utc::now()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn exception(host: &str, fingerprint: &str, reason: &str) -> CertificateException {
+        CertificateException {
+            host: host.to_string(),
+            fingerprint: fingerprint.to_string(),
+            subject: format!("CN={}", host),
+            reason: reason.to_string(),
+            created_at: NaiveDateTime::parse_from_str("2024-05-01 10:00:00", "%Y-%m-%d %H:%M:%S")
+                .unwrap()
+                .and_utc(),
+        }
+    }
+
+    #[test]
+    fn exceptions_are_kept_per_host_and_certificate() {
+        let dir = TempDir::new().unwrap();
+        let manager = CertificateExceptionManager::new(dir.path()).unwrap();
+        manager
+            .add(&exception("dev.internal", "AA:BB", "expired"))
+            .unwrap();
+        manager
+            .add(&exception("dev.internal", "CC:DD", "self-signed"))
+            .unwrap();
+        manager
+            .add(&exception("build.internal", "AA:BB", "self-signed"))
+            .unwrap();
+        // accepting the same certificate again just updates it
+        manager
+            .add(&exception("dev.internal", "AA:BB", "self-signed"))
+            .unwrap();
+
+        let manager = CertificateExceptionManager::new(dir.path()).unwrap();
+        let exceptions = manager.get_all().unwrap();
+        assert_eq!(exceptions.len(), 3);
+        assert_eq!(
+            exceptions[0],
+            exception("build.internal", "AA:BB", "self-signed")
+        );
+        assert_eq!(exceptions[1].host, "dev.internal");
+        assert!(exceptions.iter().any(|e| e.fingerprint == "AA:BB"
+            && e.host == "dev.internal"
+            && e.reason == "self-signed"));
+    }
+
+    #[test]
+    fn remove_only_drops_the_one_certificate() {
+        let dir = TempDir::new().unwrap();
+        let manager = CertificateExceptionManager::new(dir.path()).unwrap();
+        manager
+            .add(&exception("dev.internal", "AA:BB", "expired"))
+            .unwrap();
+        manager
+            .add(&exception("dev.internal", "CC:DD", "expired"))
+            .unwrap();
+
+        assert!(manager.remove("dev.internal", "AA:BB").unwrap());
+        assert!(!manager.remove("dev.internal", "AA:BB").unwrap());
+        assert!(!manager.remove("other.internal", "CC:DD").unwrap());
+
+        let exceptions = manager.get_all().unwrap();
+        assert_eq!(exceptions.len(), 1);
+        assert_eq!(exceptions[0].fingerprint, "CC:DD");
+    }
+}
@@ -0,0 +1,186 @@
+// encryption for cookie values in cookies.db
+// AES-256-GCM with the key either derived from a passphrase (argon2id)
+// or read from a key file next to the config
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::Result;
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Versions the format of an encrypted value. Whether a row is encrypted at
+/// all is recorded next to it in cookies.db, a site can set any value it likes.
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+
+/// Where the cookie encryption key comes from.
+#[derive(Debug, Clone)]
+pub enum CookieKeySource {
+    Passphrase(String),
+    /// A base64 key file, created with a random key if it doesn't exist yet.
+    KeyFile(PathBuf),
+}
+
+impl CookieKeySource {
+    /// How the key is made, stored alongside the salt so a mismatch can be reported.
+    pub fn kdf(&self) -> &'static str {
+        match self {
+            CookieKeySource::Passphrase(_) => "argon2id",
+            CookieKeySource::KeyFile(_) => "key_file",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CookieCipher {
+    cipher: Aes256Gcm,
+}
+
+// never print the key
+impl std::fmt::Debug for CookieCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CookieCipher")
+    }
+}
+
+impl CookieCipher {
+    /// Builds the cipher for `source`. `salt` is only used for passphrases.
+    pub fn new(source: &CookieKeySource, salt: &[u8]) -> Result<Self> {
+        let key = match source {
+            CookieKeySource::Passphrase(passphrase) => derive_key(passphrase, salt)?,
+            CookieKeySource::KeyFile(path) => load_or_create_key_file(path)?,
+        };
+
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        })
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt cookie value"))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}", ENCRYPTED_PREFIX, BASE64.encode(sealed)))
+    }
+
+    /// Decrypts a value written by [`Self::encrypt`].
+    pub fn decrypt(&self, stored: &str) -> Result<String> {
+        let encoded = stored
+            .strip_prefix(ENCRYPTED_PREFIX)
+            .ok_or_else(|| anyhow::anyhow!("Cookie value isn't in a known encrypted format"))?;
+
+        let sealed = BASE64.decode(encoded)?;
+        if sealed.len() < NONCE_LEN {
+            return Err(anyhow::anyhow!("Encrypted cookie value is truncated"));
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Cookie value failed to decrypt, wrong key?"))?;
+
+        Ok(String::from_utf8(plaintext)?)
+    }
+}
+
+pub fn generate_salt() -> Vec<u8> {
+    let mut salt = vec![0u8; SALT_LEN];
+    argon2::password_hash::rand_core::RngCore::fill_bytes(&mut OsRng, &mut salt);
+    salt
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; KEY_LEN]> {
+    if passphrase.is_empty() {
+        return Err(anyhow::anyhow!("Cookie passphrase can't be empty"));
+    }
+
+    let mut key = [0u8; KEY_LEN];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Failed to derive cookie key: {}", e))?;
+    Ok(key)
+}
+
+fn load_or_create_key_file(path: &Path) -> Result<[u8; KEY_LEN]> {
+    if path.exists() {
+        let contents = std::fs::read_to_string(path)?;
+        let key = BASE64.decode(contents.trim())?;
+        return key
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("{} is not a {}-byte key", path.display(), KEY_LEN));
+    }
+
+    let key: [u8; KEY_LEN] = Aes256Gcm::generate_key(&mut OsRng).into();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // the key is as good as the cookies, keep it to ourselves
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)?
+        .write_all(BASE64.encode(key).as_bytes())?;
+
+    log::info!("Created cookie key file {}", path.display());
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn values_round_trip_and_need_the_right_key() {
+        let salt = generate_salt();
+        let cipher =
+            CookieCipher::new(&CookieKeySource::Passphrase("hunter2".into()), &salt).unwrap();
+
+        let sealed = cipher.encrypt("session=abc").unwrap();
+        assert!(sealed.starts_with(ENCRYPTED_PREFIX));
+        assert_ne!(sealed, cipher.encrypt("session=abc").unwrap());
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), "session=abc");
+
+        let wrong =
+            CookieCipher::new(&CookieKeySource::Passphrase("hunter3".into()), &salt).unwrap();
+        assert!(wrong.decrypt(&sealed).is_err());
+        assert!(cipher.decrypt("plaintext").is_err());
+        assert!(cipher.decrypt("enc:v1:AAAA").is_err());
+    }
+
+    #[test]
+    fn key_files_are_created_once_and_private() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("keys").join("cookies.key");
+        let source = CookieKeySource::KeyFile(path.clone());
+
+        let sealed = CookieCipher::new(&source, &[])
+            .unwrap()
+            .encrypt("x")
+            .unwrap();
+        let reopened = CookieCipher::new(&source, &[]).unwrap();
+        assert_eq!(reopened.decrypt(&sealed).unwrap(), "x");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
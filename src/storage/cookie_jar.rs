@@ -0,0 +1,672 @@
+// cookie jar backed by cookies.db, the loader asks it for every request
+// and it doubles as a reqwest cookie store
+// reads come from memory and writes are batched back to sqlite
+// so we aren't opening a connection for every request
+use crate::storage::{
+    candidate_domains, cookie_site, format_cookie_header, sort_for_request, Cookie, CookieAccess,
+    CookieImport, CookieKey, CookieManager, CookieRule,
+};
+use crate::utils::CookiePolicy;
+use anyhow::Result;
+use reqwest::header::HeaderValue;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Pending writes are flushed once there are this many of them...
+const FLUSH_THRESHOLD: usize = 64;
+/// ...or once this long has passed since the last flush.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+struct JarState {
+    /// Cookies keyed by the domain they're stored under.
+    cookies: HashMap<String, Vec<Cookie>>,
+    dirty: HashSet<CookieKey>,
+    deleted: HashSet<CookieKey>,
+    /// Per-site rules keyed by site, mirrored from cookies.db.
+    rules: HashMap<String, CookieRule>,
+    last_flush: Instant,
+}
+
+pub struct CookieJar {
+    manager: CookieManager,
+    policy: CookiePolicy,
+    state: RwLock<JarState>,
+}
+
+impl CookieJar {
+    pub fn new(manager: CookieManager, policy: CookiePolicy) -> Result<Self> {
+        match manager.clear_session_only_cookies() {
+            Ok(0) => {}
+            Ok(count) => log::info!("Cleared {} session-only cookies", count),
+            Err(e) => log::error!("Failed to clear session-only cookies: {}", e),
+        }
+        if let Err(e) = manager.cleanup_task() {
+            log::error!("Failed to clear old cookies: {}", e);
+        }
+
+        let rules = manager
+            .get_site_rules()?
+            .into_iter()
+            .map(|rule| (rule.site, rule.rule))
+            .collect();
+
+        Ok(Self {
+            policy,
+            state: RwLock::new(JarState {
+                cookies: Self::load_cookies(&manager)?,
+                dirty: HashSet::new(),
+                deleted: HashSet::new(),
+                rules,
+                last_flush: Instant::now(),
+            }),
+            manager,
+        })
+    }
+
+    fn load_cookies(manager: &CookieManager) -> Result<HashMap<String, Vec<Cookie>>> {
+        let mut cookies: HashMap<String, Vec<Cookie>> = HashMap::new();
+        for cookie in manager.get_all_cookies()? {
+            // cookies.db predates the public suffix checks, don't resurrect supercookies
+            if !cookie.is_expired() && !cookie.is_supercookie() {
+                cookies
+                    .entry(cookie.domain.clone())
+                    .or_default()
+                    .push(cookie);
+            }
+        }
+        Ok(cookies)
+    }
+
+    /// Runs [`CookieManager::cleanup_task`] and drops what it cleared from
+    /// memory too. Returns how many expired and old cookies went.
+    pub fn clear_old_cookies(&self) -> Result<(u32, u32)> {
+        let mut state = self.state.write().unwrap();
+        self.flush_locked(&mut state)?;
+
+        let cleared = self.manager.cleanup_task()?;
+        state.cookies = Self::load_cookies(&self.manager)?;
+        Ok(cleared)
+    }
+
+    pub fn manager(&self) -> &CookieManager {
+        &self.manager
+    }
+
+    /// Imports a cookies.txt file, see [`CookieManager::import_netscape`].
+    /// Pending changes are written first so the import wins over them.
+    pub fn import_netscape_file(
+        &self,
+        path: &Path,
+        domain_filter: Option<&str>,
+    ) -> Result<CookieImport> {
+        let mut state = self.state.write().unwrap();
+        self.flush_locked(&mut state)?;
+
+        let import = self.manager.import_netscape_file(path, domain_filter)?;
+        state.cookies = Self::load_cookies(&self.manager)?;
+        Ok(import)
+    }
+
+    /// Exports to a cookies.txt file, see [`CookieManager::export_netscape_file`].
+    pub fn export_netscape_file(&self, path: &Path, domain_filter: Option<&str>) -> Result<()> {
+        self.flush()?;
+        self.manager.export_netscape_file(path, domain_filter)
+    }
+
+    /// Cookie access for a GET of `url` made for the page at `top_level_url`.
+    /// A top-level navigation is its own top-level page, and `initiator` is
+    /// the page that sent it there, see [`CookieAccess::for_navigation`].
+    pub fn access_for(
+        &self,
+        url: &Url,
+        top_level_url: &Url,
+        initiator: Option<&Url>,
+    ) -> CookieAccess {
+        match CookieAccess::for_request(self.policy, url, top_level_url) {
+            CookieAccess::FirstParty => CookieAccess::for_navigation(url, initiator, "GET"),
+            access => access,
+        }
+    }
+
+    /// Stores a cookie in memory, replacing any with the same name, domain, path
+    /// and partition.
+    pub fn insert(&self, cookie: Cookie) {
+        if cookie.is_supercookie() {
+            log::warn!(
+                "Refusing to set cookie {} on {}",
+                cookie.name,
+                cookie.domain
+            );
+            return;
+        }
+
+        let mut state = self.state.write().unwrap();
+        let cookie = match state.rules.get(&cookie_site(&cookie.domain)) {
+            Some(rule) => match rule.apply(cookie) {
+                Some(cookie) => cookie,
+                None => return,
+            },
+            None => cookie,
+        };
+        let key = cookie.key();
+
+        let cookies = state.cookies.entry(cookie.domain.clone()).or_default();
+        match cookies.iter_mut().find(|existing| existing.key() == key) {
+            Some(existing) => *existing = cookie,
+            None => cookies.push(cookie),
+        }
+
+        state.deleted.remove(&key);
+        state.dirty.insert(key);
+        self.flush_if_due(&mut state);
+    }
+
+    pub fn remove(&self, key: CookieKey) {
+        let mut state = self.state.write().unwrap();
+
+        if let Some(cookies) = state.cookies.get_mut(&key.1) {
+            cookies.retain(|cookie| cookie.key() != key);
+        }
+
+        state.dirty.remove(&key);
+        state.deleted.insert(key);
+        self.flush_if_due(&mut state);
+    }
+
+    /// Swaps the cookie stored under `old_key` for `cookie`, which may have a
+    /// different name, domain or path. Refuses cookies the jar would drop.
+    pub fn replace(&self, old_key: &CookieKey, cookie: Cookie) -> Result<()> {
+        if cookie.is_supercookie() {
+            return Err(anyhow::anyhow!(
+                "{} is a public suffix",
+                cookie.domain.trim_start_matches('.')
+            ));
+        }
+        let site = cookie_site(&cookie.domain);
+        if self.state.read().unwrap().rules.get(&site) == Some(&CookieRule::Block) {
+            return Err(anyhow::anyhow!("Cookies are blocked for {}", site));
+        }
+
+        if cookie.key() != *old_key {
+            self.remove(old_key.clone());
+        }
+        self.insert(cookie);
+        Ok(())
+    }
+
+    /// Removes every cookie `should_remove` picks and returns how many there were.
+    pub fn remove_where(&self, should_remove: impl Fn(&Cookie) -> bool) -> usize {
+        let mut state = self.state.write().unwrap();
+
+        let mut removed = Vec::new();
+        for cookies in state.cookies.values_mut() {
+            cookies.retain(|cookie| {
+                if should_remove(cookie) {
+                    removed.push(cookie.key());
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        let count = removed.len();
+        for key in removed {
+            state.dirty.remove(&key);
+            state.deleted.insert(key);
+        }
+        self.flush_if_due(&mut state);
+        count
+    }
+
+    /// Removes the cookies stored for `domain` itself, host-only or not.
+    pub fn remove_domain(&self, domain: &str) -> usize {
+        let domain = domain.trim_start_matches('.');
+        self.remove_where(|cookie| cookie.domain.trim_start_matches('.') == domain)
+    }
+
+    /// Removes every cookie belonging to `site`, including ones other sites'
+    /// embeds stored in its partition.
+    pub fn clear_site(&self, site: &str) -> usize {
+        self.remove_where(|cookie| {
+            cookie_site(&cookie.domain) == site || cookie.partition_key.as_deref() == Some(site)
+        })
+    }
+
+    /// Every cookie in the jar, by domain, then longest path, then name.
+    pub fn all_cookies(&self) -> Vec<Cookie> {
+        let state = self.state.read().unwrap();
+        let mut cookies: Vec<Cookie> = state
+            .cookies
+            .values()
+            .flatten()
+            .filter(|cookie| !cookie.is_expired())
+            .cloned()
+            .collect();
+
+        cookies.sort_by(|a, b| {
+            a.domain
+                .trim_start_matches('.')
+                .cmp(b.domain.trim_start_matches('.'))
+                .then(b.path.len().cmp(&a.path.len()))
+                .then(a.name.cmp(&b.name))
+        });
+        cookies
+    }
+
+    /// See [`CookieManager::set_site_rule`]. Blocking a site also drops the
+    /// cookies it has in memory.
+    pub fn set_site_rule(&self, input: &str, rule: CookieRule) -> Result<String> {
+        let site = self.manager.set_site_rule(input, rule)?;
+
+        let mut state = self.state.write().unwrap();
+        state.rules.insert(site.clone(), rule);
+
+        if rule == CookieRule::Block {
+            let state = &mut *state;
+            for cookies in state.cookies.values_mut() {
+                cookies.retain(|cookie| cookie_site(&cookie.domain) != site);
+            }
+            state.dirty.retain(|key| cookie_site(&key.1) != site);
+        }
+
+        Ok(site)
+    }
+
+    pub fn remove_site_rule(&self, site: &str) -> Result<()> {
+        self.manager.remove_site_rule(site)?;
+        self.state.write().unwrap().rules.remove(site);
+        Ok(())
+    }
+
+    /// Stores or expires a cookie from a `Set-Cookie` header received for `url`.
+    pub fn set_cookie_from_header(&self, header: &str, url: &Url, access: &CookieAccess) {
+        if *access == CookieAccess::Blocked {
+            log::debug!("Blocked third-party cookie from {}", url);
+            return;
+        }
+
+        match Cookie::parse_set_cookie(header, url) {
+            Ok(mut cookie) => {
+                cookie.partition_key = access.partition_key().map(str::to_string);
+                if cookie.is_expired() {
+                    self.remove(cookie.key());
+                } else {
+                    self.insert(cookie);
+                }
+            }
+            Err(e) => log::debug!("Ignoring Set-Cookie from {}: {}", url, e),
+        }
+    }
+
+    /// Cookies to send for `url`, see [`CookieManager::cookies_for_request`].
+    pub fn cookies_for_request(&self, url: &Url, access: &CookieAccess) -> Vec<Cookie> {
+        let Some(host) = url.host_str() else {
+            return Vec::new();
+        };
+
+        let state = self.state.read().unwrap();
+        let mut cookies: Vec<Cookie> = candidate_domains(host)
+            .iter()
+            .filter_map(|domain| state.cookies.get(domain))
+            .flatten()
+            .filter(|cookie| cookie.matches_request(url, access))
+            .cloned()
+            .collect();
+
+        sort_for_request(&mut cookies);
+        cookies
+    }
+
+    /// The `Cookie` header to send with a request for `url`, if anything matches.
+    pub fn cookie_header(&self, url: &Url, access: &CookieAccess) -> Option<String> {
+        let cookies = self.cookies_for_request(url, access);
+        self.flush_if_stale();
+        format_cookie_header(&cookies)
+    }
+
+    /// Writes pending changes back to cookies.db.
+    pub fn flush(&self) -> Result<()> {
+        let mut state = self.state.write().unwrap();
+        self.flush_locked(&mut state)
+    }
+
+    /// Reads only take the write lock when there's something old enough to flush.
+    fn flush_if_stale(&self) {
+        let stale = {
+            let state = self.state.read().unwrap();
+            let pending = !state.dirty.is_empty() || !state.deleted.is_empty();
+            pending && state.last_flush.elapsed() >= FLUSH_INTERVAL
+        };
+
+        if stale {
+            if let Err(e) = self.flush() {
+                log::error!("Failed to write cookies: {}", e);
+            }
+        }
+    }
+
+    fn flush_if_due(&self, state: &mut JarState) {
+        let pending = state.dirty.len() + state.deleted.len();
+        if pending >= FLUSH_THRESHOLD || state.last_flush.elapsed() >= FLUSH_INTERVAL {
+            if let Err(e) = self.flush_locked(state) {
+                log::error!("Failed to write cookies: {}", e);
+            }
+        }
+    }
+
+    fn flush_locked(&self, state: &mut JarState) -> Result<()> {
+        state.last_flush = Instant::now();
+        if state.dirty.is_empty() && state.deleted.is_empty() {
+            return Ok(());
+        }
+
+        let upserts: Vec<Cookie> = state
+            .dirty
+            .iter()
+            .filter_map(|key| {
+                state
+                    .cookies
+                    .get(&key.1)?
+                    .iter()
+                    .find(|cookie| cookie.key() == *key)
+            })
+            .cloned()
+            .collect();
+        let deletions: Vec<CookieKey> = state.deleted.iter().cloned().collect();
+
+        self.manager.apply_changes(&upserts, &deletions)?;
+
+        state.dirty.clear();
+        state.deleted.clear();
+        Ok(())
+    }
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        for header in cookie_headers {
+            if let Ok(header) = header.to_str() {
+                self.set_cookie_from_header(header, url, &CookieAccess::FirstParty);
+            }
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        // reqwest doesn't say which page a request was made for, so a client
+        // using the jar this way gets first party access. The loader doesn't,
+        // it works out access for every hop in `load`.
+        self.cookie_header(url, &CookieAccess::FirstParty)
+            .and_then(|header| HeaderValue::from_str(&header).ok())
+    }
+}
+
+impl Drop for CookieJar {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            log::error!("Failed to write cookies on shutdown: {}", e);
+        }
+        if let Err(e) = self.manager.clear_session_only_cookies() {
+            log::error!("Failed to clear session-only cookies: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn jar(dir: &TempDir) -> CookieJar {
+        jar_with_policy(dir, CookiePolicy::AllowAll)
+    }
+
+    fn jar_with_policy(dir: &TempDir, policy: CookiePolicy) -> CookieJar {
+        let manager = CookieManager::new(dir.path(), 0).unwrap();
+        CookieJar::new(manager, policy).unwrap()
+    }
+
+    fn url(input: &str) -> Url {
+        Url::parse(input).unwrap()
+    }
+
+    fn names(cookies: Vec<Cookie>) -> Vec<String> {
+        cookies.into_iter().map(|cookie| cookie.name).collect()
+    }
+
+    #[test]
+    fn serves_cookies_from_memory_before_they_are_flushed() {
+        let dir = TempDir::new().unwrap();
+        let jar = jar(&dir);
+        let page = url("https://www.example.com/");
+        let access = CookieAccess::FirstParty;
+
+        jar.set_cookie_from_header("a=1; Domain=example.com", &page, &access);
+        jar.set_cookie_from_header("b=2", &page, &access);
+        assert_eq!(
+            names(jar.cookies_for_request(&page, &access)),
+            vec!["a", "b"]
+        );
+        assert!(jar.manager().get_all_cookies().unwrap().is_empty());
+
+        jar.flush().unwrap();
+        assert_eq!(jar.manager().get_all_cookies().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn expiring_a_cookie_removes_it_from_the_database() {
+        let dir = TempDir::new().unwrap();
+        let page = url("https://example.com/");
+        let access = CookieAccess::FirstParty;
+        {
+            let jar = jar(&dir);
+            jar.set_cookie_from_header("a=1; Max-Age=3600", &page, &access);
+            jar.set_cookie_from_header("b=2; Max-Age=3600", &page, &access);
+        }
+
+        let jar = jar(&dir);
+        assert_eq!(
+            names(jar.cookies_for_request(&page, &access)),
+            vec!["a", "b"]
+        );
+        jar.set_cookie_from_header("a=1; Max-Age=0", &page, &access);
+        jar.flush().unwrap();
+        assert_eq!(names(jar.manager().get_all_cookies().unwrap()), vec!["b"]);
+    }
+
+    #[test]
+    fn pending_writes_are_flushed_on_drop() {
+        let dir = TempDir::new().unwrap();
+        let page = url("https://example.com/");
+        let access = CookieAccess::FirstParty;
+        {
+            let jar = jar(&dir);
+            jar.set_cookie_from_header("kept=1; Max-Age=3600", &page, &access);
+            jar.set_cookie_from_header("gone=1; Max-Age=3600", &page, &access);
+            jar.remove_domain("example.com");
+            jar.set_cookie_from_header("kept=2; Max-Age=3600", &page, &access);
+        }
+
+        let jar = jar(&dir);
+        let cookies = jar.all_cookies();
+        assert_eq!(names(cookies.clone()), vec!["kept"]);
+        assert_eq!(cookies[0].value, "2");
+    }
+
+    #[test]
+    fn access_follows_the_policy_for_third_parties() {
+        let dir = TempDir::new().unwrap();
+        let page = url("https://news.example/");
+        let embed = url("https://widgets.example.org/like");
+
+        let allow = jar_with_policy(&dir, CookiePolicy::AllowAll);
+        assert_eq!(
+            allow.access_for(&embed, &page, None),
+            CookieAccess::ThirdParty
+        );
+        assert_eq!(
+            allow.access_for(&page, &page, None),
+            CookieAccess::FirstParty
+        );
+        assert_eq!(
+            allow.access_for(&page, &page, Some(&embed)),
+            CookieAccess::CrossSiteNavigation { safe_method: true }
+        );
+        drop(allow);
+
+        let block = jar_with_policy(&dir, CookiePolicy::BlockThirdParty);
+        assert_eq!(block.access_for(&embed, &page, None), CookieAccess::Blocked);
+        drop(block);
+
+        let partition = jar_with_policy(&dir, CookiePolicy::PartitionThirdParty);
+        assert_eq!(
+            partition.access_for(&embed, &page, None),
+            CookieAccess::Partitioned("news.example".to_string())
+        );
+    }
+
+    #[test]
+    fn partitioned_cookies_stay_with_their_top_level_site() {
+        let dir = TempDir::new().unwrap();
+        let jar = jar_with_policy(&dir, CookiePolicy::PartitionThirdParty);
+        let embed = url("https://widgets.example.org/");
+        let first = url("https://news.example/");
+        let second = url("https://blog.example/");
+
+        let in_first = jar.access_for(&embed, &first, None);
+        jar.set_cookie_from_header("id=1; Secure; SameSite=None", &embed, &in_first);
+
+        assert_eq!(
+            jar.cookie_header(&embed, &in_first).as_deref(),
+            Some("id=1")
+        );
+        let in_second = jar.access_for(&embed, &second, None);
+        assert_eq!(jar.cookie_header(&embed, &in_second), None);
+        // visiting the embed's own site doesn't see its partitioned cookies either
+        let direct = jar.access_for(&embed, &embed, None);
+        assert_eq!(jar.cookie_header(&embed, &direct), None);
+    }
+
+    #[test]
+    fn blocked_third_parties_can_neither_set_nor_read() {
+        let dir = TempDir::new().unwrap();
+        let jar = jar_with_policy(&dir, CookiePolicy::BlockThirdParty);
+        let embed = url("https://widgets.example.org/");
+        let page = url("https://news.example/");
+
+        let direct = jar.access_for(&embed, &embed, None);
+        jar.set_cookie_from_header("id=1; Secure; SameSite=None", &embed, &direct);
+
+        let embedded = jar.access_for(&embed, &page, None);
+        jar.set_cookie_from_header("other=1; Secure; SameSite=None", &embed, &embedded);
+        assert_eq!(jar.cookie_header(&embed, &embedded), None);
+        assert_eq!(jar.cookie_header(&embed, &direct).as_deref(), Some("id=1"));
+    }
+
+    #[test]
+    fn as_a_reqwest_cookie_store_everything_is_first_party() {
+        use reqwest::cookie::CookieStore;
+
+        let dir = TempDir::new().unwrap();
+        let jar = jar_with_policy(&dir, CookiePolicy::BlockThirdParty);
+        let page = url("https://www.example.com/");
+        let headers = [
+            HeaderValue::from_static("a=1; SameSite=Strict"),
+            HeaderValue::from_static("b=2; Secure"),
+        ];
+
+        jar.set_cookies(&mut headers.iter(), &page);
+        assert_eq!(jar.cookies(&page).unwrap().to_str().unwrap(), "a=1; b=2");
+        assert_eq!(jar.cookies(&url("https://other.example/")), None);
+    }
+
+    #[test]
+    fn clearing_old_cookies_also_drops_them_from_memory() {
+        let dir = TempDir::new().unwrap();
+        let manager = CookieManager::new(dir.path(), 30).unwrap();
+        let jar = CookieJar::new(manager, CookiePolicy::AllowAll).unwrap();
+        jar.set_site_rule("kept.example", CookieRule::Allow)
+            .unwrap();
+
+        for (name, host) in [("login", "kept.example"), ("old", "gone.example")] {
+            let mut cookie = Cookie::parse_set_cookie(
+                &format!("{}=1; Max-Age=31536000", name),
+                &url(&format!("https://{}/", host)),
+            )
+            .unwrap();
+            cookie.created_at = chrono::Utc::now() - chrono::Duration::days(60);
+            jar.insert(cookie);
+        }
+        assert_eq!(jar.all_cookies().len(), 2);
+
+        assert_eq!(jar.clear_old_cookies().unwrap(), (0, 1));
+        assert_eq!(names(jar.all_cookies()), vec!["login"]);
+        assert_eq!(jar.manager().get_all_cookies().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn editing_a_cookie_can_move_it() {
+        let dir = TempDir::new().unwrap();
+        let jar = jar(&dir);
+        let page = url("https://www.example.com/");
+        jar.set_cookie_from_header("a=1", &page, &CookieAccess::FirstParty);
+        let original = jar.all_cookies().remove(0);
+
+        let mut edited = original.clone();
+        edited.name = "b".to_string();
+        edited.domain = ".example.com".to_string();
+        jar.replace(&original.key(), edited).unwrap();
+        let cookies = jar.all_cookies();
+        assert_eq!(cookies.len(), 1);
+        assert_eq!(
+            (cookies[0].name.as_str(), cookies[0].domain.as_str()),
+            ("b", ".example.com")
+        );
+
+        let mut supercookie = cookies[0].clone();
+        supercookie.domain = ".co.uk".to_string();
+        assert!(jar.replace(&cookies[0].key(), supercookie).is_err());
+        assert_eq!(jar.all_cookies().len(), 1);
+    }
+
+    #[test]
+    fn clearing_a_site_takes_its_partitions_too() {
+        let dir = TempDir::new().unwrap();
+        let jar = jar_with_policy(&dir, CookiePolicy::PartitionThirdParty);
+        let site = url("https://news.example/");
+        let embed = url("https://widgets.example.org/");
+
+        jar.set_cookie_from_header("own=1", &site, &jar.access_for(&site, &site, None));
+        let embedded = jar.access_for(&embed, &site, None);
+        jar.set_cookie_from_header("embed=1; Secure; SameSite=None", &embed, &embedded);
+        jar.set_cookie_from_header("other=1", &embed, &jar.access_for(&embed, &embed, None));
+
+        assert_eq!(jar.clear_site("news.example"), 2);
+        assert_eq!(names(jar.all_cookies()), vec!["other"]);
+    }
+
+    #[test]
+    fn blocking_a_site_drops_its_cookies() {
+        let dir = TempDir::new().unwrap();
+        let jar = jar(&dir);
+        let page = url("https://ads.example.net/");
+        let access = CookieAccess::FirstParty;
+        jar.set_cookie_from_header("id=1", &page, &access);
+
+        assert_eq!(
+            jar.set_site_rule("https://ads.example.net/x", CookieRule::Block)
+                .unwrap(),
+            "example.net"
+        );
+        assert!(jar.all_cookies().is_empty());
+        jar.set_cookie_from_header("id=2", &page, &access);
+        assert!(jar.all_cookies().is_empty());
+
+        jar.remove_site_rule("example.net").unwrap();
+        jar.set_cookie_from_header("id=3", &page, &access);
+        assert_eq!(jar.all_cookies().len(), 1);
+    }
+}
@@ -1,10 +1,17 @@
 // this may be the most wrong way to handle cookies but idc
 // sue me if it works it works
+use crate::storage::{generate_salt, CookieCipher, CookieKeySource};
+use crate::utils::{is_public_suffix, is_third_party, site_for_host, CookiePolicy};
 use anyhow::Result;
-use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, Row};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
 use std::path::Path;
+use url::Url;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Cookie {
@@ -17,11 +24,529 @@ pub struct Cookie {
     pub http_only: bool,
     pub same_site: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// The top-level site a partitioned third-party cookie belongs to.
+    #[serde(default)]
+    pub partition_key: Option<String>,
 }
 
+/// `(name, domain, path, partition_key)`, what identifies a cookie in cookies.db.
+/// Unpartitioned cookies have an empty partition key.
+pub type CookieKey = (String, String, String, String);
+
+/// Which cookies a request gets to use, worked out from the cookie policy and
+/// the page that made the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CookieAccess {
+    FirstParty,
+    /// A top-level navigation that came here from another site. Lax cookies
+    /// only go along when it uses a safe method like GET.
+    CrossSiteNavigation {
+        safe_method: bool,
+    },
+    /// A cross-site request the policy lets use the site's normal cookies.
+    ThirdParty,
+    /// A cross-site request that only sees cookies set under this top-level site.
+    Partitioned(String),
+    Blocked,
+}
+
+impl CookieAccess {
+    /// Access for a request to `url` made by the page at `top_level_url`.
+    pub fn for_request(policy: CookiePolicy, url: &Url, top_level_url: &Url) -> Self {
+        if !is_third_party(url, top_level_url) {
+            return CookieAccess::FirstParty;
+        }
+
+        match policy {
+            CookiePolicy::AllowAll => CookieAccess::ThirdParty,
+            CookiePolicy::BlockThirdParty => CookieAccess::Blocked,
+            CookiePolicy::PartitionThirdParty => match top_level_url.host_str() {
+                Some(host) => CookieAccess::Partitioned(site_for_host(host)),
+                None => CookieAccess::Blocked,
+            },
+        }
+    }
+
+    /// Access for a top-level navigation to `url` with `method`, started by the
+    /// page at `initiator`. Navigations the user started have no initiator and
+    /// count as same-site.
+    pub fn for_navigation(url: &Url, initiator: Option<&Url>, method: &str) -> Self {
+        match initiator {
+            Some(initiator) if is_third_party(url, initiator) => {
+                CookieAccess::CrossSiteNavigation {
+                    safe_method: matches!(
+                        method.to_ascii_uppercase().as_str(),
+                        "GET" | "HEAD" | "OPTIONS" | "TRACE"
+                    ),
+                }
+            }
+            _ => CookieAccess::FirstParty,
+        }
+    }
+
+    pub fn partition_key(&self) -> Option<&str> {
+        match self {
+            CookieAccess::Partitioned(site) => Some(site),
+            _ => None,
+        }
+    }
+}
+
+impl Cookie {
+    /// Parses a `Set-Cookie` header received for `request_url` following RFC 6265
+    /// section 5.2. Host-only cookies keep the bare host as their domain, cookies
+    /// with a `Domain` attribute are stored with a leading dot.
+    pub fn parse_set_cookie(header: &str, request_url: &Url) -> Result<Cookie> {
+        let host = request_url
+            .host_str()
+            .ok_or_else(|| anyhow::anyhow!("Cannot set cookies for {}", request_url))?
+            .to_ascii_lowercase();
+
+        let mut parts = header.split(';');
+        let name_value = parts.next().unwrap_or_default();
+        let (name, value) = name_value
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Set-Cookie without a name-value pair"))?;
+        let name = name.trim();
+        let value = value.trim();
+        if name.is_empty() {
+            return Err(anyhow::anyhow!("Set-Cookie with an empty name"));
+        }
+
+        let now = Utc::now();
+        let mut expires = None;
+        let mut max_age = None;
+        let mut domain = None;
+        let mut path = None;
+        let mut secure = false;
+        let mut http_only = false;
+        let mut same_site = None;
+
+        for attribute in parts {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+
+            match key.to_ascii_lowercase().as_str() {
+                "expires" => {
+                    if let Some(date) = parse_cookie_date(value) {
+                        expires = Some(date.min(latest_cookie_date()));
+                    }
+                }
+                "max-age" => {
+                    // a leading digit or '-' is required, anything else is ignored
+                    let valid = value.starts_with(|c: char| c.is_ascii_digit() || c == '-');
+                    if let Some(seconds) = value.parse::<i64>().ok().filter(|_| valid) {
+                        max_age = Some(if seconds <= 0 {
+                            DateTime::<Utc>::UNIX_EPOCH
+                        } else {
+                            now.checked_add_signed(chrono::Duration::seconds(seconds))
+                                .unwrap_or_else(latest_cookie_date)
+                                .min(latest_cookie_date())
+                        });
+                    }
+                }
+                "domain" => {
+                    let value = value.trim_start_matches('.').to_ascii_lowercase();
+                    if !value.is_empty() {
+                        domain = Some(value);
+                    }
+                }
+                "path" if value.starts_with('/') => path = Some(value.to_string()),
+                "secure" => secure = true,
+                "httponly" => http_only = true,
+                "samesite" => {
+                    same_site = match value.to_ascii_lowercase().as_str() {
+                        "strict" => Some("Strict".to_string()),
+                        "lax" => Some("Lax".to_string()),
+                        "none" => Some("None".to_string()),
+                        _ => None,
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        // RFC 6265 5.3 step 5: a Domain attribute naming a public suffix is only
+        // allowed when it is the request host itself, and then it's host-only
+        let domain = match domain {
+            Some(domain) if is_public_suffix(&domain) => {
+                if domain != host {
+                    return Err(anyhow::anyhow!(
+                        "Cookie domain {} is a public suffix",
+                        domain
+                    ));
+                }
+                None
+            }
+            domain => domain,
+        };
+
+        let domain = match domain {
+            Some(domain) => {
+                if !domain_match(&host, &domain) {
+                    return Err(anyhow::anyhow!(
+                        "Cookie domain {} does not match host {}",
+                        domain,
+                        host
+                    ));
+                }
+                // an ip address can only ever set a host-only cookie for itself
+                if host.parse::<IpAddr>().is_ok() {
+                    host.clone()
+                } else {
+                    format!(".{}", domain)
+                }
+            }
+            None => host.clone(),
+        };
+
+        if secure && request_url.scheme() != "https" {
+            return Err(anyhow::anyhow!(
+                "Secure cookie set over an insecure connection"
+            ));
+        }
+
+        if same_site.as_deref() == Some("None") && !secure {
+            return Err(anyhow::anyhow!("SameSite=None cookie without Secure"));
+        }
+
+        Ok(Cookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            domain,
+            path: path.unwrap_or_else(|| default_path(request_url)),
+            // Max-Age wins over Expires when both are present
+            expires: max_age.or(expires),
+            secure,
+            http_only,
+            same_site,
+            created_at: now,
+            partition_key: None,
+        })
+    }
+
+    pub fn key(&self) -> CookieKey {
+        (
+            self.name.clone(),
+            self.domain.clone(),
+            self.path.clone(),
+            self.partition_key.clone().unwrap_or_default(),
+        )
+    }
+
+    /// Cookies without a `Domain` attribute only go back to the exact host that set them.
+    pub fn is_host_only(&self) -> bool {
+        !self.domain.starts_with('.')
+    }
+
+    /// A domain cookie set on a public suffix, which every site under it would see.
+    pub fn is_supercookie(&self) -> bool {
+        !self.is_host_only() && is_public_suffix(&self.domain[1..])
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|expires| expires <= Utc::now())
+    }
+
+    pub fn matches_host(&self, host: &str) -> bool {
+        if self.is_host_only() {
+            self.domain.eq_ignore_ascii_case(host)
+        } else {
+            domain_match(host, &self.domain[1..])
+        }
+    }
+
+    /// Whether this cookie should be sent with a request for `url`. Only cookies
+    /// from the request's own partition are visible, and cookies without a
+    /// SameSite attribute are treated as Lax, so they still go along on
+    /// cross-site GET navigations.
+    pub fn matches_request(&self, url: &Url, access: &CookieAccess) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+
+        if *access == CookieAccess::Blocked
+            || self.partition_key.as_deref() != access.partition_key()
+        {
+            return false;
+        }
+
+        if self.is_expired() || !self.matches_host(host) || !path_match(url.path(), &self.path) {
+            return false;
+        }
+
+        if self.secure && url.scheme() != "https" {
+            return false;
+        }
+
+        match (self.same_site.as_deref(), access) {
+            (Some("None"), _) | (_, CookieAccess::FirstParty) => true,
+            (Some("Strict"), _) => false,
+            (_, CookieAccess::CrossSiteNavigation { safe_method }) => *safe_method,
+            _ => false,
+        }
+    }
+}
+
+/// A per-site override of the normal cookie handling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CookieRule {
+    /// Keep cookies, even past `auto_clear_cookies_days`.
+    Allow,
+    /// Keep cookies until the browser closes.
+    SessionOnly,
+    /// Never store cookies.
+    Block,
+}
+
+impl CookieRule {
+    pub const ALL: [CookieRule; 3] = [
+        CookieRule::Allow,
+        CookieRule::SessionOnly,
+        CookieRule::Block,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CookieRule::Allow => "allow",
+            CookieRule::SessionOnly => "session_only",
+            CookieRule::Block => "block",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|rule| rule.as_str() == value)
+    }
+
+    /// Applies the rule to a cookie about to be stored, `None` if it can't be.
+    pub fn apply(&self, mut cookie: Cookie) -> Option<Cookie> {
+        match self {
+            CookieRule::Allow => Some(cookie),
+            CookieRule::SessionOnly => {
+                cookie.expires = None;
+                Some(cookie)
+            }
+            CookieRule::Block => None,
+        }
+    }
+}
+
+impl fmt::Display for CookieRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CookieRule::Allow => write!(f, "Always allow"),
+            CookieRule::SessionOnly => write!(f, "Session only"),
+            CookieRule::Block => write!(f, "Block"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiteCookieRule {
+    pub site: String,
+    pub rule: CookieRule,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The site a rule applies to, from a url, a host or a cookie domain.
+pub fn normalize_site(input: &str) -> Option<String> {
+    let input = input.trim();
+    let host = match Url::parse(input) {
+        Ok(url) if url.has_host() => url.host_str()?.to_string(),
+        _ => input
+            .trim_start_matches("*.")
+            .trim_start_matches('.')
+            .split(['/', ':'])
+            .next()?
+            .to_string(),
+    };
+
+    (!host.is_empty()).then(|| site_for_host(&host))
+}
+
+/// The site a stored cookie belongs to, for looking up its rule.
+pub fn cookie_site(cookie_domain: &str) -> String {
+    site_for_host(cookie_domain.trim_start_matches('.'))
+}
+
+/// RFC 6265 section 5.1.3. `domain` must not have a leading dot.
+pub fn domain_match(host: &str, domain: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    let domain = domain.to_ascii_lowercase();
+
+    if host == domain {
+        return true;
+    }
+
+    host.ends_with(&domain)
+        && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+        && host.parse::<IpAddr>().is_err()
+}
+
+/// RFC 6265 section 5.1.4.
+pub fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    if request_path == cookie_path {
+        return true;
+    }
+
+    request_path.starts_with(cookie_path)
+        && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/'))
+}
+
+/// The directory of the request path, used when a cookie doesn't set `Path`.
+pub fn default_path(url: &Url) -> String {
+    let path = url.path();
+    if !path.starts_with('/') {
+        return "/".to_string();
+    }
+
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => path[..index].to_string(),
+    }
+}
+
+/// The lenient cookie-date algorithm from RFC 6265 section 5.1.1, which copes
+/// with the many date formats servers actually send in `Expires`.
+pub fn parse_cookie_date(input: &str) -> Option<DateTime<Utc>> {
+    let is_delimiter = |c: char| {
+        c == '\t'
+            || (' '..='/').contains(&c)
+            || (';'..='@').contains(&c)
+            || ('['..='`').contains(&c)
+            || ('{'..='~').contains(&c)
+    };
+
+    let mut time = None;
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+
+    for token in input.split(is_delimiter).filter(|token| !token.is_empty()) {
+        if time.is_none() {
+            if let Some(parsed) = parse_time_token(token) {
+                time = Some(parsed);
+                continue;
+            }
+        }
+
+        if day.is_none() {
+            if let Some(parsed) = leading_digits(token, 1, 2) {
+                day = Some(parsed);
+                continue;
+            }
+        }
+
+        if month.is_none() && token.len() >= 3 {
+            let prefix = token[..3].to_ascii_lowercase();
+            let months = [
+                "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+            ];
+            if let Some(index) = months.iter().position(|m| *m == prefix) {
+                month = Some(index as u32 + 1);
+                continue;
+            }
+        }
+
+        if year.is_none() {
+            if let Some(parsed) = leading_digits(token, 2, 4) {
+                year = Some(parsed);
+                continue;
+            }
+        }
+    }
+
+    let (hour, minute, second) = time?;
+    let year = match year? {
+        year @ 70..=99 => year + 1900,
+        year @ 0..=69 => year + 2000,
+        year => year,
+    };
+
+    if year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let date = NaiveDate::from_ymd_opt(year as i32, month?, day?)?;
+    let datetime = date.and_hms_opt(hour, minute, second)?;
+    Some(Utc.from_utc_datetime(&datetime))
+}
+
+/// Far-future expiry dates are clamped so they still fit our timestamp format.
+/// The furthest out an expiry date is allowed to be.
+pub fn latest_cookie_date() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(9999, 12, 31, 23, 59, 59).unwrap()
+}
+
+fn parse_time_token(token: &str) -> Option<(u32, u32, u32)> {
+    let mut fields = token.splitn(3, ':');
+    let hour = leading_digits(fields.next()?, 1, 2)?;
+    let minute = leading_digits(fields.next()?, 1, 2)?;
+    let second = leading_digits(fields.next()?, 1, 2)?;
+    Some((hour, minute, second))
+}
+
+/// Parses `min..=max` leading digits, allowing trailing non-digits as the RFC does.
+fn leading_digits(token: &str, min: usize, max: usize) -> Option<u32> {
+    let digits = token.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits < min || digits > max {
+        return None;
+    }
+    token[..digits].parse().ok()
+}
+
+/// Orders cookies the way RFC 6265 wants them sent: longer paths first,
+/// then older cookies first.
+pub fn sort_for_request(cookies: &mut [Cookie]) {
+    cookies.sort_by(|a, b| {
+        b.path
+            .len()
+            .cmp(&a.path.len())
+            .then(a.created_at.cmp(&b.created_at))
+    });
+}
+
+pub fn format_cookie_header(cookies: &[Cookie]) -> Option<String> {
+    if cookies.is_empty() {
+        return None;
+    }
+
+    Some(
+        cookies
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>()
+            .join("; "),
+    )
+}
+
+/// Every domain a cookie could be stored under and still match `host`:
+/// the host itself (host-only) plus the host and each parent with a leading dot.
+pub fn candidate_domains(host: &str) -> Vec<String> {
+    let host = host.to_ascii_lowercase();
+    let mut domains = vec![host.clone(), format!(".{}", host)];
+
+    if host.parse::<IpAddr>().is_err() {
+        let mut rest = host.as_str();
+        while let Some((_, parent)) = rest.split_once('.') {
+            domains.push(format!(".{}", parent));
+            rest = parent;
+        }
+    }
+
+    domains
+}
+
+/// Encrypted alongside the cookies so a wrong passphrase or key file is caught
+/// before anything gets written with it.
+const KEY_CHECK: &str = "bagel-cookie-key-check";
+
 pub struct CookieManager {
     db_path: std::path::PathBuf,
     auto_clear_days: u32,
+    cipher: Option<CookieCipher>,
 }
 
 impl CookieManager {
@@ -30,13 +555,14 @@ impl CookieManager {
         let manager = Self {
             db_path,
             auto_clear_days,
+            cipher: None,
         };
         manager.init_database()?;
         Ok(manager)
     }
 
     fn init_database(&self) -> Result<()> {
-        let conn = Connection::open(&self.db_path)?;
+        let mut conn = Connection::open(&self.db_path)?;
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS cookies (
@@ -49,11 +575,15 @@ impl CookieManager {
                 http_only BOOLEAN DEFAULT 0,
                 same_site TEXT,
                 created_at DATETIME NOT NULL,
-                PRIMARY KEY(name, domain, path)
+                partition_key TEXT NOT NULL DEFAULT '',
+                encrypted BOOLEAN NOT NULL DEFAULT 0,
+                PRIMARY KEY(name, domain, path, partition_key)
             )",
             [],
         )?;
 
+        Self::migrate_partition_key_column(&mut conn)?;
+
         conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_cookies_domain ON cookies(domain)",
             [],
@@ -64,75 +594,464 @@ impl CookieManager {
             [],
         )?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS cookie_encryption (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                kdf TEXT NOT NULL,
+                salt TEXT,
+                key_check TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS cookie_rules (
+                site TEXT PRIMARY KEY,
+                rule TEXT NOT NULL,
+                created_at DATETIME NOT NULL
+            )",
+            [],
+        )?;
+
+        Self::migrate_encrypted_column(&conn)?;
+
+        Ok(())
+    }
+
+    /// Older databases told encrypted values apart by their prefix alone. The
+    /// only rows that can really be encrypted are in a database with a key.
+    fn migrate_encrypted_column(conn: &Connection) -> Result<()> {
+        let has_encrypted: bool = conn
+            .prepare("SELECT 1 FROM pragma_table_info('cookies') WHERE name = 'encrypted'")?
+            .exists([])?;
+
+        if has_encrypted {
+            return Ok(());
+        }
+
+        conn.execute_batch(
+            "ALTER TABLE cookies ADD COLUMN encrypted BOOLEAN NOT NULL DEFAULT 0;
+             UPDATE cookies SET encrypted = 1
+                WHERE value LIKE 'enc:v1:%' AND EXISTS (SELECT 1 FROM cookie_encryption);",
+        )?;
+        Ok(())
+    }
+
+    /// The partition key is part of the primary key and sqlite can't change a
+    /// primary key in place, so older databases get the table rebuilt.
+    fn migrate_partition_key_column(conn: &mut Connection) -> Result<()> {
+        let has_partition_key: bool = conn
+            .prepare("SELECT 1 FROM pragma_table_info('cookies') WHERE name = 'partition_key'")?
+            .exists([])?;
+
+        if has_partition_key {
+            return Ok(());
+        }
+
+        let tx = conn.transaction()?;
+        tx.execute_batch(
+            "CREATE TABLE cookies_partitioned (
+                name TEXT NOT NULL,
+                value TEXT NOT NULL,
+                domain TEXT NOT NULL,
+                path TEXT NOT NULL,
+                expires DATETIME,
+                secure BOOLEAN DEFAULT 0,
+                http_only BOOLEAN DEFAULT 0,
+                same_site TEXT,
+                created_at DATETIME NOT NULL,
+                partition_key TEXT NOT NULL DEFAULT '',
+                PRIMARY KEY(name, domain, path, partition_key)
+            );
+            INSERT INTO cookies_partitioned
+                (name, value, domain, path, expires, secure, http_only, same_site, created_at)
+                SELECT name, value, domain, path, expires, secure, http_only, same_site, created_at
+                FROM cookies;
+            DROP TABLE cookies;
+            ALTER TABLE cookies_partitioned RENAME TO cookies;",
+        )?;
+        tx.commit()?;
+
+        log::info!("Migrated cookies.db to partitioned cookie keys");
+        Ok(())
+    }
+
+    /// Turns on encryption of cookie values. The first time, this records how the
+    /// key is made and encrypts every plaintext value already stored. After that
+    /// the same passphrase or key file has to be given again.
+    pub fn with_encryption(mut self, source: &CookieKeySource) -> Result<Self> {
+        let mut conn = Connection::open(&self.db_path)?;
+
+        let stored: Option<(String, Option<String>, String)> = conn
+            .query_row(
+                "SELECT kdf, salt, key_check FROM cookie_encryption WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+
+        let cipher = match stored {
+            Some((kdf, salt, key_check)) => {
+                if kdf != source.kdf() {
+                    return Err(anyhow::anyhow!(
+                        "cookies.db was encrypted using {}, but {} was given",
+                        kdf,
+                        source.kdf()
+                    ));
+                }
+
+                let salt = BASE64.decode(salt.unwrap_or_default())?;
+                let cipher = CookieCipher::new(source, &salt)?;
+                if cipher.decrypt(&key_check).ok().as_deref() != Some(KEY_CHECK) {
+                    return Err(anyhow::anyhow!(
+                        "Wrong passphrase or key file for cookies.db"
+                    ));
+                }
+                cipher
+            }
+            None => {
+                let salt = generate_salt();
+                let cipher = CookieCipher::new(source, &salt)?;
+                conn.execute(
+                    "INSERT INTO cookie_encryption (id, kdf, salt, key_check)
+                     VALUES (1, ?1, ?2, ?3)",
+                    params![
+                        source.kdf(),
+                        BASE64.encode(&salt),
+                        cipher.encrypt(KEY_CHECK)?
+                    ],
+                )?;
+                cipher
+            }
+        };
+
+        // rows written before encryption was turned on
+        let tx = conn.transaction()?;
+        let migrated = Self::rewrite_values(&tx, Some(&cipher), Some(&cipher), true)?;
+        tx.commit()?;
+        if migrated > 0 {
+            log::info!("Encrypted {} existing cookie values", migrated);
+        }
+
+        self.cipher = Some(cipher);
+        Ok(self)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// How the stored values' key is made, see [`CookieKeySource::kdf`].
+    /// `None` when cookies.db isn't encrypted.
+    pub fn stored_kdf(&self) -> Result<Option<String>> {
+        let conn = Connection::open(&self.db_path)?;
+        Ok(conn
+            .query_row(
+                "SELECT kdf FROM cookie_encryption WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Re-encrypts every cookie value under a new key. The database is only
+    /// updated if every value could be decrypted with the current key.
+    pub fn rotate_key(&mut self, new_source: &CookieKeySource) -> Result<()> {
+        let Some(old_cipher) = &self.cipher else {
+            return Err(anyhow::anyhow!("Cookie encryption isn't turned on"));
+        };
+
+        let salt = generate_salt();
+        let new_cipher = CookieCipher::new(new_source, &salt)?;
+
+        let mut conn = Connection::open(&self.db_path)?;
+        let tx = conn.transaction()?;
+        let count = Self::rewrite_values(&tx, Some(old_cipher), Some(&new_cipher), false)?;
+        tx.execute(
+            "UPDATE cookie_encryption SET kdf = ?1, salt = ?2, key_check = ?3 WHERE id = 1",
+            params![
+                new_source.kdf(),
+                BASE64.encode(&salt),
+                new_cipher.encrypt(KEY_CHECK)?
+            ],
+        )?;
+        tx.commit()?;
+
+        log::info!("Rotated cookie key, re-encrypted {} values", count);
+        self.cipher = Some(new_cipher);
+        Ok(())
+    }
+
+    /// Decrypts every value back to plaintext and forgets the key.
+    pub fn disable_encryption(&mut self) -> Result<()> {
+        let Some(cipher) = &self.cipher else {
+            return Ok(());
+        };
+
+        let mut conn = Connection::open(&self.db_path)?;
+        let tx = conn.transaction()?;
+        Self::rewrite_values(&tx, Some(cipher), None, false)?;
+        tx.execute("DELETE FROM cookie_encryption", [])?;
+        tx.commit()?;
+
+        self.cipher = None;
         Ok(())
     }
 
+    /// Reads every value, decrypting encrypted ones with `from`, and writes it
+    /// back with `to`, `None` meaning plaintext. With `plaintext_only` already
+    /// encrypted values are left alone. Returns how many values were rewritten.
+    fn rewrite_values(
+        conn: &Connection,
+        from: Option<&CookieCipher>,
+        to: Option<&CookieCipher>,
+        plaintext_only: bool,
+    ) -> Result<usize> {
+        let rows: Vec<(i64, String, bool)> = {
+            let mut stmt = conn.prepare("SELECT rowid, value, encrypted FROM cookies")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let mut count = 0;
+        for (rowid, stored, encrypted) in rows {
+            if plaintext_only && encrypted {
+                continue;
+            }
+
+            let value = match (encrypted, from) {
+                (false, _) => stored,
+                (true, Some(cipher)) => cipher.decrypt(&stored)?,
+                (true, None) => return Err(anyhow::anyhow!("No key for encrypted cookie values")),
+            };
+            let value = match to {
+                Some(cipher) => cipher.encrypt(&value)?,
+                None => value,
+            };
+
+            conn.execute(
+                "UPDATE cookies SET value = ?1, encrypted = ?2 WHERE rowid = ?3",
+                params![value, to.is_some(), rowid],
+            )?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Stores a cookie, refusing supercookies set on a public suffix like `.co.uk`
+    /// and cookies for sites with a block rule.
     pub fn set_cookie(&self, cookie: Cookie) -> Result<()> {
         let conn = Connection::open(&self.db_path)?;
 
+        let site = cookie_site(&cookie.domain);
+        let cookie = match Self::rule_for_domain(&conn, &cookie.domain)? {
+            Some(rule) => rule
+                .apply(cookie)
+                .ok_or_else(|| anyhow::anyhow!("Cookies are blocked for {}", site))?,
+            None => cookie,
+        };
+
+        self.insert_cookie(&conn, &cookie)
+    }
+
+    fn insert_cookie(&self, conn: &Connection, cookie: &Cookie) -> Result<()> {
+        if cookie.is_supercookie() {
+            return Err(anyhow::anyhow!(
+                "Refusing to set cookie {} on public suffix {}",
+                cookie.name,
+                cookie.domain
+            ));
+        }
+
         let expires_str = cookie
             .expires
             .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string());
+        let value = match &self.cipher {
+            Some(cipher) => cipher.encrypt(&cookie.value)?,
+            None => cookie.value.clone(),
+        };
 
         conn.execute(
             "INSERT OR REPLACE INTO cookies
-             (name, value, domain, path, expires, secure, http_only, same_site, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+             (name, value, domain, path, expires, secure, http_only, same_site, created_at,
+              partition_key, encrypted)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
             params![
                 cookie.name,
-                cookie.value,
+                value,
                 cookie.domain,
                 cookie.path,
                 expires_str,
                 cookie.secure,
                 cookie.http_only,
                 cookie.same_site,
-                cookie.created_at.format("%Y-%m-%d %H:%M:%S").to_string()
+                cookie.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                cookie.partition_key.as_deref().unwrap_or_default(),
+                self.cipher.is_some()
             ],
         )?;
 
         Ok(())
     }
 
+    /// Stores a cookie from a `Set-Cookie` header, or deletes the existing one
+    /// when the header expires it. Blocked requests can't set anything, and
+    /// partitioned ones only write into their partition.
+    // the loader goes through CookieJar, this writes to cookies.db directly
+    #[allow(dead_code)]
+    pub fn set_cookie_from_header(
+        &self,
+        header: &str,
+        request_url: &Url,
+        access: &CookieAccess,
+    ) -> Result<()> {
+        if *access == CookieAccess::Blocked {
+            return Err(anyhow::anyhow!(
+                "Third-party cookies are blocked for {}",
+                request_url
+            ));
+        }
+
+        let mut cookie = Cookie::parse_set_cookie(header, request_url)?;
+        cookie.partition_key = access.partition_key().map(str::to_string);
+
+        if cookie.is_expired() {
+            self.delete_cookie(
+                &cookie.name,
+                &cookie.domain,
+                &cookie.path,
+                cookie.partition_key.as_deref(),
+            )
+        } else {
+            self.set_cookie(cookie)
+        }
+    }
+
+    /// All unexpired cookies that domain-match `domain`, including ones set on
+    /// parent domains.
+    #[allow(dead_code)]
     pub fn get_cookies_for_domain(&self, domain: &str) -> Result<Vec<Cookie>> {
+        let cookies = self.get_candidate_cookies(domain)?;
+        Ok(cookies
+            .into_iter()
+            .filter(|cookie| !cookie.is_expired() && cookie.matches_host(domain))
+            .collect())
+    }
+
+    /// The cookies to send with a request for `url`, in RFC 6265 order: longer
+    /// paths first, then older cookies first. See [`Cookie::matches_request`].
+    pub fn cookies_for_request(&self, url: &Url, access: &CookieAccess) -> Result<Vec<Cookie>> {
+        let Some(host) = url.host_str() else {
+            return Ok(Vec::new());
+        };
+
+        let mut cookies: Vec<Cookie> = self
+            .get_candidate_cookies(host)?
+            .into_iter()
+            .filter(|cookie| cookie.matches_request(url, access))
+            .collect();
+
+        sort_for_request(&mut cookies);
+        Ok(cookies)
+    }
+
+    /// Builds the value of a `Cookie` request header for `url`.
+    // the loader asks CookieJar, this reads cookies.db directly
+    #[allow(dead_code)]
+    pub fn cookie_header_for_request(
+        &self,
+        url: &Url,
+        access: &CookieAccess,
+    ) -> Result<Option<String>> {
+        let cookies = self.cookies_for_request(url, access)?;
+        Ok(format_cookie_header(&cookies))
+    }
+
+    pub fn get_all_cookies(&self) -> Result<Vec<Cookie>> {
         let conn = Connection::open(&self.db_path)?;
 
         let mut stmt = conn.prepare(
-            "SELECT name, value, domain, path, expires, secure, http_only, same_site, created_at
+            "SELECT name, value, domain, path, expires, secure, http_only, same_site, created_at,
+                    partition_key, encrypted
              FROM cookies
-             WHERE domain = ?1 OR domain = ?2
-             ORDER BY path DESC, name",
+             ORDER BY domain, path DESC, name",
         )?;
 
-        let domain_with_dot = format!(".{}", domain);
-        let rows = stmt.query_map(params![domain, domain_with_dot], |row| {
-            Ok(self.row_to_cookie(row)?)
-        })?;
+        let rows = stmt.query_map([], |row| self.row_to_cookie(row))?;
+        Ok(Self::skip_bad_rows(rows))
+    }
 
-        let mut cookies = Vec::new();
-        for row in rows {
-            let cookie = row?;
-            // Check if cookie is still valid
-            if let Some(expires) = cookie.expires {
-                if expires < Utc::now() {
-                    continue; // Skip expired cookies
-                }
-            }
-            cookies.push(cookie);
+    /// Writes a batch of upserts and deletions in a single transaction and
+    /// returns how many upserts were stored. Cookies for blocked sites are dropped.
+    pub fn apply_changes(&self, upserts: &[Cookie], deletions: &[CookieKey]) -> Result<usize> {
+        let mut conn = Connection::open(&self.db_path)?;
+        let tx = conn.transaction()?;
+
+        for (name, domain, path, partition_key) in deletions {
+            tx.execute(
+                "DELETE FROM cookies
+                 WHERE name = ?1 AND domain = ?2 AND path = ?3 AND partition_key = ?4",
+                params![name, domain, path, partition_key],
+            )?;
         }
 
-        Ok(cookies)
+        let rules = Self::read_site_rules(&tx)?;
+        let mut stored = 0;
+        for cookie in upserts {
+            let cookie = match rules.get(&cookie_site(&cookie.domain)) {
+                Some(rule) => match rule.apply(cookie.clone()) {
+                    Some(cookie) => cookie,
+                    None => continue,
+                },
+                None => cookie.clone(),
+            };
+            self.insert_cookie(&tx, &cookie)?;
+            stored += 1;
+        }
+
+        tx.commit()?;
+        Ok(stored)
+    }
+
+    fn get_candidate_cookies(&self, host: &str) -> Result<Vec<Cookie>> {
+        let conn = Connection::open(&self.db_path)?;
+        let domains = candidate_domains(host);
+
+        let placeholders = (1..=domains.len())
+            .map(|i| format!("?{}", i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT name, value, domain, path, expires, secure, http_only, same_site, created_at,
+                    partition_key, encrypted
+             FROM cookies
+             WHERE domain IN ({})
+             ORDER BY path DESC, name",
+            placeholders
+        ))?;
+
+        let rows = stmt.query_map(params_from_iter(&domains), |row| self.row_to_cookie(row))?;
+        Ok(Self::skip_bad_rows(rows))
     }
 
-    pub fn delete_cookie(&self, name: &str, domain: &str, path: &str) -> Result<()> {
+    pub fn delete_cookie(
+        &self,
+        name: &str,
+        domain: &str,
+        path: &str,
+        partition_key: Option<&str>,
+    ) -> Result<()> {
         let conn = Connection::open(&self.db_path)?;
         conn.execute(
-            "DELETE FROM cookies WHERE name = ?1 AND domain = ?2 AND path = ?3",
-            params![name, domain, path],
+            "DELETE FROM cookies
+             WHERE name = ?1 AND domain = ?2 AND path = ?3 AND partition_key = ?4",
+            params![name, domain, path, partition_key.unwrap_or_default()],
         )?;
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub fn delete_cookies_for_domain(&self, domain: &str) -> Result<()> {
         let conn = Connection::open(&self.db_path)?;
         let domain_with_dot = format!(".{}", domain);
@@ -143,6 +1062,7 @@ impl CookieManager {
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub fn clear_all_cookies(&self) -> Result<()> {
         let conn = Connection::open(&self.db_path)?;
         conn.execute("DELETE FROM cookies", [])?;
@@ -161,29 +1081,175 @@ impl CookieManager {
         Ok(count as u32)
     }
 
+    /// Deletes cookies older than `auto_clear_days`, except for sites with an
+    /// allow rule. Does nothing when `auto_clear_days` is 0.
     pub fn clear_old_cookies(&self) -> Result<u32> {
-        let conn = Connection::open(&self.db_path)?;
+        if self.auto_clear_days == 0 {
+            return Ok(0);
+        }
+
+        let mut conn = Connection::open(&self.db_path)?;
         let cutoff_date = Utc::now() - chrono::Duration::days(self.auto_clear_days as i64);
+        let rules = Self::read_site_rules(&conn)?;
 
-        let count = conn.execute(
-            "DELETE FROM cookies WHERE created_at < ?1",
-            params![cutoff_date.format("%Y-%m-%d %H:%M:%S").to_string()],
+        let old: Vec<CookieKey> = {
+            let mut stmt = conn.prepare(
+                "SELECT name, domain, path, partition_key FROM cookies WHERE created_at < ?1",
+            )?;
+            let rows = stmt.query_map(
+                params![cutoff_date.format("%Y-%m-%d %H:%M:%S").to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )?;
+
+            let mut old = Vec::new();
+            for row in rows {
+                let key: CookieKey = row?;
+                if rules.get(&cookie_site(&key.1)) != Some(&CookieRule::Allow) {
+                    old.push(key);
+                }
+            }
+            old
+        };
+
+        let tx = conn.transaction()?;
+        for (name, domain, path, partition_key) in &old {
+            tx.execute(
+                "DELETE FROM cookies
+                 WHERE name = ?1 AND domain = ?2 AND path = ?3 AND partition_key = ?4",
+                params![name, domain, path, partition_key],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(old.len() as u32)
+    }
+
+    /// Sets the rule for the site `input` belongs to and returns that site.
+    /// Blocking a site also deletes the cookies it already has.
+    pub fn set_site_rule(&self, input: &str, rule: CookieRule) -> Result<String> {
+        if input.trim().is_empty() {
+            return Err(anyhow::anyhow!("Enter a site to add a rule for"));
+        }
+        let site = normalize_site(input)
+            .ok_or_else(|| anyhow::anyhow!("{} is not a site", input.trim()))?;
+        let conn = Connection::open(&self.db_path)?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO cookie_rules (site, rule, created_at) VALUES (?1, ?2, ?3)",
+            params![
+                site,
+                rule.as_str(),
+                Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+            ],
         )?;
 
+        if rule == CookieRule::Block {
+            self.delete_cookies_for_site(&site)?;
+        }
+
+        Ok(site)
+    }
+
+    pub fn remove_site_rule(&self, site: &str) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute("DELETE FROM cookie_rules WHERE site = ?1", params![site])?;
+        Ok(())
+    }
+
+    pub fn get_site_rules(&self) -> Result<Vec<SiteCookieRule>> {
+        let conn = Connection::open(&self.db_path)?;
+        let mut stmt =
+            conn.prepare("SELECT site, rule, created_at FROM cookie_rules ORDER BY site")?;
+
+        let rows = stmt.query_map([], |row| {
+            let rule: String = row.get(1)?;
+            let created_at: String = row.get(2)?;
+            Ok((row.get::<_, String>(0)?, rule, created_at))
+        })?;
+
+        let mut rules = Vec::new();
+        for row in rows {
+            let (site, rule, created_at) = row?;
+            let Some(rule) = CookieRule::parse(&rule) else {
+                log::warn!("Unknown cookie rule {} for {}", rule, site);
+                continue;
+            };
+            let created_at = NaiveDateTime::parse_from_str(&created_at, "%Y-%m-%d %H:%M:%S")
+                .map(|date| date.and_utc())
+                .unwrap_or_else(|_| Utc::now());
+            rules.push(SiteCookieRule {
+                site,
+                rule,
+                created_at,
+            });
+        }
+
+        Ok(rules)
+    }
+
+    /// Deletes every cookie, in every partition, belonging to `site`.
+    pub fn delete_cookies_for_site(&self, site: &str) -> Result<u32> {
+        let conn = Connection::open(&self.db_path)?;
+        let pattern = format!("%.{}", site);
+        let count = conn.execute(
+            "DELETE FROM cookies WHERE domain = ?1 OR domain LIKE ?2",
+            params![site, pattern],
+        )?;
         Ok(count as u32)
     }
 
+    /// Session-only cookies don't survive a restart. Run at startup, so they're
+    /// gone even if the last session crashed.
+    pub fn clear_session_only_cookies(&self) -> Result<u32> {
+        let mut count = 0;
+        for rule in self.get_site_rules()? {
+            if rule.rule == CookieRule::SessionOnly {
+                count += self.delete_cookies_for_site(&rule.site)?;
+            }
+        }
+        Ok(count)
+    }
+
+    fn read_site_rules(conn: &Connection) -> Result<HashMap<String, CookieRule>> {
+        let mut stmt = conn.prepare("SELECT site, rule FROM cookie_rules")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut rules = HashMap::new();
+        for row in rows {
+            let (site, rule) = row?;
+            if let Some(rule) = CookieRule::parse(&rule) {
+                rules.insert(site, rule);
+            }
+        }
+        Ok(rules)
+    }
+
+    fn rule_for_domain(conn: &Connection, domain: &str) -> Result<Option<CookieRule>> {
+        let rule: Option<String> = conn
+            .query_row(
+                "SELECT rule FROM cookie_rules WHERE site = ?1",
+                params![cookie_site(domain)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(rule.as_deref().and_then(CookieRule::parse))
+    }
+
+    #[allow(dead_code)]
     pub fn get_cookie_count(&self) -> Result<u32> {
         let conn = Connection::open(&self.db_path)?;
         let count: i64 = conn.query_row("SELECT COUNT(*) FROM cookies", [], |row| row.get(0))?;
         Ok(count as u32)
     }
 
+    #[allow(dead_code)]
     pub fn get_domains_with_cookies(&self) -> Result<Vec<String>> {
         let conn = Connection::open(&self.db_path)?;
 
         let mut stmt = conn.prepare("SELECT DISTINCT domain FROM cookies ORDER BY domain")?;
-        let rows = stmt.query_map([], |row| Ok(row.get::<_, String>(0)?))?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
 
         let mut domains = Vec::new();
         for row in rows {
@@ -193,19 +1259,47 @@ impl CookieManager {
         Ok(domains)
     }
 
+    /// Clears expired cookies and then old ones, returning how many of each.
     pub fn cleanup_task(&self) -> Result<(u32, u32)> {
         let expired_count = self.clear_expired_cookies()?;
         let old_count = self.clear_old_cookies()?;
         Ok((expired_count, old_count))
     }
 
+    /// One cookie that can't be read shouldn't take the rest down with it.
+    fn skip_bad_rows(rows: impl Iterator<Item = rusqlite::Result<Cookie>>) -> Vec<Cookie> {
+        rows.filter_map(|row| match row {
+            Ok(cookie) => Some(cookie),
+            Err(e) => {
+                log::warn!("Skipping unreadable cookie: {}", e);
+                None
+            }
+        })
+        .collect()
+    }
+
+    fn decrypt_value(&self, stored: String, encrypted: bool) -> Result<String, rusqlite::Error> {
+        let value = match (encrypted, &self.cipher) {
+            (false, _) => Ok(stored),
+            (true, Some(cipher)) => cipher.decrypt(&stored),
+            (true, None) => Err(anyhow::anyhow!(
+                "cookies.db is encrypted but no cookie key was given"
+            )),
+        };
+
+        value.map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, e.into())
+        })
+    }
+
     fn row_to_cookie(&self, row: &Row) -> Result<Cookie, rusqlite::Error> {
         let expires_str: Option<String> = row.get(4)?;
         let created_at_str: String = row.get(8)?;
 
+        // timestamps are stored without an offset, so parse them as naive UTC
         let expires = if let Some(expires_str) = expires_str {
             Some(
-                DateTime::parse_from_str(&expires_str, "%Y-%m-%d %H:%M:%S")
+                NaiveDateTime::parse_from_str(&expires_str, "%Y-%m-%d %H:%M:%S")
                     .map_err(|_| {
                         rusqlite::Error::InvalidColumnType(
                             4,
@@ -213,13 +1307,13 @@ impl CookieManager {
                             rusqlite::types::Type::Text,
                         )
                     })?
-                    .with_timezone(&Utc),
+                    .and_utc(),
             )
         } else {
             None
         };
 
-        let created_at = DateTime::parse_from_str(&created_at_str, "%Y-%m-%d %H:%M:%S")
+        let created_at = NaiveDateTime::parse_from_str(&created_at_str, "%Y-%m-%d %H:%M:%S")
             .map_err(|_| {
                 rusqlite::Error::InvalidColumnType(
                     8,
@@ -227,11 +1321,11 @@ impl CookieManager {
                     rusqlite::types::Type::Text,
                 )
             })?
-            .with_timezone(&Utc);
+            .and_utc();
 
         Ok(Cookie {
             name: row.get(0)?,
-            value: row.get(1)?,
+            value: self.decrypt_value(row.get(1)?, row.get(10)?)?,
             domain: row.get(2)?,
             path: row.get(3)?,
             expires,
@@ -239,6 +1333,349 @@ impl CookieManager {
             http_only: row.get(6)?,
             same_site: row.get(7)?,
             created_at,
+            partition_key: Some(row.get::<_, String>(9)?).filter(|key| !key.is_empty()),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(input: &str) -> Url {
+        Url::parse(input).unwrap()
+    }
+
+    fn parse(header: &str, request_url: &str) -> Result<Cookie> {
+        Cookie::parse_set_cookie(header, &url(request_url))
+    }
+
+    #[test]
+    fn parses_set_cookie_attributes() {
+        let cookie = parse(
+            "id=a3fWa; Domain=.Example.com; Path=/docs; Secure; HttpOnly; SameSite=strict",
+            "https://www.example.com/login",
+        )
+        .unwrap();
+        assert_eq!(cookie.name, "id");
+        assert_eq!(cookie.value, "a3fWa");
+        assert_eq!(cookie.domain, ".example.com");
+        assert_eq!(cookie.path, "/docs");
+        assert!(cookie.secure && cookie.http_only);
+        assert_eq!(cookie.same_site.as_deref(), Some("Strict"));
+        assert!(cookie.expires.is_none());
+        assert!(!cookie.is_host_only());
+
+        let cookie = parse("theme=dark", "https://example.com/a/b/page").unwrap();
+        assert_eq!(cookie.domain, "example.com");
+        assert_eq!(cookie.path, "/a/b");
+        assert!(cookie.is_host_only());
+    }
+
+    #[test]
+    fn rejects_bad_set_cookie_headers() {
+        assert!(parse("novalue", "https://example.com/").is_err());
+        assert!(parse("=value", "https://example.com/").is_err());
+        assert!(parse("a=b; Domain=other.com", "https://example.com/").is_err());
+        assert!(parse("a=b; Domain=com", "https://example.com/").is_err());
+        assert!(parse("a=b; Secure", "http://example.com/").is_err());
+        assert!(parse("a=b; SameSite=None", "https://example.com/").is_err());
+    }
+
+    #[test]
+    fn max_age_wins_over_expires_and_needs_a_digit_or_minus() {
+        let cookie = parse(
+            "a=b; Expires=Wed, 21 Oct 2015 07:28:00 GMT; Max-Age=3600",
+            "https://example.com/",
+        )
+        .unwrap();
+        assert!(!cookie.is_expired());
+
+        let cookie = parse("a=b; Max-Age=-1", "https://example.com/").unwrap();
+        assert!(cookie.is_expired());
+
+        // "+3600" isn't a valid max-age, so the cookie stays a session cookie
+        let cookie = parse("a=b; Max-Age=+3600", "https://example.com/").unwrap();
+        assert!(cookie.expires.is_none());
+        let cookie = parse("a=b; Max-Age= 10x", "https://example.com/").unwrap();
+        assert!(cookie.expires.is_none());
+    }
+
+    #[test]
+    fn parses_cookie_dates() {
+        let expected = Utc.with_ymd_and_hms(2015, 10, 21, 7, 28, 0).unwrap();
+        assert_eq!(
+            parse_cookie_date("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(expected)
+        );
+        assert_eq!(
+            parse_cookie_date("Wednesday, 21-Oct-15 07:28:00 GMT"),
+            Some(expected)
+        );
+        assert_eq!(
+            parse_cookie_date("Wed Oct 21 07:28:00 2015"),
+            Some(expected)
+        );
+        assert_eq!(parse_cookie_date("21 Oct 1500 07:28:00"), None);
+        assert_eq!(parse_cookie_date("tomorrow"), None);
+    }
+
+    #[test]
+    fn matches_domains_and_paths() {
+        assert!(domain_match("www.example.com", "example.com"));
+        assert!(domain_match("example.com", "EXAMPLE.com"));
+        assert!(!domain_match("badexample.com", "example.com"));
+        assert!(!domain_match("1.2.3.4", "2.3.4"));
+
+        assert!(path_match("/docs", "/docs"));
+        assert!(path_match("/docs/page", "/docs"));
+        assert!(path_match("/docs/page", "/docs/"));
+        assert!(!path_match("/docsearch", "/docs"));
+
+        assert_eq!(default_path(&url("https://example.com/")), "/");
+        assert_eq!(default_path(&url("https://example.com/a")), "/");
+        assert_eq!(default_path(&url("https://example.com/a/b")), "/a");
+    }
+
+    #[test]
+    fn same_site_follows_the_navigation() {
+        let page = url("https://example.com/");
+        let other = url("https://other.org/");
+        let lax = parse("a=1", "https://example.com/").unwrap();
+        let strict = parse("a=1; SameSite=Strict", "https://example.com/").unwrap();
+        let none = parse("a=1; SameSite=None; Secure", "https://example.com/").unwrap();
+
+        let typed = CookieAccess::for_navigation(&page, None, "GET");
+        let same_site =
+            CookieAccess::for_navigation(&page, Some(&url("https://www.example.com/")), "POST");
+        let cross_get = CookieAccess::for_navigation(&page, Some(&other), "GET");
+        let cross_post = CookieAccess::for_navigation(&page, Some(&other), "POST");
+        assert_eq!(typed, CookieAccess::FirstParty);
+        assert_eq!(same_site, CookieAccess::FirstParty);
+
+        assert!(lax.matches_request(&page, &cross_get));
+        assert!(!lax.matches_request(&page, &cross_post));
+        assert!(!strict.matches_request(&page, &cross_get));
+        assert!(strict.matches_request(&page, &typed));
+        assert!(none.matches_request(&page, &cross_post));
+        assert!(!lax.matches_request(&page, &CookieAccess::ThirdParty));
+        assert!(!none.matches_request(&page, &CookieAccess::Blocked));
+    }
+
+    #[test]
+    fn secure_cookies_stay_on_https() {
+        let cookie = parse("a=1; Secure; Path=/app", "https://example.com/").unwrap();
+        let access = CookieAccess::FirstParty;
+        assert!(cookie.matches_request(&url("https://example.com/app/x"), &access));
+        assert!(!cookie.matches_request(&url("http://example.com/app/x"), &access));
+        assert!(!cookie.matches_request(&url("https://example.com/other"), &access));
+        assert!(!cookie.matches_request(&url("https://sub.example.com/app"), &access));
+    }
+
+    #[test]
+    fn cookies_for_request_come_out_in_rfc_order() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let manager = CookieManager::new(dir.path(), 0).unwrap();
+        let page = url("https://www.example.com/docs/page");
+        let access = CookieAccess::FirstParty;
+
+        for header in [
+            "short=1; Path=/",
+            "long=2; Path=/docs",
+            "wide=3; Domain=example.com; Path=/",
+        ] {
+            manager
+                .set_cookie_from_header(header, &page, &access)
+                .unwrap();
+        }
+        manager
+            .set_cookie_from_header("other=4", &url("https://other.org/"), &access)
+            .unwrap();
+
+        let names: Vec<String> = manager
+            .cookies_for_request(&page, &access)
+            .unwrap()
+            .into_iter()
+            .map(|cookie| cookie.name)
+            .collect();
+        assert_eq!(names, vec!["long", "short", "wide"]);
+
+        manager
+            .set_cookie_from_header("long=2; Path=/docs; Max-Age=0", &page, &access)
+            .unwrap();
+        assert_eq!(
+            manager
+                .cookie_header_for_request(&page, &access)
+                .unwrap()
+                .as_deref(),
+            Some("short=1; wide=3")
+        );
+    }
+
+    fn aged_cookie(name: &str, domain: &str, days: i64) -> Cookie {
+        let mut cookie = parse(
+            &format!("{}=1; Max-Age=31536000", name),
+            &format!("https://{}/", domain),
+        )
+        .unwrap();
+        cookie.created_at = Utc::now() - chrono::Duration::days(days);
+        cookie
+    }
+
+    #[test]
+    fn old_cookies_are_cleared_except_for_allowed_sites() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let manager = CookieManager::new(dir.path(), 30).unwrap();
+        manager
+            .set_site_rule("https://mail.example.com/", CookieRule::Allow)
+            .unwrap();
+
+        manager
+            .set_cookie(aged_cookie("login", "mail.example.com", 90))
+            .unwrap();
+        manager
+            .set_cookie(aged_cookie("old", "tracker.net", 90))
+            .unwrap();
+        manager
+            .set_cookie(aged_cookie("new", "tracker.net", 1))
+            .unwrap();
+        let mut expired = aged_cookie("expired", "tracker.net", 1);
+        expired.expires = Some(Utc::now() - chrono::Duration::hours(1));
+        manager.set_cookie(expired).unwrap();
+
+        assert_eq!(manager.cleanup_task().unwrap(), (1, 1));
+        let mut names: Vec<String> = manager
+            .get_all_cookies()
+            .unwrap()
+            .into_iter()
+            .map(|cookie| cookie.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["login", "new"]);
+    }
+
+    #[test]
+    fn auto_clear_can_be_turned_off() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let manager = CookieManager::new(dir.path(), 0).unwrap();
+        manager
+            .set_cookie(aged_cookie("old", "example.com", 900))
+            .unwrap();
+        assert_eq!(manager.clear_old_cookies().unwrap(), 0);
+        assert_eq!(manager.get_all_cookies().unwrap().len(), 1);
+    }
+
+    fn key_source(dir: &tempfile::TempDir) -> CookieKeySource {
+        CookieKeySource::KeyFile(dir.path().join("cookies.key"))
+    }
+
+    fn values(manager: &CookieManager) -> Vec<String> {
+        manager
+            .get_all_cookies()
+            .unwrap()
+            .into_iter()
+            .map(|cookie| cookie.value)
+            .collect()
+    }
+
+    #[test]
+    fn values_that_look_encrypted_are_stored_as_they_are() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let page = url("https://example.com/");
+        let access = CookieAccess::FirstParty;
+        {
+            let manager = CookieManager::new(dir.path(), 0).unwrap();
+            manager
+                .set_cookie_from_header("x=enc:v1:AAAA", &page, &access)
+                .unwrap();
+        }
+
+        // a restart still reads it back
+        let manager = CookieManager::new(dir.path(), 0).unwrap();
+        assert_eq!(values(&manager), vec!["enc:v1:AAAA"]);
+
+        // and turning encryption on encrypts it like any other value
+        let manager = manager.with_encryption(&key_source(&dir)).unwrap();
+        assert_eq!(values(&manager), vec!["enc:v1:AAAA"]);
+        let stored: String = Connection::open(dir.path().join("cookies.db"))
+            .unwrap()
+            .query_row("SELECT value FROM cookies", [], |row| row.get(0))
+            .unwrap();
+        assert_ne!(stored, "enc:v1:AAAA");
+
+        let manager = CookieManager::new(dir.path(), 0)
+            .unwrap()
+            .with_encryption(&key_source(&dir))
+            .unwrap();
+        manager
+            .set_cookie_from_header("y=enc:v1:BBBB", &page, &access)
+            .unwrap();
+        let mut stored = values(&manager);
+        stored.sort();
+        assert_eq!(stored, vec!["enc:v1:AAAA", "enc:v1:BBBB"]);
+    }
+
+    #[test]
+    fn an_unreadable_row_is_skipped() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let page = url("https://example.com/");
+        let manager = CookieManager::new(dir.path(), 0)
+            .unwrap()
+            .with_encryption(&key_source(&dir))
+            .unwrap();
+        for header in ["good=1", "bad=2"] {
+            manager
+                .set_cookie_from_header(header, &page, &CookieAccess::FirstParty)
+                .unwrap();
+        }
+
+        Connection::open(dir.path().join("cookies.db"))
+            .unwrap()
+            .execute(
+                "UPDATE cookies SET value = 'enc:v1:AAAA' WHERE name = 'bad'",
+                [],
+            )
+            .unwrap();
+
+        assert_eq!(values(&manager), vec!["1"]);
+        let jar =
+            crate::storage::CookieJar::new(manager, crate::utils::CookiePolicy::AllowAll).unwrap();
+        assert_eq!(jar.all_cookies().len(), 1);
+    }
+
+    #[test]
+    fn encrypted_databases_need_their_key() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let manager = CookieManager::new(dir.path(), 0)
+            .unwrap()
+            .with_encryption(&CookieKeySource::Passphrase("right".into()))
+            .unwrap();
+        manager
+            .set_cookie_from_header(
+                "a=1",
+                &url("https://example.com/"),
+                &CookieAccess::FirstParty,
+            )
+            .unwrap();
+
+        let reopen = || CookieManager::new(dir.path(), 0).unwrap();
+        assert!(reopen()
+            .with_encryption(&CookieKeySource::Passphrase("wrong".into()))
+            .is_err());
+        assert!(reopen().with_encryption(&key_source(&dir)).is_err());
+        // without a key the encrypted row can't be read, but opening still works
+        assert!(values(&reopen()).is_empty());
+
+        let mut manager = reopen()
+            .with_encryption(&CookieKeySource::Passphrase("right".into()))
+            .unwrap();
+        assert_eq!(manager.stored_kdf().unwrap().as_deref(), Some("argon2id"));
+        manager.rotate_key(&key_source(&dir)).unwrap();
+        assert_eq!(values(&manager), vec!["1"]);
+        assert_eq!(manager.stored_kdf().unwrap().as_deref(), Some("key_file"));
+        manager.disable_encryption().unwrap();
+        assert!(!manager.is_encrypted());
+        assert_eq!(manager.stored_kdf().unwrap(), None);
+        assert_eq!(values(&reopen()), vec!["1"]);
+    }
+}
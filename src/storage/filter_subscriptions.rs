@@ -0,0 +1,304 @@
+// remote filter lists we keep a copy of
+// the list itself lives in filters/subscriptions/<id>.txt, this tracks
+// where it came from and when it's due for another download
+// also remembers which of the local lists are switched off
+use crate::browser::FilterCategory;
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// How long a list is good for when it doesn't say with `! Expires:`.
+pub const DEFAULT_LIST_EXPIRY_HOURS: i64 = 5 * 24;
+
+/// Subscriptions added the first time filters.db is created. uBlock's own
+/// list is there but off, it expects uBlock's scriptlets for a lot of it.
+const DEFAULT_SUBSCRIPTIONS: &[(&str, &str, FilterCategory, bool)] = &[
+    (
+        "EasyList",
+        "https://easylist.to/easylist/easylist.txt",
+        FilterCategory::Ads,
+        true,
+    ),
+    (
+        "EasyPrivacy",
+        "https://easylist.to/easylist/easyprivacy.txt",
+        FilterCategory::Tracking,
+        true,
+    ),
+    (
+        "uBlock filters",
+        "https://ublockorigin.github.io/uAssets/filters/filters.txt",
+        FilterCategory::Ads,
+        false,
+    ),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterSubscription {
+    pub id: i64,
+    pub title: String,
+    pub url: String,
+    pub category: FilterCategory,
+    pub enabled: bool,
+    pub last_updated: Option<DateTime<Utc>>,
+    /// From the list's `! Expires:` header.
+    pub expires_hours: Option<i64>,
+    /// Why the last download failed, cleared by the next one that works.
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FilterSubscription {
+    /// When the list should be downloaded again. Never-downloaded lists are due now.
+    pub fn next_update(&self) -> DateTime<Utc> {
+        match self.last_updated {
+            Some(last_updated) => {
+                last_updated
+                    + Duration::hours(self.expires_hours.unwrap_or(DEFAULT_LIST_EXPIRY_HOURS))
+            }
+            None => DateTime::<Utc>::MIN_UTC,
+        }
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.enabled && self.next_update() <= now
+    }
+}
+
+pub struct SubscriptionManager {
+    db_path: PathBuf,
+    lists_dir: PathBuf,
+}
+
+impl SubscriptionManager {
+    pub fn new(data_dir: &Path) -> Result<Self> {
+        let manager = Self {
+            db_path: data_dir.join("filters.db"),
+            lists_dir: data_dir.join("filters").join("subscriptions"),
+        };
+        manager.init_database()?;
+        Ok(manager)
+    }
+
+    fn init_database(&self) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let exists: bool = conn
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'subscriptions'",
+                [],
+                |_| Ok(true),
+            )
+            .optional()?
+            .unwrap_or(false);
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS subscriptions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                title TEXT NOT NULL,
+                url TEXT NOT NULL UNIQUE,
+                category TEXT NOT NULL,
+                enabled BOOLEAN DEFAULT 1,
+                last_updated DATETIME,
+                expires_hours INTEGER,
+                last_error TEXT,
+                created_at DATETIME NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS local_lists (
+                name TEXT PRIMARY KEY,
+                enabled BOOLEAN NOT NULL
+            )",
+            [],
+        )?;
+
+        if !exists {
+            for (title, url, category, enabled) in DEFAULT_SUBSCRIPTIONS {
+                let subscription = self.add_subscription(title, url, *category)?;
+                if !enabled {
+                    self.set_enabled(subscription.id, false)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Where the downloaded copy of a subscription is kept.
+    pub fn list_path(&self, id: i64) -> PathBuf {
+        self.lists_dir.join(format!("{}.txt", id))
+    }
+
+    pub fn add_subscription(
+        &self,
+        title: &str,
+        url: &str,
+        category: FilterCategory,
+    ) -> Result<FilterSubscription> {
+        let url = url.trim();
+        let parsed = url::Url::parse(url)?;
+        if parsed.scheme() != "http" && parsed.scheme() != "https" {
+            return Err(anyhow::anyhow!(
+                "Filter lists have to come from http(s) urls"
+            ));
+        }
+
+        let title = match title.trim() {
+            "" => parsed.host_str().unwrap_or(url).to_string(),
+            title => title.to_string(),
+        };
+
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "INSERT INTO subscriptions (title, url, category, enabled, created_at)
+             VALUES (?1, ?2, ?3, 1, ?4)",
+            params![
+                title,
+                url,
+                category.as_str(),
+                Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+            ],
+        )?;
+
+        self.get_subscription(conn.last_insert_rowid())?
+            .ok_or_else(|| anyhow::anyhow!("Subscription vanished after being added"))
+    }
+
+    pub fn set_enabled(&self, id: i64, enabled: bool) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "UPDATE subscriptions SET enabled = ?1 WHERE id = ?2",
+            params![enabled, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_subscriptions(&self) -> Result<Vec<FilterSubscription>> {
+        let conn = Connection::open(&self.db_path)?;
+        let mut stmt = conn.prepare(
+            "SELECT id, title, url, category, enabled, last_updated, expires_hours, last_error, created_at
+             FROM subscriptions ORDER BY id",
+        )?;
+
+        let subscriptions = stmt
+            .query_map([], Self::row_to_subscription)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(subscriptions)
+    }
+
+    pub fn get_subscription(&self, id: i64) -> Result<Option<FilterSubscription>> {
+        let conn = Connection::open(&self.db_path)?;
+        let subscription = conn
+            .query_row(
+                "SELECT id, title, url, category, enabled, last_updated, expires_hours, last_error, created_at
+                 FROM subscriptions WHERE id = ?1",
+                params![id],
+                Self::row_to_subscription,
+            )
+            .optional()?;
+        Ok(subscription)
+    }
+
+    /// Enabled subscriptions whose list has expired or was never downloaded.
+    pub fn due_subscriptions(&self) -> Result<Vec<FilterSubscription>> {
+        let now = Utc::now();
+        Ok(self
+            .get_subscriptions()?
+            .into_iter()
+            .filter(|subscription| subscription.is_due(now))
+            .collect())
+    }
+
+    /// Stores a freshly downloaded list and marks the subscription up to date.
+    pub fn save_list(&self, id: i64, contents: &str, expires_hours: Option<i64>) -> Result<()> {
+        std::fs::create_dir_all(&self.lists_dir)?;
+
+        // write next to the old copy and swap, so a crash can't leave half a list
+        let path = self.list_path(id);
+        let partial = path.with_extension("txt.partial");
+        std::fs::write(&partial, contents)?;
+        std::fs::rename(&partial, &path)?;
+
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "UPDATE subscriptions SET last_updated = ?1, expires_hours = ?2, last_error = NULL
+             WHERE id = ?3",
+            params![
+                Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                expires_hours,
+                id
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Remembers why a download failed. The old copy of the list stays in use
+    /// and the next scheduled check tries again.
+    pub fn record_error(&self, id: i64, error: &str) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "UPDATE subscriptions SET last_error = ?1 WHERE id = ?2",
+            params![error, id],
+        )?;
+        Ok(())
+    }
+
+    /// Whether a list that isn't a subscription, like a file in `filters/` or
+    /// the custom rules, is on. Lists are on until switched off.
+    pub fn local_list_enabled(&self, name: &str) -> Result<bool> {
+        let conn = Connection::open(&self.db_path)?;
+        let enabled = conn
+            .query_row(
+                "SELECT enabled FROM local_lists WHERE name = ?1",
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(enabled.unwrap_or(true))
+    }
+
+    pub fn set_local_list_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO local_lists (name, enabled) VALUES (?1, ?2)",
+            params![name, enabled],
+        )?;
+        Ok(())
+    }
+
+    /// The downloaded copy of a subscription's list, if there is one.
+    pub fn read_list(&self, id: i64) -> Result<Option<String>> {
+        match std::fs::read_to_string(self.list_path(id)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn row_to_subscription(row: &Row) -> rusqlite::Result<FilterSubscription> {
+        let parse_date = |date: String| {
+            NaiveDateTime::parse_from_str(&date, "%Y-%m-%d %H:%M:%S").map(|date| date.and_utc())
+        };
+
+        let category: String = row.get(3)?;
+        let last_updated: Option<String> = row.get(5)?;
+        let created_at: String = row.get(8)?;
+
+        Ok(FilterSubscription {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            url: row.get(2)?,
+            category: FilterCategory::parse(&category).unwrap_or(FilterCategory::Ads),
+            enabled: row.get(4)?,
+            last_updated: last_updated.and_then(|date| parse_date(date).ok()),
+            expires_hours: row.get(6)?,
+            last_error: row.get(7)?,
+            created_at: parse_date(created_at).unwrap_or_else(|_| Utc::now()),
+        })
+    }
+}
@@ -0,0 +1,290 @@
+// downloads filter list subscriptions when they expire
+// a failed download keeps the old copy, a list that fails its checksum
+// or doesn't look like a filter list never replaces a good one
+use crate::storage::{FilterSubscription, SubscriptionManager};
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use md5::{Digest, Md5};
+use regex::Regex;
+use reqwest::Client;
+use std::time::Duration;
+
+/// Lists asking for less than this get it anyway, more than this is too stale.
+const MIN_EXPIRY_HOURS: i64 = 1;
+const MAX_EXPIRY_HOURS: i64 = 14 * 24;
+
+pub struct FilterListUpdater {
+    client: Client,
+}
+
+impl FilterListUpdater {
+    pub fn new(timeout: Duration) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(timeout)
+            .user_agent(concat!("Bagel/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        Ok(Self { client })
+    }
+
+    /// Downloads every subscription that's due and returns how many lists
+    /// changed. Failures are recorded on the subscription, not returned.
+    pub async fn update_due(&self, manager: &SubscriptionManager) -> Result<usize> {
+        let mut updated = 0;
+        for subscription in manager.due_subscriptions()? {
+            match self.update(manager, &subscription).await {
+                Ok(()) => updated += 1,
+                Err(e) => {
+                    log::warn!("Failed to update filter list {}: {}", subscription.title, e);
+                    manager.record_error(subscription.id, &e.to_string())?;
+                }
+            }
+        }
+        Ok(updated)
+    }
+
+    /// Downloads one subscription now, whether it's due or not.
+    pub async fn update(
+        &self,
+        manager: &SubscriptionManager,
+        subscription: &FilterSubscription,
+    ) -> Result<()> {
+        let contents = self
+            .client
+            .get(&subscription.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        validate_list(&contents)?;
+        let expires_hours = parse_expires(&contents);
+        manager.save_list(subscription.id, &contents, expires_hours)?;
+
+        log::info!(
+            "Updated filter list {} ({} bytes)",
+            subscription.title,
+            contents.len()
+        );
+        Ok(())
+    }
+}
+
+/// Rejects things that aren't filter lists, like a captive portal's login
+/// page, and lists whose `! Checksum:` doesn't match.
+pub fn validate_list(contents: &str) -> Result<()> {
+    let first_line = contents.trim_start().lines().next().unwrap_or_default();
+    if first_line.is_empty() {
+        return Err(anyhow::anyhow!("Filter list is empty"));
+    }
+    if first_line.starts_with('<') {
+        return Err(anyhow::anyhow!("Got an html page instead of a filter list"));
+    }
+
+    verify_checksum(contents)
+}
+
+/// Checks the `! Checksum:` header Adblock Plus lists can carry: the md5 of
+/// the list without that line and with blank lines dropped, in base64 without
+/// padding. Lists without one pass.
+pub fn verify_checksum(contents: &str) -> Result<()> {
+    let checksum_line = Regex::new(r"(?mi)^\s*!\s*checksum[\s\-:]+([\w+/=]+).*\n")?;
+
+    let normalized = contents.replace('\r', "");
+    let normalized = Regex::new(r"\n+")?.replace_all(&normalized, "\n");
+    let Some(expected) = checksum_line
+        .captures(&normalized)
+        .map(|captures| captures[1].trim_end_matches('=').to_string())
+    else {
+        return Ok(());
+    };
+
+    let stripped = checksum_line.replace(&normalized, "");
+    let actual = BASE64.encode(Md5::digest(stripped.as_bytes()));
+
+    if actual.trim_end_matches('=') != expected {
+        return Err(anyhow::anyhow!("Filter list checksum doesn't match"));
+    }
+    Ok(())
+}
+
+/// Reads `! Expires: 4 days` or `! Expires: 12 hours` from the list header,
+/// in hours.
+pub fn parse_expires(contents: &str) -> Option<i64> {
+    let header = contents
+        .lines()
+        .take_while(|line| line.starts_with('!') || line.starts_with('[') || line.is_empty())
+        .find_map(|line| {
+            let comment = line.trim_start_matches('!').trim();
+            let (key, value) = comment.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case("expires")
+                .then(|| value.trim())
+        })?;
+
+    let mut words = header.split_whitespace();
+    let amount: i64 = words.next()?.parse().ok()?;
+    let hours = match words.next()?.to_ascii_lowercase().trim_end_matches('s') {
+        "day" => amount.saturating_mul(24),
+        "hour" => amount,
+        _ => return None,
+    };
+
+    Some(hours.clamp(MIN_EXPIRY_HOURS, MAX_EXPIRY_HOURS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::FilterCategory;
+    use crate::utils::test_server::{serve_http, Reply};
+    use tempfile::TempDir;
+
+    // md5 of LIST without its checksum line, worked out separately
+    const LIST: &str = "[Adblock Plus 2.0]\n! Title: Test list\n! Checksum: 03ib7ylgWU5+dbAg0Ulbvg\n! Expires: 2 days\n||ads.example^\n##.banner\n";
+
+    /// Serves a good list, a list with a bad checksum, a login page and a 500.
+    async fn serve() -> String {
+        serve_http(|_, path| match path {
+            "/good.txt" => Reply::new("200 OK").body(LIST),
+            "/tampered.txt" => Reply::new("200 OK").body(LIST.replace("ads.example", "ads.other")),
+            "/portal.txt" => Reply::new("200 OK").body("<html>Log in first</html>"),
+            _ => Reply::new("500 Internal Server Error"),
+        })
+        .await
+    }
+
+    /// A manager with the default subscriptions switched off, so nothing
+    /// goes out to the internet.
+    fn manager(dir: &TempDir) -> SubscriptionManager {
+        let manager = SubscriptionManager::new(dir.path()).unwrap();
+        for subscription in manager.get_subscriptions().unwrap() {
+            manager.set_enabled(subscription.id, false).unwrap();
+        }
+        manager
+    }
+
+    #[test]
+    fn checksum_matches_the_adblock_plus_vector() {
+        assert!(verify_checksum(LIST).is_ok());
+        // line endings and blank lines don't count
+        assert!(verify_checksum(&LIST.replace('\n', "\r\n\n")).is_ok());
+        // a padded checksum is fine too
+        assert!(verify_checksum(&LIST.replace("Ulbvg", "Ulbvg==")).is_ok());
+    }
+
+    #[test]
+    fn checksum_catches_changed_lists() {
+        assert!(verify_checksum(&LIST.replace("##.banner", "##.advert")).is_err());
+        assert!(verify_checksum(&LIST.replace("03ib7", "13ib7")).is_err());
+    }
+
+    #[test]
+    fn lists_without_a_checksum_pass() {
+        assert!(verify_checksum("[Adblock Plus 2.0]\n||ads.example^\n").is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_empty_lists_and_html() {
+        assert!(validate_list(LIST).is_ok());
+        assert!(validate_list("").is_err());
+        assert!(validate_list("  \n\n").is_err());
+        assert!(validate_list("\n<!DOCTYPE html><html></html>").is_err());
+    }
+
+    #[test]
+    fn expires_is_read_in_hours() {
+        assert_eq!(parse_expires(LIST), Some(48));
+        assert_eq!(parse_expires("! Expires: 12 hours\n"), Some(12));
+        assert_eq!(
+            parse_expires("! Expires: 1 day (update frequency)\n"),
+            Some(24)
+        );
+        assert_eq!(
+            parse_expires("[Adblock Plus 2.0]\n\n!expires:4 Days\n"),
+            Some(96)
+        );
+    }
+
+    #[test]
+    fn expires_is_clamped() {
+        assert_eq!(
+            parse_expires("! Expires: 0 hours\n"),
+            Some(MIN_EXPIRY_HOURS)
+        );
+        assert_eq!(
+            parse_expires("! Expires: -3 days\n"),
+            Some(MIN_EXPIRY_HOURS)
+        );
+        assert_eq!(
+            parse_expires("! Expires: 90 days\n"),
+            Some(MAX_EXPIRY_HOURS)
+        );
+        assert_eq!(
+            parse_expires(&format!("! Expires: {} days\n", i64::MAX)),
+            Some(MAX_EXPIRY_HOURS)
+        );
+        assert_eq!(
+            parse_expires(&format!("! Expires: {} days\n", i64::MIN)),
+            Some(MIN_EXPIRY_HOURS)
+        );
+    }
+
+    #[test]
+    fn expires_has_to_be_in_the_header() {
+        assert_eq!(parse_expires("! Expires: soon\n"), None);
+        assert_eq!(parse_expires("! Expires: 3 weeks\n"), None);
+        assert_eq!(parse_expires("||ads.example^\n! Expires: 2 days\n"), None);
+    }
+
+    #[tokio::test]
+    async fn update_due_saves_good_lists_and_keeps_old_copies() {
+        let base = serve().await;
+        let dir = TempDir::new().unwrap();
+        let manager = manager(&dir);
+        let add = |name: &str| {
+            manager
+                .add_subscription(name, &format!("{}/{}.txt", base, name), FilterCategory::Ads)
+                .unwrap()
+        };
+        let good = add("good");
+        let tampered = add("tampered");
+        let portal = add("portal");
+        let broken = add("broken");
+        // already downloaded, so not due
+        manager
+            .save_list(broken.id, "||old.example^\n", None)
+            .unwrap();
+
+        let updater = FilterListUpdater::new(Duration::from_secs(5)).unwrap();
+        assert_eq!(updater.update_due(&manager).await.unwrap(), 1);
+
+        assert_eq!(manager.read_list(good.id).unwrap().as_deref(), Some(LIST));
+        let good = manager.get_subscription(good.id).unwrap().unwrap();
+        assert_eq!(good.expires_hours, Some(48));
+        assert!(good.last_updated.is_some() && good.last_error.is_none());
+
+        for failed in [&tampered, &portal] {
+            assert_eq!(manager.read_list(failed.id).unwrap(), None);
+            let failed = manager.get_subscription(failed.id).unwrap().unwrap();
+            assert!(failed.last_error.is_some());
+        }
+
+        // the last good copy stays after a failed download
+        assert!(updater.update(&manager, &broken).await.is_err());
+        assert_eq!(
+            manager.read_list(broken.id).unwrap().as_deref(),
+            Some("||old.example^\n")
+        );
+
+        // the lists that failed are still due
+        let due: Vec<i64> = manager
+            .due_subscriptions()
+            .unwrap()
+            .iter()
+            .map(|subscription| subscription.id)
+            .collect();
+        assert_eq!(due, vec![tampered.id, portal.id]);
+    }
+}
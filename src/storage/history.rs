@@ -84,9 +84,7 @@ impl HistoryManager {
         )?;
 
         let search_pattern = format!("%{}%", query);
-        let rows = stmt.query_map(params![search_pattern, limit], |row| {
-            Ok(self.row_to_history_entry(row)?)
-        })?;
+        let rows = stmt.query_map(params![search_pattern, limit], |row| self.row_to_history_entry(row))?;
 
         let mut entries = Vec::new();
         for row in rows {
@@ -106,9 +104,7 @@ impl HistoryManager {
              LIMIT ?1"
         )?;
 
-        let rows = stmt.query_map(params![limit], |row| {
-            Ok(self.row_to_history_entry(row)?)
-        })?;
+        let rows = stmt.query_map(params![limit], |row| self.row_to_history_entry(row))?;
 
         let mut entries = Vec::new();
         for row in rows {
@@ -128,9 +124,7 @@ impl HistoryManager {
              LIMIT ?1"
         )?;
 
-        let rows = stmt.query_map(params![limit], |row| {
-            Ok(self.row_to_history_entry(row)?)
-        })?;
+        let rows = stmt.query_map(params![limit], |row| self.row_to_history_entry(row))?;
 
         let mut entries = Vec::new();
         for row in rows {
@@ -0,0 +1,285 @@
+// hosts that told us to only use https, from their Strict-Transport-Security
+// headers, and the keys hosts pinned with Public-Key-Pins, kept in hsts.db.
+// the preload list isn't stored, it ships with us
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HstsEntry {
+    pub host: String,
+    pub include_subdomains: bool,
+    /// When max-age runs out, counted from the last time we saw the header.
+    pub expires_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The keys a host pinned with a Public-Key-Pins header.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinEntry {
+    pub host: String,
+    /// Base64 SHA-256 hashes of the pinned public keys.
+    pub pins: Vec<String>,
+    pub include_subdomains: bool,
+    pub expires_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct HstsManager {
+    db_path: PathBuf,
+}
+
+impl HstsManager {
+    pub fn new(data_dir: &Path) -> Result<Self> {
+        let manager = Self {
+            db_path: data_dir.join("hsts.db"),
+        };
+        manager.init_database()?;
+        Ok(manager)
+    }
+
+    fn init_database(&self) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS hsts_entries (
+                host TEXT PRIMARY KEY,
+                include_subdomains BOOLEAN NOT NULL,
+                expires_at DATETIME NOT NULL,
+                updated_at DATETIME NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS pinned_hosts (
+                host TEXT PRIMARY KEY,
+                pins TEXT NOT NULL,
+                include_subdomains BOOLEAN NOT NULL,
+                expires_at DATETIME NOT NULL,
+                updated_at DATETIME NOT NULL
+            )",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    /// The entries that haven't expired. Expired ones are deleted on the way.
+    pub fn get_all(&self) -> Result<Vec<HstsEntry>> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "DELETE FROM hsts_entries WHERE expires_at <= ?1",
+            params![Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()],
+        )?;
+
+        let mut stmt = conn.prepare(
+            "SELECT host, include_subdomains, expires_at, updated_at
+             FROM hsts_entries ORDER BY host",
+        )?;
+        let entries = stmt
+            .query_map([], Self::row_to_entry)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    pub fn set(&self, entry: &HstsEntry) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO hsts_entries
+             (host, include_subdomains, expires_at, updated_at) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                entry.host,
+                entry.include_subdomains,
+                entry.expires_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                entry.updated_at.format("%Y-%m-%d %H:%M:%S").to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove(&self, host: &str) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute("DELETE FROM hsts_entries WHERE host = ?1", params![host])?;
+        Ok(())
+    }
+
+    /// The pinned hosts that haven't expired. Expired ones are deleted on the way.
+    pub fn get_all_pins(&self) -> Result<Vec<PinEntry>> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "DELETE FROM pinned_hosts WHERE expires_at <= ?1",
+            params![Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()],
+        )?;
+
+        let mut stmt = conn.prepare(
+            "SELECT host, pins, include_subdomains, expires_at, updated_at
+             FROM pinned_hosts ORDER BY host",
+        )?;
+        let entries = stmt
+            .query_map([], Self::row_to_pins)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    pub fn set_pins(&self, entry: &PinEntry) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        // base64 has no spaces, so the pins go in one column
+        conn.execute(
+            "INSERT OR REPLACE INTO pinned_hosts
+             (host, pins, include_subdomains, expires_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                entry.host,
+                entry.pins.join(" "),
+                entry.include_subdomains,
+                entry.expires_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                entry.updated_at.format("%Y-%m-%d %H:%M:%S").to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_pins(&self, host: &str) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute("DELETE FROM pinned_hosts WHERE host = ?1", params![host])?;
+        Ok(())
+    }
+
+    fn row_to_entry(row: &Row) -> rusqlite::Result<HstsEntry> {
+        let expires_at: String = row.get(2)?;
+        let updated_at: String = row.get(3)?;
+        Ok(HstsEntry {
+            host: row.get(0)?,
+            include_subdomains: row.get(1)?,
+            expires_at: NaiveDateTime::parse_from_str(&expires_at, "%Y-%m-%d %H:%M:%S")
+                .map(|date| date.and_utc())
+                .unwrap_or_else(|_| Utc::now()),
+            updated_at: NaiveDateTime::parse_from_str(&updated_at, "%Y-%m-%d %H:%M:%S")
+                .map(|date| date.and_utc())
+                .unwrap_or_else(|_| Utc::now()),
+        })
+    }
+
+    fn row_to_pins(row: &Row) -> rusqlite::Result<PinEntry> {
+        let pins: String = row.get(1)?;
+        let expires_at: String = row.get(3)?;
+        let updated_at: String = row.get(4)?;
+        Ok(PinEntry {
+            host: row.get(0)?,
+            pins: pins.split_whitespace().map(str::to_string).collect(),
+            include_subdomains: row.get(2)?,
+            expires_at: NaiveDateTime::parse_from_str(&expires_at, "%Y-%m-%d %H:%M:%S")
+                .map(|date| date.and_utc())
+                .unwrap_or_else(|_| Utc::now()),
+            updated_at: NaiveDateTime::parse_from_str(&updated_at, "%Y-%m-%d %H:%M:%S")
+                .map(|date| date.and_utc())
+                .unwrap_or_else(|_| Utc::now()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use tempfile::TempDir;
+
+    fn entry(host: &str, include_subdomains: bool, expires_in: Duration) -> HstsEntry {
+        let now = NaiveDateTime::parse_from_str(
+            &Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            "%Y-%m-%d %H:%M:%S",
+        )
+        .unwrap()
+        .and_utc();
+        HstsEntry {
+            host: host.to_string(),
+            include_subdomains,
+            expires_at: now + expires_in,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn entries_are_stored_and_replaced() {
+        let dir = TempDir::new().unwrap();
+        let manager = HstsManager::new(dir.path()).unwrap();
+        let first = entry("example.com", false, Duration::days(1));
+        manager.set(&first).unwrap();
+        manager
+            .set(&entry("example.net", true, Duration::days(1)))
+            .unwrap();
+        let replaced = entry("example.com", true, Duration::days(30));
+        manager.set(&replaced).unwrap();
+
+        let entries = HstsManager::new(dir.path()).unwrap().get_all().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], replaced);
+        assert_eq!(entries[1].host, "example.net");
+
+        manager.remove("example.net").unwrap();
+        assert_eq!(manager.get_all().unwrap(), vec![replaced]);
+    }
+
+    #[test]
+    fn expired_entries_are_dropped() {
+        let dir = TempDir::new().unwrap();
+        let manager = HstsManager::new(dir.path()).unwrap();
+        manager
+            .set(&entry("old.example", false, Duration::seconds(-5)))
+            .unwrap();
+        manager
+            .set(&entry("new.example", false, Duration::hours(1)))
+            .unwrap();
+
+        let hosts: Vec<String> = manager
+            .get_all()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.host)
+            .collect();
+        assert_eq!(hosts, vec!["new.example"]);
+    }
+
+    #[test]
+    fn pins_are_stored_replaced_and_expire() {
+        let dir = TempDir::new().unwrap();
+        let manager = HstsManager::new(dir.path()).unwrap();
+        let base = entry("example.com", true, Duration::days(30));
+        let pins = PinEntry {
+            host: base.host,
+            pins: vec!["AAAA".to_string(), "BBBB".to_string()],
+            include_subdomains: true,
+            expires_at: base.expires_at,
+            updated_at: base.updated_at,
+        };
+        manager.set_pins(&pins).unwrap();
+        manager
+            .set_pins(&PinEntry {
+                host: "old.example".to_string(),
+                expires_at: base.updated_at - Duration::seconds(5),
+                ..pins.clone()
+            })
+            .unwrap();
+        assert_eq!(
+            HstsManager::new(dir.path())
+                .unwrap()
+                .get_all_pins()
+                .unwrap(),
+            vec![pins.clone()]
+        );
+
+        let replaced = PinEntry {
+            pins: vec!["CCCC".to_string()],
+            ..pins
+        };
+        manager.set_pins(&replaced).unwrap();
+        assert_eq!(manager.get_all_pins().unwrap(), vec![replaced]);
+        // pins and hsts entries are separate
+        assert!(manager.get_all().unwrap().is_empty());
+
+        manager.remove_pins("example.com").unwrap();
+        assert!(manager.get_all_pins().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+// walks every bookmark and checks whether it still resolves
+// so the manager can flag dead links and ones that moved
+use crate::storage::{Bookmark, BookmarkManager, LinkCheck};
+use anyhow::Result;
+use chrono::Utc;
+use reqwest::{redirect, Client, StatusCode};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+pub struct LinkChecker {
+    client: Client,
+    concurrency: usize,
+}
+
+impl LinkChecker {
+    pub fn new(concurrency: usize, timeout: Duration) -> Result<Self> {
+        let client = Client::builder()
+            .redirect(redirect::Policy::limited(10))
+            .timeout(timeout)
+            .user_agent(concat!("Bagel/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        Ok(Self {
+            client,
+            concurrency: concurrency.max(1),
+        })
+    }
+
+    /// Checks every http(s) bookmark, at most `concurrency` at a time, and records
+    /// the results in the bookmark database.
+    pub async fn check_all(&self, manager: &BookmarkManager) -> Result<Vec<LinkCheck>> {
+        let bookmarks: Vec<Bookmark> = manager
+            .get_all_bookmarks()?
+            .into_iter()
+            .filter(Self::is_checkable)
+            .collect();
+
+        let permits = Arc::new(Semaphore::new(self.concurrency));
+        let mut tasks = JoinSet::new();
+        // which bookmark each task is for, in case it dies without a result
+        let mut pending = HashMap::new();
+
+        for bookmark in bookmarks {
+            let client = self.client.clone();
+            let permits = Arc::clone(&permits);
+            let task = bookmark.clone();
+            let handle = tasks.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                Self::check_bookmark(&client, &task).await
+            });
+            pending.insert(handle.id(), bookmark);
+        }
+
+        let mut checks = Vec::new();
+        while let Some(result) = tasks.join_next().await {
+            let check = match result {
+                Ok(check) => check,
+                // one bad task shouldn't throw away everything checked so far
+                Err(e) => {
+                    let Some(bookmark) = pending.remove(&e.id()) else {
+                        log::error!("Link check task failed: {}", e);
+                        continue;
+                    };
+                    log::error!("Link check for {} failed: {}", bookmark.url, e);
+                    LinkCheck {
+                        bookmark_id: bookmark.id,
+                        url: bookmark.url,
+                        status: None,
+                        final_url: None,
+                        error: Some(format!("Check failed: {}", e)),
+                        checked_at: Utc::now(),
+                    }
+                }
+            };
+            manager.record_link_check(&check)?;
+            checks.push(check);
+        }
+
+        log::info!("Checked {} bookmark links", checks.len());
+        Ok(checks)
+    }
+
+    pub async fn check_bookmark(client: &Client, bookmark: &Bookmark) -> LinkCheck {
+        let (status, final_url, error) = match Self::fetch(client, &bookmark.url).await {
+            Ok((status, final_url)) => (Some(status.as_u16()), Some(final_url), None),
+            Err(e) => (None, None, Some(e.to_string())),
+        };
+
+        LinkCheck {
+            bookmark_id: bookmark.id.clone(),
+            url: bookmark.url.clone(),
+            status,
+            final_url,
+            error,
+            checked_at: Utc::now(),
+        }
+    }
+
+    async fn fetch(client: &Client, url: &str) -> Result<(StatusCode, String), reqwest::Error> {
+        let response = client.head(url).send().await?;
+
+        // plenty of servers don't bother implementing HEAD, ask again properly
+        let response = match response.status() {
+            StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED => {
+                client.get(url).send().await?
+            }
+            _ => response,
+        };
+
+        Ok((response.status(), response.url().to_string()))
+    }
+
+    fn is_checkable(bookmark: &Bookmark) -> bool {
+        // keyword templates like https://github.com/%s aren't real pages
+        (bookmark.url.starts_with("http://") || bookmark.url.starts_with("https://"))
+            && !bookmark.url.contains("%s")
+            && !bookmark.url.contains("%S")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_server::{closed_port, serve_http, Reply};
+    use tempfile::TempDir;
+
+    /// Serves a handful of fixed responses. `/no-head` refuses HEAD like
+    /// some real servers do.
+    async fn serve() -> String {
+        serve_http(|method, path| match (method, path) {
+            (_, "/ok") => Reply::new("200 OK"),
+            (_, "/old") => Reply::new("301 Moved Permanently").header("Location", "/ok"),
+            ("HEAD", "/no-head") => Reply::new("405 Method Not Allowed"),
+            ("GET", "/no-head") => Reply::new("200 OK"),
+            _ => Reply::new("404 Not Found"),
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn checks_bookmarks_against_a_local_server() {
+        let base = serve().await;
+        let dir = TempDir::new().unwrap();
+        let manager = BookmarkManager::new(dir.path()).unwrap();
+        let add = |title: &str, path: &str| {
+            manager
+                .add_bookmark(title, &format!("{}{}", base, path), None)
+                .unwrap()
+                .id
+        };
+        let ok = add("ok", "/ok");
+        let old = add("old", "/old");
+        let missing = add("missing", "/missing");
+        let no_head = add("no-head", "/no-head");
+        add("template", "/search?q=%s");
+
+        let checker = LinkChecker::new(2, Duration::from_secs(5)).unwrap();
+        let checks = checker.check_all(&manager).await.unwrap();
+        assert_eq!(checks.len(), 4);
+
+        let recorded = manager.get_link_checks().unwrap();
+        let check = |id: &str| {
+            recorded
+                .iter()
+                .find(|check| check.bookmark_id == id)
+                .unwrap()
+        };
+
+        assert_eq!(check(&ok).status, Some(200));
+        assert!(!check(&ok).is_broken() && !check(&ok).is_moved());
+
+        assert_eq!(check(&old).status, Some(200));
+        assert_eq!(check(&old).final_url, Some(format!("{}/ok", base)));
+        assert!(check(&old).is_moved());
+
+        assert_eq!(check(&missing).status, Some(404));
+        assert!(check(&missing).is_broken());
+
+        assert_eq!(check(&no_head).status, Some(200));
+        assert!(!check(&no_head).is_broken());
+    }
+
+    #[tokio::test]
+    async fn unreachable_bookmarks_are_broken() {
+        let bookmark = Bookmark {
+            id: "gone".to_string(),
+            title: "Gone".to_string(),
+            url: format!("http://127.0.0.1:{}/", closed_port()),
+            folder_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            favicon: None,
+            description: None,
+            tags: Vec::new(),
+            keyword: None,
+        };
+
+        let checker = LinkChecker::new(1, Duration::from_secs(5)).unwrap();
+        let check = LinkChecker::check_bookmark(&checker.client, &bookmark).await;
+        assert!(check.error.is_some());
+        assert!(check.is_broken());
+    }
+}
@@ -1,7 +1,30 @@
 pub mod bookmarks;
+// nothing records history yet
+#[allow(dead_code)]
 pub mod history;
 pub mod cookies;
+pub mod cookie_crypto;
+pub mod cookie_jar;
+pub mod filter_subscriptions;
+pub mod filter_updater;
+pub mod link_checker;
+pub mod netscape_cookies;
+pub mod privacy_report;
+pub mod site_shields;
+pub mod certificate_exceptions;
+pub mod hsts;
 
 pub use bookmarks::*;
+#[allow(unused_imports)]
 pub use history::*;
-pub use cookies::*;
\ No newline at end of file
+pub use cookies::*;
+pub use cookie_crypto::*;
+pub use cookie_jar::*;
+pub use filter_subscriptions::*;
+pub use filter_updater::*;
+pub use link_checker::*;
+pub use netscape_cookies::*;
+pub use privacy_report::*;
+pub use site_shields::*;
+pub use certificate_exceptions::*;
+pub use hsts::*;
\ No newline at end of file
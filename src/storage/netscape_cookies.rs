@@ -0,0 +1,359 @@
+// netscape cookies.txt import/export
+// the format curl, wget and yt-dlp read and write, one cookie per line:
+// domain, include subdomains, path, secure, expiry, name, value
+use crate::storage::{domain_match, latest_cookie_date, Cookie, CookieManager};
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use std::io::Write;
+use std::path::Path;
+
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CookieImport {
+    pub imported: usize,
+    /// Malformed lines, expired cookies, cookies outside the domain filter and
+    /// cookies for blocked sites.
+    pub skipped: usize,
+}
+
+/// Parses a cookies.txt file. Lines that aren't cookies are skipped, and so are
+/// cookies that would be refused anyway, like expired ones and supercookies.
+pub fn parse_netscape_cookies(contents: &str) -> (Vec<Cookie>, usize) {
+    let mut cookies = Vec::new();
+    let mut skipped = 0;
+
+    for line in contents.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // curl marks HttpOnly cookies by commenting them out with a prefix
+        let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+            Some(rest) => (rest, true),
+            None if line.starts_with('#') => continue,
+            None => (line, false),
+        };
+
+        match parse_netscape_line(line, http_only) {
+            Some(cookie) if !cookie.is_expired() && !cookie.is_supercookie() => {
+                cookies.push(cookie)
+            }
+            _ => skipped += 1,
+        }
+    }
+
+    (cookies, skipped)
+}
+
+fn parse_netscape_line(line: &str, http_only: bool) -> Option<Cookie> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    // the value may be empty, and some writers drop the trailing tab for it
+    if fields.len() < 6 || fields.len() > 7 {
+        return None;
+    }
+
+    let domain = fields[0]
+        .trim()
+        .trim_start_matches('.')
+        .to_ascii_lowercase();
+    let include_subdomains = parse_flag(fields[1])?;
+    let path = fields[2].trim();
+    let secure = parse_flag(fields[3])?;
+    let expiry: i64 = fields[4].trim().parse().ok()?;
+    let name = fields[5].trim();
+    let value = fields.get(6).copied().unwrap_or_default();
+
+    if domain.is_empty() || name.is_empty() || !path.starts_with('/') {
+        return None;
+    }
+
+    // 0 means a session cookie
+    let expires = if expiry == 0 {
+        None
+    } else {
+        Some(
+            Utc.timestamp_opt(expiry, 0)
+                .single()?
+                .min(latest_cookie_date()),
+        )
+    };
+
+    Some(Cookie {
+        name: name.to_string(),
+        value: value.to_string(),
+        domain: if include_subdomains {
+            format!(".{}", domain)
+        } else {
+            domain
+        },
+        path: path.to_string(),
+        expires,
+        secure,
+        http_only,
+        same_site: None,
+        created_at: Utc::now(),
+        partition_key: None,
+    })
+}
+
+fn parse_flag(field: &str) -> Option<bool> {
+    match field.trim().to_ascii_uppercase().as_str() {
+        "TRUE" => Some(true),
+        "FALSE" => Some(false),
+        _ => None,
+    }
+}
+
+/// Writes cookies in cookies.txt format, with the header curl expects.
+pub fn format_netscape_cookies(cookies: &[Cookie]) -> String {
+    let mut out = String::from("# Netscape HTTP Cookie File\n# Exported by Bagel Browser\n\n");
+
+    for cookie in cookies {
+        out.push_str(&format!(
+            "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            if cookie.http_only {
+                HTTP_ONLY_PREFIX
+            } else {
+                ""
+            },
+            cookie.domain,
+            netscape_flag(!cookie.is_host_only()),
+            cookie.path,
+            netscape_flag(cookie.secure),
+            cookie
+                .expires
+                .as_ref()
+                .map_or(0, DateTime::<Utc>::timestamp),
+            cookie.name,
+            cookie.value,
+        ));
+    }
+
+    out
+}
+
+fn netscape_flag(value: bool) -> &'static str {
+    if value {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
+
+/// Whether a cookie belongs to `filter`, a domain like `example.com` that
+/// covers its subdomains too. No filter matches everything.
+fn matches_domain_filter(cookie: &Cookie, filter: Option<&str>) -> bool {
+    match filter.map(|filter| filter.trim().trim_start_matches('.')) {
+        Some(filter) if !filter.is_empty() => {
+            domain_match(cookie.domain.trim_start_matches('.'), filter)
+        }
+        _ => true,
+    }
+}
+
+impl CookieManager {
+    /// Unexpired cookies as a cookies.txt file, optionally only those for
+    /// `domain_filter` and its subdomains. Partitioned cookies are left out
+    /// since the format has nowhere to put the partition.
+    pub fn export_netscape(&self, domain_filter: Option<&str>) -> Result<String> {
+        let cookies: Vec<Cookie> = self
+            .get_all_cookies()?
+            .into_iter()
+            .filter(|cookie| {
+                cookie.partition_key.is_none()
+                    && !cookie.is_expired()
+                    && matches_domain_filter(cookie, domain_filter)
+            })
+            .collect();
+
+        Ok(format_netscape_cookies(&cookies))
+    }
+
+    /// Writes [`Self::export_netscape`] to `path`, readable only by us since
+    /// anyone holding the file is logged in as us.
+    pub fn export_netscape_file(&self, path: &Path, domain_filter: Option<&str>) -> Result<()> {
+        let contents = self.export_netscape(domain_filter)?;
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path)?;
+        // the mode only applies to new files, tighten one we're overwriting too
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Imports cookies from a cookies.txt file, replacing existing cookies with
+    /// the same name, domain and path. Per-site cookie rules still apply.
+    pub fn import_netscape(
+        &self,
+        contents: &str,
+        domain_filter: Option<&str>,
+    ) -> Result<CookieImport> {
+        let (cookies, mut skipped) = parse_netscape_cookies(contents);
+
+        let (cookies, filtered): (Vec<Cookie>, Vec<Cookie>) = cookies
+            .into_iter()
+            .partition(|cookie| matches_domain_filter(cookie, domain_filter));
+        skipped += filtered.len();
+
+        let imported = self.apply_changes(&cookies, &[])?;
+        skipped += cookies.len() - imported;
+
+        log::info!("Imported {} cookies, skipped {}", imported, skipped);
+        Ok(CookieImport { imported, skipped })
+    }
+
+    pub fn import_netscape_file(
+        &self,
+        path: &Path,
+        domain_filter: Option<&str>,
+    ) -> Result<CookieImport> {
+        let contents = std::fs::read_to_string(path)?;
+        self.import_netscape(&contents, domain_filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const COOKIES_TXT: &str = "# Netscape HTTP Cookie File\n\
+        .example.com\tTRUE\t/\tTRUE\t4102444800\tsid\tabc123\n\
+        #HttpOnly_www.example.com\tFALSE\t/app\tFALSE\t0\tsession\tx=y\n\
+        other.org\tFALSE\t/\tFALSE\t4102444800\tpref\t\n\
+        # a comment\n\
+        old.example.com\tFALSE\t/\tFALSE\t1\texpired\t1\n\
+        .com\tTRUE\t/\tFALSE\t4102444800\tsuper\t1\n\
+        not\tenough\tfields\n";
+
+    fn manager(dir: &TempDir) -> CookieManager {
+        CookieManager::new(dir.path(), 0).unwrap()
+    }
+
+    #[test]
+    fn parses_curl_cookie_files() {
+        let (cookies, skipped) = parse_netscape_cookies(COOKIES_TXT);
+        assert_eq!(skipped, 3);
+        assert_eq!(cookies.len(), 3);
+
+        let sid = &cookies[0];
+        assert_eq!(sid.domain, ".example.com");
+        assert!(sid.secure && !sid.http_only);
+        assert_eq!(
+            sid.expires.map(|expires| expires.timestamp()),
+            Some(4102444800)
+        );
+
+        let session = &cookies[1];
+        assert_eq!(session.domain, "www.example.com");
+        assert_eq!(session.path, "/app");
+        assert_eq!(session.value, "x=y");
+        assert!(session.http_only);
+        assert!(session.expires.is_none());
+
+        assert_eq!(cookies[2].value, "");
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let dir = TempDir::new().unwrap();
+        let source = manager(&dir);
+        let import = source.import_netscape(COOKIES_TXT, None).unwrap();
+        assert_eq!(
+            import,
+            CookieImport {
+                imported: 3,
+                skipped: 3
+            }
+        );
+
+        let path = dir.path().join("cookies.txt");
+        source.export_netscape_file(&path, None).unwrap();
+        let exported = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            exported.contains("#HttpOnly_www.example.com\tFALSE\t/app\tFALSE\t0\tsession\tx=y\n")
+        );
+        assert!(exported.contains(".example.com\tTRUE\t/\tTRUE\t4102444800\tsid\tabc123\n"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let other = TempDir::new().unwrap();
+        let target = manager(&other);
+        let import = target.import_netscape_file(&path, None).unwrap();
+        assert_eq!(
+            import,
+            CookieImport {
+                imported: 3,
+                skipped: 0
+            }
+        );
+        assert_eq!(
+            target.export_netscape(None).unwrap(),
+            source.export_netscape(None).unwrap()
+        );
+    }
+
+    #[test]
+    fn domain_filter_covers_subdomains_only() {
+        let dir = TempDir::new().unwrap();
+        let manager = manager(&dir);
+        let import = manager
+            .import_netscape(COOKIES_TXT, Some("example.com"))
+            .unwrap();
+        assert_eq!(
+            import,
+            CookieImport {
+                imported: 2,
+                skipped: 4
+            }
+        );
+
+        manager.import_netscape(COOKIES_TXT, None).unwrap();
+        let exported = manager.export_netscape(Some("other.org")).unwrap();
+        let lines: Vec<&str> = exported
+            .lines()
+            .filter(|line| line.contains('\t'))
+            .collect();
+        assert_eq!(
+            lines,
+            vec!["other.org\tFALSE\t/\tFALSE\t4102444800\tpref\t"]
+        );
+    }
+
+    #[test]
+    fn exporting_over_a_file_tightens_its_permissions() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("cookies.txt");
+        std::fs::write(&path, "stale").unwrap();
+
+        manager(&dir).export_netscape_file(&path, None).unwrap();
+        let exported = std::fs::read_to_string(&path).unwrap();
+        assert!(exported.starts_with("# Netscape HTTP Cookie File"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
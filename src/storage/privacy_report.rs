@@ -0,0 +1,350 @@
+// what the filters have blocked, for the bagel://privacy report
+// only the tracker's domain and the site it was blocked on are kept,
+// not full urls, so this doesn't turn into a second browsing history
+use crate::browser::{BlockedRequest, FilterCategory};
+use crate::utils::site_for_host;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Everything blocked from one tracker domain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackerDomainTotal {
+    pub domain: String,
+    pub category: FilterCategory,
+    pub blocked: u64,
+    /// How many different sites it was blocked on.
+    pub sites: u64,
+    pub last_blocked: DateTime<Utc>,
+}
+
+/// Blocked requests on one day, in UTC.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DailyBlockTotal {
+    pub day: NaiveDate,
+    pub ads: u64,
+    pub tracking: u64,
+    pub custom: u64,
+}
+
+impl DailyBlockTotal {
+    pub fn total(&self) -> u64 {
+        self.ads + self.tracking + self.custom
+    }
+}
+
+pub struct PrivacyReportManager {
+    db_path: PathBuf,
+}
+
+impl PrivacyReportManager {
+    pub fn new(data_dir: &Path) -> Result<Self> {
+        let manager = Self {
+            db_path: data_dir.join("privacy.db"),
+        };
+        manager.init_database()?;
+        Ok(manager)
+    }
+
+    fn init_database(&self) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS blocked_requests (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                domain TEXT NOT NULL,
+                page_site TEXT,
+                request_type TEXT NOT NULL,
+                rule TEXT NOT NULL,
+                list TEXT NOT NULL,
+                category TEXT NOT NULL,
+                blocked_at DATETIME NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_blocked_requests_blocked_at
+             ON blocked_requests(blocked_at)",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    /// Adds a page's worth of blocked requests in one go.
+    pub fn record(&self, blocked: &[BlockedRequest]) -> Result<()> {
+        if blocked.is_empty() {
+            return Ok(());
+        }
+
+        let mut conn = Connection::open(&self.db_path)?;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO blocked_requests
+                 (domain, page_site, request_type, rule, list, category, blocked_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for request in blocked {
+                let page_site = request
+                    .page_url
+                    .as_deref()
+                    .and_then(|page| url::Url::parse(page).ok())
+                    .and_then(|page| page.host_str().map(site_for_host));
+
+                stmt.execute(params![
+                    request.domain(),
+                    page_site,
+                    request.request_type.as_str(),
+                    request.rule,
+                    request.list,
+                    request.category.as_str(),
+                    request.blocked_at.format(DATE_FORMAT).to_string()
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Totals by tracker domain since `since`, or for all time, most blocked first.
+    pub fn domain_totals(&self, since: Option<DateTime<Utc>>) -> Result<Vec<TrackerDomainTotal>> {
+        let conn = Connection::open(&self.db_path)?;
+        let mut stmt = conn.prepare(
+            "SELECT domain, category, COUNT(*), COUNT(DISTINCT page_site), MAX(blocked_at)
+             FROM blocked_requests WHERE blocked_at >= ?1
+             GROUP BY domain, category
+             ORDER BY COUNT(*) DESC, domain",
+        )?;
+
+        let totals = stmt
+            .query_map(params![Self::since_param(since)], |row| {
+                let category: String = row.get(1)?;
+                let last_blocked: String = row.get(4)?;
+                Ok(TrackerDomainTotal {
+                    domain: row.get(0)?,
+                    category: FilterCategory::parse(&category).unwrap_or(FilterCategory::Ads),
+                    blocked: row.get(2)?,
+                    sites: row.get(3)?,
+                    last_blocked: NaiveDateTime::parse_from_str(&last_blocked, DATE_FORMAT)
+                        .map(|date| date.and_utc())
+                        .unwrap_or_else(|_| Utc::now()),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(totals)
+    }
+
+    /// Blocked requests per day since `since`, oldest first. Days with nothing
+    /// blocked are left out.
+    pub fn daily_totals(&self, since: Option<DateTime<Utc>>) -> Result<Vec<DailyBlockTotal>> {
+        let conn = Connection::open(&self.db_path)?;
+        let mut stmt = conn.prepare(
+            "SELECT substr(blocked_at, 1, 10), category, COUNT(*)
+             FROM blocked_requests WHERE blocked_at >= ?1
+             GROUP BY 1, 2 ORDER BY 1",
+        )?;
+
+        let rows = stmt
+            .query_map(params![Self::since_param(since)], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, u64>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut days: Vec<DailyBlockTotal> = Vec::new();
+        for (day, category, count) in rows {
+            let Ok(day) = NaiveDate::parse_from_str(&day, "%Y-%m-%d") else {
+                continue;
+            };
+            if days.last().is_none_or(|last| last.day != day) {
+                days.push(DailyBlockTotal {
+                    day,
+                    ..Default::default()
+                });
+            }
+            let total = days.last_mut().unwrap();
+            match FilterCategory::parse(&category) {
+                Some(FilterCategory::Tracking) => total.tracking += count,
+                Some(FilterCategory::Custom) => total.custom += count,
+                Some(FilterCategory::Ads) | None => total.ads += count,
+            }
+        }
+        Ok(days)
+    }
+
+    /// Forgets everything blocked so far.
+    pub fn clear(&self) -> Result<usize> {
+        let conn = Connection::open(&self.db_path)?;
+        Ok(conn.execute("DELETE FROM blocked_requests", [])?)
+    }
+
+    // dates are stored as text in DATE_FORMAT, so they compare as strings
+    fn since_param(since: Option<DateTime<Utc>>) -> String {
+        since
+            .map(|since| since.format(DATE_FORMAT).to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::{ListKind, RequestType};
+    use chrono::Duration;
+    use tempfile::TempDir;
+
+    fn blocked(
+        url: &str,
+        page: Option<&str>,
+        category: FilterCategory,
+        blocked_at: DateTime<Utc>,
+    ) -> BlockedRequest {
+        BlockedRequest {
+            url: url.to_string(),
+            page_url: page.map(str::to_string),
+            request_type: RequestType::Script,
+            rule: "||tracker^".to_string(),
+            list: "EasyPrivacy".to_string(),
+            kind: ListKind::Filters,
+            category,
+            blocked_at,
+        }
+    }
+
+    fn date(input: &str) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(input, DATE_FORMAT)
+            .unwrap()
+            .and_utc()
+    }
+
+    #[test]
+    fn totals_group_by_tracker_site() {
+        let dir = TempDir::new().unwrap();
+        let report = PrivacyReportManager::new(dir.path()).unwrap();
+        let at = date("2024-05-01 10:00:00");
+        report
+            .record(&[
+                blocked(
+                    "https://a.tracker.com/t.js",
+                    Some("https://news.example/story"),
+                    FilterCategory::Tracking,
+                    at,
+                ),
+                blocked(
+                    "https://b.tracker.com/t.js",
+                    Some("https://www.news.example/"),
+                    FilterCategory::Tracking,
+                    at + Duration::minutes(5),
+                ),
+                blocked(
+                    "https://cdn.tracker.com/t.js",
+                    Some("https://blog.example.co.uk/"),
+                    FilterCategory::Tracking,
+                    at,
+                ),
+                blocked(
+                    "https://ads.example.net/ad.js",
+                    None,
+                    FilterCategory::Ads,
+                    at,
+                ),
+            ])
+            .unwrap();
+
+        let totals = report.domain_totals(None).unwrap();
+        assert_eq!(totals.len(), 2);
+
+        assert_eq!(totals[0].domain, "tracker.com");
+        assert_eq!(totals[0].category, FilterCategory::Tracking);
+        assert_eq!(totals[0].blocked, 3);
+        // news.example and www.news.example are the same site
+        assert_eq!(totals[0].sites, 2);
+        assert_eq!(totals[0].last_blocked, at + Duration::minutes(5));
+
+        assert_eq!(totals[1].domain, "example.net");
+        assert_eq!(totals[1].blocked, 1);
+        assert_eq!(totals[1].sites, 0);
+    }
+
+    #[test]
+    fn daily_totals_split_by_category() {
+        let dir = TempDir::new().unwrap();
+        let report = PrivacyReportManager::new(dir.path()).unwrap();
+        let first = date("2024-05-01 23:59:59");
+        let second = date("2024-05-03 00:00:00");
+        report
+            .record(&[
+                blocked("https://ads.example/", None, FilterCategory::Ads, first),
+                blocked("https://t.example/", None, FilterCategory::Tracking, first),
+                blocked("https://t.example/", None, FilterCategory::Tracking, second),
+                blocked(
+                    "https://mine.example/",
+                    None,
+                    FilterCategory::Custom,
+                    second,
+                ),
+            ])
+            .unwrap();
+
+        let days = report.daily_totals(None).unwrap();
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].day, first.date_naive());
+        assert_eq!((days[0].ads, days[0].tracking, days[0].custom), (1, 1, 0));
+        assert_eq!(days[1].day, second.date_naive());
+        assert_eq!((days[1].ads, days[1].tracking, days[1].custom), (0, 1, 1));
+        assert_eq!(days[1].total(), 2);
+    }
+
+    #[test]
+    fn since_limits_the_report() {
+        let dir = TempDir::new().unwrap();
+        let report = PrivacyReportManager::new(dir.path()).unwrap();
+        let now = Utc::now();
+        report
+            .record(&[
+                blocked(
+                    "https://old.example/",
+                    None,
+                    FilterCategory::Ads,
+                    now - Duration::days(40),
+                ),
+                blocked("https://new.example/", None, FilterCategory::Ads, now),
+            ])
+            .unwrap();
+
+        let since = Some(now - Duration::days(7));
+        let totals = report.domain_totals(since).unwrap();
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals[0].domain, "new.example");
+        assert_eq!(report.daily_totals(since).unwrap().len(), 1);
+
+        assert_eq!(report.domain_totals(None).unwrap().len(), 2);
+        assert_eq!(report.daily_totals(None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn clear_forgets_everything() {
+        let dir = TempDir::new().unwrap();
+        let report = PrivacyReportManager::new(dir.path()).unwrap();
+        report.record(&[]).unwrap();
+        report
+            .record(&[
+                blocked("https://a.example/", None, FilterCategory::Ads, Utc::now()),
+                blocked("https://b.example/", None, FilterCategory::Ads, Utc::now()),
+            ])
+            .unwrap();
+
+        assert_eq!(report.clear().unwrap(), 2);
+        assert!(report.domain_totals(None).unwrap().is_empty());
+        assert!(report.daily_totals(None).unwrap().is_empty());
+    }
+}
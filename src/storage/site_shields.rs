@@ -0,0 +1,253 @@
+// per-site overrides for the privacy shields, kept in shields.db
+// a site only has a row while one of its shields differs from the global
+// privacy settings, so changing those still reaches every other site
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// One of the protections that can be turned off for a site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Shield {
+    AdBlocking,
+    TrackingProtection,
+    HttpsUpgrade,
+}
+
+impl Shield {
+    pub const ALL: [Shield; 3] = [
+        Shield::AdBlocking,
+        Shield::TrackingProtection,
+        Shield::HttpsUpgrade,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Shield::AdBlocking => "Block ads",
+            Shield::TrackingProtection => "Block trackers",
+            Shield::HttpsUpgrade => "Upgrade to HTTPS",
+        }
+    }
+
+    fn column(&self) -> &'static str {
+        match self {
+            Shield::AdBlocking => "ad_blocking",
+            Shield::TrackingProtection => "tracking_protection",
+            Shield::HttpsUpgrade => "https_upgrade",
+        }
+    }
+}
+
+/// A site's overrides. `None` follows the global setting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SiteShields {
+    pub site: String,
+    pub ad_blocking: Option<bool>,
+    pub tracking_protection: Option<bool>,
+    pub https_upgrade: Option<bool>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct SiteShieldManager {
+    db_path: PathBuf,
+}
+
+impl SiteShieldManager {
+    pub fn new(data_dir: &Path) -> Result<Self> {
+        let manager = Self {
+            db_path: data_dir.join("shields.db"),
+        };
+        manager.init_database()?;
+        Ok(manager)
+    }
+
+    fn init_database(&self) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS site_shields (
+                site TEXT PRIMARY KEY,
+                ad_blocking BOOLEAN,
+                tracking_protection BOOLEAN,
+                https_upgrade BOOLEAN,
+                updated_at DATETIME NOT NULL
+            )",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_all(&self) -> Result<Vec<SiteShields>> {
+        let conn = Connection::open(&self.db_path)?;
+        let mut stmt = conn.prepare(
+            "SELECT site, ad_blocking, tracking_protection, https_upgrade, updated_at
+             FROM site_shields ORDER BY site",
+        )?;
+
+        let shields = stmt
+            .query_map([], Self::row_to_shields)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(shields)
+    }
+
+    pub fn get(&self, site: &str) -> Result<Option<SiteShields>> {
+        let conn = Connection::open(&self.db_path)?;
+        let shields = conn
+            .query_row(
+                "SELECT site, ad_blocking, tracking_protection, https_upgrade, updated_at
+                 FROM site_shields WHERE site = ?1",
+                params![site],
+                Self::row_to_shields,
+            )
+            .optional()?;
+        Ok(shields)
+    }
+
+    /// Overrides one shield for `site`, or goes back to the global setting
+    /// with `None`. Returns what the site is left with, `None` once nothing
+    /// is overridden any more.
+    pub fn set(
+        &self,
+        site: &str,
+        shield: Shield,
+        value: Option<bool>,
+    ) -> Result<Option<SiteShields>> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO site_shields (site, updated_at) VALUES (?1, ?2)",
+            params![site, Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()],
+        )?;
+        // the column comes from Shield, never from input
+        conn.execute(
+            &format!(
+                "UPDATE site_shields SET {} = ?1, updated_at = ?2 WHERE site = ?3",
+                shield.column()
+            ),
+            params![
+                value,
+                Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                site
+            ],
+        )?;
+        conn.execute(
+            "DELETE FROM site_shields WHERE site = ?1 AND ad_blocking IS NULL
+             AND tracking_protection IS NULL AND https_upgrade IS NULL",
+            params![site],
+        )?;
+
+        self.get(site)
+    }
+
+    /// Puts every shield for `site` back to the global settings.
+    pub fn remove(&self, site: &str) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute("DELETE FROM site_shields WHERE site = ?1", params![site])?;
+        Ok(())
+    }
+
+    fn row_to_shields(row: &Row) -> rusqlite::Result<SiteShields> {
+        let updated_at: String = row.get(4)?;
+        Ok(SiteShields {
+            site: row.get(0)?,
+            ad_blocking: row.get(1)?,
+            tracking_protection: row.get(2)?,
+            https_upgrade: row.get(3)?,
+            updated_at: NaiveDateTime::parse_from_str(&updated_at, "%Y-%m-%d %H:%M:%S")
+                .map(|date| date.and_utc())
+                .unwrap_or_else(|_| Utc::now()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn overrides_are_kept_per_shield() {
+        let dir = TempDir::new().unwrap();
+        let manager = SiteShieldManager::new(dir.path()).unwrap();
+        assert_eq!(manager.get("example.com").unwrap(), None);
+
+        let shields = manager
+            .set("example.com", Shield::AdBlocking, Some(false))
+            .unwrap()
+            .unwrap();
+        assert_eq!(shields.site, "example.com");
+        assert_eq!(shields.ad_blocking, Some(false));
+        assert_eq!(shields.tracking_protection, None);
+        assert_eq!(shields.https_upgrade, None);
+
+        let shields = manager
+            .set("example.com", Shield::HttpsUpgrade, Some(true))
+            .unwrap()
+            .unwrap();
+        assert_eq!(shields.ad_blocking, Some(false));
+        assert_eq!(shields.https_upgrade, Some(true));
+        assert_eq!(manager.get("example.com").unwrap(), Some(shields));
+        assert_eq!(manager.get("other.example").unwrap(), None);
+    }
+
+    #[test]
+    fn sites_are_dropped_once_nothing_is_overridden() {
+        let dir = TempDir::new().unwrap();
+        let manager = SiteShieldManager::new(dir.path()).unwrap();
+        manager
+            .set("example.com", Shield::AdBlocking, Some(false))
+            .unwrap();
+        manager
+            .set("example.com", Shield::TrackingProtection, Some(false))
+            .unwrap();
+
+        let shields = manager
+            .set("example.com", Shield::AdBlocking, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(shields.ad_blocking, None);
+        assert_eq!(shields.tracking_protection, Some(false));
+
+        assert_eq!(
+            manager
+                .set("example.com", Shield::TrackingProtection, None)
+                .unwrap(),
+            None
+        );
+        assert!(manager.get_all().unwrap().is_empty());
+
+        // going back to the default for a site with no row leaves no row
+        assert_eq!(
+            manager
+                .set("new.example", Shield::HttpsUpgrade, None)
+                .unwrap(),
+            None
+        );
+        assert!(manager.get_all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn remove_resets_a_site_and_survives_reopening() {
+        let dir = TempDir::new().unwrap();
+        let manager = SiteShieldManager::new(dir.path()).unwrap();
+        for site in ["b.example", "a.example"] {
+            manager.set(site, Shield::AdBlocking, Some(false)).unwrap();
+        }
+
+        let manager = SiteShieldManager::new(dir.path()).unwrap();
+        let sites: Vec<String> = manager
+            .get_all()
+            .unwrap()
+            .into_iter()
+            .map(|shields| shields.site)
+            .collect();
+        assert_eq!(sites, vec!["a.example", "b.example"]);
+
+        manager.remove("a.example").unwrap();
+        manager.remove("missing.example").unwrap();
+        assert_eq!(manager.get("a.example").unwrap(), None);
+        assert!(manager.get("b.example").unwrap().is_some());
+    }
+}
@@ -0,0 +1,1366 @@
+// bookmark ui: the add bookmark dialog, the bookmarks bar under the
+// address bar and the bagel://bookmarks manager page
+use crate::storage::{Bookmark, BookmarkFolder, DuplicateGroup, LinkCheck, LinkChecker, TagCount};
+use crate::ui::{BagelApp, Message};
+use crate::utils::log_error;
+use iced::{
+    widget::{button, container, mouse_area, pick_list, scrollable, text, text_input, Column, Row},
+    Application, Background, Color, Command, Element, Font, Length,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::time::Duration;
+
+/// A folder as shown in a folder picker, indented by its depth in the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FolderOption {
+    pub id: String,
+    pub label: String,
+}
+
+impl fmt::Display for FolderOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BookmarkDialog {
+    pub title: String,
+    pub url: String,
+    pub folder: Option<FolderOption>,
+    pub tags: String,
+    pub existing: Vec<Bookmark>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BookmarkEditor {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub folder: Option<FolderOption>,
+    pub tags: String,
+    pub description: String,
+    pub keyword: String,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BookmarksPage {
+    pub search: String,
+    pub search_results: Vec<Bookmark>,
+    pub expanded: HashSet<String>,
+    pub editor: Option<BookmarkEditor>,
+    pub dragging: Option<String>,
+    pub new_folder_name: String,
+    /// The folder being renamed, by id, and its new name so far.
+    pub renaming_folder: Option<(String, String)>,
+    pub tags: Vec<TagCount>,
+    pub renaming_tag: Option<(String, String)>,
+    pub link_checks: Vec<LinkCheck>,
+    pub checking_links: bool,
+    pub duplicates: Option<Vec<DuplicateGroup>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum BookmarkMessage {
+    DialogTitleChanged(String),
+    DialogFolderSelected(FolderOption),
+    DialogTagsChanged(String),
+    SaveDialog,
+    CancelDialog,
+    Open(String),
+    OpenFolder(String),
+    SearchChanged(String),
+    ToggleFolder(String),
+    Edit(String),
+    EditorTitleChanged(String),
+    EditorUrlChanged(String),
+    EditorFolderSelected(FolderOption),
+    EditorTagsChanged(String),
+    EditorDescriptionChanged(String),
+    EditorKeywordChanged(String),
+    SaveEdit,
+    CancelEdit,
+    Delete(String),
+    StartDrag(String),
+    DropOnFolder(String),
+    CancelDrag,
+    NewFolderNameChanged(String),
+    CreateFolder,
+    DeleteFolder(String),
+    StartRenameFolder(String),
+    RenameFolderChanged(String),
+    SaveRenameFolder,
+    CancelRenameFolder,
+    FilterByTag(String),
+    StartRenameTag(String),
+    RenameTagChanged(String),
+    SaveRenameTag,
+    CancelRenameTag,
+    CheckLinks,
+    LinkCheckFinished(Result<Vec<LinkCheck>, String>),
+    ApplyRedirect(String),
+    DismissLinkCheck(String),
+    FindDuplicates,
+    MergeDuplicates(Vec<String>),
+    CloseDuplicates,
+}
+
+fn parse_tags(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+impl BagelApp {
+    /// Reloads the cached bookmark tree from the database.
+    /// The view reads from the cache so we don't hit sqlite every frame.
+    pub fn refresh_bookmarks(&mut self) {
+        let Some(manager) = &self.bookmark_manager else {
+            return;
+        };
+
+        match manager.get_folders() {
+            Ok(folders) => self.bookmark_folders = folders,
+            Err(e) => log_error("Failed to load bookmark folders", e.as_ref()),
+        }
+
+        match manager.get_all_bookmarks() {
+            Ok(bookmarks) => self.bookmarks = bookmarks,
+            Err(e) => log_error("Failed to load bookmarks", e.as_ref()),
+        }
+
+        match manager.get_tags_with_counts() {
+            Ok(tags) => self.bookmarks_page.tags = tags,
+            Err(e) => log_error("Failed to load bookmark tags", e.as_ref()),
+        }
+
+        match manager.get_link_checks() {
+            Ok(checks) => self.bookmarks_page.link_checks = checks,
+            Err(e) => log_error("Failed to load link checks", e.as_ref()),
+        }
+
+        self.refresh_bookmark_search();
+    }
+
+    fn refresh_bookmark_search(&mut self) {
+        let query = self.bookmarks_page.search.trim();
+        if query.is_empty() {
+            self.bookmarks_page.search_results.clear();
+            return;
+        }
+
+        if let Some(manager) = &self.bookmark_manager {
+            match manager.search_bookmarks(query) {
+                Ok(results) => self.bookmarks_page.search_results = results,
+                Err(e) => log_error("Bookmark search failed", e.as_ref()),
+            }
+        }
+    }
+
+    /// Folders in tree order with their labels indented by depth.
+    /// The root folder itself is left out since everything lives under it.
+    pub fn folder_options(&self) -> Vec<FolderOption> {
+        let mut options = Vec::new();
+        self.collect_folder_options("root", 0, &mut options);
+        options
+    }
+
+    fn collect_folder_options(
+        &self,
+        parent_id: &str,
+        depth: usize,
+        options: &mut Vec<FolderOption>,
+    ) {
+        for folder in self.child_folders(parent_id) {
+            options.push(FolderOption {
+                id: folder.id.clone(),
+                label: format!("{}{}", "  ".repeat(depth), folder.name),
+            });
+            self.collect_folder_options(&folder.id, depth + 1, options);
+        }
+    }
+
+    fn folder_option(&self, folder_id: Option<&str>) -> Option<FolderOption> {
+        let folder_id = folder_id.unwrap_or("toolbar");
+        self.folder_options()
+            .into_iter()
+            .find(|option| option.id == folder_id)
+    }
+
+    fn child_folders(&self, parent_id: &str) -> Vec<&BookmarkFolder> {
+        self.bookmark_folders
+            .iter()
+            .filter(|folder| folder.parent_id.as_deref() == Some(parent_id))
+            .collect()
+    }
+
+    fn bookmarks_in(&self, folder_id: &str) -> Vec<&Bookmark> {
+        self.bookmarks
+            .iter()
+            .filter(|bookmark| bookmark.folder_id.as_deref() == Some(folder_id))
+            .collect()
+    }
+
+    pub fn open_bookmark_dialog(&mut self) {
+        let Some(tab) = self.tabs.get(self.active_tab_index) else {
+            return;
+        };
+
+        let existing = match &self.bookmark_manager {
+            Some(manager) => manager.find_bookmarks_by_url(&tab.url).unwrap_or_else(|e| {
+                log_error("Failed to look up existing bookmarks", e.as_ref());
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+
+        self.bookmark_dialog = Some(BookmarkDialog {
+            title: tab.title.clone(),
+            url: tab.url.clone(),
+            folder: self.folder_option(Some("toolbar")),
+            tags: String::new(),
+            existing,
+        });
+    }
+
+    pub fn update_bookmarks(&mut self, message: BookmarkMessage) -> Command<Message> {
+        match message {
+            BookmarkMessage::DialogTitleChanged(title) => {
+                if let Some(dialog) = &mut self.bookmark_dialog {
+                    dialog.title = title;
+                }
+            }
+            BookmarkMessage::DialogFolderSelected(folder) => {
+                if let Some(dialog) = &mut self.bookmark_dialog {
+                    dialog.folder = Some(folder);
+                }
+            }
+            BookmarkMessage::DialogTagsChanged(tags) => {
+                if let Some(dialog) = &mut self.bookmark_dialog {
+                    dialog.tags = tags;
+                }
+            }
+            BookmarkMessage::SaveDialog => {
+                if let (Some(dialog), Some(manager)) =
+                    (self.bookmark_dialog.take(), &self.bookmark_manager)
+                {
+                    let folder_id = dialog.folder.map(|folder| folder.id);
+                    let result = manager
+                        .add_bookmark(&dialog.title, &dialog.url, folder_id)
+                        .and_then(|added| {
+                            manager.set_bookmark_tags(&added.id, &parse_tags(&dialog.tags))?;
+                            Ok(added)
+                        });
+
+                    let mut new_duplicate = false;
+                    match result {
+                        Ok(added) => new_duplicate = !added.duplicates.is_empty(),
+                        Err(e) => log_error("Failed to add bookmark", e.as_ref()),
+                    }
+                    self.refresh_bookmarks();
+
+                    // keep an open duplicates list in step with the new bookmark
+                    if new_duplicate && self.bookmarks_page.duplicates.is_some() {
+                        return self.update_bookmarks(BookmarkMessage::FindDuplicates);
+                    }
+                }
+            }
+            BookmarkMessage::CancelDialog => {
+                self.bookmark_dialog = None;
+            }
+            BookmarkMessage::Open(url) => {
+                return self.update(Message::NavigateTo(url));
+            }
+            BookmarkMessage::OpenFolder(folder_id) => {
+                self.bookmarks_page.expanded.insert(folder_id);
+                return self.update(Message::NavigateTo("bagel://bookmarks".to_string()));
+            }
+            BookmarkMessage::SearchChanged(query) => {
+                self.bookmarks_page.search = query;
+                self.refresh_bookmark_search();
+            }
+            BookmarkMessage::ToggleFolder(folder_id) => {
+                if !self.bookmarks_page.expanded.remove(&folder_id) {
+                    self.bookmarks_page.expanded.insert(folder_id);
+                }
+            }
+            BookmarkMessage::Edit(id) => {
+                let bookmark = self.bookmarks.iter().find(|bookmark| bookmark.id == id);
+                if let Some(bookmark) = bookmark {
+                    self.bookmarks_page.editor = Some(BookmarkEditor {
+                        id: bookmark.id.clone(),
+                        title: bookmark.title.clone(),
+                        url: bookmark.url.clone(),
+                        folder: self.folder_option(bookmark.folder_id.as_deref()),
+                        tags: bookmark.tags.join(", "),
+                        description: bookmark.description.clone().unwrap_or_default(),
+                        keyword: bookmark.keyword.clone().unwrap_or_default(),
+                        error: None,
+                    });
+                }
+            }
+            BookmarkMessage::EditorTitleChanged(title) => {
+                if let Some(editor) = &mut self.bookmarks_page.editor {
+                    editor.title = title;
+                }
+            }
+            BookmarkMessage::EditorUrlChanged(url) => {
+                if let Some(editor) = &mut self.bookmarks_page.editor {
+                    editor.url = url;
+                }
+            }
+            BookmarkMessage::EditorFolderSelected(folder) => {
+                if let Some(editor) = &mut self.bookmarks_page.editor {
+                    editor.folder = Some(folder);
+                }
+            }
+            BookmarkMessage::EditorTagsChanged(tags) => {
+                if let Some(editor) = &mut self.bookmarks_page.editor {
+                    editor.tags = tags;
+                }
+            }
+            BookmarkMessage::EditorDescriptionChanged(description) => {
+                if let Some(editor) = &mut self.bookmarks_page.editor {
+                    editor.description = description;
+                }
+            }
+            BookmarkMessage::EditorKeywordChanged(keyword) => {
+                if let Some(editor) = &mut self.bookmarks_page.editor {
+                    editor.keyword = keyword;
+                }
+            }
+            BookmarkMessage::SaveEdit => {
+                if let (Some(mut editor), Some(manager)) =
+                    (self.bookmarks_page.editor.take(), &self.bookmark_manager)
+                {
+                    // keywords can clash with another bookmark, so check that first
+                    // and keep the editor open to show why
+                    if let Err(e) = manager.set_bookmark_keyword(&editor.id, Some(&editor.keyword))
+                    {
+                        editor.error = Some(e.to_string());
+                        self.bookmarks_page.editor = Some(editor);
+                        return Command::none();
+                    }
+
+                    let description = editor.description.trim();
+                    let folder_id = editor.folder.as_ref().map(|folder| folder.id.as_str());
+                    let result = manager
+                        .update_bookmark(
+                            &editor.id,
+                            Some(&editor.title),
+                            Some(&editor.url),
+                            folder_id,
+                        )
+                        .and_then(|_| {
+                            manager.set_bookmark_description(
+                                &editor.id,
+                                (!description.is_empty()).then_some(description),
+                            )
+                        })
+                        .and_then(|_| {
+                            manager.set_bookmark_tags(&editor.id, &parse_tags(&editor.tags))
+                        });
+
+                    if let Err(e) = result {
+                        log_error("Failed to save bookmark", e.as_ref());
+                    }
+                    self.refresh_bookmarks();
+                }
+            }
+            BookmarkMessage::CancelEdit => {
+                self.bookmarks_page.editor = None;
+            }
+            BookmarkMessage::Delete(id) => {
+                if let Some(manager) = &self.bookmark_manager {
+                    if let Err(e) = manager.delete_bookmark(&id) {
+                        log_error("Failed to delete bookmark", e.as_ref());
+                    }
+                }
+                if self.bookmarks_page.editor.as_ref().map(|editor| &editor.id) == Some(&id) {
+                    self.bookmarks_page.editor = None;
+                }
+                self.refresh_bookmarks();
+            }
+            BookmarkMessage::StartDrag(id) => {
+                self.bookmarks_page.dragging = Some(id);
+            }
+            BookmarkMessage::DropOnFolder(folder_id) => {
+                if let (Some(id), Some(manager)) =
+                    (self.bookmarks_page.dragging.take(), &self.bookmark_manager)
+                {
+                    if let Err(e) = manager.update_bookmark(&id, None, None, Some(&folder_id)) {
+                        log_error("Failed to move bookmark", e.as_ref());
+                    }
+                    self.bookmarks_page.expanded.insert(folder_id);
+                    self.refresh_bookmarks();
+                }
+            }
+            BookmarkMessage::CancelDrag => {
+                self.bookmarks_page.dragging = None;
+            }
+            BookmarkMessage::NewFolderNameChanged(name) => {
+                self.bookmarks_page.new_folder_name = name;
+            }
+            BookmarkMessage::CreateFolder => {
+                let name = self.bookmarks_page.new_folder_name.trim().to_string();
+                if let (false, Some(manager)) = (name.is_empty(), &self.bookmark_manager) {
+                    if let Err(e) = manager.create_folder(&name, None) {
+                        log_error("Failed to create folder", e.as_ref());
+                    }
+                    self.bookmarks_page.new_folder_name.clear();
+                    self.refresh_bookmarks();
+                }
+            }
+            BookmarkMessage::DeleteFolder(folder_id) => {
+                if let Some(manager) = &self.bookmark_manager {
+                    if let Err(e) = manager.delete_folder(&folder_id) {
+                        log_error("Failed to delete folder", e.as_ref());
+                    }
+                }
+                self.refresh_bookmarks();
+            }
+            BookmarkMessage::StartRenameFolder(folder_id) => {
+                let name = self
+                    .bookmark_folders
+                    .iter()
+                    .find(|folder| folder.id == folder_id)
+                    .map(|folder| folder.name.clone())
+                    .unwrap_or_default();
+                self.bookmarks_page.renaming_folder = Some((folder_id, name));
+            }
+            BookmarkMessage::RenameFolderChanged(value) => {
+                if let Some((_, name)) = &mut self.bookmarks_page.renaming_folder {
+                    *name = value;
+                }
+            }
+            BookmarkMessage::SaveRenameFolder => {
+                if let (Some((folder_id, name)), Some(manager)) = (
+                    self.bookmarks_page.renaming_folder.take(),
+                    &self.bookmark_manager,
+                ) {
+                    let name = name.trim();
+                    if !name.is_empty() {
+                        if let Err(e) = manager.rename_folder(&folder_id, name) {
+                            log_error("Failed to rename folder", e.as_ref());
+                        }
+                    }
+                    self.refresh_bookmarks();
+                }
+            }
+            BookmarkMessage::CancelRenameFolder => {
+                self.bookmarks_page.renaming_folder = None;
+            }
+            BookmarkMessage::FilterByTag(tag) => {
+                self.bookmarks_page.search = format!("tag:{}", tag);
+                self.refresh_bookmark_search();
+            }
+            BookmarkMessage::StartRenameTag(tag) => {
+                self.bookmarks_page.renaming_tag = Some((tag.clone(), tag));
+            }
+            BookmarkMessage::RenameTagChanged(value) => {
+                if let Some((_, new_tag)) = &mut self.bookmarks_page.renaming_tag {
+                    *new_tag = value;
+                }
+            }
+            BookmarkMessage::SaveRenameTag => {
+                // renaming onto an existing tag merges the two
+                if let (Some((old_tag, new_tag)), Some(manager)) = (
+                    self.bookmarks_page.renaming_tag.take(),
+                    &self.bookmark_manager,
+                ) {
+                    if let Err(e) = manager.rename_tag(&old_tag, &new_tag) {
+                        log_error("Failed to rename tag", e.as_ref());
+                    }
+                    self.refresh_bookmarks();
+                }
+            }
+            BookmarkMessage::CancelRenameTag => {
+                self.bookmarks_page.renaming_tag = None;
+            }
+            BookmarkMessage::CheckLinks => {
+                if let (false, Some(manager)) = (
+                    self.bookmarks_page.checking_links,
+                    self.bookmark_manager.clone(),
+                ) {
+                    self.bookmarks_page.checking_links = true;
+                    return Command::perform(
+                        async move {
+                            let checker = LinkChecker::new(8, Duration::from_secs(15))?;
+                            checker.check_all(&manager).await
+                        },
+                        |result| {
+                            Message::Bookmarks(BookmarkMessage::LinkCheckFinished(
+                                result.map_err(|e| e.to_string()),
+                            ))
+                        },
+                    );
+                }
+            }
+            BookmarkMessage::LinkCheckFinished(result) => {
+                self.bookmarks_page.checking_links = false;
+                if let Err(e) = result {
+                    log::error!("Bookmark link check failed: {}", e);
+                }
+                self.refresh_bookmarks();
+            }
+            BookmarkMessage::ApplyRedirect(bookmark_id) => {
+                let final_url = self
+                    .bookmarks_page
+                    .link_checks
+                    .iter()
+                    .find(|check| check.bookmark_id == bookmark_id)
+                    .and_then(|check| check.final_url.clone());
+
+                if let (Some(final_url), Some(manager)) = (final_url, &self.bookmark_manager) {
+                    let result = manager
+                        .update_bookmark(&bookmark_id, None, Some(&final_url), None)
+                        .and_then(|_| manager.clear_link_check(&bookmark_id));
+                    if let Err(e) = result {
+                        log_error("Failed to update moved bookmark", e.as_ref());
+                    }
+                    self.refresh_bookmarks();
+                }
+            }
+            BookmarkMessage::FindDuplicates => {
+                if let Some(manager) = &self.bookmark_manager {
+                    match manager.find_duplicates() {
+                        Ok(groups) => self.bookmarks_page.duplicates = Some(groups),
+                        Err(e) => log_error("Failed to find duplicate bookmarks", e.as_ref()),
+                    }
+                }
+            }
+            BookmarkMessage::MergeDuplicates(ids) => {
+                // the first bookmark in a group is the oldest and survives the merge
+                if let (Some((survivor, others)), Some(manager)) =
+                    (ids.split_first(), &self.bookmark_manager)
+                {
+                    if let Err(e) = manager.merge_bookmarks(survivor, others) {
+                        log_error("Failed to merge bookmarks", e.as_ref());
+                    }
+                    self.refresh_bookmarks();
+                    return self.update_bookmarks(BookmarkMessage::FindDuplicates);
+                }
+            }
+            BookmarkMessage::CloseDuplicates => {
+                self.bookmarks_page.duplicates = None;
+            }
+            BookmarkMessage::DismissLinkCheck(bookmark_id) => {
+                if let Some(manager) = &self.bookmark_manager {
+                    if let Err(e) = manager.clear_link_check(&bookmark_id) {
+                        log_error("Failed to dismiss link check", e.as_ref());
+                    }
+                }
+                self.refresh_bookmarks();
+            }
+        }
+        Command::none()
+    }
+
+    pub fn create_bookmark_dialog<'a>(
+        &'a self,
+        dialog: &'a BookmarkDialog,
+    ) -> Element<'a, Message> {
+        let fields = Row::new()
+            .push(
+                text("Add bookmark")
+                    .size(14)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.9, 0.9, 0.9)),
+            )
+            .push(
+                text_input("Title", &dialog.title)
+                    .on_input(|title| {
+                        Message::Bookmarks(BookmarkMessage::DialogTitleChanged(title))
+                    })
+                    .padding(6)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu"))
+                    .width(Length::FillPortion(3)),
+            )
+            .push(
+                pick_list(self.folder_options(), dialog.folder.clone(), |folder| {
+                    Message::Bookmarks(BookmarkMessage::DialogFolderSelected(folder))
+                })
+                .placeholder("Folder")
+                .padding(6)
+                .text_size(13)
+                .font(Font::with_name("Ubuntu"))
+                .width(Length::FillPortion(2)),
+            )
+            .push(
+                text_input("Tags, comma separated", &dialog.tags)
+                    .on_input(|tags| Message::Bookmarks(BookmarkMessage::DialogTagsChanged(tags)))
+                    .on_submit(Message::Bookmarks(BookmarkMessage::SaveDialog))
+                    .padding(6)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu"))
+                    .width(Length::FillPortion(2)),
+            )
+            .push(
+                button(text("Save").font(Font::with_name("Ubuntu")).size(13))
+                    .on_press(Message::Bookmarks(BookmarkMessage::SaveDialog))
+                    .padding([6, 12])
+                    .style(iced::theme::Button::Primary),
+            )
+            .push(
+                button(text("Cancel").font(Font::with_name("Ubuntu")).size(13))
+                    .on_press(Message::Bookmarks(BookmarkMessage::CancelDialog))
+                    .padding([6, 12])
+                    .style(iced::theme::Button::Secondary),
+            )
+            .spacing(8)
+            .align_items(iced::Alignment::Center);
+
+        let mut dialog_content = Column::new().push(fields).spacing(4);
+
+        if !dialog.existing.is_empty() {
+            let folders = dialog
+                .existing
+                .iter()
+                .filter_map(|bookmark| {
+                    let folder_id = bookmark.folder_id.as_deref()?;
+                    self.bookmark_folders
+                        .iter()
+                        .find(|folder| folder.id == folder_id)
+                        .map(|folder| folder.name.clone())
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            dialog_content = dialog_content.push(
+                text(format!("Already bookmarked in {}", folders))
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.95, 0.8, 0.4)),
+            );
+        }
+
+        container(dialog_content)
+            .width(Length::Fill)
+            .padding(8)
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.18, 0.18, 0.22))),
+                border: iced::Border {
+                    radius: 0.0.into(),
+                    width: 1.0,
+                    color: Color::from_rgb(0.25, 0.25, 0.3),
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+
+    pub fn create_bookmarks_bar(&self) -> Element<'_, Message> {
+        let mut bar = Row::new().spacing(4).align_items(iced::Alignment::Center);
+
+        for folder in self.child_folders("toolbar") {
+            bar = bar.push(
+                button(
+                    text(format!("▸ {}", folder.name))
+                        .font(Font::with_name("Ubuntu"))
+                        .size(12),
+                )
+                .on_press(Message::Bookmarks(BookmarkMessage::OpenFolder(
+                    folder.id.clone(),
+                )))
+                .padding([4, 8])
+                .style(iced::theme::Button::Text),
+            );
+        }
+
+        let bookmarks = self.bookmarks_in("toolbar");
+        for bookmark in &bookmarks {
+            let title = if bookmark.title.chars().count() > 20 {
+                format!("{}...", bookmark.title.chars().take(20).collect::<String>())
+            } else {
+                bookmark.title.clone()
+            };
+
+            bar = bar.push(
+                button(text(title).font(Font::with_name("Ubuntu")).size(12))
+                    .on_press(Message::Bookmarks(BookmarkMessage::Open(
+                        bookmark.url.clone(),
+                    )))
+                    .padding([4, 8])
+                    .style(iced::theme::Button::Text),
+            );
+        }
+
+        if bookmarks.is_empty() && self.child_folders("toolbar").is_empty() {
+            bar = bar.push(
+                text("Bookmarks in the toolbar folder show up here")
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.6, 0.6, 0.6)),
+            );
+        }
+
+        container(bar)
+            .width(Length::Fill)
+            .padding([2, 8])
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.15, 0.15, 0.18))),
+                border: iced::Border {
+                    radius: 0.0.into(),
+                    width: 1.0,
+                    color: Color::from_rgb(0.25, 0.25, 0.3),
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+
+    pub fn create_bookmarks_page(&self) -> Element<'_, Message> {
+        let page = &self.bookmarks_page;
+
+        let header = Row::new()
+            .push(
+                text("Bookmarks")
+                    .size(22)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.9, 0.9, 0.9)),
+            )
+            .push(
+                text_input("Search bookmarks...", &page.search)
+                    .on_input(|query| Message::Bookmarks(BookmarkMessage::SearchChanged(query)))
+                    .padding(6)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu"))
+                    .width(Length::Fill),
+            )
+            .push(
+                text_input("New folder", &page.new_folder_name)
+                    .on_input(|name| {
+                        Message::Bookmarks(BookmarkMessage::NewFolderNameChanged(name))
+                    })
+                    .on_submit(Message::Bookmarks(BookmarkMessage::CreateFolder))
+                    .padding(6)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu"))
+                    .width(Length::Fixed(180.0)),
+            )
+            .push(
+                button(text("Add folder").font(Font::with_name("Ubuntu")).size(13))
+                    .on_press(Message::Bookmarks(BookmarkMessage::CreateFolder))
+                    .padding([6, 12])
+                    .style(iced::theme::Button::Secondary),
+            )
+            .spacing(12)
+            .align_items(iced::Alignment::Center);
+
+        let listing = if page.search.trim().is_empty() {
+            let mut tree = Column::new().spacing(2);
+            for folder in self.child_folders("root") {
+                tree = self.push_folder_tree(tree, folder, 0);
+            }
+            tree
+        } else if page.search_results.is_empty() {
+            Column::new().push(
+                text("No bookmarks match your search")
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.6, 0.6, 0.6)),
+            )
+        } else {
+            page.search_results
+                .iter()
+                .fold(Column::new().spacing(2), |column, bookmark| {
+                    column.push(self.create_bookmark_row(bookmark, 0))
+                })
+        };
+
+        let mut body = Row::new()
+            .push(self.create_tag_list())
+            .push(scrollable(listing).width(Length::FillPortion(3)))
+            .spacing(16);
+
+        if let Some(editor) = &page.editor {
+            body = body.push(self.create_bookmark_editor(editor));
+        }
+
+        let content = Column::new()
+            .push(header)
+            .push(self.create_link_health())
+            .push(self.create_duplicates())
+            .push(body)
+            .spacing(16)
+            .padding(24)
+            .width(Length::Fill)
+            .height(Length::Fill);
+
+        // releasing a drag anywhere that isn't a folder drops it on the floor
+        let page_area =
+            mouse_area(content).on_release(Message::Bookmarks(BookmarkMessage::CancelDrag));
+
+        container(page_area)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.12, 0.12, 0.15))),
+                border: iced::Border {
+                    radius: 0.0.into(),
+                    width: 1.0,
+                    color: Color::from_rgb(0.25, 0.25, 0.3),
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+
+    fn create_link_health(&self) -> Element<'_, Message> {
+        let page = &self.bookmarks_page;
+
+        let check_button = button(
+            text(if page.checking_links {
+                "Checking links..."
+            } else {
+                "Check links"
+            })
+            .font(Font::with_name("Ubuntu"))
+            .size(13),
+        )
+        .on_press_maybe(
+            (!page.checking_links).then_some(Message::Bookmarks(BookmarkMessage::CheckLinks)),
+        )
+        .padding([6, 12])
+        .style(iced::theme::Button::Secondary);
+
+        let problems: Vec<&LinkCheck> = page
+            .link_checks
+            .iter()
+            .filter(|check| check.is_broken() || check.is_moved())
+            .collect();
+
+        let summary = if page.link_checks.is_empty() {
+            "Links haven't been checked yet".to_string()
+        } else {
+            format!(
+                "{} checked, {} broken, {} moved",
+                page.link_checks.len(),
+                problems.iter().filter(|check| check.is_broken()).count(),
+                problems.iter().filter(|check| check.is_moved()).count(),
+            )
+        };
+
+        let mut panel = Column::new().spacing(4).push(
+            Row::new()
+                .push(check_button)
+                .push(
+                    text(summary)
+                        .size(12)
+                        .font(Font::with_name("Ubuntu"))
+                        .style(Color::from_rgb(0.6, 0.6, 0.6)),
+                )
+                .spacing(12)
+                .align_items(iced::Alignment::Center),
+        );
+
+        for check in problems {
+            let title = self
+                .bookmarks
+                .iter()
+                .find(|bookmark| bookmark.id == check.bookmark_id)
+                .map_or(check.url.as_str(), |bookmark| bookmark.title.as_str());
+
+            let (status, fix) = if check.is_broken() {
+                let status = match (&check.error, check.status) {
+                    (Some(error), _) => format!("Unreachable: {}", error),
+                    (None, Some(status)) => format!("HTTP {}", status),
+                    (None, None) => "Unreachable".to_string(),
+                };
+                let fix = button(text("Delete").font(Font::with_name("Ubuntu")).size(11)).on_press(
+                    Message::Bookmarks(BookmarkMessage::Delete(check.bookmark_id.clone())),
+                );
+                (status, fix)
+            } else {
+                let final_url = check.final_url.clone().unwrap_or_default();
+                let status = format!("Moved to {}", final_url);
+                let fix = button(text("Update URL").font(Font::with_name("Ubuntu")).size(11))
+                    .on_press(Message::Bookmarks(BookmarkMessage::ApplyRedirect(
+                        check.bookmark_id.clone(),
+                    )));
+                (status, fix)
+            };
+
+            panel = panel.push(
+                Row::new()
+                    .push(
+                        text(title)
+                            .size(12)
+                            .font(Font::with_name("Ubuntu"))
+                            .style(Color::from_rgb(0.9, 0.9, 0.9))
+                            .width(Length::FillPortion(1)),
+                    )
+                    .push(
+                        text(status)
+                            .size(12)
+                            .font(Font::with_name("Ubuntu"))
+                            .style(if check.is_broken() {
+                                Color::from_rgb(0.95, 0.45, 0.45)
+                            } else {
+                                Color::from_rgb(0.95, 0.8, 0.4)
+                            })
+                            .width(Length::FillPortion(2)),
+                    )
+                    .push(fix.padding([2, 8]).style(iced::theme::Button::Text))
+                    .push(
+                        button(text("Dismiss").font(Font::with_name("Ubuntu")).size(11))
+                            .on_press(Message::Bookmarks(BookmarkMessage::DismissLinkCheck(
+                                check.bookmark_id.clone(),
+                            )))
+                            .padding([2, 8])
+                            .style(iced::theme::Button::Text),
+                    )
+                    .spacing(8)
+                    .align_items(iced::Alignment::Center),
+            );
+        }
+
+        panel.into()
+    }
+
+    fn create_duplicates(&self) -> Element<'_, Message> {
+        let Some(groups) = &self.bookmarks_page.duplicates else {
+            return button(
+                text("Find duplicates")
+                    .font(Font::with_name("Ubuntu"))
+                    .size(13),
+            )
+            .on_press(Message::Bookmarks(BookmarkMessage::FindDuplicates))
+            .padding([6, 12])
+            .style(iced::theme::Button::Secondary)
+            .into();
+        };
+
+        let mut panel = Column::new().spacing(4).push(
+            Row::new()
+                .push(
+                    text(format!("{} duplicated pages", groups.len()))
+                        .size(13)
+                        .font(Font::with_name("Ubuntu"))
+                        .style(Color::from_rgb(0.8, 0.8, 0.8)),
+                )
+                .push(
+                    button(text("Close").font(Font::with_name("Ubuntu")).size(11))
+                        .on_press(Message::Bookmarks(BookmarkMessage::CloseDuplicates))
+                        .padding([2, 8])
+                        .style(iced::theme::Button::Text),
+                )
+                .spacing(12)
+                .align_items(iced::Alignment::Center),
+        );
+
+        for group in groups {
+            let folders = group
+                .bookmarks
+                .iter()
+                .map(|bookmark| {
+                    let folder_id = bookmark.folder_id.as_deref().unwrap_or("other");
+                    self.bookmark_folders
+                        .iter()
+                        .find(|folder| folder.id == folder_id)
+                        .map_or(folder_id, |folder| folder.name.as_str())
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            let ids = group
+                .bookmarks
+                .iter()
+                .map(|bookmark| bookmark.id.clone())
+                .collect();
+
+            panel = panel.push(
+                Row::new()
+                    .push(
+                        text(&group.bookmarks[0].title)
+                            .size(12)
+                            .font(Font::with_name("Ubuntu"))
+                            .style(Color::from_rgb(0.9, 0.9, 0.9))
+                            .width(Length::FillPortion(1)),
+                    )
+                    .push(
+                        text(format!("{} copies in {}", group.bookmarks.len(), folders))
+                            .size(12)
+                            .font(Font::with_name("Ubuntu"))
+                            .style(Color::from_rgb(0.6, 0.6, 0.6))
+                            .width(Length::FillPortion(2)),
+                    )
+                    .push(
+                        button(text("Merge").font(Font::with_name("Ubuntu")).size(11))
+                            .on_press(Message::Bookmarks(BookmarkMessage::MergeDuplicates(ids)))
+                            .padding([2, 8])
+                            .style(iced::theme::Button::Text),
+                    )
+                    .spacing(8)
+                    .align_items(iced::Alignment::Center),
+            );
+        }
+
+        panel.into()
+    }
+
+    fn create_tag_list(&self) -> Element<'_, Message> {
+        let page = &self.bookmarks_page;
+
+        let mut list = Column::new().spacing(2).push(
+            text("Tags")
+                .size(14)
+                .font(Font::with_name("Ubuntu"))
+                .style(Color::from_rgb(0.8, 0.8, 0.8)),
+        );
+
+        if page.tags.is_empty() {
+            list = list.push(
+                text("No tags yet")
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.6, 0.6, 0.6)),
+            );
+        }
+
+        for tag in &page.tags {
+            let row = match &page.renaming_tag {
+                Some((old_tag, new_tag)) if old_tag == &tag.tag => Row::new()
+                    .push(
+                        text_input("New name", new_tag)
+                            .on_input(|value| {
+                                Message::Bookmarks(BookmarkMessage::RenameTagChanged(value))
+                            })
+                            .on_submit(Message::Bookmarks(BookmarkMessage::SaveRenameTag))
+                            .padding(4)
+                            .size(12)
+                            .font(Font::with_name("Ubuntu")),
+                    )
+                    .push(
+                        button(text("×").font(Font::with_name("Ubuntu")).size(12))
+                            .on_press(Message::Bookmarks(BookmarkMessage::CancelRenameTag))
+                            .padding([2, 6])
+                            .style(iced::theme::Button::Text),
+                    ),
+                _ => Row::new()
+                    .push(
+                        button(
+                            text(format!("#{} ({})", tag.tag, tag.count))
+                                .font(Font::with_name("Ubuntu"))
+                                .size(12),
+                        )
+                        .on_press(Message::Bookmarks(BookmarkMessage::FilterByTag(
+                            tag.tag.clone(),
+                        )))
+                        .padding([2, 6])
+                        .width(Length::Fill)
+                        .style(iced::theme::Button::Text),
+                    )
+                    .push(
+                        button(text("Rename").font(Font::with_name("Ubuntu")).size(11))
+                            .on_press(Message::Bookmarks(BookmarkMessage::StartRenameTag(
+                                tag.tag.clone(),
+                            )))
+                            .padding([2, 6])
+                            .style(iced::theme::Button::Text),
+                    ),
+            };
+            list = list.push(row.align_items(iced::Alignment::Center));
+        }
+
+        scrollable(list).width(Length::FillPortion(1)).into()
+    }
+
+    fn push_folder_tree<'a>(
+        &'a self,
+        mut tree: Column<'a, Message>,
+        folder: &'a BookmarkFolder,
+        depth: u16,
+    ) -> Column<'a, Message> {
+        let expanded = self.bookmarks_page.expanded.contains(&folder.id);
+        let is_drop_target = self.bookmarks_page.dragging.is_some();
+
+        let mut folder_row = Row::new()
+            .push(
+                button(
+                    text(if expanded { "▾" } else { "▸" })
+                        .font(Font::with_name("Ubuntu"))
+                        .size(12),
+                )
+                .on_press(Message::Bookmarks(BookmarkMessage::ToggleFolder(
+                    folder.id.clone(),
+                )))
+                .padding([2, 6])
+                .style(iced::theme::Button::Text),
+            )
+            .spacing(6)
+            .padding([0, 0, 0, depth * 16])
+            .align_items(iced::Alignment::Center);
+
+        let renaming = match &self.bookmarks_page.renaming_folder {
+            Some((folder_id, name)) if folder_id == &folder.id => Some(name),
+            _ => None,
+        };
+        if let Some(name) = renaming {
+            folder_row = folder_row
+                .push(
+                    text_input("Folder name", name)
+                        .on_input(|value| {
+                            Message::Bookmarks(BookmarkMessage::RenameFolderChanged(value))
+                        })
+                        .on_submit(Message::Bookmarks(BookmarkMessage::SaveRenameFolder))
+                        .padding(4)
+                        .size(12)
+                        .font(Font::with_name("Ubuntu")),
+                )
+                .push(
+                    button(text("×").font(Font::with_name("Ubuntu")).size(12))
+                        .on_press(Message::Bookmarks(BookmarkMessage::CancelRenameFolder))
+                        .padding([2, 6])
+                        .style(iced::theme::Button::Text),
+                );
+        } else {
+            folder_row = folder_row.push(
+                text(&folder.name)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(if is_drop_target {
+                        Color::from_rgb(0.5, 0.7, 1.0)
+                    } else {
+                        Color::from_rgb(0.85, 0.85, 0.85)
+                    })
+                    .width(Length::Fill),
+            );
+        }
+
+        if renaming.is_none() && !matches!(folder.id.as_str(), "root" | "toolbar" | "other") {
+            folder_row = folder_row
+                .push(
+                    button(text("Rename").font(Font::with_name("Ubuntu")).size(11))
+                        .on_press(Message::Bookmarks(BookmarkMessage::StartRenameFolder(
+                            folder.id.clone(),
+                        )))
+                        .padding([2, 8])
+                        .style(iced::theme::Button::Text),
+                )
+                .push(
+                    button(text("Delete").font(Font::with_name("Ubuntu")).size(11))
+                        .on_press(Message::Bookmarks(BookmarkMessage::DeleteFolder(
+                            folder.id.clone(),
+                        )))
+                        .padding([2, 8])
+                        .style(iced::theme::Button::Text),
+                );
+        }
+
+        tree = tree.push(mouse_area(folder_row).on_release(Message::Bookmarks(
+            BookmarkMessage::DropOnFolder(folder.id.clone()),
+        )));
+
+        if expanded {
+            for child in self.child_folders(&folder.id) {
+                tree = self.push_folder_tree(tree, child, depth + 1);
+            }
+            for bookmark in self.bookmarks_in(&folder.id) {
+                tree = tree.push(self.create_bookmark_row(bookmark, depth + 1));
+            }
+        }
+
+        tree
+    }
+
+    fn create_bookmark_row<'a>(
+        &'a self,
+        bookmark: &'a Bookmark,
+        depth: u16,
+    ) -> Element<'a, Message> {
+        let is_dragging = self.bookmarks_page.dragging.as_deref() == Some(bookmark.id.as_str());
+
+        // the grip is the drag handle, buttons would swallow the press otherwise
+        let grip = mouse_area(
+            text("⠿")
+                .size(13)
+                .font(Font::with_name("Ubuntu"))
+                .style(Color::from_rgb(0.5, 0.5, 0.55)),
+        )
+        .on_press(Message::Bookmarks(BookmarkMessage::StartDrag(
+            bookmark.id.clone(),
+        )));
+
+        let mut details = Column::new().push(
+            text(&bookmark.title)
+                .size(13)
+                .font(Font::with_name("Ubuntu"))
+                .style(if is_dragging {
+                    Color::from_rgb(0.5, 0.7, 1.0)
+                } else {
+                    Color::from_rgb(0.9, 0.9, 0.9)
+                }),
+        );
+        details = details.push(
+            text(&bookmark.url)
+                .size(11)
+                .font(Font::with_name("Ubuntu"))
+                .style(Color::from_rgb(0.6, 0.6, 0.6)),
+        );
+        if !bookmark.tags.is_empty() {
+            details = details.push(
+                text(
+                    bookmark
+                        .tags
+                        .iter()
+                        .map(|tag| format!("#{}", tag))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                )
+                .size(11)
+                .font(Font::with_name("Ubuntu"))
+                .style(Color::from_rgb(0.5, 0.7, 0.9)),
+            );
+        }
+
+        Row::new()
+            .push(grip)
+            .push(details.width(Length::Fill))
+            .push(
+                button(text("Open").font(Font::with_name("Ubuntu")).size(11))
+                    .on_press(Message::Bookmarks(BookmarkMessage::Open(
+                        bookmark.url.clone(),
+                    )))
+                    .padding([2, 8])
+                    .style(iced::theme::Button::Text),
+            )
+            .push(
+                button(text("Edit").font(Font::with_name("Ubuntu")).size(11))
+                    .on_press(Message::Bookmarks(BookmarkMessage::Edit(
+                        bookmark.id.clone(),
+                    )))
+                    .padding([2, 8])
+                    .style(iced::theme::Button::Text),
+            )
+            .push(
+                button(text("Delete").font(Font::with_name("Ubuntu")).size(11))
+                    .on_press(Message::Bookmarks(BookmarkMessage::Delete(
+                        bookmark.id.clone(),
+                    )))
+                    .padding([2, 8])
+                    .style(iced::theme::Button::Text),
+            )
+            .spacing(8)
+            .padding([2, 0, 2, depth * 16 + 22])
+            .align_items(iced::Alignment::Center)
+            .into()
+    }
+
+    fn create_bookmark_editor<'a>(&'a self, editor: &'a BookmarkEditor) -> Element<'a, Message> {
+        let label = |value: &'static str| {
+            text(value)
+                .size(12)
+                .font(Font::with_name("Ubuntu"))
+                .style(Color::from_rgb(0.7, 0.7, 0.7))
+        };
+
+        let mut form = Column::new()
+            .push(label("Title"))
+            .push(
+                text_input("Title", &editor.title)
+                    .on_input(|title| {
+                        Message::Bookmarks(BookmarkMessage::EditorTitleChanged(title))
+                    })
+                    .padding(6)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu")),
+            )
+            .push(label("URL"))
+            .push(
+                text_input("URL", &editor.url)
+                    .on_input(|url| Message::Bookmarks(BookmarkMessage::EditorUrlChanged(url)))
+                    .padding(6)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu")),
+            )
+            .push(label("Folder"))
+            .push(
+                pick_list(self.folder_options(), editor.folder.clone(), |folder| {
+                    Message::Bookmarks(BookmarkMessage::EditorFolderSelected(folder))
+                })
+                .padding(6)
+                .text_size(13)
+                .font(Font::with_name("Ubuntu"))
+                .width(Length::Fill),
+            )
+            .push(label("Tags"))
+            .push(
+                text_input("Tags, comma separated", &editor.tags)
+                    .on_input(|tags| Message::Bookmarks(BookmarkMessage::EditorTagsChanged(tags)))
+                    .padding(6)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu")),
+            )
+            .push(label("Keyword"))
+            .push(
+                text_input("e.g. gh for https://github.com/%s", &editor.keyword)
+                    .on_input(|keyword| {
+                        Message::Bookmarks(BookmarkMessage::EditorKeywordChanged(keyword))
+                    })
+                    .padding(6)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu")),
+            )
+            .push(label("Description"))
+            .push(
+                text_input("Description", &editor.description)
+                    .on_input(|description| {
+                        Message::Bookmarks(BookmarkMessage::EditorDescriptionChanged(description))
+                    })
+                    .on_submit(Message::Bookmarks(BookmarkMessage::SaveEdit))
+                    .padding(6)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu")),
+            )
+            .push(
+                Row::new()
+                    .push(
+                        button(text("Save").font(Font::with_name("Ubuntu")).size(13))
+                            .on_press(Message::Bookmarks(BookmarkMessage::SaveEdit))
+                            .padding([6, 12])
+                            .style(iced::theme::Button::Primary),
+                    )
+                    .push(
+                        button(text("Cancel").font(Font::with_name("Ubuntu")).size(13))
+                            .on_press(Message::Bookmarks(BookmarkMessage::CancelEdit))
+                            .padding([6, 12])
+                            .style(iced::theme::Button::Secondary),
+                    )
+                    .spacing(8),
+            )
+            .spacing(6);
+
+        if let Some(error) = &editor.error {
+            form = form.push(
+                text(error)
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.95, 0.45, 0.45)),
+            );
+        }
+
+        container(form)
+            .width(Length::FillPortion(2))
+            .padding(12)
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.15, 0.15, 0.18))),
+                border: iced::Border {
+                    radius: 4.0.into(),
+                    width: 1.0,
+                    color: Color::from_rgb(0.25, 0.25, 0.3),
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+}
@@ -0,0 +1,752 @@
+// the lock at the start of the address bar, and the certificate viewer it
+// opens with what the site's tls handshake showed. also the warning shown
+// instead of pages with untrusted certificates, and bagel://certificates for
+// the exceptions made from it. the lock also reflects http content on https
+// pages
+use crate::browser::{
+    CertificateCheck, CertificateError, CertificateInfo, MixedContent, MixedContentAction,
+    UntrustedCertificate,
+};
+use crate::storage::CertificateException;
+use crate::ui::{BagelApp, Message};
+use crate::utils::log_error;
+use iced::{
+    widget::{button, container, scrollable, text, tooltip, Column, Row},
+    Application, Background, Color, Command, Element, Font, Length,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Default)]
+pub struct CertificateViewer {
+    pub checking: bool,
+    /// Why the last check couldn't look at the certificate at all.
+    pub status: Option<String>,
+}
+
+/// The warning a tab shows instead of a page with an untrusted certificate.
+#[derive(Debug, Clone)]
+pub struct CertificateWarning {
+    pub url: String,
+    pub untrusted: UntrustedCertificate,
+    pub show_details: bool,
+    /// HSTS hosts can't be given an exception.
+    pub hsts: bool,
+    /// Neither can hosts that pinned their keys.
+    pub pinned: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CertificatesPage {
+    pub exceptions: Vec<CertificateException>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CertificateMessage {
+    ToggleViewer,
+    Check,
+    Checked(Result<CertificateCheck, String>),
+    Untrusted(Uuid, String, UntrustedCertificate),
+    ToggleWarningDetails,
+    AcceptRisk,
+    LeaveSite,
+    RemoveException(String, String),
+}
+
+impl BagelApp {
+    pub fn update_certificates(&mut self, message: CertificateMessage) -> Command<Message> {
+        match message {
+            CertificateMessage::ToggleViewer => {
+                self.show_certificate = !self.show_certificate;
+                self.show_menu = false;
+                self.show_shields = false;
+                self.certificate_viewer.status = None;
+
+                // pages loaded before the check existed, or that never got
+                // as far as a handshake, have nothing to show yet
+                let unchecked = self.current_https_url().is_some_and(|url| {
+                    let security = self.webview_manager.lock().unwrap().security_manager();
+                    url.host_str()
+                        .and_then(|host| security.certificate_for(host))
+                        .is_none()
+                });
+                if self.show_certificate && unchecked {
+                    return self.update_certificates(CertificateMessage::Check);
+                }
+            }
+            CertificateMessage::Check => {
+                let Some(url) = self.current_https_url() else {
+                    return Command::none();
+                };
+                let security = self.webview_manager.lock().unwrap().security_manager();
+                self.certificate_viewer.checking = true;
+                self.certificate_viewer.status = None;
+                return Command::perform(
+                    async move {
+                        security
+                            .validate_certificate(url.as_str())
+                            .await
+                            .map_err(|e| e.to_string())
+                    },
+                    |result| Message::Certificates(CertificateMessage::Checked(result)),
+                );
+            }
+            CertificateMessage::Checked(result) => {
+                self.certificate_viewer.checking = false;
+                // the check itself lands in the security manager's store
+                if let Err(e) = result {
+                    self.certificate_viewer.status = Some(e);
+                }
+            }
+            CertificateMessage::Untrusted(tab_id, url, untrusted) => {
+                let error = untrusted.to_string();
+                let security = self.webview_manager.lock().unwrap().security_manager();
+                let hsts = security.is_hsts_host(&untrusted.check.host);
+                let pinned = security.is_pinned_host(&untrusted.check.host);
+                self.certificate_warnings.insert(
+                    tab_id,
+                    CertificateWarning {
+                        url,
+                        untrusted,
+                        show_details: false,
+                        hsts,
+                        pinned,
+                    },
+                );
+                let command = self.update(Message::PageLoaded(tab_id, Err(error)));
+                if let Some(tab) = self.tabs.iter_mut().find(|tab| tab.id == tab_id) {
+                    tab.title = "Privacy error".to_string();
+                }
+                return command;
+            }
+            CertificateMessage::ToggleWarningDetails => {
+                if let Some(warning) = self.active_certificate_warning() {
+                    warning.show_details = !warning.show_details;
+                }
+            }
+            CertificateMessage::AcceptRisk => {
+                let Some(warning) = self
+                    .active_certificate_warning()
+                    .filter(|warning| !warning.hsts && !warning.pinned)
+                    .cloned()
+                else {
+                    return Command::none();
+                };
+                let security = self.webview_manager.lock().unwrap().security_manager();
+                if let Err(e) = security.add_certificate_exception(&warning.untrusted.check) {
+                    log_error("Failed to add certificate exception", e.as_ref());
+                    return Command::none();
+                }
+                return self.update(Message::NavigateTo(warning.url));
+            }
+            CertificateMessage::LeaveSite => {
+                return self.update(Message::NavigateTo("bagel://home".to_string()));
+            }
+            CertificateMessage::RemoveException(host, fingerprint) => {
+                let security = self.webview_manager.lock().unwrap().security_manager();
+                if let Err(e) = security.remove_certificate_exception(&host, &fingerprint) {
+                    log_error("Failed to remove certificate exception", e.as_ref());
+                }
+                self.refresh_certificate_exceptions();
+            }
+        }
+        Command::none()
+    }
+
+    pub fn refresh_certificate_exceptions(&mut self) {
+        let security = self.webview_manager.lock().unwrap().security_manager();
+        match security.certificate_exceptions() {
+            Ok(exceptions) => {
+                self.certificates_page.exceptions = exceptions;
+                self.certificates_page.error = None;
+            }
+            Err(e) => {
+                self.certificates_page.error = Some(format!("Couldn't load exceptions: {}", e))
+            }
+        }
+    }
+
+    fn active_certificate_warning(&mut self) -> Option<&mut CertificateWarning> {
+        let tab = self.tabs.get(self.active_tab_index)?;
+        self.certificate_warnings.get_mut(&tab.id)
+    }
+
+    /// The active tab's url if it's an https page.
+    fn current_https_url(&self) -> Option<url::Url> {
+        let tab = self.tabs.get(self.active_tab_index)?;
+        url::Url::parse(&tab.url)
+            .ok()
+            .filter(|url| url.scheme() == "https")
+    }
+
+    /// What the last handshake with the active tab's host turned up.
+    fn current_certificate(&self) -> Option<CertificateCheck> {
+        let url = self.current_https_url()?;
+        let security = self.webview_manager.lock().unwrap().security_manager();
+        security.certificate_for(url.host_str()?)
+    }
+
+    /// The http subresources of the active tab's page, if it's an https page
+    /// that had any.
+    pub fn active_tab_mixed_content(&self) -> &[MixedContent] {
+        self.tabs
+            .get(self.active_tab_index)
+            .and_then(|tab| self.pages.get(&tab.id))
+            .and_then(|page| page.as_ref().ok())
+            .map(|page| page.mixed_content.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The lock before the address bar. Only web pages get one.
+    pub fn create_lock(&self) -> Element<'_, Message> {
+        let Some(url) = self
+            .tabs
+            .get(self.active_tab_index)
+            .and_then(|tab| url::Url::parse(&tab.url).ok())
+            .filter(|url| url.scheme() == "http" || url.scheme() == "https")
+        else {
+            return Row::new().into();
+        };
+
+        let check = self.current_certificate();
+        let level = self.tabs.get(self.active_tab_index).and_then(|tab| {
+            let security = self.webview_manager.lock().unwrap().security_manager();
+            security
+                .check_url_security(&tab.url, &[], self.active_tab_mixed_content())
+                .ok()
+        });
+        let (label, color, mut summary) = if url.scheme() == "http" {
+            (
+                "Not secure",
+                Color::from_rgb(0.7, 0.7, 0.7),
+                "The connection to this site isn't encrypted".to_string(),
+            )
+        } else {
+            match check.as_ref().and_then(|check| check.error.as_ref()) {
+                Some(error) => (
+                    "Not secure",
+                    Color::from_rgb(0.9, 0.45, 0.45),
+                    if check.as_ref().is_some_and(|check| check.accepted) {
+                        format!(
+                            "{}, you made an exception for it",
+                            capitalize(&error.to_string())
+                        )
+                    } else {
+                        capitalize(&error.to_string())
+                    },
+                ),
+                None if level.as_ref().is_some_and(|level| level.mixed_content) => (
+                    "Partly secure",
+                    Color::from_rgb(0.95, 0.75, 0.45),
+                    "Parts of this page were loaded over plain http".to_string(),
+                ),
+                None => (
+                    "🔒",
+                    Color::from_rgb(0.55, 0.85, 0.6),
+                    "Connection is secure, click to see the certificate".to_string(),
+                ),
+            }
+        };
+        if let Some(level) = level.filter(|level| level.mixed_content_blocked > 0) {
+            summary = format!(
+                "{}\n{} insecure items blocked",
+                summary, level.mixed_content_blocked
+            );
+        }
+
+        let lock = button(
+            text(label)
+                .font(Font::with_name("Ubuntu"))
+                .size(12)
+                .style(color),
+        )
+        .on_press(Message::Certificates(CertificateMessage::ToggleViewer))
+        .padding([7, 10])
+        .style(iced::theme::Button::Secondary);
+
+        tooltip(
+            lock,
+            text(summary).font(Font::with_name("Ubuntu")).size(12),
+            tooltip::Position::Bottom,
+        )
+        .style(iced::theme::Container::Box)
+        .into()
+    }
+
+    pub fn create_certificate_viewer(&self) -> Element<'_, Message> {
+        let url = self
+            .tabs
+            .get(self.active_tab_index)
+            .and_then(|tab| url::Url::parse(&tab.url).ok());
+        let host = url
+            .as_ref()
+            .and_then(|url| url.host_str())
+            .unwrap_or_default()
+            .to_string();
+
+        let mut items = Column::new()
+            .push(
+                text(format!("Connection to {}", host))
+                    .size(14)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.9, 0.9, 0.9)),
+            )
+            .spacing(8)
+            .padding(12)
+            .width(Length::Fixed(440.0));
+
+        let is_https = url.as_ref().is_some_and(|url| url.scheme() == "https");
+        let check = self.current_certificate();
+
+        if !is_https {
+            items = items.push(note(
+                "This page was loaded over plain http, so anyone on the network can read or change it"
+                    .to_string(),
+            ));
+        } else if let Some(check) = &check {
+            let (status, color) = match &check.error {
+                Some(error) => (
+                    capitalize(&error.to_string()),
+                    Color::from_rgb(0.9, 0.45, 0.45),
+                ),
+                None => (
+                    "The certificate is valid and trusted".to_string(),
+                    Color::from_rgb(0.55, 0.85, 0.6),
+                ),
+            };
+            items = items.push(
+                text(status)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(color),
+            );
+            if check.accepted {
+                items = items.push(
+                    Row::new()
+                        .push(note(
+                            "You made an exception to trust this certificate anyway".to_string(),
+                        ))
+                        .push(
+                            button(text("Manage").font(Font::with_name("Ubuntu")).size(11))
+                                .on_press(Message::NavigateTo("bagel://certificates".to_string()))
+                                .padding([2, 8])
+                                .style(iced::theme::Button::Text),
+                        )
+                        .spacing(8)
+                        .align_items(iced::Alignment::Center),
+                );
+            }
+            if let (Some(protocol), Some(cipher_suite)) = (&check.protocol, &check.cipher_suite) {
+                items = items.push(note(format!("{}, {}", protocol, cipher_suite)));
+            }
+            items = items.push(note(format!(
+                "Checked {}",
+                check
+                    .checked_at
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+            )));
+
+            let mut chain = Column::new().spacing(12);
+            for (index, cert) in check.chain.iter().enumerate() {
+                chain = chain.push(certificate_details(cert, index));
+            }
+            items = items.push(scrollable(chain).height(Length::Fixed(320.0)));
+        } else if !self.certificate_viewer.checking {
+            items = items.push(note("The certificate hasn't been checked yet".to_string()));
+        }
+
+        let mixed_content = self.active_tab_mixed_content();
+        if is_https && !mixed_content.is_empty() {
+            let mut list = Column::new().spacing(4);
+            for content in mixed_content {
+                list = list.push(mixed_content_row(content));
+            }
+            items = items
+                .push(
+                    text("Insecure content")
+                        .size(13)
+                        .font(Font::with_name("Ubuntu"))
+                        .style(Color::from_rgb(0.95, 0.75, 0.45)),
+                )
+                .push(scrollable(list).height(if mixed_content.len() > 5 {
+                    Length::Fixed(120.0)
+                } else {
+                    Length::Shrink
+                }));
+        }
+
+        if self.certificate_viewer.checking {
+            items = items.push(note("Checking the certificate...".to_string()));
+        }
+        if let Some(status) = &self.certificate_viewer.status {
+            items = items.push(
+                text(status)
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.9, 0.45, 0.45)),
+            );
+        }
+
+        if is_https {
+            items = items.push(
+                button(text("Check again").font(Font::with_name("Ubuntu")).size(12))
+                    .on_press_maybe(
+                        (!self.certificate_viewer.checking)
+                            .then_some(Message::Certificates(CertificateMessage::Check)),
+                    )
+                    .padding([4, 10])
+                    .style(iced::theme::Button::Secondary),
+            );
+        }
+
+        container(container(items).style(container::Appearance {
+            background: Some(Background::Color(Color::from_rgb(0.15, 0.15, 0.18))),
+            border: iced::Border {
+                radius: 4.0.into(),
+                width: 1.0,
+                color: Color::from_rgb(0.25, 0.25, 0.3),
+            },
+            shadow: iced::Shadow::default(),
+            text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+        }))
+        .width(Length::Fill)
+        .padding([4, 4, 4, 180])
+        .into()
+    }
+}
+
+impl BagelApp {
+    pub fn create_certificate_warning<'a>(
+        &self,
+        warning: &'a CertificateWarning,
+    ) -> Element<'a, Message> {
+        let check = &warning.untrusted.check;
+        let leaf = check.leaf();
+
+        let explanation = match (&warning.untrusted.reason, leaf) {
+            (CertificateError::Expired, Some(leaf)) => format!(
+                "Its certificate expired on {}. If your computer's clock is right, \
+                 the site hasn't renewed it.",
+                leaf.not_after.format("%Y-%m-%d")
+            ),
+            (CertificateError::NotYetValid, Some(leaf)) => format!(
+                "Its certificate isn't valid until {}. Check that your computer's clock is right.",
+                leaf.not_before.format("%Y-%m-%d")
+            ),
+            (CertificateError::WrongHost, Some(leaf)) if !leaf.sans.is_empty() => format!(
+                "Its certificate is for {}, not {}.",
+                leaf.sans.join(", "),
+                check.host
+            ),
+            (CertificateError::SelfSigned, _) => "Its certificate was signed by the server \
+                 itself, so no authority vouches for it. That's normal for development \
+                 servers but not for public sites."
+                .to_string(),
+            (CertificateError::UnknownIssuer, Some(leaf)) => format!(
+                "Its certificate was issued by {}, which Bagel doesn't trust.",
+                leaf.issuer
+            ),
+            (reason, _) => format!("{}.", capitalize(&reason.to_string())),
+        };
+
+        let mut content = Column::new()
+            .push(
+                text(format!("Your connection to {} isn't private", check.host))
+                    .size(24)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.95, 0.55, 0.5)),
+            )
+            .push(
+                text(explanation)
+                    .size(14)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.8, 0.8, 0.8)),
+            )
+            .push(note(format!(
+                "Someone could be pretending to be {} to steal your passwords or other data.",
+                check.host
+            )))
+            .push(
+                Row::new()
+                    .push(
+                        button(text("Go back").font(Font::with_name("Ubuntu")).size(13))
+                            .on_press(Message::Certificates(CertificateMessage::LeaveSite))
+                            .padding([6, 12])
+                            .style(iced::theme::Button::Primary),
+                    )
+                    .push(
+                        button(
+                            text(if warning.show_details {
+                                "Hide details"
+                            } else {
+                                "Details"
+                            })
+                            .font(Font::with_name("Ubuntu"))
+                            .size(13),
+                        )
+                        .on_press(Message::Certificates(
+                            CertificateMessage::ToggleWarningDetails,
+                        ))
+                        .padding([6, 12])
+                        .style(iced::theme::Button::Secondary),
+                    )
+                    .spacing(8),
+            )
+            .spacing(16)
+            .padding(32)
+            .max_width(640);
+
+        if warning.show_details {
+            if let Some(leaf) = leaf {
+                content = content.push(certificate_details(leaf, 0));
+            }
+            if warning.hsts {
+                content = content.push(note(format!(
+                    "{} uses HSTS, it asked to only ever be loaded with a valid \
+                     certificate, so there's no way past this warning.",
+                    check.host
+                )));
+            } else if warning.pinned {
+                content = content.push(note(format!(
+                    "{} pinned the keys its certificates have to use, so there's \
+                     no way past this warning.",
+                    check.host
+                )));
+            } else {
+                content = content
+                    .push(note(
+                        "Only continue if you know why this certificate can't be verified, \
+                         like an internal server with its own certificate. The exception is \
+                         for this certificate only, you'll be warned again if it changes."
+                            .to_string(),
+                    ))
+                    .push(
+                        button(
+                            text(format!("Accept the risk and continue to {}", check.host))
+                                .font(Font::with_name("Ubuntu"))
+                                .size(13),
+                        )
+                        .on_press_maybe(
+                            leaf.map(|_| Message::Certificates(CertificateMessage::AcceptRisk)),
+                        )
+                        .padding([6, 12])
+                        .style(iced::theme::Button::Destructive),
+                    );
+            }
+        }
+
+        container(scrollable(content))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.1, 0.1, 0.13))),
+                border: iced::Border {
+                    radius: 0.0.into(),
+                    width: 0.0,
+                    color: Color::TRANSPARENT,
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+
+    pub fn create_certificates_page(&self) -> Element<'_, Message> {
+        let page = &self.certificates_page;
+
+        let mut content = Column::new()
+            .push(
+                text("Certificate exceptions")
+                    .size(22)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.9, 0.9, 0.9)),
+            )
+            .push(note(
+                "Certificates you chose to trust even though they couldn't be verified. \
+                 Each exception covers one certificate for one site, a new certificate \
+                 gets the warning again. Removing one applies to new connections."
+                    .to_string(),
+            ));
+
+        if let Some(error) = &page.error {
+            content = content.push(
+                text(error)
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.95, 0.45, 0.45)),
+            );
+        }
+
+        let listing = if page.exceptions.is_empty() {
+            Column::new().push(note("No certificate exceptions".to_string()))
+        } else {
+            page.exceptions
+                .iter()
+                .fold(Column::new().spacing(12), |column, exception| {
+                    column.push(certificate_exception_row(exception))
+                })
+        };
+
+        content = content
+            .push(scrollable(listing).height(Length::Fill))
+            .spacing(16)
+            .padding(24)
+            .width(Length::Fill)
+            .height(Length::Fill);
+
+        container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.12, 0.12, 0.15))),
+                border: iced::Border {
+                    radius: 0.0.into(),
+                    width: 1.0,
+                    color: Color::from_rgb(0.25, 0.25, 0.3),
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+}
+
+fn certificate_exception_row(exception: &CertificateException) -> Element<'_, Message> {
+    Row::new()
+        .push(
+            Column::new()
+                .push(
+                    text(&exception.host)
+                        .size(13)
+                        .font(Font::with_name("Ubuntu"))
+                        .style(Color::from_rgb(0.9, 0.9, 0.9)),
+                )
+                .push(note(format!(
+                    "{} · {} · added {}",
+                    exception.subject,
+                    capitalize(&exception.reason),
+                    exception
+                        .created_at
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d")
+                )))
+                .push(note(format!("SHA-256 {}", exception.fingerprint)))
+                .spacing(2)
+                .width(Length::Fill),
+        )
+        .push(
+            button(text("Remove").font(Font::with_name("Ubuntu")).size(11))
+                .on_press(Message::Certificates(CertificateMessage::RemoveException(
+                    exception.host.clone(),
+                    exception.fingerprint.clone(),
+                )))
+                .padding([2, 8])
+                .style(iced::theme::Button::Text),
+        )
+        .spacing(8)
+        .align_items(iced::Alignment::Center)
+        .into()
+}
+
+/// One http subresource of an https page and what happened to it.
+fn mixed_content_row(content: &MixedContent) -> Element<'_, Message> {
+    let (action, color) = match content.action {
+        MixedContentAction::Blocked => ("Blocked", Color::from_rgb(0.55, 0.85, 0.6)),
+        MixedContentAction::Upgraded => ("Upgraded", Color::from_rgb(0.55, 0.85, 0.6)),
+        MixedContentAction::Allowed => ("Allowed", Color::from_rgb(0.95, 0.75, 0.45)),
+    };
+    Row::new()
+        .push(
+            text(action)
+                .size(11)
+                .font(Font::with_name("Ubuntu"))
+                .style(color)
+                .width(Length::Fixed(64.0)),
+        )
+        .push(note(content.url.to_string()))
+        .spacing(8)
+        .into()
+}
+
+/// One certificate in the chain, the leaf first and then whoever issued it.
+fn certificate_details<'a>(cert: &CertificateInfo, index: usize) -> Element<'a, Message> {
+    let role = match index {
+        0 => "Issued to the site",
+        _ if cert.self_signed => "Root authority",
+        _ => "Intermediate authority",
+    };
+
+    let mut sans = cert
+        .sans
+        .iter()
+        .take(6)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if cert.sans.len() > 6 {
+        sans.push_str(&format!(" and {} more", cert.sans.len() - 6));
+    }
+
+    let rows = [
+        ("Subject", cert.subject.clone()),
+        ("Issuer", cert.issuer.clone()),
+        ("Also valid for", sans),
+        ("Valid from", cert.not_before.format("%Y-%m-%d").to_string()),
+        ("Valid until", cert.not_after.format("%Y-%m-%d").to_string()),
+        ("Serial number", cert.serial.clone()),
+        ("SHA-256", cert.sha256_fingerprint.clone()),
+        ("SHA-1", cert.sha1_fingerprint.clone()),
+        ("Public key SHA-256", cert.spki_sha256.clone()),
+        ("Signature", cert.signature_algorithm.clone()),
+    ];
+
+    let mut details = Column::new()
+        .push(
+            text(format!("{} ({})", cert.display_name(), role))
+                .size(13)
+                .font(Font::with_name("Ubuntu"))
+                .style(Color::from_rgb(0.85, 0.85, 0.85)),
+        )
+        .spacing(4);
+    for (label, value) in rows {
+        if value.is_empty() {
+            continue;
+        }
+        details = details.push(
+            Row::new()
+                .push(
+                    text(label)
+                        .size(12)
+                        .font(Font::with_name("Ubuntu"))
+                        .style(Color::from_rgb(0.6, 0.6, 0.6))
+                        .width(Length::Fixed(100.0)),
+                )
+                .push(
+                    text(value)
+                        .size(12)
+                        .font(Font::with_name("Ubuntu"))
+                        .width(Length::Fill),
+                )
+                .spacing(8),
+        );
+    }
+    details.into()
+}
+
+fn note<'a>(message: String) -> Element<'a, Message> {
+    text(message)
+        .size(12)
+        .font(Font::with_name("Ubuntu"))
+        .style(Color::from_rgb(0.6, 0.6, 0.6))
+        .into()
+}
+
+fn capitalize(message: &str) -> String {
+    let mut chars = message.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,950 @@
+// cookie ui: the bagel://cookies viewer and editor, and the
+// bagel://cookie-exceptions page for per-site rules
+use crate::storage::{Cookie, CookieJar, CookieKey, CookieRule, SiteCookieRule};
+use crate::ui::{BagelApp, Message};
+use crate::utils::{log_error, site_for_host};
+use chrono::NaiveDateTime;
+use iced::{
+    widget::{button, checkbox, container, pick_list, scrollable, text, text_input, Column, Row},
+    Background, Color, Command, Element, Font, Length,
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+const SAME_SITE_OPTIONS: [&str; 4] = ["Default", "Lax", "Strict", "None"];
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, Default)]
+pub struct CookieEditor {
+    pub original: CookieKey,
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub include_subdomains: bool,
+    pub path: String,
+    /// `DATE_FORMAT` in UTC, empty for a session cookie.
+    pub expires: String,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: &'static str,
+    pub partition_key: Option<String>,
+    pub error: Option<String>,
+}
+
+impl CookieEditor {
+    fn new(cookie: &Cookie) -> Self {
+        Self {
+            original: cookie.key(),
+            name: cookie.name.clone(),
+            value: cookie.value.clone(),
+            domain: cookie.domain.trim_start_matches('.').to_string(),
+            include_subdomains: !cookie.is_host_only(),
+            path: cookie.path.clone(),
+            expires: cookie
+                .expires
+                .map(|expires| expires.format(DATE_FORMAT).to_string())
+                .unwrap_or_default(),
+            secure: cookie.secure,
+            http_only: cookie.http_only,
+            same_site: SAME_SITE_OPTIONS
+                .into_iter()
+                .find(|option| Some(*option) == cookie.same_site.as_deref())
+                .unwrap_or("Default"),
+            partition_key: cookie.partition_key.clone(),
+            error: None,
+        }
+    }
+
+    fn to_cookie(&self) -> Result<Cookie, String> {
+        let name = self.name.trim();
+        let domain = self
+            .domain
+            .trim()
+            .trim_start_matches('.')
+            .to_ascii_lowercase();
+        let path = self.path.trim();
+
+        if name.is_empty() {
+            return Err("Name can't be empty".to_string());
+        }
+        if domain.is_empty() {
+            return Err("Domain can't be empty".to_string());
+        }
+        if !path.starts_with('/') {
+            return Err("Path has to start with /".to_string());
+        }
+
+        let expires = match self.expires.trim() {
+            "" => None,
+            expires => Some(
+                NaiveDateTime::parse_from_str(expires, DATE_FORMAT)
+                    .map_err(|_| format!("Expires should look like {}", DATE_FORMAT))?
+                    .and_utc(),
+            ),
+        };
+
+        Ok(Cookie {
+            name: name.to_string(),
+            value: self.value.clone(),
+            domain: if self.include_subdomains {
+                format!(".{}", domain)
+            } else {
+                domain
+            },
+            path: path.to_string(),
+            expires,
+            secure: self.secure,
+            http_only: self.http_only,
+            same_site: (self.same_site != "Default").then(|| self.same_site.to_string()),
+            created_at: chrono::Utc::now(),
+            partition_key: self.partition_key.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CookiesPage {
+    pub cookies: Vec<Cookie>,
+    pub search: String,
+    pub expanded: HashSet<String>,
+    pub editor: Option<CookieEditor>,
+    pub status: Option<String>,
+    pub rules: Vec<SiteCookieRule>,
+    pub new_rule_site: String,
+    pub new_rule: Option<CookieRule>,
+    pub error: Option<String>,
+    /// The cookies.txt file to import from or export to.
+    pub transfer_path: String,
+    /// Only import or export cookies for this domain, when set.
+    pub transfer_domain: String,
+    /// Whether cookie values are encrypted in cookies.db.
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum CookieMessage {
+    SearchChanged(String),
+    ToggleDomain(String),
+    Edit(CookieKey),
+    EditorNameChanged(String),
+    EditorValueChanged(String),
+    EditorDomainChanged(String),
+    EditorSubdomainsToggled(bool),
+    EditorPathChanged(String),
+    EditorExpiresChanged(String),
+    EditorSecureToggled(bool),
+    EditorHttpOnlyToggled(bool),
+    EditorSameSiteSelected(&'static str),
+    SaveEdit,
+    CancelEdit,
+    Delete(CookieKey),
+    DeleteDomain(String),
+    ClearCurrentSite,
+    NewRuleSiteChanged(String),
+    NewRuleSelected(CookieRule),
+    AddRule,
+    ChangeRule(String, CookieRule),
+    RemoveRule(String),
+    TransferPathChanged(String),
+    TransferDomainChanged(String),
+    Import,
+    Export,
+}
+
+/// The domain a cookie is listed under, without the leading dot.
+fn display_domain(cookie: &Cookie) -> &str {
+    cookie.domain.trim_start_matches('.')
+}
+
+impl BagelApp {
+    /// The cookie jar page loads go through, so rule changes apply right away.
+    pub fn cookie_jar(&self) -> Option<Arc<CookieJar>> {
+        let loader = self.webview_manager.lock().unwrap().page_loader()?;
+        Some(Arc::clone(loader.cookie_jar()))
+    }
+
+    /// Snapshots the jar for the cookies page. The jar is the live copy,
+    /// cookies.db can be a few seconds behind it.
+    pub fn refresh_cookies(&mut self) {
+        if let Some(jar) = self.cookie_jar() {
+            self.cookies_page.cookies = jar.all_cookies();
+            self.cookies_page.encrypted = jar.manager().is_encrypted();
+        }
+    }
+
+    /// The site of the page in the active tab, if it's a web page.
+    pub fn current_site(&self) -> Option<String> {
+        let tab = self.tabs.get(self.active_tab_index)?;
+        let url = url::Url::parse(&tab.url).ok()?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return None;
+        }
+        Some(site_for_host(url.host_str()?))
+    }
+
+    fn editor_mut(&mut self) -> Option<&mut CookieEditor> {
+        self.cookies_page.editor.as_mut()
+    }
+
+    pub fn refresh_cookie_rules(&mut self) {
+        let Some(jar) = self.cookie_jar() else {
+            return;
+        };
+
+        match jar.manager().get_site_rules() {
+            Ok(rules) => self.cookies_page.rules = rules,
+            Err(e) => log_error("Failed to load cookie rules", e.as_ref()),
+        }
+    }
+
+    pub fn update_cookies(&mut self, message: CookieMessage) -> Command<Message> {
+        match message {
+            CookieMessage::SearchChanged(search) => {
+                self.cookies_page.search = search;
+            }
+            CookieMessage::ToggleDomain(domain) => {
+                if !self.cookies_page.expanded.remove(&domain) {
+                    self.cookies_page.expanded.insert(domain);
+                }
+            }
+            CookieMessage::Edit(key) => {
+                self.cookies_page.editor = self
+                    .cookies_page
+                    .cookies
+                    .iter()
+                    .find(|cookie| cookie.key() == key)
+                    .map(CookieEditor::new);
+            }
+            CookieMessage::EditorNameChanged(name) => {
+                if let Some(editor) = self.editor_mut() {
+                    editor.name = name;
+                }
+            }
+            CookieMessage::EditorValueChanged(value) => {
+                if let Some(editor) = self.editor_mut() {
+                    editor.value = value;
+                }
+            }
+            CookieMessage::EditorDomainChanged(domain) => {
+                if let Some(editor) = self.editor_mut() {
+                    editor.domain = domain;
+                }
+            }
+            CookieMessage::EditorSubdomainsToggled(include) => {
+                if let Some(editor) = self.editor_mut() {
+                    editor.include_subdomains = include;
+                }
+            }
+            CookieMessage::EditorPathChanged(path) => {
+                if let Some(editor) = self.editor_mut() {
+                    editor.path = path;
+                }
+            }
+            CookieMessage::EditorExpiresChanged(expires) => {
+                if let Some(editor) = self.editor_mut() {
+                    editor.expires = expires;
+                }
+            }
+            CookieMessage::EditorSecureToggled(secure) => {
+                if let Some(editor) = self.editor_mut() {
+                    editor.secure = secure;
+                }
+            }
+            CookieMessage::EditorHttpOnlyToggled(http_only) => {
+                if let Some(editor) = self.editor_mut() {
+                    editor.http_only = http_only;
+                }
+            }
+            CookieMessage::EditorSameSiteSelected(same_site) => {
+                if let Some(editor) = self.editor_mut() {
+                    editor.same_site = same_site;
+                }
+            }
+            CookieMessage::SaveEdit => {
+                let Some(jar) = self.cookie_jar() else {
+                    return Command::none();
+                };
+                let Some(editor) = &mut self.cookies_page.editor else {
+                    return Command::none();
+                };
+
+                let result = editor.to_cookie().and_then(|cookie| {
+                    jar.replace(&editor.original, cookie)
+                        .map_err(|e| e.to_string())
+                });
+                match result {
+                    Ok(()) => {
+                        self.cookies_page.editor = None;
+                        self.refresh_cookies();
+                    }
+                    Err(error) => editor.error = Some(error),
+                }
+            }
+            CookieMessage::CancelEdit => {
+                self.cookies_page.editor = None;
+            }
+            CookieMessage::Delete(key) => {
+                if let Some(jar) = self.cookie_jar() {
+                    if self
+                        .cookies_page
+                        .editor
+                        .as_ref()
+                        .is_some_and(|editor| editor.original == key)
+                    {
+                        self.cookies_page.editor = None;
+                    }
+                    jar.remove(key);
+                    self.refresh_cookies();
+                }
+            }
+            CookieMessage::DeleteDomain(domain) => {
+                if let Some(jar) = self.cookie_jar() {
+                    let count = jar.remove_domain(&domain);
+                    self.cookies_page.status =
+                        Some(format!("Deleted {} cookies for {}", count, domain));
+                    self.cookies_page.editor = None;
+                    self.refresh_cookies();
+                }
+            }
+            CookieMessage::ClearCurrentSite => {
+                let (Some(site), Some(jar)) = (self.current_site(), self.cookie_jar()) else {
+                    return Command::none();
+                };
+
+                let count = jar.clear_site(&site);
+                if let Err(e) = jar.flush() {
+                    log_error("Failed to write cookies", e.as_ref());
+                }
+                log::info!("Cleared {} cookies for {}", count, site);
+                self.cookies_page.status = Some(format!("Cleared {} cookies for {}", count, site));
+                self.refresh_cookies();
+            }
+            CookieMessage::NewRuleSiteChanged(site) => {
+                self.cookies_page.new_rule_site = site;
+                self.cookies_page.error = None;
+            }
+            CookieMessage::NewRuleSelected(rule) => {
+                self.cookies_page.new_rule = Some(rule);
+            }
+            CookieMessage::AddRule => {
+                let Some(jar) = self.cookie_jar() else {
+                    return Command::none();
+                };
+                let rule = self.cookies_page.new_rule.unwrap_or(CookieRule::Allow);
+
+                match jar.set_site_rule(&self.cookies_page.new_rule_site, rule) {
+                    Ok(_) => {
+                        self.cookies_page.new_rule_site.clear();
+                        self.cookies_page.error = None;
+                    }
+                    Err(e) => self.cookies_page.error = Some(e.to_string()),
+                }
+                self.refresh_cookie_rules();
+            }
+            CookieMessage::ChangeRule(site, rule) => {
+                if let Some(jar) = self.cookie_jar() {
+                    if let Err(e) = jar.set_site_rule(&site, rule) {
+                        log_error("Failed to update cookie rule", e.as_ref());
+                    }
+                    self.refresh_cookie_rules();
+                }
+            }
+            CookieMessage::RemoveRule(site) => {
+                if let Some(jar) = self.cookie_jar() {
+                    if let Err(e) = jar.remove_site_rule(&site) {
+                        log_error("Failed to remove cookie rule", e.as_ref());
+                    }
+                    self.refresh_cookie_rules();
+                }
+            }
+            CookieMessage::TransferPathChanged(path) => {
+                self.cookies_page.transfer_path = path;
+            }
+            CookieMessage::TransferDomainChanged(domain) => {
+                self.cookies_page.transfer_domain = domain;
+            }
+            CookieMessage::Import | CookieMessage::Export => {
+                let Some(jar) = self.cookie_jar() else {
+                    return Command::none();
+                };
+                let page = &self.cookies_page;
+                let path = std::path::PathBuf::from(page.transfer_path.trim());
+                let domain = Some(page.transfer_domain.trim()).filter(|domain| !domain.is_empty());
+                if path.as_os_str().is_empty() {
+                    self.cookies_page.status = Some("Enter a cookies.txt path first".to_string());
+                    return Command::none();
+                }
+
+                let status = if matches!(message, CookieMessage::Import) {
+                    jar.import_netscape_file(&path, domain).map(|import| {
+                        format!(
+                            "Imported {} cookies, skipped {}",
+                            import.imported, import.skipped
+                        )
+                    })
+                } else {
+                    jar.export_netscape_file(&path, domain)
+                        .map(|_| format!("Exported cookies to {}", path.display()))
+                };
+
+                self.cookies_page.status = Some(match status {
+                    Ok(status) => status,
+                    Err(e) => {
+                        log_error("Failed to transfer cookies", e.as_ref());
+                        e.to_string()
+                    }
+                });
+                self.refresh_cookies();
+            }
+        }
+        Command::none()
+    }
+
+    pub fn create_cookies_page(&self) -> Element<'_, Message> {
+        let page = &self.cookies_page;
+        let search = page.search.trim().to_lowercase();
+
+        let cookies: Vec<&Cookie> = page
+            .cookies
+            .iter()
+            .filter(|cookie| {
+                search.is_empty()
+                    || display_domain(cookie).contains(&search)
+                    || cookie.name.to_lowercase().contains(&search)
+            })
+            .collect();
+
+        let header = Row::new()
+            .push(
+                text("Cookies")
+                    .size(22)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.9, 0.9, 0.9)),
+            )
+            .push(
+                text_input("Search by domain or name...", &page.search)
+                    .on_input(|search| Message::Cookies(CookieMessage::SearchChanged(search)))
+                    .padding(6)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu"))
+                    .width(Length::Fill),
+            )
+            .push(
+                button(text("Exceptions").font(Font::with_name("Ubuntu")).size(13))
+                    .on_press(Message::NavigateTo("bagel://cookie-exceptions".to_string()))
+                    .padding([6, 12])
+                    .style(iced::theme::Button::Secondary),
+            )
+            .spacing(12)
+            .align_items(iced::Alignment::Center);
+
+        let domain_count = cookies
+            .iter()
+            .map(|cookie| display_domain(cookie))
+            .collect::<HashSet<_>>()
+            .len();
+        let mut content = Column::new().push(header).push(
+            text(page.status.clone().unwrap_or_else(|| {
+                format!(
+                    "{} cookies from {} domains, {}",
+                    cookies.len(),
+                    domain_count,
+                    if page.encrypted {
+                        "values encrypted on disk"
+                    } else {
+                        "values stored in plain text"
+                    }
+                )
+            }))
+            .size(12)
+            .font(Font::with_name("Ubuntu"))
+            .style(Color::from_rgb(0.6, 0.6, 0.6)),
+        );
+
+        content = content.push(
+            Row::new()
+                .push(
+                    text_input("Path to cookies.txt", &page.transfer_path)
+                        .on_input(|path| Message::Cookies(CookieMessage::TransferPathChanged(path)))
+                        .padding(6)
+                        .size(13)
+                        .font(Font::with_name("Ubuntu"))
+                        .width(Length::FillPortion(3)),
+                )
+                .push(
+                    text_input("Only this domain (optional)", &page.transfer_domain)
+                        .on_input(|domain| {
+                            Message::Cookies(CookieMessage::TransferDomainChanged(domain))
+                        })
+                        .padding(6)
+                        .size(13)
+                        .font(Font::with_name("Ubuntu"))
+                        .width(Length::FillPortion(2)),
+                )
+                .push(
+                    button(text("Import").font(Font::with_name("Ubuntu")).size(13))
+                        .on_press(Message::Cookies(CookieMessage::Import))
+                        .padding([6, 12])
+                        .style(iced::theme::Button::Secondary),
+                )
+                .push(
+                    button(text("Export").font(Font::with_name("Ubuntu")).size(13))
+                        .on_press(Message::Cookies(CookieMessage::Export))
+                        .padding([6, 12])
+                        .style(iced::theme::Button::Secondary),
+                )
+                .spacing(8)
+                .align_items(iced::Alignment::Center),
+        );
+
+        // cookies come sorted by domain, so each domain is one run
+        let mut listing = Column::new().spacing(2);
+        let mut rest = cookies.as_slice();
+        while let Some(first) = rest.first() {
+            let domain = display_domain(first);
+            let count = rest
+                .iter()
+                .take_while(|cookie| display_domain(cookie) == domain)
+                .count();
+            let (group, remaining) = rest.split_at(count);
+            rest = remaining;
+
+            let expanded = !search.is_empty() || page.expanded.contains(domain);
+            listing = listing.push(
+                Row::new()
+                    .push(
+                        button(
+                            text(format!("{} {}", if expanded { "▾" } else { "▸" }, domain))
+                                .font(Font::with_name("Ubuntu"))
+                                .size(13),
+                        )
+                        .on_press(Message::Cookies(CookieMessage::ToggleDomain(
+                            domain.to_string(),
+                        )))
+                        .padding([4, 8])
+                        .width(Length::Fill)
+                        .style(iced::theme::Button::Text),
+                    )
+                    .push(
+                        text(format!("{} cookies", group.len()))
+                            .size(12)
+                            .font(Font::with_name("Ubuntu"))
+                            .style(Color::from_rgb(0.6, 0.6, 0.6)),
+                    )
+                    .push(
+                        button(text("Delete all").font(Font::with_name("Ubuntu")).size(11))
+                            .on_press(Message::Cookies(CookieMessage::DeleteDomain(
+                                domain.to_string(),
+                            )))
+                            .padding([2, 8])
+                            .style(iced::theme::Button::Text),
+                    )
+                    .spacing(8)
+                    .align_items(iced::Alignment::Center),
+            );
+
+            if expanded {
+                for cookie in group {
+                    listing = listing.push(self.create_cookie_row(cookie));
+                }
+            }
+        }
+
+        if cookies.is_empty() {
+            listing = listing.push(
+                text(if search.is_empty() {
+                    "No cookies stored"
+                } else {
+                    "No cookies match your search"
+                })
+                .size(12)
+                .font(Font::with_name("Ubuntu"))
+                .style(Color::from_rgb(0.6, 0.6, 0.6)),
+            );
+        }
+
+        let mut body = Row::new()
+            .push(scrollable(listing).width(Length::FillPortion(3)))
+            .spacing(16);
+        if let Some(editor) = &page.editor {
+            body = body.push(self.create_cookie_editor(editor));
+        }
+
+        content = content
+            .push(body)
+            .spacing(16)
+            .padding(24)
+            .width(Length::Fill)
+            .height(Length::Fill);
+
+        container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.12, 0.12, 0.15))),
+                border: iced::Border {
+                    radius: 0.0.into(),
+                    width: 1.0,
+                    color: Color::from_rgb(0.25, 0.25, 0.3),
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+
+    fn create_cookie_row<'a>(&self, cookie: &'a Cookie) -> Element<'a, Message> {
+        let value = if cookie.value.chars().count() > 48 {
+            format!("{}...", cookie.value.chars().take(48).collect::<String>())
+        } else {
+            cookie.value.clone()
+        };
+
+        let mut attributes = vec![
+            format!("Path {}", cookie.path),
+            match cookie.expires {
+                Some(expires) => format!("Expires {}", expires.format(DATE_FORMAT)),
+                None => "Session".to_string(),
+            },
+        ];
+        if !cookie.is_host_only() {
+            attributes.push("Subdomains".to_string());
+        }
+        if cookie.secure {
+            attributes.push("Secure".to_string());
+        }
+        if cookie.http_only {
+            attributes.push("HttpOnly".to_string());
+        }
+        if let Some(same_site) = &cookie.same_site {
+            attributes.push(format!("SameSite={}", same_site));
+        }
+        if let Some(partition) = &cookie.partition_key {
+            attributes.push(format!("Partitioned under {}", partition));
+        }
+
+        let details = Column::new()
+            .push(
+                Row::new()
+                    .push(
+                        text(&cookie.name)
+                            .size(13)
+                            .font(Font::with_name("Ubuntu"))
+                            .style(Color::from_rgb(0.9, 0.9, 0.9)),
+                    )
+                    .push(
+                        text(value)
+                            .size(12)
+                            .font(Font::with_name("Ubuntu"))
+                            .style(Color::from_rgb(0.7, 0.7, 0.7)),
+                    )
+                    .spacing(8),
+            )
+            .push(
+                text(attributes.join(" · "))
+                    .size(11)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.55, 0.55, 0.6)),
+            )
+            .spacing(2)
+            .width(Length::Fill);
+
+        Row::new()
+            .push(details)
+            .push(
+                button(text("Edit").font(Font::with_name("Ubuntu")).size(11))
+                    .on_press(Message::Cookies(CookieMessage::Edit(cookie.key())))
+                    .padding([2, 8])
+                    .style(iced::theme::Button::Text),
+            )
+            .push(
+                button(text("Delete").font(Font::with_name("Ubuntu")).size(11))
+                    .on_press(Message::Cookies(CookieMessage::Delete(cookie.key())))
+                    .padding([2, 8])
+                    .style(iced::theme::Button::Text),
+            )
+            .spacing(8)
+            .padding([4, 8, 4, 28])
+            .align_items(iced::Alignment::Center)
+            .into()
+    }
+
+    fn create_cookie_editor<'a>(&'a self, editor: &'a CookieEditor) -> Element<'a, Message> {
+        let field = |label: &'a str, input: Element<'a, Message>| -> Element<'a, Message> {
+            Column::new()
+                .push(
+                    text(label)
+                        .size(11)
+                        .font(Font::with_name("Ubuntu"))
+                        .style(Color::from_rgb(0.6, 0.6, 0.6)),
+                )
+                .push(input)
+                .spacing(2)
+                .into()
+        };
+        let input = |placeholder: &str, value: &str, on_input: fn(String) -> CookieMessage| {
+            text_input(placeholder, value)
+                .on_input(move |value| Message::Cookies(on_input(value)))
+                .on_submit(Message::Cookies(CookieMessage::SaveEdit))
+                .padding(6)
+                .size(13)
+                .font(Font::with_name("Ubuntu"))
+        };
+
+        let mut form = Column::new()
+            .push(
+                text("Edit cookie")
+                    .size(16)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.9, 0.9, 0.9)),
+            )
+            .push(field(
+                "Name",
+                input("Name", &editor.name, CookieMessage::EditorNameChanged).into(),
+            ))
+            .push(field(
+                "Value",
+                input("Value", &editor.value, CookieMessage::EditorValueChanged).into(),
+            ))
+            .push(field(
+                "Domain",
+                input("Domain", &editor.domain, CookieMessage::EditorDomainChanged).into(),
+            ))
+            .push(
+                checkbox("Send to subdomains", editor.include_subdomains)
+                    .on_toggle(|include| {
+                        Message::Cookies(CookieMessage::EditorSubdomainsToggled(include))
+                    })
+                    .size(14)
+                    .text_size(12)
+                    .font(Font::with_name("Ubuntu")),
+            )
+            .push(field(
+                "Path",
+                input("/", &editor.path, CookieMessage::EditorPathChanged).into(),
+            ))
+            .push(field(
+                "Expires (UTC), empty for a session cookie",
+                input(
+                    DATE_FORMAT,
+                    &editor.expires,
+                    CookieMessage::EditorExpiresChanged,
+                )
+                .into(),
+            ))
+            .push(
+                Row::new()
+                    .push(
+                        checkbox("Secure", editor.secure)
+                            .on_toggle(|secure| {
+                                Message::Cookies(CookieMessage::EditorSecureToggled(secure))
+                            })
+                            .size(14)
+                            .text_size(12)
+                            .font(Font::with_name("Ubuntu")),
+                    )
+                    .push(
+                        checkbox("HttpOnly", editor.http_only)
+                            .on_toggle(|http_only| {
+                                Message::Cookies(CookieMessage::EditorHttpOnlyToggled(http_only))
+                            })
+                            .size(14)
+                            .text_size(12)
+                            .font(Font::with_name("Ubuntu")),
+                    )
+                    .spacing(16),
+            )
+            .push(field(
+                "SameSite",
+                pick_list(
+                    &SAME_SITE_OPTIONS[..],
+                    Some(editor.same_site),
+                    |same_site| Message::Cookies(CookieMessage::EditorSameSiteSelected(same_site)),
+                )
+                .padding(6)
+                .text_size(13)
+                .font(Font::with_name("Ubuntu"))
+                .into(),
+            ));
+
+        if let Some(partition) = &editor.partition_key {
+            form = form.push(
+                text(format!("Partitioned under {}", partition))
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.6, 0.6, 0.6)),
+            );
+        }
+
+        if let Some(error) = &editor.error {
+            form = form.push(
+                text(error)
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.95, 0.45, 0.45)),
+            );
+        }
+
+        form = form.push(
+            Row::new()
+                .push(
+                    button(text("Save").font(Font::with_name("Ubuntu")).size(13))
+                        .on_press(Message::Cookies(CookieMessage::SaveEdit))
+                        .padding([6, 12])
+                        .style(iced::theme::Button::Primary),
+                )
+                .push(
+                    button(text("Cancel").font(Font::with_name("Ubuntu")).size(13))
+                        .on_press(Message::Cookies(CookieMessage::CancelEdit))
+                        .padding([6, 12])
+                        .style(iced::theme::Button::Secondary),
+                )
+                .spacing(8),
+        );
+
+        container(form.spacing(10))
+            .width(Length::FillPortion(2))
+            .padding(12)
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.16, 0.16, 0.2))),
+                border: iced::Border {
+                    radius: 4.0.into(),
+                    width: 1.0,
+                    color: Color::from_rgb(0.25, 0.25, 0.3),
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+
+    pub fn create_cookie_exceptions_page(&self) -> Element<'_, Message> {
+        let page = &self.cookies_page;
+
+        let header = Row::new()
+            .push(
+                text("Cookie exceptions")
+                    .size(22)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.9, 0.9, 0.9)),
+            )
+            .push(
+                text_input("Site, e.g. example.com", &page.new_rule_site)
+                    .on_input(|site| Message::Cookies(CookieMessage::NewRuleSiteChanged(site)))
+                    .on_submit(Message::Cookies(CookieMessage::AddRule))
+                    .padding(6)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu"))
+                    .width(Length::Fill),
+            )
+            .push(
+                pick_list(
+                    &CookieRule::ALL[..],
+                    Some(page.new_rule.unwrap_or(CookieRule::Allow)),
+                    |rule| Message::Cookies(CookieMessage::NewRuleSelected(rule)),
+                )
+                .padding(6)
+                .text_size(13)
+                .font(Font::with_name("Ubuntu"))
+                .width(Length::Fixed(160.0)),
+            )
+            .push(
+                button(text("Add").font(Font::with_name("Ubuntu")).size(13))
+                    .on_press(Message::Cookies(CookieMessage::AddRule))
+                    .padding([6, 12])
+                    .style(iced::theme::Button::Primary),
+            )
+            .spacing(12)
+            .align_items(iced::Alignment::Center);
+
+        let mut content = Column::new().push(header).push(
+            text(format!(
+                "Always allowed sites are never auto-cleared, other cookies are cleared after {} days. \
+                 Session-only cookies are deleted when Bagel closes, blocked sites can't set any.",
+                self.config.privacy.auto_clear_cookies_days
+            ))
+            .size(12)
+            .font(Font::with_name("Ubuntu"))
+            .style(Color::from_rgb(0.6, 0.6, 0.6)),
+        );
+
+        if let Some(error) = &page.error {
+            content = content.push(
+                text(error)
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.95, 0.45, 0.45)),
+            );
+        }
+
+        let listing = if page.rules.is_empty() {
+            Column::new().push(
+                text("No cookie exceptions yet")
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.6, 0.6, 0.6)),
+            )
+        } else {
+            page.rules
+                .iter()
+                .fold(Column::new().spacing(2), |column, rule| {
+                    column.push(self.create_cookie_rule_row(rule))
+                })
+        };
+
+        content = content
+            .push(scrollable(listing).height(Length::Fill))
+            .spacing(16)
+            .padding(24)
+            .width(Length::Fill)
+            .height(Length::Fill);
+
+        container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.12, 0.12, 0.15))),
+                border: iced::Border {
+                    radius: 0.0.into(),
+                    width: 1.0,
+                    color: Color::from_rgb(0.25, 0.25, 0.3),
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+
+    fn create_cookie_rule_row<'a>(&self, rule: &'a SiteCookieRule) -> Element<'a, Message> {
+        let site = rule.site.clone();
+
+        Row::new()
+            .push(
+                text(&rule.site)
+                    .size(13)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.9, 0.9, 0.9))
+                    .width(Length::Fill),
+            )
+            .push(
+                pick_list(&CookieRule::ALL[..], Some(rule.rule), move |new_rule| {
+                    Message::Cookies(CookieMessage::ChangeRule(site.clone(), new_rule))
+                })
+                .padding([2, 8])
+                .text_size(12)
+                .font(Font::with_name("Ubuntu"))
+                .width(Length::Fixed(160.0)),
+            )
+            .push(
+                button(text("Remove").font(Font::with_name("Ubuntu")).size(11))
+                    .on_press(Message::Cookies(CookieMessage::RemoveRule(
+                        rule.site.clone(),
+                    )))
+                    .padding([2, 8])
+                    .style(iced::theme::Button::Text),
+            )
+            .spacing(8)
+            .align_items(iced::Alignment::Center)
+            .into()
+    }
+}
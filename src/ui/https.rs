@@ -0,0 +1,175 @@
+// https-first: http urls are tried over https, and when that fails the tab
+// shows this page offering plain http instead. in https-only mode or for
+// HSTS hosts it just explains why the page won't load
+use crate::storage::Shield;
+use crate::ui::{BagelApp, Message};
+use crate::utils::log_error;
+use iced::{
+    widget::{button, container, text, Column, Row},
+    Application, Background, Color, Command, Element, Font, Length,
+};
+use uuid::Uuid;
+
+/// What a tab shows after the https try of an http url failed.
+#[derive(Debug, Clone)]
+pub struct HttpsFallback {
+    pub http_url: String,
+    /// Why the https try failed.
+    pub reason: String,
+    /// Https-only mode is on or the host uses HSTS, so there's no going on
+    /// over http.
+    pub strict: bool,
+    pub hsts: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum HttpsMessage {
+    /// The https try of the http url failed with the given reason.
+    Unavailable(Uuid, String, String),
+    ContinueOverHttp,
+    LeaveSite,
+}
+
+impl BagelApp {
+    pub fn update_https(&mut self, message: HttpsMessage) -> Command<Message> {
+        match message {
+            HttpsMessage::Unavailable(tab_id, http_url, reason) => {
+                let security = self.webview_manager.lock().unwrap().security_manager();
+                let host = url::Url::parse(&http_url)
+                    .ok()
+                    .and_then(|url| url.host_str().map(str::to_string))
+                    .unwrap_or_default();
+                self.https_fallbacks.insert(
+                    tab_id,
+                    HttpsFallback {
+                        http_url: http_url.clone(),
+                        reason: reason.clone(),
+                        strict: security.https_required(&host),
+                        hsts: security.is_hsts_host(&host),
+                    },
+                );
+                let command = self.update(Message::PageLoaded(tab_id, Err(reason)));
+                if let Some(tab) = self.tabs.iter_mut().find(|tab| tab.id == tab_id) {
+                    tab.url = http_url;
+                    tab.title = "Secure connection unavailable".to_string();
+                }
+                command
+            }
+            HttpsMessage::ContinueOverHttp => {
+                let Some(fallback) = self
+                    .tabs
+                    .get(self.active_tab_index)
+                    .and_then(|tab| self.https_fallbacks.get(&tab.id))
+                    .filter(|fallback| !fallback.strict)
+                    .cloned()
+                else {
+                    return Command::none();
+                };
+                let Some(host) = url::Url::parse(&fallback.http_url)
+                    .ok()
+                    .and_then(|url| url.host_str().map(str::to_string))
+                else {
+                    return Command::none();
+                };
+
+                // remembered as the site's https upgrade shield, so it can
+                // be switched back on from the shields popover
+                let security = self.webview_manager.lock().unwrap().security_manager();
+                if let Err(e) = security.set_site_shield(&host, Shield::HttpsUpgrade, false) {
+                    log_error("Failed to remember http for site", e.as_ref());
+                    return Command::none();
+                }
+                self.update(Message::NavigateTo(fallback.http_url))
+            }
+            HttpsMessage::LeaveSite => self.update(Message::NavigateTo("bagel://home".to_string())),
+        }
+    }
+
+    pub fn create_https_fallback<'a>(&self, fallback: &'a HttpsFallback) -> Element<'a, Message> {
+        let host = url::Url::parse(&fallback.http_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+
+        let mut content = Column::new()
+            .push(
+                text(format!("{} doesn't support a secure connection", host))
+                    .size(24)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.95, 0.75, 0.45)),
+            )
+            .push(
+                text(if fallback.hsts {
+                    "This site asked to only ever be loaded securely, so Bagel won't fall \
+                     back to an insecure connection."
+                } else if fallback.strict {
+                    "Bagel is in HTTPS-only mode, so it won't load this site over an \
+                     insecure connection."
+                } else {
+                    "Bagel tried to load this site over https first, but couldn't. You can \
+                     continue over http, where anyone on the network can see or change what \
+                     you send and receive."
+                })
+                .size(14)
+                .font(Font::with_name("Ubuntu"))
+                .style(Color::from_rgb(0.8, 0.8, 0.8)),
+            )
+            .push(
+                text(&fallback.reason)
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.6, 0.6, 0.6)),
+            );
+
+        let mut actions = Row::new().push(
+            button(text("Go back").font(Font::with_name("Ubuntu")).size(13))
+                .on_press(Message::Https(HttpsMessage::LeaveSite))
+                .padding([6, 12])
+                .style(iced::theme::Button::Primary),
+        );
+        if !fallback.strict {
+            actions = actions.push(
+                button(
+                    text("Continue to HTTP site")
+                        .font(Font::with_name("Ubuntu"))
+                        .size(13),
+                )
+                .on_press(Message::Https(HttpsMessage::ContinueOverHttp))
+                .padding([6, 12])
+                .style(iced::theme::Button::Secondary),
+            );
+            content = content.push(
+                text(format!(
+                    "Bagel will remember this for {}, turn the https upgrade shield back on \
+                     to try https again.",
+                    host
+                ))
+                .size(12)
+                .font(Font::with_name("Ubuntu"))
+                .style(Color::from_rgb(0.6, 0.6, 0.6)),
+            );
+        }
+
+        content = content
+            .push(actions.spacing(8))
+            .spacing(16)
+            .padding(32)
+            .max_width(640);
+
+        container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.1, 0.1, 0.13))),
+                border: iced::Border {
+                    radius: 0.0.into(),
+                    width: 0.0,
+                    color: Color::TRANSPARENT,
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+}
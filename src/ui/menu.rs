@@ -1,3 +1,127 @@
 // Placeholder for menu components
 // like the hamburger menu and stuff
 // settings is going to be a mess
+use crate::ui::{BagelApp, CookieMessage, Message};
+use iced::{
+    widget::{button, container, text, Column},
+    Application, Background, Color, Command, Element, Font, Length,
+};
+
+#[derive(Debug, Clone)]
+pub enum MenuMessage {
+    OpenPage(String),
+    ClearSiteCookies,
+}
+
+impl BagelApp {
+    pub fn update_menu(&mut self, message: MenuMessage) -> Command<Message> {
+        match message {
+            MenuMessage::OpenPage(url) => {
+                self.show_menu = false;
+                self.update(Message::NavigateTo(url))
+            }
+            MenuMessage::ClearSiteCookies => {
+                self.show_menu = false;
+                self.update_cookies(CookieMessage::ClearCurrentSite)
+            }
+        }
+    }
+
+    pub fn create_menu(&self) -> Element<'_, Message> {
+        let current_site = self.current_site();
+        let items = Column::new()
+            .push(
+                button(text("Bookmarks").font(Font::with_name("Ubuntu")).size(13))
+                    .on_press(Message::Menu(MenuMessage::OpenPage(
+                        "bagel://bookmarks".to_string(),
+                    )))
+                    .padding([6, 12])
+                    .width(Length::Fill)
+                    .style(iced::theme::Button::Text),
+            )
+            .push(
+                button(text("Cookies").font(Font::with_name("Ubuntu")).size(13))
+                    .on_press(Message::Menu(MenuMessage::OpenPage(
+                        "bagel://cookies".to_string(),
+                    )))
+                    .padding([6, 12])
+                    .width(Length::Fill)
+                    .style(iced::theme::Button::Text),
+            )
+            .push(
+                button(
+                    text(match &current_site {
+                        Some(site) => format!("Clear cookies for {}", site),
+                        None => "Clear cookies for this site".to_string(),
+                    })
+                    .font(Font::with_name("Ubuntu"))
+                    .size(13),
+                )
+                .on_press_maybe(
+                    current_site
+                        .as_ref()
+                        .map(|_| Message::Menu(MenuMessage::ClearSiteCookies)),
+                )
+                .padding([6, 12])
+                .width(Length::Fill)
+                .style(iced::theme::Button::Text),
+            )
+            .push(
+                button(
+                    text("Privacy report")
+                        .font(Font::with_name("Ubuntu"))
+                        .size(13),
+                )
+                .on_press(Message::Menu(MenuMessage::OpenPage(
+                    "bagel://privacy".to_string(),
+                )))
+                .padding([6, 12])
+                .width(Length::Fill)
+                .style(iced::theme::Button::Text),
+            )
+            .push(
+                button(
+                    text("Cookie exceptions")
+                        .font(Font::with_name("Ubuntu"))
+                        .size(13),
+                )
+                .on_press(Message::Menu(MenuMessage::OpenPage(
+                    "bagel://cookie-exceptions".to_string(),
+                )))
+                .padding([6, 12])
+                .width(Length::Fill)
+                .style(iced::theme::Button::Text),
+            )
+            .push(
+                button(
+                    text("Certificate exceptions")
+                        .font(Font::with_name("Ubuntu"))
+                        .size(13),
+                )
+                .on_press(Message::Menu(MenuMessage::OpenPage(
+                    "bagel://certificates".to_string(),
+                )))
+                .padding([6, 12])
+                .width(Length::Fill)
+                .style(iced::theme::Button::Text),
+            )
+            .spacing(2)
+            .width(Length::Fixed(220.0));
+
+        container(items)
+            .width(Length::Fill)
+            .padding(4)
+            .align_x(iced::alignment::Horizontal::Right)
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.15, 0.15, 0.18))),
+                border: iced::Border {
+                    radius: 0.0.into(),
+                    width: 1.0,
+                    color: Color::from_rgb(0.25, 0.25, 0.3),
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+}
@@ -1,7 +1,18 @@
 pub mod window;
 pub mod toolbar;
 pub mod menu;
+pub mod bookmarks;
+pub mod cookies;
+pub mod privacy;
+pub mod certificates;
+pub mod https;
 
 pub use window::*;
+#[allow(unused_imports)]
 pub use toolbar::*;
-pub use menu::*;
\ No newline at end of file
+pub use menu::*;
+pub use bookmarks::*;
+pub use cookies::*;
+pub use privacy::*;
+pub use certificates::*;
+pub use https::*;
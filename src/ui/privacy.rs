@@ -0,0 +1,671 @@
+// privacy ui: the shield next to the address bar with its per-site toggles,
+// and the bagel://privacy report of what the filters blocked, per tab and
+// over time
+use crate::browser::{
+    BlockedRequest, FilterCategory, FilterListInfo, FilterListSource, ListBlockStats,
+};
+use crate::storage::{DailyBlockTotal, PrivacyReportManager, Shield, TrackerDomainTotal};
+use crate::ui::{BagelApp, Message};
+use crate::utils::log_error;
+use chrono::{Duration, Utc};
+use iced::{
+    widget::{button, checkbox, container, scrollable, text, tooltip, Column, Row},
+    Application, Background, Color, Command, Element, Font, Length,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportPeriod {
+    #[default]
+    Week,
+    Month,
+    AllTime,
+}
+
+impl ReportPeriod {
+    const ALL: [ReportPeriod; 3] = [
+        ReportPeriod::Week,
+        ReportPeriod::Month,
+        ReportPeriod::AllTime,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            ReportPeriod::Week => "Last 7 days",
+            ReportPeriod::Month => "Last 30 days",
+            ReportPeriod::AllTime => "All time",
+        }
+    }
+
+    fn since(&self) -> Option<chrono::DateTime<Utc>> {
+        match self {
+            ReportPeriod::Week => Some(Utc::now() - Duration::days(7)),
+            ReportPeriod::Month => Some(Utc::now() - Duration::days(30)),
+            ReportPeriod::AllTime => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PrivacyPage {
+    pub period: ReportPeriod,
+    pub domains: Vec<TrackerDomainTotal>,
+    pub days: Vec<DailyBlockTotal>,
+    pub lists: Vec<FilterListInfo>,
+    /// Blocks per list since startup.
+    pub list_blocks: Vec<ListBlockStats>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum PrivacyMessage {
+    SelectPeriod(ReportPeriod),
+    ToggleList(FilterListSource, bool),
+    /// Picks up edits to the custom rules file.
+    ReloadCustomFilters,
+    ClearReport,
+    ToggleShields,
+    SetShield(Shield, bool),
+    ResetShields,
+    OpenReport,
+}
+
+impl BagelApp {
+    pub fn refresh_privacy_report(&mut self) {
+        let security = self.webview_manager.lock().unwrap().security_manager();
+        let since = self.privacy_page.period.since();
+
+        let report = PrivacyReportManager::new(self.config.data_dir())
+            .and_then(|report| Ok((report.domain_totals(since)?, report.daily_totals(since)?)));
+        match report {
+            Ok((domains, days)) => {
+                self.privacy_page.domains = domains;
+                self.privacy_page.days = days;
+            }
+            Err(e) => log_error("Failed to load privacy report", e.as_ref()),
+        }
+
+        match security.filter_lists() {
+            Ok(lists) => self.privacy_page.lists = lists,
+            Err(e) => log_error("Failed to load filter lists", e.as_ref()),
+        }
+        self.privacy_page.list_blocks = security.block_statistics();
+    }
+
+    pub fn update_privacy(&mut self, message: PrivacyMessage) -> Command<Message> {
+        match message {
+            PrivacyMessage::ToggleShields => {
+                self.show_shields = !self.show_shields;
+                self.show_menu = false;
+                self.show_certificate = false;
+                return Command::none();
+            }
+            PrivacyMessage::SetShield(shield, enabled) => {
+                let Some(site) = self.current_site() else {
+                    return Command::none();
+                };
+                let security = self.webview_manager.lock().unwrap().security_manager();
+                if let Err(e) = security.set_site_shield(&site, shield, enabled) {
+                    log_error("Failed to change site shields", e.as_ref());
+                }
+                return self.reload_with_shields();
+            }
+            PrivacyMessage::ResetShields => {
+                let Some(site) = self.current_site() else {
+                    return Command::none();
+                };
+                let security = self.webview_manager.lock().unwrap().security_manager();
+                if let Err(e) = security.reset_site_shields(&site) {
+                    log_error("Failed to reset site shields", e.as_ref());
+                }
+                return self.reload_with_shields();
+            }
+            PrivacyMessage::OpenReport => {
+                self.show_shields = false;
+                return self.update(Message::NavigateTo("bagel://privacy".to_string()));
+            }
+            PrivacyMessage::SelectPeriod(period) => {
+                self.privacy_page.period = period;
+            }
+            PrivacyMessage::ToggleList(source, enabled) => {
+                let security = self.webview_manager.lock().unwrap().security_manager();
+                if let Err(e) = security.set_filter_list_enabled(&source, enabled) {
+                    log_error("Failed to switch filter list", e.as_ref());
+                }
+            }
+            PrivacyMessage::ReloadCustomFilters => {
+                let security = self.webview_manager.lock().unwrap().security_manager();
+                security.reload_filters();
+                self.privacy_page.status = Some("Reloaded your rules".to_string());
+            }
+            PrivacyMessage::ClearReport => {
+                match PrivacyReportManager::new(self.config.data_dir())
+                    .and_then(|report| report.clear())
+                {
+                    Ok(count) => {
+                        self.privacy_page.status =
+                            Some(format!("Cleared {} blocked requests", count))
+                    }
+                    Err(e) => log_error("Failed to clear privacy report", e.as_ref()),
+                }
+            }
+        }
+        self.refresh_privacy_report();
+        Command::none()
+    }
+
+    /// Loads the page again so the changed shields apply, leaving the
+    /// popover open for more changes.
+    fn reload_with_shields(&mut self) -> Command<Message> {
+        let Some(url) = self
+            .tabs
+            .get(self.active_tab_index)
+            .map(|tab| tab.url.clone())
+        else {
+            return Command::none();
+        };
+        let command = self.update(Message::NavigateTo(url));
+        self.show_shields = true;
+        command
+    }
+
+    /// What the filters blocked in the active tab.
+    pub fn active_tab_blocked(&self) -> &[BlockedRequest] {
+        self.tabs
+            .get(self.active_tab_index)
+            .and_then(|tab| self.blocked_requests.get(&tab.id))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The shield at the end of the address bar, with how much was blocked on
+    /// the page. Opens the shields popover.
+    pub fn create_shield(&self) -> Element<'_, Message> {
+        let blocked = self.active_tab_blocked();
+        let level = self.tabs.get(self.active_tab_index).and_then(|tab| {
+            let security = self.webview_manager.lock().unwrap().security_manager();
+            security
+                .check_url_security(&tab.url, blocked, self.active_tab_mixed_content())
+                .ok()
+        });
+
+        let summary = match &level {
+            Some(level) if !blocked.is_empty() => {
+                let trackers = blocked
+                    .iter()
+                    .filter(|request| request.category == FilterCategory::Tracking)
+                    .count();
+                format!(
+                    "{} ads and {} trackers blocked on this page",
+                    level.ads_blocked, trackers
+                )
+            }
+            _ => "Nothing blocked on this page".to_string(),
+        };
+
+        let shield = button(
+            text(if blocked.is_empty() {
+                "Shield".to_string()
+            } else {
+                format!("Shield {}", blocked.len())
+            })
+            .font(Font::with_name("Ubuntu"))
+            .size(12)
+            .style(if blocked.is_empty() {
+                Color::from_rgb(0.7, 0.7, 0.7)
+            } else {
+                Color::from_rgb(0.55, 0.85, 0.6)
+            }),
+        )
+        .on_press(Message::Privacy(PrivacyMessage::ToggleShields))
+        .padding([7, 10])
+        .style(iced::theme::Button::Secondary);
+
+        tooltip(
+            shield,
+            container(text(summary).font(Font::with_name("Ubuntu")).size(12))
+                .padding(6)
+                .style(container::Appearance {
+                    background: Some(Background::Color(Color::from_rgb(0.2, 0.2, 0.24))),
+                    border: iced::Border {
+                        radius: 4.0.into(),
+                        width: 1.0,
+                        color: Color::from_rgb(0.3, 0.3, 0.35),
+                    },
+                    shadow: iced::Shadow::default(),
+                    text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+                }),
+            tooltip::Position::Bottom,
+        )
+        .into()
+    }
+
+    /// Per-site toggles for the page in the active tab, under the toolbar.
+    pub fn create_shields_popover(&self) -> Element<'_, Message> {
+        let site = self.current_site();
+        let security = self.webview_manager.lock().unwrap().security_manager();
+        let blocked = self.active_tab_blocked();
+
+        let mut items = Column::new()
+            .push(
+                text(match &site {
+                    Some(site) => format!("Shields for {}", site),
+                    None => "Shields".to_string(),
+                })
+                .size(14)
+                .font(Font::with_name("Ubuntu"))
+                .style(Color::from_rgb(0.9, 0.9, 0.9)),
+            )
+            .push(note(match blocked.len() {
+                0 => "Nothing blocked on this page".to_string(),
+                1 => "1 request blocked on this page".to_string(),
+                count => format!("{} requests blocked on this page", count),
+            }))
+            .spacing(8)
+            .padding(12)
+            .width(Length::Fixed(260.0));
+
+        match &site {
+            Some(site) => {
+                let shields = security.shields_for(site);
+                for shield in Shield::ALL {
+                    items = items.push(
+                        checkbox(shield.label(), shields.get(shield))
+                            .on_toggle(move |enabled| {
+                                Message::Privacy(PrivacyMessage::SetShield(shield, enabled))
+                            })
+                            .size(14)
+                            .text_size(13)
+                            .font(Font::with_name("Ubuntu")),
+                    );
+                }
+                items = items.push(note(
+                    "Turn a shield off if it breaks this site, the page reloads".to_string(),
+                ));
+
+                let overridden = security.has_site_shields(site);
+                items = items.push(
+                    button(
+                        text("Use default settings")
+                            .font(Font::with_name("Ubuntu"))
+                            .size(12),
+                    )
+                    .on_press_maybe(
+                        overridden.then_some(Message::Privacy(PrivacyMessage::ResetShields)),
+                    )
+                    .padding([4, 10])
+                    .style(iced::theme::Button::Secondary),
+                );
+            }
+            None => {
+                items = items.push(note("Shields can be changed on web pages".to_string()));
+            }
+        }
+
+        items = items.push(
+            button(
+                text("Privacy report")
+                    .font(Font::with_name("Ubuntu"))
+                    .size(12),
+            )
+            .on_press(Message::Privacy(PrivacyMessage::OpenReport))
+            .padding([4, 10])
+            .style(iced::theme::Button::Text),
+        );
+
+        container(container(items).style(container::Appearance {
+            background: Some(Background::Color(Color::from_rgb(0.15, 0.15, 0.18))),
+            border: iced::Border {
+                radius: 4.0.into(),
+                width: 1.0,
+                color: Color::from_rgb(0.25, 0.25, 0.3),
+            },
+            shadow: iced::Shadow::default(),
+            text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+        }))
+        .width(Length::Fill)
+        .padding([4, 110, 4, 4])
+        .align_x(iced::alignment::Horizontal::Right)
+        .into()
+    }
+
+    pub fn create_privacy_page(&self) -> Element<'_, Message> {
+        let page = &self.privacy_page;
+
+        let mut periods = Row::new().spacing(4);
+        for period in ReportPeriod::ALL {
+            periods = periods.push(
+                button(
+                    text(period.label())
+                        .font(Font::with_name("Ubuntu"))
+                        .size(12),
+                )
+                .on_press(Message::Privacy(PrivacyMessage::SelectPeriod(period)))
+                .padding([4, 10])
+                .style(if period == page.period {
+                    iced::theme::Button::Primary
+                } else {
+                    iced::theme::Button::Secondary
+                }),
+            );
+        }
+
+        let header = Row::new()
+            .push(
+                text("Privacy report")
+                    .size(22)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.9, 0.9, 0.9))
+                    .width(Length::Fill),
+            )
+            .push(periods)
+            .push(
+                button(
+                    text("Clear report")
+                        .font(Font::with_name("Ubuntu"))
+                        .size(12),
+                )
+                .on_press(Message::Privacy(PrivacyMessage::ClearReport))
+                .padding([4, 10])
+                .style(iced::theme::Button::Text),
+            )
+            .spacing(12)
+            .align_items(iced::Alignment::Center);
+
+        let (ads, tracking, custom) = page.days.iter().fold((0, 0, 0), |totals, day| {
+            (
+                totals.0 + day.ads,
+                totals.1 + day.tracking,
+                totals.2 + day.custom,
+            )
+        });
+        let mut summary = format!("{} ads and {} trackers blocked", ads, tracking);
+        if custom > 0 {
+            summary.push_str(&format!(", {} more by your own rules", custom));
+        }
+
+        let content = Column::new()
+            .push(header)
+            .push(note(page.status.clone().unwrap_or(summary)))
+            .push(section("Over time"))
+            .push(self.create_daily_chart())
+            .push(section("Trackers"))
+            .push(self.create_tracker_table())
+            .push(section("Open tabs"))
+            .push(self.create_tab_blocks())
+            .push(section("Filter lists"))
+            .push(self.create_list_table())
+            .spacing(12)
+            .padding(24)
+            .max_width(960);
+
+        container(scrollable(content).width(Length::Fill).height(Length::Fill))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.12, 0.12, 0.15))),
+                border: iced::Border {
+                    radius: 0.0.into(),
+                    width: 1.0,
+                    color: Color::from_rgb(0.25, 0.25, 0.3),
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+
+    /// A bar per day, ads and trackers side by side.
+    fn create_daily_chart(&self) -> Element<'_, Message> {
+        let days = &self.privacy_page.days;
+        if days.is_empty() {
+            return note("Nothing blocked yet".to_string());
+        }
+
+        let most = days
+            .iter()
+            .map(DailyBlockTotal::total)
+            .max()
+            .unwrap_or(1)
+            .max(1);
+        let bar = |count: u64, color: Color| {
+            container(text(""))
+                .width(Length::Fixed(400.0 * count as f32 / most as f32))
+                .height(Length::Fixed(10.0))
+                .style(container::Appearance {
+                    background: Some(Background::Color(color)),
+                    ..Default::default()
+                })
+        };
+
+        let mut chart = Column::new().spacing(4);
+        // the last month is plenty to read as a chart
+        for day in days.iter().rev().take(30).rev() {
+            chart = chart.push(
+                Row::new()
+                    .push(
+                        text(day.day.format("%b %d").to_string())
+                            .size(11)
+                            .font(Font::with_name("Ubuntu"))
+                            .style(Color::from_rgb(0.6, 0.6, 0.6))
+                            .width(Length::Fixed(60.0)),
+                    )
+                    .push(bar(day.ads, Color::from_rgb(0.85, 0.55, 0.3)))
+                    .push(bar(day.tracking, Color::from_rgb(0.4, 0.6, 0.9)))
+                    .push(bar(day.custom, Color::from_rgb(0.6, 0.6, 0.65)))
+                    .push(
+                        text(day.total().to_string())
+                            .size(11)
+                            .font(Font::with_name("Ubuntu"))
+                            .style(Color::from_rgb(0.7, 0.7, 0.7)),
+                    )
+                    .spacing(6)
+                    .align_items(iced::Alignment::Center),
+            );
+        }
+        chart
+            .push(note(
+                "Orange is ads, blue is trackers, grey is your own rules".to_string(),
+            ))
+            .into()
+    }
+
+    fn create_tracker_table(&self) -> Element<'_, Message> {
+        let domains = &self.privacy_page.domains;
+        if domains.is_empty() {
+            return note("No trackers blocked in this period".to_string());
+        }
+
+        let mut table = Column::new().spacing(2).push(table_row(
+            ["Domain", "Kind", "Blocked", "Sites", "Last blocked"],
+            Color::from_rgb(0.6, 0.6, 0.6),
+        ));
+        for domain in domains.iter().take(100) {
+            table = table.push(table_row(
+                [
+                    &domain.domain,
+                    category_label(domain.category),
+                    &domain.blocked.to_string(),
+                    &domain.sites.to_string(),
+                    &domain.last_blocked.format("%Y-%m-%d %H:%M").to_string(),
+                ],
+                Color::from_rgb(0.85, 0.85, 0.85),
+            ));
+        }
+        if domains.len() > 100 {
+            table = table.push(note(format!("and {} more", domains.len() - 100)));
+        }
+        table.into()
+    }
+
+    /// Every blocked request in the open tabs, newest page first.
+    fn create_tab_blocks(&self) -> Element<'_, Message> {
+        let mut listing = Column::new().spacing(4);
+        let mut any = false;
+
+        for tab in &self.tabs {
+            let Some(blocked) = self.blocked_requests.get(&tab.id) else {
+                continue;
+            };
+            if blocked.is_empty() {
+                continue;
+            }
+            any = true;
+
+            listing = listing.push(
+                text(format!("{} · {} blocked", tab.title, blocked.len()))
+                    .size(13)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.85, 0.85, 0.85)),
+            );
+            for request in blocked {
+                let url = if request.url.chars().count() > 90 {
+                    format!("{}...", request.url.chars().take(90).collect::<String>())
+                } else {
+                    request.url.clone()
+                };
+                listing = listing.push(
+                    Column::new()
+                        .push(
+                            text(url)
+                                .size(12)
+                                .font(Font::with_name("Ubuntu"))
+                                .style(Color::from_rgb(0.8, 0.8, 0.8)),
+                        )
+                        .push(
+                            text(format!(
+                                "{} · {} · {} in {}",
+                                request.request_type.as_str(),
+                                category_label(request.category),
+                                request.rule,
+                                request.list
+                            ))
+                            .size(11)
+                            .font(Font::with_name("Ubuntu"))
+                            .style(Color::from_rgb(0.55, 0.55, 0.6)),
+                        )
+                        .padding([0, 0, 0, 16]),
+                );
+            }
+        }
+
+        if !any {
+            return note("Nothing blocked in your open tabs".to_string());
+        }
+        listing.into()
+    }
+
+    fn create_list_table(&self) -> Element<'_, Message> {
+        let page = &self.privacy_page;
+        let mut table = Column::new().spacing(2);
+
+        for list in &page.lists {
+            let blocked = page
+                .list_blocks
+                .iter()
+                .find(|stats| stats.list == list.name)
+                .map(|stats| stats.blocked)
+                .unwrap_or(0);
+            let detail = match list.kind {
+                Some(kind) => format!(
+                    "{} · {} rules · {} blocked since start",
+                    kind.as_str(),
+                    list.stats.filters + list.stats.hosts + list.stats.cosmetic,
+                    blocked
+                ),
+                None if list.enabled => "not downloaded yet".to_string(),
+                None => "off".to_string(),
+            };
+
+            let source = list.source.clone();
+            table = table.push(
+                Row::new()
+                    .push(
+                        checkbox(&list.name, list.enabled)
+                            .on_toggle(move |enabled| {
+                                Message::Privacy(PrivacyMessage::ToggleList(
+                                    source.clone(),
+                                    enabled,
+                                ))
+                            })
+                            .size(14)
+                            .text_size(13)
+                            .font(Font::with_name("Ubuntu"))
+                            .width(Length::FillPortion(2)),
+                    )
+                    .push(
+                        text(detail)
+                            .size(11)
+                            .font(Font::with_name("Ubuntu"))
+                            .style(Color::from_rgb(0.6, 0.6, 0.6))
+                            .width(Length::FillPortion(3)),
+                    )
+                    .spacing(8)
+                    .align_items(iced::Alignment::Center),
+            );
+        }
+
+        let custom_path = self
+            .webview_manager
+            .lock()
+            .unwrap()
+            .security_manager()
+            .custom_filters_path();
+        table
+            .push(
+                Row::new()
+                    .push(note(format!(
+                        "Your own rules go in {}",
+                        custom_path.display()
+                    )))
+                    .push(
+                        button(text("Reload").font(Font::with_name("Ubuntu")).size(12))
+                            .on_press(Message::Privacy(PrivacyMessage::ReloadCustomFilters))
+                            .padding([4, 10])
+                            .style(iced::theme::Button::Text),
+                    )
+                    .spacing(8)
+                    .align_items(iced::Alignment::Center),
+            )
+            .into()
+    }
+}
+
+fn category_label(category: FilterCategory) -> &'static str {
+    match category {
+        FilterCategory::Ads => "Ads",
+        FilterCategory::Tracking => "Tracker",
+        FilterCategory::Custom => "Custom rule",
+    }
+}
+
+fn section<'a>(title: &str) -> Element<'a, Message> {
+    text(title)
+        .size(16)
+        .font(Font::with_name("Ubuntu"))
+        .style(Color::from_rgb(0.8, 0.8, 0.8))
+        .into()
+}
+
+fn note<'a>(message: String) -> Element<'a, Message> {
+    text(message)
+        .size(12)
+        .font(Font::with_name("Ubuntu"))
+        .style(Color::from_rgb(0.6, 0.6, 0.6))
+        .into()
+}
+
+fn table_row<'a>(cells: [&str; 5], color: Color) -> Element<'a, Message> {
+    let widths = [4, 2, 1, 1, 2];
+    let mut row = Row::new().spacing(8);
+    for (cell, width) in cells.into_iter().zip(widths) {
+        row = row.push(
+            text(cell)
+                .size(12)
+                .font(Font::with_name("Ubuntu"))
+                .style(color)
+                .width(Length::FillPortion(width)),
+        );
+    }
+    row.into()
+}
@@ -1,10 +1,22 @@
-use crate::browser::{Tab, WebViewManager};
-use crate::utils::BrowserConfig;
+use crate::browser::{
+    BlockKind, BlockedRequest, PageLoader, PageResponse, RequestType, Tab, UntrustedCertificate,
+    WebViewManager,
+};
+use crate::storage::{Bookmark, BookmarkFolder, BookmarkManager};
+use crate::ui::{
+    BookmarkDialog, BookmarkMessage, BookmarksPage, CertificateMessage, CertificateViewer,
+    CertificateWarning, CertificatesPage, CookieMessage, CookiesPage, HttpsFallback, HttpsMessage,
+    MenuMessage, PrivacyMessage, PrivacyPage,
+};
+use crate::utils::{log_error, log_security_event, BrowserConfig};
 use iced::{
-    widget::{button, container, text, text_input, Column, Row},
-    {Application, Background, Color, Command, Element, Length, Theme}, {Font, Settings},
+    widget::{button, container, scrollable, text, text_input, Column, Row},
+    {Application, Background, Color, Command, Element, Length, Subscription, Theme},
+    {Font, Settings},
 };
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use uuid::Uuid;
 
 pub struct BagelApp {
@@ -13,6 +25,26 @@ pub struct BagelApp {
     pub active_tab_index: usize,
     pub address_bar_value: String,
     pub is_loading: bool,
+    pub config: BrowserConfig,
+    pub bookmark_manager: Option<BookmarkManager>,
+    pub bookmarks: Vec<Bookmark>,
+    pub bookmark_folders: Vec<BookmarkFolder>,
+    pub bookmark_dialog: Option<BookmarkDialog>,
+    pub bookmarks_page: BookmarksPage,
+    pub cookies_page: CookiesPage,
+    pub privacy_page: PrivacyPage,
+    pub show_menu: bool,
+    pub show_shields: bool,
+    pub show_certificate: bool,
+    pub certificate_viewer: CertificateViewer,
+    pub certificates_page: CertificatesPage,
+    pub pages: HashMap<Uuid, Result<PageResponse, String>>,
+    /// What the filters blocked for each tab's current page.
+    pub blocked_requests: HashMap<Uuid, Vec<BlockedRequest>>,
+    /// Tabs stopped by a certificate we don't trust, showing the warning.
+    pub certificate_warnings: HashMap<Uuid, CertificateWarning>,
+    /// Tabs whose http url couldn't be loaded over https first.
+    pub https_fallbacks: HashMap<Uuid, HttpsFallback>,
 }
 
 #[derive(Debug, Clone)]
@@ -27,6 +59,16 @@ pub enum Message {
     Reload,
     AddBookmark,
     ShowMenu,
+    Bookmarks(BookmarkMessage),
+    Cookies(CookieMessage),
+    Menu(MenuMessage),
+    Privacy(PrivacyMessage),
+    Certificates(CertificateMessage),
+    Https(HttpsMessage),
+    PageLoaded(Uuid, Result<PageResponse, String>),
+    UpdateFilterLists,
+    FilterListsUpdated(Result<usize, String>),
+    ClearOldCookies,
 }
 
 impl Application for BagelApp {
@@ -37,7 +79,15 @@ impl Application for BagelApp {
 
     fn new(flags: Self::Flags) -> (Self, Command<Message>) {
         let config = flags.0;
-        let webview_manager = Arc::new(Mutex::new(WebViewManager::new(config)));
+        let webview_manager = Arc::new(Mutex::new(WebViewManager::new(config.clone())));
+
+        let bookmark_manager = match BookmarkManager::new(config.data_dir()) {
+            Ok(manager) => Some(manager),
+            Err(e) => {
+                log_error("Failed to open bookmarks database", e.as_ref());
+                None
+            }
+        };
 
         // start with a fresh tab
         // later on we can configure with onboarding whether
@@ -56,16 +106,35 @@ impl Application for BagelApp {
             is_muted: false,
         };
 
-        (
-            BagelApp {
-                webview_manager,
-                tabs: vec![initial_tab],
-                active_tab_index: 0,
-                address_bar_value: String::new(),
-                is_loading: false,
-            },
-            Command::none(),
-        )
+        let mut app = BagelApp {
+            webview_manager,
+            tabs: vec![initial_tab],
+            active_tab_index: 0,
+            address_bar_value: String::new(),
+            is_loading: false,
+            config,
+            bookmark_manager,
+            bookmarks: Vec::new(),
+            bookmark_folders: Vec::new(),
+            bookmark_dialog: None,
+            bookmarks_page: BookmarksPage::default(),
+            cookies_page: CookiesPage::default(),
+            privacy_page: PrivacyPage::default(),
+            show_menu: false,
+            show_shields: false,
+            show_certificate: false,
+            certificate_viewer: CertificateViewer::default(),
+            certificates_page: CertificatesPage::default(),
+            pages: HashMap::new(),
+            blocked_requests: HashMap::new(),
+            certificate_warnings: HashMap::new(),
+            https_fallbacks: HashMap::new(),
+        };
+        app.refresh_bookmarks();
+        app.refresh_cookie_rules();
+
+        let command = app.update(Message::UpdateFilterLists);
+        (app, command)
     }
 
     fn title(&self) -> String {
@@ -95,7 +164,11 @@ impl Application for BagelApp {
             }
             Message::CloseTab(index) => {
                 if self.tabs.len() > 1 && index < self.tabs.len() {
-                    self.tabs.remove(index);
+                    let tab = self.tabs.remove(index);
+                    self.pages.remove(&tab.id);
+                    self.blocked_requests.remove(&tab.id);
+                    self.certificate_warnings.remove(&tab.id);
+                    self.https_fallbacks.remove(&tab.id);
                     if self.active_tab_index >= index && self.active_tab_index > 0 {
                         self.active_tab_index -= 1;
                     }
@@ -113,40 +186,255 @@ impl Application for BagelApp {
             Message::AddressBarChanged(value) => {
                 self.address_bar_value = value;
             }
-            Message::NavigateTo(url) => {
-                if self.active_tab_index < self.tabs.len() {
-                    self.tabs[self.active_tab_index].url = url.clone();
-                    self.tabs[self.active_tab_index].title = "Loading...".to_string();
-                    self.tabs[self.active_tab_index].is_loading = true;
-
-                    // Simulate navigation completion
-                    self.tabs[self.active_tab_index].is_loading = false;
-                    self.tabs[self.active_tab_index].title = self.extract_title_from_url(&url);
+            Message::NavigateTo(input) => {
+                // resolve typed input (domains, bookmark keywords, searches) to a url
+                let url = self
+                    .webview_manager
+                    .lock()
+                    .unwrap()
+                    .process_url(&input)
+                    .unwrap_or(input);
+
+                self.show_menu = false;
+                self.show_shields = false;
+                self.show_certificate = false;
+                if url == "bagel://cookies" {
+                    self.refresh_cookies();
+                }
+
+                let Some(tab) = self.tabs.get_mut(self.active_tab_index) else {
+                    return Command::none();
+                };
+                tab.url = url.clone();
+                self.pages.remove(&tab.id);
+                self.blocked_requests.remove(&tab.id);
+                self.certificate_warnings.remove(&tab.id);
+                self.https_fallbacks.remove(&tab.id);
+
+                // internal pages are drawn by us, everything else gets fetched
+                let (loader, security) = {
+                    let webview_manager = self.webview_manager.lock().unwrap();
+                    (
+                        webview_manager.page_loader(),
+                        webview_manager.security_manager(),
+                    )
+                };
+                match loader {
+                    Some(loader) if url.starts_with("http://") || url.starts_with("https://") => {
+                        let tab_id = tab.id;
+
+                        let blocked = url::Url::parse(&url).ok().and_then(|parsed| {
+                            let filter =
+                                security.check_request(&parsed, None, RequestType::Document)?;
+                            Some(BlockedRequest::new(
+                                &parsed,
+                                None,
+                                RequestType::Document,
+                                filter,
+                            ))
+                        });
+                        if let Some(blocked) = blocked {
+                            security.record_blocked(std::slice::from_ref(&blocked));
+                            tab.is_loading = false;
+                            tab.title = "Page blocked".to_string();
+                            self.pages.insert(
+                                tab_id,
+                                Err(format!("Blocked by {} in {}", blocked.rule, blocked.list)),
+                            );
+                            self.blocked_requests.insert(tab_id, vec![blocked]);
+                            return Command::none();
+                        }
+
+                        // https first, http only if that can't connect and
+                        // the user says so
+                        let upgraded = security.suggest_https_upgrade(&url);
+                        if let Some(upgraded) = &upgraded {
+                            tab.url = upgraded.clone();
+                        }
+
+                        tab.title = "Loading...".to_string();
+                        tab.is_loading = true;
+                        self.is_loading = true;
+
+                        return Command::perform(
+                            async move {
+                                let load_url = upgraded.clone().unwrap_or_else(|| url.clone());
+                                match loader.load(&load_url).await {
+                                    Ok(page) => Message::PageLoaded(tab_id, Ok(page)),
+                                    Err(e)
+                                        if upgraded.is_some()
+                                            && PageLoader::is_connection_failure(&e) =>
+                                    {
+                                        Message::Https(HttpsMessage::Unavailable(
+                                            tab_id,
+                                            url,
+                                            e.to_string(),
+                                        ))
+                                    }
+                                    Err(e) => match e.downcast::<UntrustedCertificate>() {
+                                        Ok(untrusted) => {
+                                            Message::Certificates(CertificateMessage::Untrusted(
+                                                tab_id, load_url, untrusted,
+                                            ))
+                                        }
+                                        Err(e) => Message::PageLoaded(tab_id, Err(e.to_string())),
+                                    },
+                                }
+                            },
+                            |message| message,
+                        );
+                    }
+                    _ => {
+                        tab.is_loading = false;
+                        tab.title = Self::extract_title_from_url(&url);
+                    }
+                }
+
+                if url == "bagel://privacy" {
+                    self.refresh_privacy_report();
+                }
+                if url == "bagel://certificates" {
+                    self.refresh_certificate_exceptions();
+                }
+            }
+            Message::PageLoaded(tab_id, result) => {
+                if let Some(tab) = self.tabs.iter_mut().find(|tab| tab.id == tab_id) {
+                    tab.is_loading = false;
+                    tab.title = match &result {
+                        Ok(page) => page
+                            .title
+                            .clone()
+                            .unwrap_or_else(|| Self::extract_title_from_url(&page.final_url)),
+                        Err(_) => "Problem loading page".to_string(),
+                    };
+                    if let Ok(page) = &result {
+                        tab.url = page.final_url.clone();
+                        self.blocked_requests
+                            .insert(tab_id, page.blocked_requests.clone());
+                        // per tab, so a page's policy problems can be told apart
+                        for violation in &page.csp_violations {
+                            log_security_event(
+                                "CSP_VIOLATION",
+                                &format!("tab {}: {}", tab_id, violation),
+                            );
+                        }
+                    }
+                }
+                self.is_loading = self.tabs.iter().any(|tab| tab.is_loading);
+                self.pages.insert(tab_id, result);
+            }
+            Message::AddBookmark => {
+                self.open_bookmark_dialog();
+            }
+            Message::ShowMenu => {
+                self.show_menu = !self.show_menu;
+                self.show_shields = false;
+                self.show_certificate = false;
+            }
+            Message::Bookmarks(message) => {
+                return self.update_bookmarks(message);
+            }
+            Message::Cookies(message) => {
+                return self.update_cookies(message);
+            }
+            Message::Menu(message) => {
+                return self.update_menu(message);
+            }
+            Message::Privacy(message) => {
+                return self.update_privacy(message);
+            }
+            Message::Certificates(message) => {
+                return self.update_certificates(message);
+            }
+            Message::Https(message) => {
+                return self.update_https(message);
+            }
+            Message::UpdateFilterLists => {
+                let security = self.webview_manager.lock().unwrap().security_manager();
+                return Command::perform(
+                    async move {
+                        security
+                            .update_filter_lists()
+                            .await
+                            .map_err(|e| e.to_string())
+                    },
+                    Message::FilterListsUpdated,
+                );
+            }
+            Message::FilterListsUpdated(result) => match result {
+                Ok(0) => {}
+                Ok(updated) => log::info!("Updated {} filter lists", updated),
+                Err(e) => log::error!("Failed to update filter lists: {}", e),
+            },
+            Message::ClearOldCookies => {
+                if let Some(jar) = self.cookie_jar() {
+                    match jar.clear_old_cookies() {
+                        Ok((0, 0)) => {}
+                        Ok((expired, old)) => {
+                            log::info!("Cleared {} expired and {} old cookies", expired, old)
+                        }
+                        Err(e) => log_error("Failed to clear old cookies", e.as_ref()),
+                    }
+                    self.refresh_cookies();
                 }
             }
+            Message::GoBack | Message::GoForward | Message::Reload => {
+                // the ui tabs aren't backed by the webview manager yet
+                // so there's no history to walk
+            }
         }
         Command::none()
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         let toolbar = self.create_toolbar();
         let content = self.create_content();
 
-        Column::new()
-            .push(toolbar)
+        let mut layout = Column::new().push(toolbar);
+
+        if self.show_menu {
+            layout = layout.push(self.create_menu());
+        }
+
+        if self.show_shields {
+            layout = layout.push(self.create_shields_popover());
+        }
+
+        if self.show_certificate {
+            layout = layout.push(self.create_certificate_viewer());
+        }
+
+        if let Some(dialog) = &self.bookmark_dialog {
+            layout = layout.push(self.create_bookmark_dialog(dialog));
+        }
+
+        if self.config.ui.show_bookmarks_bar {
+            layout = layout.push(self.create_bookmarks_bar());
+        }
+
+        layout
             .push(content)
             .width(Length::Fill)
             .height(Length::Fill)
             .into()
     }
 
+    fn subscription(&self) -> Subscription<Message> {
+        // lists say when they expire, so checking hourly is plenty. old
+        // cookies are also cleared at startup, this is for long sessions
+        Subscription::batch([
+            iced::time::every(Duration::from_secs(60 * 60)).map(|_| Message::UpdateFilterLists),
+            iced::time::every(Duration::from_secs(6 * 60 * 60)).map(|_| Message::ClearOldCookies),
+        ])
+    }
+
     fn theme(&self) -> Theme {
         Theme::Dark
     }
 }
 
 impl BagelApp {
-    fn create_toolbar(&self) -> Element<Message> {
+    fn create_toolbar(&self) -> Element<'_, Message> {
         // Tab bar
         let mut tab_row = Row::new().spacing(4);
 
@@ -249,7 +537,9 @@ impl BagelApp {
         let toolbar = Row::new()
             .push(tab_row)
             .push(nav_buttons)
+            .push(self.create_lock())
             .push(address_bar)
+            .push(self.create_shield())
             .push(actions)
             .spacing(8)
             .padding(8)
@@ -270,17 +560,21 @@ impl BagelApp {
             .into()
     }
 
-    fn create_content(&self) -> Element<Message> {
+    fn create_content(&self) -> Element<'_, Message> {
         let current_tab = &self.tabs[self.active_tab_index];
 
-        if current_tab.url == "bagel://home" {
-            self.create_home_page()
-        } else {
-            self.create_web_content(&current_tab.url)
+        match current_tab.url.as_str() {
+            "bagel://home" => self.create_home_page(),
+            "bagel://bookmarks" => self.create_bookmarks_page(),
+            "bagel://cookies" => self.create_cookies_page(),
+            "bagel://cookie-exceptions" => self.create_cookie_exceptions_page(),
+            "bagel://privacy" => self.create_privacy_page(),
+            "bagel://certificates" => self.create_certificates_page(),
+            url => self.create_web_content(url),
         }
     }
 
-    fn create_home_page(&self) -> Element<Message> {
+    fn create_home_page(&self) -> Element<'_, Message> {
         let logo = Column::new()
             .push(
                 text("🥯 Bagel Browser") // bagel dont render; i sad
@@ -353,16 +647,49 @@ impl BagelApp {
             .into()
     }
 
-    fn create_web_content(&self, _url: &str) -> Element<Message> {
+    fn create_web_content(&self, url: &str) -> Element<'_, Message> {
+        let current_tab = &self.tabs[self.active_tab_index];
+
+        if let Some(fallback) = self.https_fallbacks.get(&current_tab.id) {
+            if !current_tab.is_loading {
+                return self.create_https_fallback(fallback);
+            }
+        }
+
+        if let Some(warning) = self.certificate_warnings.get(&current_tab.id) {
+            if !current_tab.is_loading {
+                return self.create_certificate_warning(warning);
+            }
+        }
+
+        if let Some(Ok(page)) = self.pages.get(&current_tab.id) {
+            if !current_tab.is_loading {
+                return self.create_page_view(page);
+            }
+        }
+
+        let (heading, detail) = match self.pages.get(&current_tab.id) {
+            _ if current_tab.is_loading => ("Loading...".to_string(), url.to_string()),
+            Some(Ok(page)) => (
+                page.title.clone().unwrap_or_else(|| page.final_url.clone()),
+                format!("HTTP {} · {} bytes", page.status, page.body.len()),
+            ),
+            Some(Err(error)) => ("Problem loading page".to_string(), error.clone()),
+            None => (
+                "Web Content Area".to_string(),
+                "This would show the actual web page".to_string(),
+            ),
+        };
+
         let content = Column::new()
             .push(
-                text("Web Content Area")
+                text(heading)
                     .size(24)
                     .font(Font::with_name("Ubuntu"))
                     .style(Color::from_rgb(0.8, 0.8, 0.8)),
             )
             .push(
-                text("This would show the actual web page")
+                text(detail)
                     .size(14)
                     .font(Font::with_name("Ubuntu"))
                     .style(Color::from_rgb(0.6, 0.6, 0.6)),
@@ -390,7 +717,75 @@ impl BagelApp {
             .into()
     }
 
-    fn extract_title_from_url(&self, url: &str) -> String {
+    /// Lays the page's text out block by block. There's no css or layout
+    /// engine yet, so this is closer to a reader view than a real rendering.
+    fn create_page_view<'a>(&self, page: &'a PageResponse) -> Element<'a, Message> {
+        let mut detail = format!("HTTP {} · {} bytes", page.status, page.body.len());
+        if page.hidden_elements > 0 {
+            detail.push_str(&format!(" · {} elements hidden", page.hidden_elements));
+        }
+
+        let mut content = Column::new()
+            .push(
+                text(page.title.clone().unwrap_or_else(|| page.final_url.clone()))
+                    .size(24)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.9, 0.9, 0.9)),
+            )
+            .push(
+                text(detail)
+                    .size(12)
+                    .font(Font::with_name("Ubuntu"))
+                    .style(Color::from_rgb(0.6, 0.6, 0.6)),
+            )
+            .spacing(10)
+            .padding(24)
+            .max_width(860);
+
+        for block in &page.blocks {
+            let (size, color, prefix) = match block.kind {
+                BlockKind::Heading(level) => (
+                    28 - 2 * level.min(6) as u16,
+                    Color::from_rgb(0.95, 0.95, 0.95),
+                    "",
+                ),
+                BlockKind::ListItem => (15, Color::from_rgb(0.85, 0.85, 0.85), "• "),
+                BlockKind::Quote => (15, Color::from_rgb(0.7, 0.7, 0.75), "│ "),
+                BlockKind::Paragraph | BlockKind::Preformatted => {
+                    (15, Color::from_rgb(0.85, 0.85, 0.85), "")
+                }
+            };
+            let font = match block.kind {
+                BlockKind::Preformatted => Font::MONOSPACE,
+                _ => Font::with_name("Ubuntu"),
+            };
+
+            content = content.push(
+                text(format!("{}{}", prefix, block.text))
+                    .size(size)
+                    .font(font)
+                    .style(color),
+            );
+        }
+
+        container(scrollable(content).width(Length::Fill).height(Length::Fill))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .style(container::Appearance {
+                background: Some(Background::Color(Color::from_rgb(0.1, 0.1, 0.13))),
+                border: iced::Border {
+                    radius: 0.0.into(),
+                    width: 0.0,
+                    color: Color::TRANSPARENT,
+                },
+                shadow: iced::Shadow::default(),
+                text_color: Some(Color::from_rgb(0.9, 0.9, 0.9)),
+            })
+            .into()
+    }
+
+    fn extract_title_from_url(url: &str) -> String {
         if let Ok(parsed_url) = url::Url::parse(url) {
             parsed_url.host_str().unwrap_or("New Tab").to_string()
         } else {
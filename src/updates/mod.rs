@@ -1,3 +1,4 @@
 pub mod github;
 
+#[allow(unused_imports)]
 pub use github::*;
\ No newline at end of file
@@ -19,10 +19,57 @@ pub struct SearchEngineConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrivacyConfig {
+    /// Cookies older than this are cleared, 0 keeps them until they expire.
     pub auto_clear_cookies_days: u32,
     pub tracking_protection: bool,
     pub ad_blocking: bool,
     pub https_upgrade: bool,
+    /// Never load pages over plain http, not even when the site has no https.
+    #[serde(default)]
+    pub https_only: bool,
+    #[serde(default)]
+    pub passive_mixed_content: PassiveMixedContent,
+    #[serde(default)]
+    pub cookie_policy: CookiePolicy,
+    #[serde(default)]
+    pub cookie_encryption: CookieEncryption,
+}
+
+/// How cookie values in cookies.db are protected at rest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CookieEncryption {
+    #[default]
+    Off,
+    /// A random key kept in `cookies.key` in the config directory.
+    KeyFile,
+    /// A key derived from the passphrase in `BAGEL_COOKIE_PASSPHRASE`. To change
+    /// it, also set `BAGEL_COOKIE_NEW_PASSPHRASE` for one start.
+    Passphrase,
+}
+
+/// What to do with images and media over http on https pages. Scripts, frames
+/// and stylesheets over http are always blocked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PassiveMixedContent {
+    /// Load them over https instead.
+    #[default]
+    Upgrade,
+    Allow,
+    Block,
+}
+
+/// What to do with cookies for requests that go to a different site than the page.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CookiePolicy {
+    AllowAll,
+    BlockThirdParty,
+    /// Third-party cookies are kept in a separate jar per top-level site, so an
+    /// embed can't follow you from one site to the next.
+    #[default]
+    PartitionThirdParty,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,6 +104,10 @@ impl Default for BrowserConfig {
                 tracking_protection: true,
                 ad_blocking: true,
                 https_upgrade: true,
+                https_only: false,
+                passive_mixed_content: PassiveMixedContent::default(),
+                cookie_policy: CookiePolicy::default(),
+                cookie_encryption: CookieEncryption::default(),
             },
             ui: UiConfig {
                 font_family: "Ubuntu".to_string(),
@@ -101,9 +152,13 @@ impl BrowserConfig {
     }
 
     fn config_path() -> Result<PathBuf> {
+        Ok(Self::config_dir()?.join("config.json"))
+    }
+
+    pub fn config_dir() -> Result<PathBuf> {
         let config_dir = dirs::config_dir()
             .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
-        Ok(config_dir.join("bagel-browser").join("config.json"))
+        Ok(config_dir.join("bagel-browser"))
     }
 
     pub fn data_dir(&self) -> &PathBuf {
@@ -114,6 +169,7 @@ impl BrowserConfig {
         std::fs::create_dir_all(&self.storage.data_dir)?;
         std::fs::create_dir_all(self.storage.data_dir.join("userscripts"))?;
         std::fs::create_dir_all(self.storage.data_dir.join("userstyles"))?;
+        std::fs::create_dir_all(self.storage.data_dir.join("filters"))?;
         Ok(())
     }
 }
\ No newline at end of file
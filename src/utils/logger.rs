@@ -31,6 +31,7 @@ pub fn log_security_event(event: &str, details: &str) {
     warn!("Security Event - {}: {}", event, details);
 }
 
+#[allow(dead_code)]
 pub fn log_performance(operation: &str, duration_ms: u64) {
     info!("Performance - {}: {}ms", operation, duration_ms);
 }
\ No newline at end of file
@@ -1,5 +1,9 @@
 pub mod config;
 pub mod logger;
+pub mod public_suffix;
+#[cfg(test)]
+pub mod test_server;
 
 pub use config::*;
-pub use logger::*;
\ No newline at end of file
+pub use logger::*;
+pub use public_suffix::*;
\ No newline at end of file
@@ -0,0 +1,275 @@
+// public suffix list lookups
+// tells us where a "site" starts so co.uk can't get cookies
+// and a.example.com / b.example.com count as the same site
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+use url::{Host, Url};
+
+/// Snapshot of https://publicsuffix.org/list/public_suffix_list.dat, both the
+/// ICANN and private sections.
+const PUBLIC_SUFFIX_LIST: &str = include_str!("public_suffix_list.dat");
+
+static LIST: OnceLock<PublicSuffixList> = OnceLock::new();
+
+#[derive(Debug, Default)]
+pub struct PublicSuffixList {
+    rules: HashSet<String>,
+    /// `*.foo` rules, stored without the `*.`
+    wildcards: HashSet<String>,
+    /// `!foo` rules, stored without the `!`
+    exceptions: HashSet<String>,
+}
+
+impl PublicSuffixList {
+    /// The list bundled with the browser.
+    pub fn get() -> &'static PublicSuffixList {
+        LIST.get_or_init(|| Self::parse(PUBLIC_SUFFIX_LIST))
+    }
+
+    pub fn parse(data: &str) -> Self {
+        let mut list = Self::default();
+
+        for line in data.lines() {
+            // a rule is everything up to the first whitespace
+            let Some(rule) = line.split_whitespace().next() else {
+                continue;
+            };
+            if rule.starts_with("//") {
+                continue;
+            }
+
+            if let Some(rule) = rule.strip_prefix('!') {
+                if let Some(rule) = to_ascii(rule) {
+                    list.exceptions.insert(rule);
+                }
+            } else if let Some(rule) = rule.strip_prefix("*.") {
+                if let Some(rule) = to_ascii(rule) {
+                    list.wildcards.insert(rule);
+                }
+            } else if let Some(rule) = to_ascii(rule) {
+                list.rules.insert(rule);
+            }
+        }
+
+        list
+    }
+
+    /// The public suffix of `host` following the list's algorithm: exception
+    /// rules win, otherwise the longest matching rule, otherwise the last label.
+    /// Returns `None` for ip addresses and empty hosts.
+    pub fn public_suffix(&self, host: &str) -> Option<String> {
+        let host = normalize_host(host)?;
+        let labels: Vec<&str> = host.split('.').collect();
+
+        for i in 0..labels.len() {
+            let candidate = labels[i..].join(".");
+
+            if self.exceptions.contains(&candidate) {
+                return Some(labels[i + 1..].join("."));
+            }
+            if self.rules.contains(&candidate) {
+                return Some(candidate);
+            }
+            if i + 1 < labels.len() && self.wildcards.contains(&labels[i + 1..].join(".")) {
+                return Some(candidate);
+            }
+        }
+
+        labels.last().map(|label| label.to_string())
+    }
+
+    /// The public suffix plus one more label, e.g. `example.co.uk` for
+    /// `www.example.co.uk`. `None` when the host is itself a public suffix.
+    pub fn registrable_domain(&self, host: &str) -> Option<String> {
+        let host = normalize_host(host)?;
+        let suffix = self.public_suffix(&host)?;
+        if host.len() == suffix.len() {
+            return None;
+        }
+
+        let prefix = &host[..host.len() - suffix.len() - 1];
+        let label = prefix.rsplit('.').next()?;
+        Some(format!("{}.{}", label, suffix))
+    }
+
+    pub fn is_public_suffix(&self, domain: &str) -> bool {
+        match normalize_host(domain) {
+            Some(domain) => self.public_suffix(&domain).as_deref() == Some(domain.as_str()),
+            None => false,
+        }
+    }
+}
+
+/// Shorthand for [`PublicSuffixList::registrable_domain`] on the bundled list.
+pub fn registrable_domain(host: &str) -> Option<String> {
+    PublicSuffixList::get().registrable_domain(host)
+}
+
+pub fn is_public_suffix(domain: &str) -> bool {
+    PublicSuffixList::get().is_public_suffix(domain)
+}
+
+/// The site a host belongs to: its registrable domain, or the host itself for
+/// ip addresses and hosts that are a public suffix.
+pub fn site_for_host(host: &str) -> String {
+    registrable_domain(host).unwrap_or_else(|| {
+        normalize_host(host).unwrap_or_else(|| host.trim_end_matches('.').to_ascii_lowercase())
+    })
+}
+
+pub fn is_same_site(a: &str, b: &str) -> bool {
+    site_for_host(a) == site_for_host(b)
+}
+
+/// Whether a request for `request_url` made by a page at `top_level_url` is
+/// third party. Urls without a host (about:, data:, bagel:) are never first party.
+pub fn is_third_party(request_url: &Url, top_level_url: &Url) -> bool {
+    match (request_url.host_str(), top_level_url.host_str()) {
+        (Some(request_host), Some(top_level_host)) => !is_same_site(request_host, top_level_host),
+        _ => true,
+    }
+}
+
+/// `host` and each parent domain of it, stopping at its registrable domain,
+/// so `a.b.example.co.uk` gives `a.b.example.co.uk`, `b.example.co.uk` and
+/// `example.co.uk`. Public suffixes are never included.
+pub fn domains_within_site(host: &str) -> Vec<String> {
+    let Some(host) = normalize_host(host) else {
+        return vec![host.trim_end_matches('.').to_ascii_lowercase()];
+    };
+    let Some(site) = registrable_domain(&host) else {
+        return vec![host];
+    };
+
+    let mut domains = vec![host.clone()];
+    let mut rest = host.as_str();
+    while rest.len() > site.len() {
+        match rest.split_once('.') {
+            Some((_, parent)) => {
+                rest = parent;
+                domains.push(rest.to_string());
+            }
+            None => break,
+        }
+    }
+    domains
+}
+
+/// Lowercased, punycoded and without a trailing dot. `None` for ip addresses.
+fn normalize_host(host: &str) -> Option<String> {
+    let host = host.trim_end_matches('.');
+    if host.is_empty() || host.trim_matches(['[', ']']).parse::<IpAddr>().is_ok() {
+        return None;
+    }
+
+    if host.is_ascii() {
+        Some(host.to_ascii_lowercase())
+    } else {
+        to_ascii(host)
+    }
+}
+
+fn to_ascii(domain: &str) -> Option<String> {
+    if domain.is_ascii() {
+        return Some(domain.to_ascii_lowercase());
+    }
+
+    match Host::parse(domain).ok()? {
+        Host::Domain(domain) => Some(domain),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cases from the list's own checkPublicSuffix test file.
+    #[test]
+    fn registrable_domains_match_the_reference_tests() {
+        let cases = [
+            ("com", None),
+            ("example.com", Some("example.com")),
+            ("a.b.example.com", Some("example.com")),
+            ("uk.com", None),
+            ("b.example.uk.com", Some("example.uk.com")),
+            ("ac.jp", None),
+            ("test.ac.jp", Some("test.ac.jp")),
+            ("www.test.ac.jp", Some("test.ac.jp")),
+            ("ide.kyoto.jp", None),
+            ("b.ide.kyoto.jp", Some("b.ide.kyoto.jp")),
+            ("c.kobe.jp", None),
+            ("b.c.kobe.jp", Some("b.c.kobe.jp")),
+            ("city.kobe.jp", Some("city.kobe.jp")),
+            ("www.city.kobe.jp", Some("city.kobe.jp")),
+            ("k12.ak.us", None),
+            ("www.test.k12.ak.us", Some("test.k12.ak.us")),
+            ("example", None),
+            ("example.example", Some("example.example")),
+            ("WWW.Example.COM.", Some("example.com")),
+            ("食狮.com.cn", Some("xn--85x722f.com.cn")),
+            ("www.食狮.公司.cn", Some("xn--85x722f.xn--55qx5d.cn")),
+        ];
+
+        for (host, expected) in cases {
+            assert_eq!(registrable_domain(host).as_deref(), expected, "{}", host);
+        }
+    }
+
+    #[test]
+    fn wildcards_and_exceptions() {
+        let list = PublicSuffixList::parse(
+            "// comment\nuk\n*.ck\n!www.ck\n\n*.platform.dev  trailing text\n",
+        );
+        assert_eq!(list.public_suffix("a.b.ck").as_deref(), Some("b.ck"));
+        assert_eq!(list.public_suffix("www.ck").as_deref(), Some("ck"));
+        assert_eq!(
+            list.registrable_domain("x.www.ck").as_deref(),
+            Some("www.ck")
+        );
+        assert_eq!(list.registrable_domain("test.ck"), None);
+        assert_eq!(
+            list.registrable_domain("a.app.platform.dev").as_deref(),
+            Some("a.app.platform.dev")
+        );
+        assert!(list.is_public_suffix("anything.platform.dev"));
+        assert!(!list.is_public_suffix("example.uk"));
+    }
+
+    #[test]
+    fn ip_addresses_are_their_own_site() {
+        assert_eq!(registrable_domain("192.168.0.1"), None);
+        assert_eq!(site_for_host("192.168.0.1"), "192.168.0.1");
+        assert_eq!(site_for_host("[::1]"), "[::1]");
+        assert!(!is_public_suffix("127.0.0.1"));
+        assert_eq!(domains_within_site("10.0.0.1"), vec!["10.0.0.1"]);
+    }
+
+    #[test]
+    fn sites_and_third_parties() {
+        assert_eq!(site_for_host("co.uk"), "co.uk");
+        assert!(is_same_site("a.example.co.uk", "b.example.co.uk"));
+        assert!(!is_same_site("a.github.io", "b.github.io"));
+
+        let url = |input: &str| Url::parse(input).unwrap();
+        assert!(!is_third_party(
+            &url("https://cdn.example.com/x.js"),
+            &url("http://www.example.com/")
+        ));
+        assert!(is_third_party(
+            &url("https://tracker.net/p.gif"),
+            &url("https://example.com/")
+        ));
+        assert!(is_third_party(
+            &url("https://example.com/"),
+            &url("bagel://home")
+        ));
+
+        assert_eq!(
+            domains_within_site("a.b.example.co.uk"),
+            vec!["a.b.example.co.uk", "b.example.co.uk", "example.co.uk"]
+        );
+        assert_eq!(domains_within_site("co.uk"), vec!["co.uk"]);
+    }
+}
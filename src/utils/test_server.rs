@@ -0,0 +1,115 @@
+// tiny local servers for tests, so nothing goes out to the internet
+use std::future::Future;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A canned http response. `Content-Length` and `Connection: close` are
+/// added when it's sent.
+pub struct Reply {
+    status: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+impl Reply {
+    pub fn new(status: &'static str) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut response = format!("HTTP/1.1 {}\r\n", self.status);
+        for (name, value) in &self.headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.body.len(),
+            self.body
+        ));
+        response.into_bytes()
+    }
+}
+
+/// Accepts connections on a local port for as long as the test runs and
+/// hands each one to `handle` on its own task. Returns the port.
+pub async fn accept<F, Fut>(handle: F) -> u16
+where
+    F: Fn(TcpStream) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(handle(stream));
+        }
+    });
+
+    port
+}
+
+/// Serves plain http, answering every request with what `respond` gives
+/// for its method and path. Returns the base url, without a trailing slash.
+pub async fn serve_http<F>(respond: F) -> String
+where
+    F: Fn(&str, &str) -> Reply + Send + Sync + 'static,
+{
+    let respond = Arc::new(respond);
+    let port = accept(move |mut stream| {
+        let respond = Arc::clone(&respond);
+        async move {
+            let Some(head) = read_head(&mut stream).await else {
+                return;
+            };
+            let mut line = head.split_whitespace();
+            let method = line.next().unwrap_or_default();
+            let path = line.next().unwrap_or_default();
+            let reply = respond(method, path);
+            let _ = stream.write_all(&reply.to_bytes()).await;
+        }
+    })
+    .await;
+
+    format!("http://127.0.0.1:{}", port)
+}
+
+/// Reads up to the blank line after the headers. `None` if the client hung up first.
+async fn read_head(stream: &mut TcpStream) -> Option<String> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    Some(String::from_utf8_lossy(&head).into_owned())
+}
+
+/// A local port nothing listens on, for connections that should fail.
+pub fn closed_port() -> u16 {
+    // bind and drop to get a free port
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}